@@ -1,4 +1,74 @@
 #![allow(non_snake_case)]
+use std::path::Path;
+use std::process::Command;
+use std::{env, fs};
+
+/// Commits listed in the in-app changelog.
+const CHANGELOG_COMMITS: &str = "20";
+
+fn git(args: &[&str]) -> Option<String> {
+    let output = Command::new("git").args(args).output().ok()?;
+    output
+        .status
+        .success()
+        .then(|| String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
 fn main() {
     slint_build::compile("ui/main_window.slint").unwrap();
+
+    // Which build this is, see src/version.rs. A source tree without git
+    // (e.g. a tarball) builds as "unknown" with an empty changelog.
+    let hash = git(&["rev-parse", "--short", "HEAD"]).unwrap_or_else(|| "unknown".to_string());
+    let dirty = git(&["status", "--porcelain", "--untracked-files=no"])
+        .is_some_and(|status| !status.is_empty());
+    println!(
+        "cargo:rustc-env=DRAMMA_GIT_HASH={}{}",
+        hash,
+        if dirty { "-dirty" } else { "" }
+    );
+    let date = git(&["log", "-1", "--format=%cs"]).unwrap_or_default();
+    println!("cargo:rustc-env=DRAMMA_COMMIT_DATE={}", date);
+
+    let changelog: Vec<String> = git(&[
+        "log",
+        "-n",
+        CHANGELOG_COMMITS,
+        "--no-merges",
+        "--format=%cs %h%x09%s",
+    ])
+    .unwrap_or_default()
+    .lines()
+    .map(|line| {
+        let (head, subject) = line.split_once('\t').unwrap_or(("", line));
+        // "[tracker#123] Subject" → "Subject"
+        let subject = subject
+            .strip_prefix('[')
+            .and_then(|tagged| tagged.split_once("] "))
+            .map_or(subject, |(_, subject)| subject);
+        format!("{} {}", head, subject)
+    })
+    .collect();
+    let out_dir = env::var("OUT_DIR").unwrap();
+    fs::write(
+        Path::new(&out_dir).join("changelog.txt"),
+        changelog.join("\n"),
+    )
+    .unwrap();
+
+    // Rebuilt when HEAD moves: a checkout, or a commit on the branch it
+    // points to. Not the index, which git touches on every `git status`;
+    // a changed "-dirty" flag waits for the next commit or source change.
+    let mut watched = vec![".git/HEAD".to_string(), ".git/packed-refs".to_string()];
+    if let Some(branch) = fs::read_to_string(".git/HEAD")
+        .ok()
+        .and_then(|head| head.strip_prefix("ref: ").map(|r| r.trim().to_string()))
+    {
+        watched.push(format!(".git/{}", branch));
+    }
+    for path in watched {
+        if Path::new(&path).exists() {
+            println!("cargo:rerun-if-changed={}", path);
+        }
+    }
 }
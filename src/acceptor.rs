@@ -0,0 +1,209 @@
+//! What the bill driver in main needs from a bill acceptor: a CashCode
+//! (CCNET, which the mock acceptor speaks too) or an ITL validator (eSSP),
+//! picked by `acceptor_type`.
+
+use log::{debug, info};
+use rusqlite::Connection;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use crate::cashcode::{
+    BillEvent, BillNominal, BillTable, CashCode, CashCodeError, DeviceState, DeviceStatus,
+    Identification,
+};
+use crate::config::{AcceptorType, CashCodeMode, Config};
+use crate::essp::Essp;
+use crate::frame_log::FrameLog;
+use crate::ledger;
+use crate::transactions::{self, Transaction};
+
+/// A bill acceptor, driven from the driver thread. Bills are reported by
+/// `poll`, which is called in a loop; everything else is a command from
+/// the UI or a check of the device.
+pub trait BillAcceptor: Send {
+    fn reset(&mut self) -> Result<(), CashCodeError>;
+    /// Waits for the device to finish starting up; false on `timeout`.
+    fn wait_ready(&mut self, timeout: Duration) -> Result<bool, CashCodeError>;
+    fn identify(&mut self) -> Result<Option<Identification>, CashCodeError>;
+    /// The device's own bill values by type code, if it has them.
+    fn read_bill_table(&mut self) -> Result<Option<BillTable>, CashCodeError>;
+    /// Bill values by type code, 0 for unused codes.
+    fn set_bill_types(&mut self, values: &[i32]) -> Result<(), CashCodeError>;
+    /// Bills taken from the next `enable` on.
+    fn set_accepted_nominals(&mut self, values: &[i32]);
+
+    /// Starts a ledger session for the bills that follow.
+    fn begin_session(&mut self) -> Result<(), CashCodeError>;
+    fn enable(&mut self) -> Result<(), CashCodeError>;
+    fn disable(&mut self) -> Result<(), CashCodeError>;
+    /// Bills are recognised and handed back, not taken.
+    fn set_test_mode(&mut self, on: bool) -> Result<(), CashCodeError>;
+    /// Takes the bill held in escrow.
+    fn stack(&mut self) -> Result<(), CashCodeError>;
+    /// Hands the bill held in escrow back.
+    fn return_bill(&mut self) -> Result<(), CashCodeError>;
+    fn poll(&mut self) -> Result<Option<BillEvent>, CashCodeError>;
+
+    /// Which bill types the device really has enabled.
+    fn get_status(&mut self) -> Result<Option<DeviceStatus>, CashCodeError>;
+    /// Bill types the device should have enabled right now.
+    fn expected_mask(&self) -> [u8; 3];
+    /// Sends the enable mask again, after the device dropped it.
+    fn reapply_mask(&mut self) -> Result<(), CashCodeError>;
+    fn is_enabled(&self) -> bool;
+    fn in_test_mode(&self) -> bool;
+    /// Between bills, so a status query won't get in the way of one.
+    fn is_idle(&self) -> bool;
+    /// A bill is on its way in, so a credit for it may follow.
+    fn bill_in_transit(&self) -> bool;
+    fn state(&self) -> DeviceState;
+
+    /// Bill counters (`accepted_bills`), by value.
+    fn get_bill_counts(&self) -> Result<Vec<(i32, i32)>, CashCodeError>;
+    /// Zeroes the bill counters, returning what they had.
+    fn clear_bill_counts(&self) -> Result<Vec<(i32, i32)>, CashCodeError>;
+    fn get_total_amount(&self) -> Result<i32, CashCodeError>;
+}
+
+/// Opens the acceptor `acceptor_type` names, set up for escrow, dry run
+/// and the serial timings in config.
+pub fn open(
+    config: &Config,
+    frame_log: Arc<FrameLog>,
+) -> Result<Box<dyn BillAcceptor>, CashCodeError> {
+    match (config.cashcode_mode, config.acceptor_type) {
+        (CashCodeMode::Serial, AcceptorType::Essp) => {
+            let mut essp = Essp::new(
+                &config.cashcode_serial_port,
+                &config.stats_db_path,
+                &config.essp,
+                frame_log,
+            )?;
+            if config.escrow.enabled {
+                essp.set_escrow(Some(Duration::from_secs(config.escrow.timeout_secs)));
+            }
+            essp.set_dry_run(config.dry_run);
+            Ok(Box::new(essp))
+        }
+        // the mock is a CCNET device on a pty, see `mock_acceptor`
+        (CashCodeMode::Serial, AcceptorType::CashCode) | (CashCodeMode::Mock, _) => {
+            let mut cashcode = CashCode::new(
+                &config.cashcode_serial_port,
+                &config.stats_db_path,
+                frame_log,
+            )?;
+            if config.escrow.enabled {
+                cashcode.set_escrow(Some(Duration::from_secs(config.escrow.timeout_secs)));
+            }
+            cashcode.set_dry_run(config.dry_run);
+            cashcode.set_timings(config.serial.timings());
+            Ok(Box::new(cashcode))
+        }
+    }
+}
+
+/// Bills taken, counted per value (`accepted_bills`) and in the ledger
+/// session they came in under. Kept in Stats.db, whichever driver runs.
+pub struct BillCounters {
+    db: Arc<Mutex<Connection>>,
+    /// Ledger session bills are recorded under, see `begin_session`.
+    session: Option<i64>,
+}
+
+impl BillCounters {
+    pub fn open(db_path: &str) -> Result<Self, CashCodeError> {
+        info!("opening database: {}", db_path);
+        let db = Connection::open(db_path)?;
+        db.execute(
+            "CREATE TABLE IF NOT EXISTS accepted_bills (
+                nominal INTEGER PRIMARY KEY,
+                quantity INTEGER NOT NULL
+            )",
+            [],
+        )?;
+        ledger::init_db(&db)?;
+        transactions::init_db(&db)?;
+        Ok(Self {
+            db: Arc::new(Mutex::new(db)),
+            session: None,
+        })
+    }
+
+    /// Gives each bill value in `values` a counter, if it has none yet.
+    pub fn add_values(&self, values: &[i32]) -> Result<(), CashCodeError> {
+        let db = self.db.lock().unwrap();
+        for value in values.iter().filter(|value| **value > 0) {
+            db.execute(
+                "INSERT OR IGNORE INTO accepted_bills (nominal, quantity) VALUES (?1, 0)",
+                [value],
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Starts a new ledger session; bills stacked from now on are recorded
+    /// under it until the next one begins.
+    pub fn begin_session(&mut self) -> Result<(), CashCodeError> {
+        let db = self.db.lock().unwrap();
+        let session = ledger::begin_session(&db)?;
+        debug!("ledger session {} started", session);
+        self.session = Some(session);
+        Ok(())
+    }
+
+    pub fn record(&self, nominal: BillNominal) -> Result<(), CashCodeError> {
+        let db = self.db.lock().unwrap();
+        db.execute(
+            "UPDATE accepted_bills SET quantity = quantity + 1 WHERE nominal = ?1",
+            [nominal.value()],
+        )?;
+        if let Some(session) = self.session {
+            ledger::record(&db, session, nominal.value())?;
+        }
+        transactions::insert(
+            &db,
+            &Transaction::BillAccepted {
+                nominal: nominal.value(),
+                session: self.session,
+            },
+        )?;
+        Ok(())
+    }
+
+    pub fn counts(&self) -> Result<Vec<(i32, i32)>, CashCodeError> {
+        let db = self.db.lock().unwrap();
+        let mut stmt =
+            db.prepare("SELECT nominal, quantity FROM accepted_bills ORDER BY nominal")?;
+
+        let rows = stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?;
+
+        let mut results = Vec::new();
+        for row in rows {
+            results.push(row?);
+        }
+
+        Ok(results)
+    }
+
+    /// Sets every counter back to zero once the stacker has been emptied,
+    /// returning them as they were.
+    pub fn clear(&self) -> Result<Vec<(i32, i32)>, CashCodeError> {
+        let counts = self.counts()?;
+        let db = self.db.lock().unwrap();
+        db.execute("UPDATE accepted_bills SET quantity = 0", [])?;
+        Ok(counts)
+    }
+
+    pub fn total(&self) -> Result<i32, CashCodeError> {
+        let db = self.db.lock().unwrap();
+        let total: i32 = db
+            .query_row(
+                "SELECT SUM(nominal * quantity) FROM accepted_bills",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap_or(0);
+
+        Ok(total)
+    }
+}
@@ -0,0 +1,93 @@
+//! The service page for whoever empties the cash box: what the stacker
+//! holds per denomination, the acceptor's state, a reset, and clearing the
+//! counters once the cash is out. Reached by holding the logo on the main
+//! page for three seconds and entering `admin_pin`, or a member PIN the
+//! gateway gives one of `service_roles` (see `admin_handler` in main.rs).
+
+use std::time::{Duration, Instant};
+
+use crate::money;
+
+/// Wrong PINs in a row before the keypad stops checking them for `LOCKOUT`.
+const MAX_ATTEMPTS: u32 = 3;
+const LOCKOUT: Duration = Duration::from_secs(60);
+
+/// Why a PIN typed on the keypad didn't open the service page.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Unlock {
+    Wrong {
+        attempts_left: u32,
+    },
+    /// This was the last wrong PIN allowed; the keypad is locked.
+    LockedOut,
+}
+
+/// Checks PINs against `admin_pin`, locking the keypad for a minute after
+/// `MAX_ATTEMPTS` wrong ones so the PIN can't be guessed at the box. A PIN
+/// that isn't `admin_pin` may still be a member's (`features.member_roles`);
+/// the gateway decides and the outcome comes back through `granted` or
+/// `failed`, so member PINs count towards the lockout too.
+pub struct PinGate {
+    pin: Option<String>,
+    failures: u32,
+    locked_until: Option<Instant>,
+}
+
+impl PinGate {
+    pub fn new(pin: Option<String>) -> PinGate {
+        PinGate {
+            pin,
+            failures: 0,
+            locked_until: None,
+        }
+    }
+
+    /// How long the keypad is still locked, `None` when PINs are checked.
+    pub fn refused(&mut self, now: Instant) -> Option<Duration> {
+        let until = self.locked_until?;
+        if now < until {
+            return Some(until - now);
+        }
+        self.locked_until = None;
+        self.failures = 0;
+        None
+    }
+
+    pub fn is_admin_pin(&self, entered: &str) -> bool {
+        self.pin.as_deref() == Some(entered)
+    }
+
+    pub fn granted(&mut self) {
+        self.failures = 0;
+    }
+
+    pub fn failed(&mut self, now: Instant) -> Unlock {
+        self.failures += 1;
+        if self.failures >= MAX_ATTEMPTS {
+            self.locked_until = Some(now + LOCKOUT);
+            return Unlock::LockedOut;
+        }
+        Unlock::Wrong {
+            attempts_left: MAX_ATTEMPTS - self.failures,
+        }
+    }
+}
+
+/// Amount in the stacker from the driver's (nominal, count) counters.
+pub fn total(counts: &[(i32, i32)]) -> i64 {
+    counts
+        .iter()
+        .map(|&(nominal, count)| i64::from(nominal) * i64::from(count))
+        .sum()
+}
+
+/// "12 bills, 84000 AMD" for the audit trail, like a collection.
+pub fn summary(counts: &[(i32, i32)]) -> String {
+    let bills: i64 = counts.iter().map(|&(_, count)| i64::from(count)).sum();
+    format!(
+        "{} bills, {} {currency}",
+        bills,
+        total(counts),
+        currency = money::currency()
+    )
+}
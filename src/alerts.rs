@@ -0,0 +1,199 @@
+//! Messages to the space admins' chat (`[alerts]`): a line for each
+//! donation sent and for each failure reported under an error code
+//! (stacker removed, jams, hardware and database errors), so problems are
+//! heard about without logging in to the kiosk. Sent from a thread of its
+//! own, so the UI and the device threads never wait on Telegram or Matrix.
+
+use http::Request;
+use isahc::ReadResponseExt;
+use log::{info, warn};
+use std::collections::HashMap;
+use std::sync::OnceLock;
+use std::sync::mpsc::{Sender, channel};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::backend::Donation;
+use crate::config::Alerts;
+use crate::error::RequestError;
+use crate::error_code::ErrorCode;
+use crate::{api, clock};
+
+/// Telegram refuses longer messages.
+const TELEGRAM_LIMIT: usize = 4096;
+
+static ALERTS: OnceLock<Sender<Alert>> = OnceLock::new();
+
+enum Alert {
+    Donation(String),
+    Error(ErrorCode, String),
+}
+
+/// Where alerts go; either or both may be missing.
+struct Targets {
+    /// (bot token, chat id)
+    telegram: Option<(String, String)>,
+    /// (homeserver, room id, access token)
+    matrix: Option<(String, String, String)>,
+}
+
+/// Starts the sending thread. Without a Telegram chat or Matrix room
+/// configured nothing is started and alerts are dropped; in a dry run they
+/// are only logged.
+pub fn start(settings: &Alerts, dry_run: bool) {
+    let targets = Targets {
+        telegram: settings
+            .telegram_bot_token
+            .clone()
+            .zip(settings.telegram_chat_id.clone()),
+        matrix: match (
+            &settings.matrix_homeserver,
+            &settings.matrix_room_id,
+            &settings.matrix_access_token,
+        ) {
+            (Some(homeserver), Some(room), Some(token)) => Some((
+                homeserver.trim_end_matches('/').to_string(),
+                room.clone(),
+                token.clone(),
+            )),
+            _ => None,
+        },
+    };
+    if targets.telegram.is_none() && targets.matrix.is_none() {
+        return;
+    }
+    let (tx, rx) = channel();
+    if ALERTS.set(tx).is_err() {
+        return;
+    }
+    let donations = settings.donations;
+    let errors = settings.errors;
+    let repeat_after = Duration::from_secs(settings.repeat_after_secs);
+    thread::spawn(move || {
+        let mut last_posted: HashMap<&'static str, Instant> = HashMap::new();
+        let mut txn = 0u64;
+        while let Ok(alert) = rx.recv() {
+            let text = match alert {
+                Alert::Donation(text) if donations => text,
+                Alert::Error(code, text) if errors => {
+                    if last_posted
+                        .get(code.as_str())
+                        .is_some_and(|at| clock::since(*at) < repeat_after)
+                    {
+                        continue;
+                    }
+                    last_posted.insert(code.as_str(), clock::instant());
+                    text
+                }
+                _ => continue,
+            };
+            if dry_run {
+                info!("🧪 Dry run, not sending alert: {}", text);
+                continue;
+            }
+            if let Some((bot_token, chat_id)) = &targets.telegram
+                && let Err(e) = send_telegram(bot_token, chat_id, &text)
+            {
+                warn!("📣 Failed to send an alert to Telegram: {}", e);
+            }
+            if let Some((homeserver, room, token)) = &targets.matrix {
+                txn += 1;
+                if let Err(e) = send_matrix(homeserver, room, token, txn, &text) {
+                    warn!("📣 Failed to send an alert to Matrix: {}", e);
+                }
+            }
+        }
+    });
+    info!("📣 Alerts to the admins' chat enabled");
+}
+
+/// A donation went through to the backend.
+pub fn donation(donation: &Donation) {
+    let from = if donation.username == "anon" {
+        "anonymously".to_string()
+    } else {
+        format!("from @{}", donation.username)
+    };
+    let mut text = format!(
+        "💸 Dramma: {} {} {} to {}",
+        donation.amount, donation.currency, from, donation.fund_name
+    );
+    if donation.auto_committed {
+        text.push_str(" (committed on timeout)");
+    }
+    post(Alert::Donation(text));
+}
+
+/// A failure was reported under `code` (see `error_code::report`).
+pub fn error(code: ErrorCode, details: &str) {
+    post(Alert::Error(
+        code,
+        format!(
+            "🛑 Dramma: {} {} at {}\n{}",
+            code,
+            code.description(),
+            clock::local().format("%Y-%m-%d %H:%M"),
+            details
+        ),
+    ));
+}
+
+fn post(alert: Alert) {
+    if let Some(tx) = ALERTS.get() {
+        let _ = tx.send(alert);
+    }
+}
+
+fn send_telegram(bot_token: &str, chat_id: &str, text: &str) -> Result<(), RequestError> {
+    let mut text = text.to_string();
+    if text.len() > TELEGRAM_LIMIT {
+        let mut cut = TELEGRAM_LIMIT - 1;
+        while !text.is_char_boundary(cut) {
+            cut -= 1;
+        }
+        text.truncate(cut);
+        text.push('…');
+    }
+    let body = serde_json::to_vec(&serde_json::json!({ "chat_id": chat_id, "text": text }))?;
+    let request = Request::post(format!(
+        "https://api.telegram.org/bot{}/sendMessage",
+        bot_token
+    ))
+    .header("Content-Type", "application/json")
+    .body(body)?;
+    check(isahc::send(request)?)
+}
+
+/// Sends `text` to a Matrix room. Matrix wants a transaction id unique
+/// per message; `txn` counts them since start.
+fn send_matrix(
+    homeserver: &str,
+    room: &str,
+    token: &str,
+    txn: u64,
+    text: &str,
+) -> Result<(), RequestError> {
+    let body = serde_json::to_vec(&serde_json::json!({ "msgtype": "m.text", "body": text }))?;
+    let request = Request::put(format!(
+        "{}/_matrix/client/v3/rooms/{}/send/m.room.message/dramma-{}-{}",
+        homeserver,
+        api::encode(room),
+        clock::timestamp(),
+        txn
+    ))
+    .header("Authorization", format!("Bearer {}", token))
+    .header("Content-Type", "application/json")
+    .body(body)?;
+    check(isahc::send(request)?)
+}
+
+fn check(mut response: http::Response<isahc::Body>) -> Result<(), RequestError> {
+    if response.status().is_success() {
+        Ok(())
+    } else {
+        Err(RequestError::Api {
+            status: response.status().as_u16(),
+            message: response.text().unwrap_or_default(),
+        })
+    }
+}
@@ -0,0 +1,529 @@
+use http::{Request, Response};
+use isahc::config::{CaCertificate, ClientCertificate, PrivateKey};
+use isahc::error::ErrorKind;
+use isahc::prelude::*;
+use isahc::{AsyncBody, HttpClient};
+use log::{debug, error, info, warn};
+use serde::Deserialize;
+use serde::de::DeserializeOwned;
+use std::path::Path;
+use std::sync::OnceLock;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+use std::time::Duration;
+
+use crate::config::GatewayTls;
+use crate::error::RequestError;
+use crate::{clock, signing};
+
+/// Gateway API version this kiosk was built against. Sent with every request
+/// so the backend can keep serving old kiosks or refuse them explicitly.
+pub const API_VERSION: &str = "1";
+pub const API_VERSION_HEADER: &str = "X-Api-Version";
+/// Headers of a signed request, see `send`.
+pub const TIMESTAMP_HEADER: &str = "X-Kiosk-Timestamp";
+pub const SIGNATURE_HEADER: &str = "X-Kiosk-Signature";
+
+const GATEWAY: &str = "https://gateway.hackem.cc";
+
+/// With more than one gateway, how long connecting to one may take before
+/// the next is tried, and how often a preferred one that was skipped is
+/// checked again.
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+const RECHECK_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Gateway base URLs in order of preference, set once at startup (the
+/// soak test's mock, or `gateways` from the config).
+static GATEWAYS: OnceLock<Vec<String>> = OnceLock::new();
+
+/// Index into `gateways()` of the one requests go to first.
+static ACTIVE: AtomicUsize = AtomicUsize::new(0);
+
+pub fn set_gateway(base: &str) {
+    set_gateways(&[base.to_string()]);
+}
+
+fn set_gateways(bases: &[String]) {
+    let bases: Vec<String> = bases
+        .iter()
+        .map(|base| base.trim_end_matches('/').to_string())
+        .collect();
+    if !bases.is_empty() {
+        let _ = GATEWAYS.set(bases);
+    }
+}
+
+fn gateways() -> &'static [String] {
+    GATEWAYS.get_or_init(|| vec![GATEWAY.to_string()])
+}
+
+/// Full URL of a gateway endpoint; `path` starts with `/api/`.
+pub fn url(path: &str) -> String {
+    format!("{}{}", base(), path)
+}
+
+/// A GET to the gateway with the token and API version every call carries;
+/// goes out through `send`.
+pub fn get(url: &str, token: &str) -> http::request::Builder {
+    authorized(Request::get(url), token)
+}
+
+/// A JSON POST to the gateway, see `get`.
+pub fn post(url: &str, token: &str) -> http::request::Builder {
+    authorized(Request::post(url), token).header("Content-Type", "application/json")
+}
+
+fn authorized(request: http::request::Builder, token: &str) -> http::request::Builder {
+    request
+        .header("Authorization", format!("Bearer {}", token))
+        .header(API_VERSION_HEADER, API_VERSION)
+}
+
+/// Gateway base URL requests currently go to, no trailing slash.
+fn base() -> &'static str {
+    let gateways = gateways();
+    &gateways[ACTIVE.load(Ordering::Relaxed).min(gateways.len() - 1)]
+}
+
+/// The gateway in use when it isn't the preferred one, for Diagnostics.
+pub fn fallback_gateway() -> Option<&'static str> {
+    (ACTIVE.load(Ordering::Relaxed) != 0).then(base)
+}
+
+/// Sends requests to the preferred gateway again, e.g. when staff know it
+/// is back before the background check notices.
+pub fn reset_gateway() {
+    ACTIVE.store(0, Ordering::Relaxed);
+}
+
+/// The configured gateway `url` points at and the rest of it, or `None`
+/// for a URL elsewhere.
+fn split_gateway(url: &str) -> Option<(usize, &str)> {
+    gateways().iter().enumerate().find_map(|(i, base)| {
+        let rest = url.strip_prefix(base.as_str())?;
+        (rest.is_empty() || rest.starts_with(['/', '?'])).then_some((i, rest))
+    })
+}
+
+/// Errors that mean the request never reached the gateway, so it is safe
+/// to send again elsewhere. No request has an overall timeout, so a
+/// timeout is always the connect timeout.
+fn unreachable(e: &isahc::Error) -> bool {
+    matches!(
+        e.kind(),
+        ErrorKind::ConnectionFailed | ErrorKind::NameResolution | ErrorKind::Timeout
+    )
+}
+
+/// Client every gateway call goes through, or why it could not be set up.
+static CLIENT: OnceLock<Result<HttpClient, String>> = OnceLock::new();
+
+/// Shared secret gateway requests are signed with, when configured.
+static SIGNING_SECRET: OnceLock<String> = OnceLock::new();
+
+/// Sets up the gateway client from `[gateway_tls]`. With `ca_file` only that
+/// CA (or the gateway's own certificate) is trusted instead of the system
+/// store; with `client_cert` the kiosk authenticates itself. A configured
+/// file that can't be read fails closed: no gateway call is made at all.
+/// With a `signing_secret` every request is signed as well. With more than
+/// one of `gateways`, requests fail over between them (see `send`).
+pub fn configure(gateways: &[String], tls: &GatewayTls, signing_secret: Option<&str>) {
+    set_gateways(gateways);
+    let failover = self::gateways().len() > 1;
+    let _ = CLIENT.set(
+        build_client(tls, failover)
+            .inspect_err(|e| error!("🔒 Gateway TLS setup failed, gateway calls disabled: {}", e)),
+    );
+    if let Some(secret) = signing_secret.filter(|secret| !secret.is_empty()) {
+        info!("🔒 Gateway requests signed");
+        let _ = SIGNING_SECRET.set(secret.to_string());
+    }
+    if failover {
+        info!("🌐 Gateways in order: {}", self::gateways().join(", "));
+        thread::spawn(recheck_preferred);
+    }
+}
+
+fn build_client(tls: &GatewayTls, failover: bool) -> Result<HttpClient, String> {
+    let readable = |path: &str| {
+        if Path::new(path).is_file() {
+            Ok(())
+        } else {
+            Err(format!("{} is not readable", path))
+        }
+    };
+    let mut builder = HttpClient::builder();
+    if failover {
+        builder = builder.connect_timeout(CONNECT_TIMEOUT);
+    }
+    if let Some(ca_file) = &tls.ca_file {
+        readable(ca_file)?;
+        info!("🔒 Gateway certificate pinned to {}", ca_file);
+        builder = builder.ssl_ca_certificate(CaCertificate::file(ca_file));
+    }
+    match (&tls.client_cert, &tls.client_key) {
+        (Some(cert), key) => {
+            readable(cert)?;
+            let key = match key {
+                Some(key) => {
+                    readable(key)?;
+                    Some(PrivateKey::pem_file(key, None))
+                }
+                None => None,
+            };
+            info!("🔒 Gateway client certificate {}", cert);
+            builder = builder.ssl_client_certificate(ClientCertificate::pem_file(cert, key));
+        }
+        (None, Some(_)) => return Err("client_key set without client_cert".to_string()),
+        (None, None) => {}
+    }
+    builder.build().map_err(|e| e.to_string())
+}
+
+/// A request body `send` can sign.
+pub trait Body: Into<AsyncBody> {
+    fn bytes(&self) -> &[u8];
+}
+
+impl Body for () {
+    fn bytes(&self) -> &[u8] {
+        &[]
+    }
+}
+
+impl Body for Vec<u8> {
+    fn bytes(&self) -> &[u8] {
+        self
+    }
+}
+
+/// Adds the timestamp and signature headers: hex HMAC-SHA256 under the
+/// shared secret of "<timestamp>\n<METHOD>\n<path and query>\n<body>", so
+/// the bearer token alone can't forge a request and an old one can't be
+/// replayed once the gateway stops accepting its timestamp.
+fn sign<B: Body>(request: &mut Request<B>, secret: &str) {
+    let timestamp = clock::timestamp().to_string();
+    let target = request
+        .uri()
+        .path_and_query()
+        .map(|target| target.as_str())
+        .unwrap_or("/");
+    let signature = signing::hmac_sha256(
+        secret.as_bytes(),
+        &[
+            timestamp.as_bytes(),
+            b"\n",
+            request.method().as_str().as_bytes(),
+            b"\n",
+            target.as_bytes(),
+            b"\n",
+            request.body().bytes(),
+        ],
+    );
+    let headers = request.headers_mut();
+    // Both are plain ASCII
+    headers.insert(TIMESTAMP_HEADER, timestamp.parse().unwrap());
+    headers.insert(SIGNATURE_HEADER, signing::hex(&signature).parse().unwrap());
+}
+
+fn client() -> Result<&'static HttpClient, RequestError> {
+    CLIENT
+        .get_or_init(|| HttpClient::new().map_err(|e| e.to_string()))
+        .as_ref()
+        .map_err(|e| RequestError::Tls(e.clone()))
+}
+
+/// Sends a gateway request through the configured client, signed if a
+/// signing secret is configured. With several gateways it goes to the one
+/// in use first, then to the others in order of preference while they
+/// can't be reached; the first that answers is used from then on.
+pub async fn send<B: Body>(request: Request<B>) -> Result<Response<AsyncBody>, RequestError> {
+    let client = client()?;
+    let (parts, body) = request.into_parts();
+    let body = body.bytes().to_vec();
+    let url = parts.uri.to_string();
+
+    let gateways = gateways();
+    let active = ACTIVE.load(Ordering::Relaxed);
+    // (gateway, URL) in the order they are tried
+    let targets: Vec<(Option<usize>, String)> = match split_gateway(&url) {
+        Some((_, rest)) => std::iter::once(active)
+            .chain((0..gateways.len()).filter(|&i| i != active))
+            .map(|i| (Some(i), format!("{}{}", gateways[i], rest)))
+            .collect(),
+        None => vec![(None, url)],
+    };
+
+    let mut last_error = None;
+    for (gateway, target) in targets {
+        let mut request = Request::builder()
+            .method(parts.method.clone())
+            .uri(target)
+            .version(parts.version)
+            .body(body.clone())?;
+        *request.headers_mut() = parts.headers.clone();
+        if let Some(secret) = SIGNING_SECRET.get() {
+            sign(&mut request, secret);
+        }
+        match (client.send_async(request).await, gateway) {
+            (Ok(response), Some(i)) if i != active => {
+                warn!("🌐 Now using gateway {}", gateways[i]);
+                ACTIVE.store(i, Ordering::Relaxed);
+                return Ok(response);
+            }
+            (Ok(response), _) => return Ok(response),
+            (Err(e), Some(i)) if unreachable(&e) => {
+                warn!("🌐 Gateway {} unreachable: {}", gateways[i], e);
+                last_error = Some(e);
+            }
+            (Err(e), _) => return Err(e.into()),
+        }
+    }
+    Err(last_error.expect("at least one gateway was tried").into())
+}
+
+/// Background check while a fallback gateway is in use: once a preferred
+/// one answers again (any HTTP response will do), requests go back to it.
+fn recheck_preferred() {
+    loop {
+        clock::sleep(RECHECK_INTERVAL);
+        let active = ACTIVE.load(Ordering::Relaxed);
+        let Ok(client) = client() else {
+            return;
+        };
+        for (i, base) in gateways().iter().enumerate().take(active) {
+            let Ok(request) = Request::get(format!("{}/api/funds?status=open", base))
+                .header(API_VERSION_HEADER, API_VERSION)
+                .body(())
+            else {
+                continue;
+            };
+            if client.send(request).is_ok() {
+                info!("🌐 Gateway {} reachable again, switching back", base);
+                ACTIVE.store(i, Ordering::Relaxed);
+                break;
+            }
+        }
+    }
+}
+
+/// Statuses the gateway uses to say it no longer speaks our API version.
+pub(crate) fn is_incompatible_status(status: u16) -> bool {
+    matches!(status, 406 | 410 | 426)
+}
+
+/// Version the server reports in its response, for error messages.
+fn server_version(response: &Response<AsyncBody>) -> String {
+    response
+        .headers()
+        .get(API_VERSION_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or("unknown")
+        .to_string()
+}
+
+/// Decodes a JSON body. A body that does not match our types means the
+/// backend has moved on, so it is reported as `Incompatible` rather than
+/// a plain parse error.
+pub async fn read_json<T: DeserializeOwned>(
+    response: &mut Response<AsyncBody>,
+) -> Result<T, RequestError> {
+    let body = response.bytes().await?;
+    serde_json::from_slice(&body).map_err(|e| {
+        let server = server_version(response);
+        error!(
+            "❌ API response does not match v{} schema (server v{}): {}",
+            API_VERSION, server, e
+        );
+        RequestError::Incompatible(format!("kiosk v{}, server v{}: {}", API_VERSION, server, e))
+    })
+}
+
+/// Turns a non-success response into the matching `RequestError`.
+pub async fn error_for_status(response: &mut Response<AsyncBody>) -> RequestError {
+    let status = response.status().as_u16();
+    let message = response
+        .text()
+        .await
+        .unwrap_or_else(|_| "Unknown error".to_string());
+
+    error!("❌ API error {}: {}", status, message);
+    if is_incompatible_status(status) {
+        RequestError::Incompatible(format!(
+            "kiosk v{}, server v{}: {}",
+            API_VERSION,
+            server_version(response),
+            message
+        ))
+    } else {
+        RequestError::Api { status, message }
+    }
+}
+
+/// Upper bound on pages followed for one list, in case the gateway keeps
+/// pointing at more.
+const MAX_PAGES: usize = 100;
+
+/// One page of a list endpoint. Unpaginated endpoints answer with a bare
+/// array; paginated ones wrap it with a way to the next page: a `next` URL,
+/// a `next_cursor` to send back as `cursor`, or a `total` to walk with
+/// `offset`. A `Link: <…>; rel="next"` header works with either shape.
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum ListPage<T> {
+    Plain(Vec<T>),
+    Wrapped {
+        #[serde(alias = "data", alias = "results")]
+        items: Vec<T>,
+        #[serde(default, alias = "next_url", alias = "nextUrl")]
+        next: Option<String>,
+        #[serde(default, alias = "nextCursor")]
+        next_cursor: Option<String>,
+        #[serde(default, alias = "count", alias = "totalCount")]
+        total: Option<usize>,
+    },
+}
+
+/// The `rel="next"` target of a `Link` header, if any.
+fn link_next(response: &Response<AsyncBody>) -> Option<String> {
+    response
+        .headers()
+        .get_all("Link")
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .find_map(|link| {
+            let (target, params) = link.split_once(';')?;
+            params
+                .split(';')
+                .any(|param| {
+                    let param = param.trim().replace(' ', "");
+                    param == "rel=next" || param == "rel=\"next\""
+                })
+                .then(|| {
+                    target
+                        .trim()
+                        .trim_start_matches('<')
+                        .trim_end_matches('>')
+                        .to_string()
+                })
+        })
+}
+
+/// Percent-encodes everything but unreserved characters, for a path
+/// segment or query value.
+pub fn encode(value: &str) -> String {
+    value
+        .bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                (b as char).to_string()
+            }
+            _ => format!("%{:02X}", b),
+        })
+        .collect()
+}
+
+/// `url` with `key=value` added (or replaced) in its query string.
+fn with_query(url: &str, key: &str, value: &str) -> String {
+    let (path, query) = url.split_once('?').unwrap_or((url, ""));
+    let mut params: Vec<&str> = query
+        .split('&')
+        .filter(|param| !param.is_empty() && param.split('=').next() != Some(key))
+        .collect();
+    // cursors are often base64
+    let added = format!("{}={}", key, encode(value));
+    params.push(&added);
+    format!("{}?{}", path, params.join("&"))
+}
+
+/// A next-page link as a full URL. Relative links (a path, or just a
+/// query for the same path) are taken against the current page; absolute
+/// ones only when they point back at the gateway, so the token is never
+/// sent elsewhere.
+fn resolve_next(next: &str, current: &str) -> Option<String> {
+    if next.starts_with('/') {
+        Some(url(next))
+    } else if next.starts_with('?') {
+        let path = current.split_once('?').map_or(current, |(path, _)| path);
+        Some(format!("{}{}", path, next))
+    } else if split_gateway(next).is_some() {
+        Some(next.to_string())
+    } else {
+        warn!("⚠️  Ignoring next page outside the gateway: {}", next);
+        None
+    }
+}
+
+/// Fetches every item of a list endpoint, following pagination however the
+/// gateway offers it (see [`ListPage`]), so a long list is never cut at the
+/// first page. A 404 on the first page comes back as `Api { status: 404 }`
+/// without being logged, for endpoints the gateway may not have.
+pub async fn fetch_all<T: DeserializeOwned>(
+    path: &str,
+    token: &str,
+) -> Result<Vec<T>, RequestError> {
+    let mut items: Vec<T> = Vec::new();
+    let mut next = Some(url(path));
+    let mut seen: Vec<String> = Vec::new();
+    while let Some(page_url) = next.take() {
+        if seen.len() == MAX_PAGES {
+            warn!(
+                "⚠️  {} has more than {} pages, keeping the first {} items",
+                path,
+                MAX_PAGES,
+                items.len()
+            );
+            break;
+        }
+        if seen.contains(&page_url) {
+            warn!("⚠️  {} points back at a page already read, stopping", path);
+            break;
+        }
+        let request = get(&page_url, token).body(())?;
+        let mut response = send(request).await?;
+        let status = response.status().as_u16();
+        if seen.is_empty() && status == 404 {
+            return Err(RequestError::Api {
+                status,
+                message: String::new(),
+            });
+        }
+        if !response.status().is_success() {
+            return Err(error_for_status(&mut response).await);
+        }
+        let link = link_next(&response);
+        let page: ListPage<T> = read_json(&mut response).await?;
+        seen.push(page_url.clone());
+
+        let (page_items, next_link, cursor, total) = match page {
+            ListPage::Plain(page_items) => (page_items, None, None, None),
+            ListPage::Wrapped {
+                items,
+                next,
+                next_cursor,
+                total,
+            } => (items, next, next_cursor, total),
+        };
+        let empty = page_items.is_empty();
+        items.extend(page_items);
+        debug!(
+            "{}: page {}, {} items so far",
+            path,
+            seen.len(),
+            items.len()
+        );
+
+        next = match (next_link.or(link), cursor, total) {
+            (Some(link), _, _) => resolve_next(&link, &page_url),
+            (None, Some(cursor), _) if !cursor.is_empty() && !empty => {
+                Some(with_query(&page_url, "cursor", &cursor))
+            }
+            (None, _, Some(total)) if items.len() < total && !empty => {
+                Some(with_query(&page_url, "offset", &items.len().to_string()))
+            }
+            _ => None,
+        };
+    }
+    Ok(items)
+}
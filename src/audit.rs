@@ -0,0 +1,50 @@
+use log::error;
+use rusqlite::{Connection, Result as SqlResult, params};
+
+use crate::{clock, db_writes};
+
+pub fn init_db(db: &Connection) -> SqlResult<()> {
+    db.execute(
+        "CREATE TABLE IF NOT EXISTS audit_log (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            timestamp INTEGER NOT NULL,
+            event TEXT NOT NULL,
+            details TEXT NOT NULL
+        )",
+        [],
+    )?;
+    Ok(())
+}
+
+fn insert(db_path: &str, timestamp: u64, event: &str, details: &str) -> SqlResult<()> {
+    let db = Connection::open(db_path)?;
+    init_db(&db)?;
+    db.execute(
+        "INSERT INTO audit_log (timestamp, event, details) VALUES (?1, ?2, ?3)",
+        params![timestamp as i64, event, details],
+    )?;
+    Ok(())
+}
+
+/// Appends an entry to the audit trail in the stats DB. Like
+/// `donation_log::record`, this runs on its own thread and is best-effort.
+pub fn record(db_path: &str, event: &str, details: &str) {
+    let db_path = db_path.to_string();
+    let event = event.to_string();
+    let details = details.to_string();
+    let timestamp = clock::timestamp();
+
+    db_writes::spawn(move || {
+        if let Err(e) = insert(&db_path, timestamp, &event, &details) {
+            error!("Failed to record audit log entry ({}): {}", event, e);
+        }
+    });
+}
+
+/// Like `record`, but blocks until the entry is written — for events right
+/// before the process goes away.
+pub fn record_now(db_path: &str, event: &str, details: &str) {
+    if let Err(e) = insert(db_path, clock::timestamp(), event, details) {
+        error!("Failed to record audit log entry ({}): {}", event, e);
+    }
+}
@@ -0,0 +1,267 @@
+use http::Request;
+use isahc::AsyncReadResponseExt;
+use log::{info, warn};
+use serde::Serialize;
+use std::fs::{self, OpenOptions};
+use std::future::Future;
+use std::io::{self, Write};
+use std::path::Path;
+use std::pin::Pin;
+use std::rc::Rc;
+
+use crate::audit;
+use crate::config::{BackendKind, Config};
+use crate::donation;
+use crate::error::RequestError;
+use crate::mqtt::Publisher;
+use crate::receipt;
+use crate::usb_export::csv_quote;
+
+/// A committed donation (one part of a campaign split), as handed to the
+/// backend.
+#[derive(Debug, Clone, Serialize)]
+pub struct Donation {
+    pub fund_id: i32,
+    pub fund_name: String,
+    pub username: String,
+    pub amount: i32,
+    pub currency: &'static str,
+    /// Kiosk receipt number, shared by the parts of a split
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub receipt: Option<i64>,
+    pub timestamp: u64,
+    pub auto_committed: bool,
+}
+
+pub type SendFuture<'a> = Pin<Box<dyn Future<Output = Result<(), RequestError>> + 'a>>;
+
+/// Where committed donations go. Sends run on the Slint event loop, so
+/// backends don't need to be `Send`.
+pub trait DonationBackend {
+    /// For logs, e.g. "gateway".
+    fn name(&self) -> &'static str;
+
+    /// Sends one donation; an error is reported with its code and the
+    /// donation isn't recorded as committed.
+    fn send<'a>(&'a self, donation: &'a Donation) -> SendFuture<'a>;
+}
+
+/// The hackem gateway, the default.
+pub struct Gateway {
+    token: String,
+}
+
+impl DonationBackend for Gateway {
+    fn name(&self) -> &'static str {
+        "gateway"
+    }
+
+    fn send<'a>(&'a self, donation: &'a Donation) -> SendFuture<'a> {
+        Box::pin(donation::send_donation(
+            &self.token,
+            donation.fund_id,
+            &donation.username,
+            donation.amount,
+            donation.receipt,
+        ))
+    }
+}
+
+/// A JSON POST of the donation to any URL.
+pub struct Webhook {
+    url: String,
+    token: Option<String>,
+}
+
+impl DonationBackend for Webhook {
+    fn name(&self) -> &'static str {
+        "webhook"
+    }
+
+    fn send<'a>(&'a self, donation: &'a Donation) -> SendFuture<'a> {
+        Box::pin(async move {
+            let body = serde_json::to_vec(donation)?;
+            let mut request = Request::post(&self.url).header("Content-Type", "application/json");
+            if let Some(ref token) = self.token {
+                request = request.header("Authorization", format!("Bearer {}", token));
+            }
+            let mut response = isahc::send_async(request.body(body)?).await?;
+            if response.status().is_success() {
+                info!("✅ Donation posted to {}", self.url);
+                Ok(())
+            } else {
+                Err(RequestError::Api {
+                    status: response.status().as_u16(),
+                    message: response.text().await.unwrap_or_default(),
+                })
+            }
+        })
+    }
+}
+
+const CSV_HEADER: &str =
+    "timestamp,receipt,username,amount,currency,fund_id,fund_name,auto_committed";
+
+/// One line per donation appended to a local CSV file, for spaces that
+/// keep their books in a spreadsheet.
+pub struct Csv {
+    path: String,
+}
+
+impl Csv {
+    fn append(&self, donation: &Donation) -> io::Result<()> {
+        let path = Path::new(&self.path);
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let new = !path.exists();
+        let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+        if new {
+            writeln!(file, "{}", CSV_HEADER)?;
+        }
+        writeln!(
+            file,
+            "{},{},{},{},{},{},{},{}",
+            donation.timestamp,
+            donation.receipt.map(receipt::format).unwrap_or_default(),
+            csv_quote(&donation.username),
+            donation.amount,
+            donation.currency,
+            donation.fund_id,
+            csv_quote(&donation.fund_name),
+            donation.auto_committed,
+        )?;
+        file.sync_data()
+    }
+}
+
+impl DonationBackend for Csv {
+    fn name(&self) -> &'static str {
+        "csv"
+    }
+
+    fn send<'a>(&'a self, donation: &'a Donation) -> SendFuture<'a> {
+        Box::pin(async move { Ok(self.append(donation)?) })
+    }
+}
+
+/// The donation as JSON on an MQTT topic. Publishing is fire-and-forget,
+/// so a donation only counts as sent while the broker is connected.
+pub struct Mqtt {
+    publisher: Publisher,
+    topic: String,
+}
+
+impl DonationBackend for Mqtt {
+    fn name(&self) -> &'static str {
+        "mqtt"
+    }
+
+    fn send<'a>(&'a self, donation: &'a Donation) -> SendFuture<'a> {
+        Box::pin(async move {
+            match self.publisher.connection() {
+                Some(Ok(())) => {
+                    let payload = serde_json::to_string(donation)?;
+                    self.publisher.publish(&self.topic, &payload, false);
+                    Ok(())
+                }
+                Some(Err(reason)) => {
+                    Err(io::Error::new(io::ErrorKind::NotConnected, reason).into())
+                }
+                None => Err(io::Error::new(
+                    io::ErrorKind::NotConnected,
+                    "broker not connected yet",
+                )
+                .into()),
+            }
+        })
+    }
+}
+
+/// Stands in for the selected backend in a dry run: each donation is logged
+/// and audited as `dry_run_donation` instead of sent.
+pub struct DryRun {
+    /// What would have been sent to, for the log.
+    target: &'static str,
+    db_path: String,
+}
+
+impl DonationBackend for DryRun {
+    fn name(&self) -> &'static str {
+        "dry run"
+    }
+
+    fn send<'a>(&'a self, donation: &'a Donation) -> SendFuture<'a> {
+        Box::pin(async move {
+            let payload = serde_json::to_string(donation)?;
+            info!(
+                "🧪 Dry run, not sending to the {} backend: {}",
+                self.target, payload
+            );
+            audit::record(&self.db_path, "dry_run_donation", &payload);
+            Ok(())
+        })
+    }
+}
+
+/// The backend selected under `[backend]`, or `None` (with a warning) when
+/// it's missing what it needs; donations are then only kept locally. A dry
+/// run always gets `DryRun`, even before the selected backend is set up.
+pub fn from_config(
+    config: &Config,
+    publisher: Option<&Publisher>,
+) -> Option<Rc<dyn DonationBackend>> {
+    let backend = &config.backend;
+    if config.dry_run {
+        let target = match backend.kind {
+            BackendKind::Gateway => "gateway",
+            BackendKind::Webhook => "webhook",
+            BackendKind::Csv => "csv",
+            BackendKind::Mqtt => "mqtt",
+        };
+        info!(
+            "🧪 Dry run, donations for the {} backend are only logged",
+            target
+        );
+        return Some(Rc::new(DryRun {
+            target,
+            db_path: config.stats_db_path.clone(),
+        }));
+    }
+    let selected: Rc<dyn DonationBackend> = match backend.kind {
+        BackendKind::Gateway => {
+            let Some(ref token) = config.token else {
+                warn!("⚠️  No token loaded, donations won't be sent to the gateway");
+                return None;
+            };
+            Rc::new(Gateway {
+                token: token.clone(),
+            })
+        }
+        BackendKind::Webhook => {
+            let Some(ref url) = backend.url else {
+                warn!("⚠️  backend.url not set, donations won't be posted anywhere");
+                return None;
+            };
+            Rc::new(Webhook {
+                url: url.clone(),
+                token: backend.token.clone(),
+            })
+        }
+        BackendKind::Csv => Rc::new(Csv {
+            path: backend.path.clone(),
+        }),
+        BackendKind::Mqtt => {
+            let Some(publisher) = publisher else {
+                warn!("⚠️  mqtt.host not set, donations won't be published");
+                return None;
+            };
+            Rc::new(Mqtt {
+                topic: publisher.topic(&backend.topic),
+                publisher: publisher.clone(),
+            })
+        }
+    };
+    info!("💸 Sending donations to the {} backend", selected.name());
+    Some(selected)
+}
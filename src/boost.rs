@@ -0,0 +1,95 @@
+//! Boost mode for fundraising pushes (a party, a stream): for a set time the
+//! kiosk wears a party theme, shows the running total for one fund on every
+//! page and publishes each donation over MQTT for a projector overlay.
+//! Started from Diagnostics or the listener's `POST /boost/start` and ends
+//! by itself when its time is up (see `boost_handler` in main.rs).
+
+use serde::{Deserialize, Serialize};
+
+use crate::config;
+
+/// A running boost, kept in the kv store so it outlives a restart.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Boost {
+    /// Only donations to this fund count; `None` counts every donation.
+    pub fund_id: Option<i32>,
+    pub fund_name: String,
+    pub started: u64,
+    pub ends: u64,
+    /// Raised since the boost started, in AMD.
+    pub total: i64,
+}
+
+/// What `POST /boost/start` may override from `[boost]`, all optional:
+/// `{"fund_id": 3, "fund_name": "Party", "minutes": 90}`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+pub struct Request {
+    #[serde(alias = "fundId")]
+    pub fund_id: Option<i32>,
+    #[serde(alias = "fundName")]
+    pub fund_name: Option<String>,
+    pub minutes: Option<u64>,
+}
+
+impl Request {
+    /// The request in a listener body; an empty body takes the config as
+    /// is. `None` when it isn't valid JSON.
+    pub fn parse(body: &str) -> Option<Request> {
+        if body.trim().is_empty() {
+            return Some(Request::default());
+        }
+        serde_json::from_str(body).ok()
+    }
+}
+
+impl Boost {
+    /// A boost starting `now`, from `[boost]` with the request's overrides.
+    pub fn start(config: &config::Boost, request: &Request, now: u64) -> Boost {
+        let minutes = request.minutes.unwrap_or(config.duration_mins);
+        Boost {
+            fund_id: request.fund_id.or(config.fund_id),
+            fund_name: request
+                .fund_name
+                .clone()
+                .unwrap_or_else(|| config.fund_name.clone()),
+            started: now,
+            ends: now + minutes * 60,
+            total: 0,
+        }
+    }
+
+    pub fn counts(&self, fund_id: i32) -> bool {
+        self.fund_id.is_none_or(|id| id == fund_id)
+    }
+
+    /// Whole minutes left, rounded up; 0 once it's over.
+    pub fn minutes_left(&self, now: u64) -> u64 {
+        self.ends.saturating_sub(now).div_ceil(60)
+    }
+
+    /// Retained state for the overlay, `{"active":false}` without a boost.
+    pub fn state(boost: Option<&Boost>) -> String {
+        match boost {
+            Some(boost) => serde_json::json!({
+                "active": true,
+                "fund": boost.fund_name,
+                "total": boost.total,
+                "ends": boost.ends,
+            }),
+            None => serde_json::json!({ "active": false }),
+        }
+        .to_string()
+    }
+
+    /// One donation for the overlay, with the total it brought the boost to.
+    pub fn event(&self, username: &str, amount: i32, timestamp: u64) -> String {
+        serde_json::json!({
+            "username": username,
+            "amount": amount,
+            "fund": self.fund_name,
+            "total": self.total,
+            "timestamp": timestamp,
+        })
+        .to_string()
+    }
+}
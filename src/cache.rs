@@ -0,0 +1,92 @@
+//! The last good answer to a gateway fetch, shown at once while a new one
+//! is fetched (stale-while-revalidate) and kept through an outage. With a
+//! kv key it also survives a restart, so a kiosk that boots while the
+//! gateway is down still offers yesterday's funds.
+
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+use crate::kv::{self, Key};
+
+/// A cached value and when it was fetched (Unix time).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Entry<T> {
+    pub value: T,
+    pub fetched: u64,
+}
+
+/// What the cache has for now.
+pub enum Lookup<'a, T> {
+    /// Recent enough that fetching again isn't worth it.
+    Fresh(&'a T),
+    /// Good to show, but fetch a new one; the `u64` is its age in seconds.
+    Stale(&'a T, u64),
+    /// Nothing, or too old to show.
+    Missing,
+}
+
+pub struct Cache<T: 'static> {
+    /// Younger than this is `Fresh`.
+    fresh: u64,
+    /// Older than this is `Missing`.
+    keep: u64,
+    entry: Option<Entry<T>>,
+    /// Where the entry is persisted: Stats.db and its kv key.
+    store: Option<(String, &'static Key<Entry<T>>)>,
+}
+
+impl<T: Serialize + DeserializeOwned> Cache<T> {
+    pub fn new(fresh: Duration, keep: Duration) -> Cache<T> {
+        Cache {
+            fresh: fresh.as_secs(),
+            keep: keep.as_secs(),
+            entry: None,
+            store: None,
+        }
+    }
+
+    /// Keeps the entry under `key` in the kv store, starting from what is
+    /// there already.
+    pub fn persisted(mut self, db_path: &str, key: &'static Key<Entry<T>>) -> Cache<T> {
+        self.entry = kv::get(db_path, key);
+        self.store = Some((db_path.to_string(), key));
+        self
+    }
+
+    pub fn get(&self, now: u64) -> Lookup<'_, T> {
+        let Some(entry) = &self.entry else {
+            return Lookup::Missing;
+        };
+        let age = now.saturating_sub(entry.fetched);
+        if age < self.fresh {
+            Lookup::Fresh(&entry.value)
+        } else if age < self.keep {
+            Lookup::Stale(&entry.value, age)
+        } else {
+            Lookup::Missing
+        }
+    }
+
+    /// The value just fetched.
+    pub fn put(&mut self, value: T, now: u64) {
+        let entry = Entry {
+            value,
+            fetched: now,
+        };
+        if let Some((db_path, key)) = &self.store {
+            kv::set(db_path, key, &entry);
+        }
+        self.entry = Some(entry);
+    }
+}
+
+impl<T> Lookup<'_, T> {
+    /// The value, fresh or stale.
+    pub fn value(&self) -> Option<&T> {
+        match self {
+            Lookup::Fresh(value) | Lookup::Stale(value, _) => Some(value),
+            Lookup::Missing => None,
+        }
+    }
+}
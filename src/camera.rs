@@ -7,7 +7,9 @@ use nokhwa::utils::{
 use std::path::PathBuf;
 use std::sync::mpsc::{Receiver, SyncSender, TryRecvError};
 use std::thread;
-use std::time::{Duration, Instant};
+use std::time::Duration;
+
+use crate::clock;
 
 /// Deterministic filename for a donation photo. Shared with `donation_log` so
 /// a logged donation (timestamp, username) can be re-associated with the
@@ -42,6 +44,21 @@ pub fn capture_donation_photo(photos_dir: &str, username: &str, timestamp: u64)
     });
 }
 
+/// Moves a donation photo over to a corrected username, or deletes it when
+/// the donor switched to anonymous. A photo still being taken is missed.
+pub fn rename_donation_photo(photos_dir: &str, timestamp: u64, from: &str, to: &str) {
+    let dir = PathBuf::from(photos_dir);
+    let old = dir.join(photo_filename(timestamp, from));
+    let result = if to == "anon" {
+        std::fs::remove_file(&old)
+    } else {
+        std::fs::rename(&old, dir.join(photo_filename(timestamp, to)))
+    };
+    if let Err(e) = result {
+        error!("📷 Failed to update donation photo {old:?}: {e}");
+    }
+}
+
 fn open_camera() -> Result<Camera, String> {
     let index = CameraIndex::Index(0);
     let requested =
@@ -154,7 +171,7 @@ pub fn spawn_preview(cmd_rx: Receiver<PreviewCommand>, frame_tx: SyncSender<Prev
                 continue;
             };
 
-            let frame_start = Instant::now();
+            let frame_start = clock::instant();
             match cam.frame().and_then(|f| f.decode_image::<RgbFormat>()) {
                 Ok(image) => {
                     let _ = frame_tx.try_send(PreviewFrame {
@@ -167,8 +184,8 @@ pub fn spawn_preview(cmd_rx: Receiver<PreviewCommand>, frame_tx: SyncSender<Prev
             }
             // Capture+decode already ate into the budget; only sleep the remainder
             // so the preview holds close to its target cadence instead of drifting.
-            if let Some(remaining) = PREVIEW_FRAME_INTERVAL.checked_sub(frame_start.elapsed()) {
-                thread::sleep(remaining);
+            if let Some(remaining) = PREVIEW_FRAME_INTERVAL.checked_sub(clock::since(frame_start)) {
+                clock::sleep(remaining);
             }
         }
     });
@@ -1,44 +1,106 @@
 use log::{debug, error, info, warn};
-use rusqlite::{Connection, Result as SqlResult};
 use serialport::SerialPort;
-use std::io::{Read, Write};
-use std::sync::{Arc, Mutex};
-use std::thread;
-use std::time::Duration;
+use std::fmt;
+use std::io::{self, Read, Write};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::time::{Duration, Instant};
 use thiserror::Error;
 
+use crate::acceptor::{BillAcceptor, BillCounters};
+use crate::error_code::ErrorCode;
+use crate::frame_log::{Direction, FrameLog};
+use crate::i18n::Message;
+use crate::{clock, money, preflight};
+
 // protocol constants
-const COMMAND_POLL: &[u8] = &[0x02, 0x03, 0x06, 0x33, 0xDA, 0x81];
-const COMMAND_RESET: &[u8] = &[0x02, 0x03, 0x06, 0x30, 0x41, 0xB3];
-const COMMAND_ENABLE: &[u8] = &[
-    0x02, 0x03, 0x0C, 0x34, 0xFF, 0xFF, 0xFF, 0x00, 0x00, 0x00, 0xB5, 0xC1,
-];
-const COMMAND_DISABLE: &[u8] = &[
-    0x02, 0x03, 0x0C, 0x34, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x17, 0x0C,
-];
-const ACK: &[u8] = &[0x02, 0x03, 0x06, 0x00, 0xC2, 0x82];
+const SYNC: u8 = 0x02;
+const ADDRESS_BILL_VALIDATOR: u8 = 0x03;
+
+// command codes
+const COMMAND_ACK: u8 = 0x00;
+const COMMAND_RESET: u8 = 0x30;
+const COMMAND_GET_STATUS: u8 = 0x31;
+const COMMAND_POLL: u8 = 0x33;
+// followed by the enable mask and the escrow mask, 3 bytes each
+const COMMAND_ENABLE_BILL_TYPES: u8 = 0x34;
+const COMMAND_STACK: u8 = 0x35;
+const COMMAND_RETURN: u8 = 0x36;
+// part number, serial number and asset number
+const COMMAND_IDENTIFICATION: u8 = 0x37;
+// keeps a bill in escrow for another 10 s
+const COMMAND_HOLD: u8 = 0x38;
+// 24 entries of 5 bytes: value digit, 3-letter country code, power of ten
+const COMMAND_GET_BILL_TABLE: u8 = 0x41;
+
+// bill type masks sent with ENABLE BILL TYPES
+const MASK_ALL: [u8; 3] = [0xFF, 0xFF, 0xFF];
+const MASK_NONE: [u8; 3] = [0x00, 0x00, 0x00];
 
 // status codes
+const STATUS_POWER_UP: u8 = 0x10;
 const STATUS_INITIALIZING: u8 = 0x13;
 const STATUS_DISABLED: u8 = 0x19;
 const STATUS_IDLING: u8 = 0x14;
 const STATUS_ACCEPTING: u8 = 0x15;
 const STATUS_STACKING: u8 = 0x17;
-#[allow(dead_code)]
+const STATUS_RETURNING: u8 = 0x18;
+const STATUS_HOLDING: u8 = 0x1A;
 const STATUS_STACKER_FULL: u8 = 0x41;
 const STATUS_STACKER_REMOVED: u8 = 0x42;
 const STATUS_JAM_IN_ACCEPTOR: u8 = 0x43;
 const STATUS_JAM_IN_STACKER: u8 = 0x44;
 const STATUS_FAILURE: u8 = 0x47;
 const STATUS_REJECTED: u8 = 0x1C;
+const STATUS_ESCROW: u8 = 0x80;
 const STATUS_BILL_STACKED: u8 = 0x81;
+const STATUS_BILL_RETURNED: u8 = 0x82;
+
+// HOLD is sent this often while a bill waits in escrow, well inside the
+// device's own 10 s
+const HOLD_INTERVAL: Duration = Duration::from_secs(5);
+
+// CCNET bus timing; all but T_RESPONSE are defaults for `Timings`
+// t_response: the device starts answering at most this long after a command
+const T_RESPONSE: Duration = Duration::from_millis(10);
+// longest gap between two bytes of one frame
+const T_INTER_BYTE: Duration = Duration::from_millis(5);
+// t_free: the bus stays quiet this long after a frame before the next command
+const T_FREE: Duration = Duration::from_millis(20);
+// USB serial adapters pass received bytes on in batches, up to 16 ms late;
+// allowed on top of the limits above before a delay counts as a violation
+const ADAPTER_LATENCY: Duration = Duration::from_millis(16);
+// an answer that hasn't started by then isn't coming
+const RESPONSE_TIMEOUT: Duration = Duration::from_millis(100);
+
+/// Serial timings the driver works to (`[serial]` in config). The defaults
+/// follow the CCNET spec; a flaky USB serial adapter may need more slack.
+#[derive(Debug, Clone, Copy)]
+pub struct Timings {
+    /// An answer that hasn't started by then isn't coming.
+    pub response_timeout: Duration,
+    /// Longest gap between two bytes of one frame.
+    pub inter_byte: Duration,
+    /// The bus stays quiet this long after a frame before the next command.
+    pub bus_free: Duration,
+    /// Allowed on top of the limits above for adapters that pass received
+    /// bytes on in batches.
+    pub adapter_latency: Duration,
+}
 
-// bill nominals (index-based)
-const NOMINAL_1000: u8 = 0x00;
-const NOMINAL_5000: u8 = 0x01;
-const NOMINAL_10000: u8 = 0x02;
-const NOMINAL_2000: u8 = 0x0C;
-const NOMINAL_20000: u8 = 0x03;
+impl Default for Timings {
+    fn default() -> Self {
+        Self {
+            response_timeout: RESPONSE_TIMEOUT,
+            inter_byte: T_INTER_BYTE,
+            bus_free: T_FREE,
+            adapter_latency: ADAPTER_LATENCY,
+        }
+    }
+}
+
+// bill types one ENABLE BILL TYPES mask covers
+const BILL_TYPES: usize = 24;
 
 // reject reasons
 const REJECT_INSERTION: u8 = 0x60;
@@ -52,11 +114,121 @@ const REJECT_OPERATION: u8 = 0x6A;
 // failure codes
 const FAILURE_55: u8 = 0x55;
 
+/// CCNET CRC-16 (polynomial 0x8408, reflected, starting at 0) of a frame
+/// without its CRC.
+pub fn crc16(bytes: &[u8]) -> u16 {
+    let mut crc: u16 = 0;
+    for byte in bytes {
+        crc ^= u16::from(*byte);
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ 0x8408
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    crc
+}
+
+/// A frame from the host to the bill validator: sync, address, length,
+/// command, data and the CRC, low byte first.
+pub fn packet(command: u8, data: &[u8]) -> Vec<u8> {
+    let mut frame = vec![
+        SYNC,
+        ADDRESS_BILL_VALIDATOR,
+        (data.len() + 6) as u8,
+        command,
+    ];
+    frame.extend_from_slice(data);
+    frame.extend_from_slice(&crc16(&frame).to_le_bytes());
+    frame
+}
+
+fn is_ack(response: &[u8]) -> bool {
+    response == packet(COMMAND_ACK, &[])
+}
+
+/// Ways an exchange with the device can break the CCNET timing.
+#[derive(Debug, Clone, Copy)]
+enum Violation {
+    /// The answer started after `T_RESPONSE`.
+    Late,
+    /// No answer at all.
+    Missing,
+    /// The answer stopped mid-frame, or didn't start with a frame.
+    Malformed,
+    BadCrc,
+    /// Bytes waiting before a command: an answer that came after we gave up
+    /// on it, or one the device sent again because our ACK came too late.
+    Stale,
+}
+
+/// Counted since startup, across driver restarts; see `timing_violations`.
+static VIOLATIONS: [AtomicU32; 5] = [const { AtomicU32::new(0) }; 5];
+
+fn count(violation: Violation) {
+    VIOLATIONS[violation as usize].fetch_add(1, Ordering::Relaxed);
+}
+
+/// CCNET timing violations since startup, by kind, for Diagnostics.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TimingViolations {
+    pub late: u32,
+    pub missing: u32,
+    pub malformed: u32,
+    pub bad_crc: u32,
+    pub stale: u32,
+}
+
+impl TimingViolations {
+    pub fn total(&self) -> u32 {
+        self.late + self.missing + self.malformed + self.bad_crc + self.stale
+    }
+}
+
+/// "2 late, 1 resent": the kinds seen, or "none".
+impl fmt::Display for TimingViolations {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let kinds = [
+            (self.late, "late"),
+            (self.missing, "unanswered"),
+            (self.malformed, "malformed"),
+            (self.bad_crc, "bad CRC"),
+            (self.stale, "resent"),
+        ];
+        let seen: Vec<String> = kinds
+            .iter()
+            .filter(|(n, _)| *n > 0)
+            .map(|(n, kind)| format!("{} {}", n, kind))
+            .collect();
+        if seen.is_empty() {
+            write!(f, "none")
+        } else {
+            write!(f, "{}", seen.join(", "))
+        }
+    }
+}
+
+pub fn timing_violations() -> TimingViolations {
+    let get = |violation: Violation| VIOLATIONS[violation as usize].load(Ordering::Relaxed);
+    TimingViolations {
+        late: get(Violation::Late),
+        missing: get(Violation::Missing),
+        malformed: get(Violation::Malformed),
+        bad_crc: get(Violation::BadCrc),
+        stale: get(Violation::Stale),
+    }
+}
+
 #[derive(Debug, Error)]
 pub enum CashCodeError {
     #[error("serial port error: {0}")]
     SerialPort(#[from] serialport::Error),
 
+    #[error("{0}")]
+    Port(#[from] preflight::Problem),
+
     #[error("io error: {0}")]
     Io(#[from] std::io::Error),
 
@@ -76,218 +248,758 @@ pub enum CashCodeError {
     DeviceError(String),
 }
 
+impl CashCodeError {
+    pub fn code(&self) -> ErrorCode {
+        match self {
+            CashCodeError::SerialPort(_) | CashCodeError::Port(_) => ErrorCode::SerialOpen,
+            CashCodeError::Io(_)
+            | CashCodeError::InvalidResponse(_)
+            | CashCodeError::UnexpectedAck => ErrorCode::SerialIo,
+            CashCodeError::Database(_) => ErrorCode::Database,
+            CashCodeError::DeviceError(_) => ErrorCode::BillFailure,
+        }
+    }
+}
+
+/// The device's answer to GET STATUS: which bill types it has enabled and
+/// which it checks with high security. Bit n of the 24-bit masks is bill
+/// type n, first byte most significant.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
-pub enum BillNominal {
-    Dram1000 = 1000,
-    Dram2000 = 2000,
-    Dram5000 = 5000,
-    Dram10000 = 10000,
-    Dram20000 = 20000,
+pub struct DeviceStatus {
+    pub enabled: [u8; 3],
+    pub security: [u8; 3],
 }
 
-impl BillNominal {
-    fn from_code(code: u8) -> Option<Self> {
-        match code {
-            NOMINAL_1000 => Some(BillNominal::Dram1000),
-            NOMINAL_2000 => Some(BillNominal::Dram2000),
-            NOMINAL_5000 => Some(BillNominal::Dram5000),
-            NOMINAL_10000 => Some(BillNominal::Dram10000),
-            NOMINAL_20000 => Some(BillNominal::Dram20000),
-            _ => None,
+/// The device's answer to IDENTIFICATION.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Identification {
+    pub part_number: String,
+    pub serial_number: String,
+    pub asset_number: [u8; 7],
+}
+
+impl fmt::Display for Identification {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} serial {} asset {:02X?}",
+            self.part_number, self.serial_number, self.asset_number
+        )
+    }
+}
+
+/// IDENTIFICATION data: 15 bytes of part number and 12 of serial number
+/// (ASCII, space padded), then 7 bytes of asset number.
+pub fn parse_identification(data: &[u8]) -> Option<Identification> {
+    if data.len() < 34 {
+        return None;
+    }
+    let text = |bytes: &[u8]| {
+        String::from_utf8_lossy(bytes)
+            .trim_matches(|c: char| c == ' ' || c == '\0')
+            .to_string()
+    };
+    Some(Identification {
+        part_number: text(&data[..15]),
+        serial_number: text(&data[15..27]),
+        asset_number: data[27..34].try_into().ok()?,
+    })
+}
+
+/// The device's own bill table (GET BILL TABLE), by bill type code.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct BillTable {
+    /// Bill values, 0 for unused codes and bills worth less than a whole
+    /// unit, which can't be counted.
+    pub values: Vec<i32>,
+    /// Country codes the firmware gives its bills, e.g. "ARM", "USA".
+    pub countries: Vec<String>,
+}
+
+/// GET BILL TABLE data: 24 entries of 5 bytes. The value is the first
+/// byte times ten to the power in the last, which counts down instead when
+/// its top bit is set (fractions, skipped).
+pub fn parse_bill_table(data: &[u8]) -> Option<BillTable> {
+    if data.len() < BILL_TYPES * 5 {
+        return None;
+    }
+    let mut table = BillTable::default();
+    for (code, entry) in data.chunks_exact(5).take(BILL_TYPES).enumerate() {
+        let (digit, country, power) = (entry[0], &entry[1..4], entry[4]);
+        let value = if digit == 0 || power & 0x80 != 0 {
+            0
+        } else {
+            10i32
+                .checked_pow(u32::from(power))
+                .and_then(|scale| scale.checked_mul(i32::from(digit)))
+                .unwrap_or(0)
+        };
+        if value == 0 && digit != 0 {
+            warn!(
+                "bill type {} ({:02X?}) can't be counted in whole units, ignored",
+                code, entry
+            );
+        }
+        let country = String::from_utf8_lossy(country).trim().to_string();
+        if value > 0 && !table.countries.contains(&country) {
+            table.countries.push(country);
         }
+        table.values.push(value);
     }
+    while table.values.last() == Some(&0) {
+        table.values.pop();
+    }
+    Some(table)
+}
 
-    fn value(&self) -> i32 {
-        *self as i32
+/// A bill's value in the kiosk's currency, looked up by its bill type in
+/// the bill table (see `CashCode::set_bill_types`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BillNominal(i32);
+
+impl BillNominal {
+    /// A bill worth `value`, as a driver's bill table has it.
+    pub fn new(value: i32) -> Self {
+        Self(value)
+    }
+
+    pub fn value(&self) -> i32 {
+        self.0
     }
 }
 
+/// ENABLE BILL TYPES mask for bill types `codes`: bit n stands for bill
+/// type n, most significant byte first.
+pub fn bill_type_mask(codes: &[u8]) -> [u8; 3] {
+    let bits = codes.iter().fold(0u32, |bits, code| bits | 1 << code);
+    let [_, high, middle, low] = bits.to_be_bytes();
+    [high, middle, low]
+}
+
+/// What the bill acceptor can do right now, from its last poll answer.
+/// Only `Idle` and `Disabled` can take money (once enabled).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DeviceState {
+    /// Powering up or resetting, or not polled yet.
+    Initializing,
+    /// Enabled and waiting for (or taking in) a bill.
+    Idle,
+    Disabled,
+    StackerRemoved,
+    StackerFull,
+    Jammed,
+    /// The device reports a failure, or the driver isn't running.
+    Offline,
+}
+
 #[derive(Debug, Clone)]
 pub enum BillEvent {
     Accepted(BillNominal),
-    Rejected(String),
+    Rejected(Message),
     StackerRemoved,
     StackerReplaced,
-    Jam(String),
-    Error(String),
+    /// The device reports its stacker full; it takes no bills until emptied.
+    StackerFull,
+    Jam(Message),
+    Error(Message),
     /// Lifecycle / device-state update for the diagnostics page.
     /// level: 0 = neutral · 1 = ok · 2 = warn · 3 = error
     Status(String, i32),
+    /// Device finished initialising and can be enabled.
+    Ready,
+    /// The driver failed; it is started again after this long.
+    Offline(Duration),
+    /// The device's state changed, see `CashCode::state`.
+    State(DeviceState),
+    /// A bill started (true) or finished (false) going in; while one is,
+    /// its credit may still arrive.
+    InTransit(bool),
+    /// Test-bill mode: a bill was recognised (nominal, raw bill-type code)
+    /// and is being handed back. Nothing is credited.
+    TestBill(Option<BillNominal>, u8),
+    /// Escrow mode: a bill waits to be stacked or returned, see
+    /// `CashCode::stack` and `CashCode::return_bill`.
+    Escrow(BillNominal),
+    /// The bill held in escrow went back to the donor.
+    Returned,
+}
+
+/// A bill held in escrow until the donor decides.
+struct Held {
+    since: Instant,
+    last_hold: Instant,
 }
 
 pub struct CashCode {
     port: Box<dyn SerialPort>,
     stacker_removed: bool,
-    db: Arc<Mutex<Connection>>,
+    stacker_full: bool,
+    /// Status byte from the most recent poll answer, cleared on reset.
+    last_status: Option<u8>,
+    /// Bills are held in escrow and returned instead of stacked.
+    test_mode: bool,
+    /// Dry run: bills are held in escrow, reported as accepted and
+    /// returned, never stacked or recorded in the ledger.
+    dry_run: bool,
+    /// Escrow mode: how long a bill is held for the donor to accept it
+    /// before it is returned. `None` stacks bills straight away.
+    escrow: Option<Duration>,
+    held: Option<Held>,
+    /// A RETURN was sent for the held bill; its BILL RETURNED is reported.
+    returning: bool,
+    /// Bill values by bill type code, 0 for codes that aren't a bill (the
+    /// `bill_types` table in config).
+    bill_types: Vec<i32>,
+    /// Bill types taken while enabled (`accepted_nominals` in config).
+    accepted_mask: [u8; 3],
+    /// Bill types we last asked the device to enable.
+    enabled_mask: [u8; 3],
+    /// When the last command went out, for timing its answer.
+    sent_at: Instant,
+    /// No command goes out before this (`bus_free` after the last frame).
+    bus_free_at: Instant,
+    timings: Timings,
+    counters: BillCounters,
+    frame_log: Arc<FrameLog>,
 }
 
 impl CashCode {
-    pub fn new(port_path: &str, db_path: &str) -> Result<Self, CashCodeError> {
+    pub fn new(
+        port_path: &str,
+        db_path: &str,
+        frame_log: Arc<FrameLog>,
+    ) -> Result<Self, CashCodeError> {
         info!("opening serial port: {}", port_path);
 
+        preflight::check_serial(port_path)?;
         let port = serialport::new(port_path, 19200)
             .timeout(Duration::from_millis(100))
             .open()?;
 
-        info!("opening database: {}", db_path);
-        let db = Connection::open(db_path)?;
-
-        // initialize database
-        Self::init_database(&db)?;
+        let counters = BillCounters::open(db_path)?;
 
-        Ok(CashCode {
+        let mut cashcode = CashCode {
             port,
             stacker_removed: false,
-            db: Arc::new(Mutex::new(db)),
-        })
+            stacker_full: false,
+            last_status: None,
+            test_mode: false,
+            dry_run: false,
+            escrow: None,
+            held: None,
+            returning: false,
+            bill_types: Vec::new(),
+            accepted_mask: MASK_NONE,
+            enabled_mask: MASK_NONE,
+            sent_at: clock::instant(),
+            bus_free_at: clock::instant(),
+            timings: Timings::default(),
+            counters,
+            frame_log,
+        };
+        cashcode.set_bill_types(money::bill_table(money::currency()))?;
+        Ok(cashcode)
     }
 
-    fn init_database(db: &Connection) -> SqlResult<()> {
-        db.execute(
-            "CREATE TABLE IF NOT EXISTS accepted_bills (
-                nominal INTEGER PRIMARY KEY,
-                quantity INTEGER NOT NULL
-            )",
-            [],
-        )?;
-
-        let nominals = [1000, 2000, 5000, 10000, 20000];
-        for nominal in nominals {
-            db.execute(
-                "INSERT OR IGNORE INTO accepted_bills (nominal, quantity) VALUES (?1, 0)",
-                [nominal],
-            )?;
+    /// Sends `command` with its `data` once the bus has been quiet for
+    /// `bus_free`. Anything the device sent meanwhile is dropped first, so it
+    /// can't be taken for the answer.
+    fn send_packet(&mut self, command: u8, data: &[u8]) -> Result<(), CashCodeError> {
+        self.drop_stale()?;
+        let wait = self.bus_free_at.saturating_duration_since(clock::instant());
+        if !wait.is_zero() {
+            clock::sleep(wait);
         }
-
+        self.write_frame(&packet(command, data))?;
+        self.sent_at = clock::instant();
         Ok(())
     }
 
-    fn send_command(&mut self, command: &[u8]) -> Result<(), CashCodeError> {
-        self.port.write_all(command)?;
-        thread::sleep(Duration::from_millis(20));
+    /// Writes `frame` and waits until its last byte is on the wire, so the
+    /// answer's delay is measured from there.
+    fn write_frame(&mut self, frame: &[u8]) -> Result<(), CashCodeError> {
+        self.frame_log.record(Direction::Tx, frame);
+        self.port.write_all(frame)?;
+        self.port.flush()?;
+        self.bus_free_at = clock::instant() + self.timings.bus_free;
         Ok(())
     }
 
+    /// Reads the answer to the command just sent: one whole frame with a
+    /// good CRC, or nothing. Late, missing and broken answers are counted
+    /// (see `timing_violations`).
     fn read_response(&mut self) -> Result<Vec<u8>, CashCodeError> {
-        let mut buffer = vec![0u8; 256];
-        thread::sleep(Duration::from_millis(20));
+        let mut frame = Vec::new();
+        let mut chunk = [0u8; 256];
+        let mut last_byte = self.sent_at;
+        let Timings {
+            response_timeout,
+            inter_byte,
+            adapter_latency,
+            ..
+        } = self.timings;
+        loop {
+            let limit = if frame.is_empty() {
+                response_timeout
+            } else {
+                inter_byte + adapter_latency
+            };
+            let Some(left) = limit.checked_sub(clock::since(last_byte)) else {
+                break;
+            };
+            self.port.set_timeout(left)?;
+            let n = match self.port.read(&mut chunk) {
+                Ok(0) => break,
+                Ok(n) => n,
+                Err(e) if e.kind() == io::ErrorKind::TimedOut => break,
+                Err(e) => return Err(e.into()),
+            };
+            if frame.is_empty() && clock::since(self.sent_at) > T_RESPONSE + adapter_latency {
+                count(Violation::Late);
+                debug!(
+                    "answer started after {:?}",
+                    clock::since(self.sent_at) - adapter_latency
+                );
+            }
+            frame.extend_from_slice(&chunk[..n]);
+            last_byte = clock::instant();
+            if frame.len() >= 3 && frame.len() >= usize::from(frame[2]) {
+                break;
+            }
+        }
+        self.bus_free_at = clock::instant() + self.timings.bus_free;
 
-        let bytes_available = self.port.bytes_to_read()? as usize;
-        if bytes_available == 0 {
+        if frame.is_empty() {
+            count(Violation::Missing);
+            return Ok(frame);
+        }
+        self.frame_log.record(Direction::Rx, &frame);
+        let length = frame.get(2).map_or(0, |length| usize::from(*length));
+        if frame[0] != SYNC || length < 6 || frame.len() < length {
+            count(Violation::Malformed);
+            debug!("malformed answer: {:02X?}", frame);
             return Ok(vec![]);
         }
-
-        let bytes_read = self.port.read(&mut buffer[..bytes_available])?;
-        Ok(buffer[..bytes_read].to_vec())
+        if frame.len() > length {
+            // the device sending it again, or something else: dropped
+            debug!("trailing bytes after answer: {:02X?}", &frame[length..]);
+            count(Violation::Stale);
+            frame.truncate(length);
+        }
+        let crc = u16::from_le_bytes([frame[length - 2], frame[length - 1]]);
+        if crc16(&frame[..length - 2]) != crc {
+            count(Violation::BadCrc);
+            debug!("answer with a bad CRC: {:02X?}", frame);
+            return Ok(vec![]);
+        }
+        Ok(frame)
     }
 
-    fn clear_buffer(&mut self) -> Result<(), CashCodeError> {
+    /// Drops whatever came in since the last answer was read.
+    fn drop_stale(&mut self) -> Result<(), CashCodeError> {
         let bytes_available = self.port.bytes_to_read()? as usize;
         if bytes_available > 0 {
             let mut buffer = vec![0u8; bytes_available];
             self.port.read_exact(&mut buffer)?;
+            self.frame_log.record(Direction::Rx, &buffer);
+            count(Violation::Stale);
+            debug!("dropped stale bytes: {:02X?}", buffer);
         }
         Ok(())
     }
 
+    /// Acknowledges an answer as soon as it is known to be whole, before
+    /// anything is done with it, so the device doesn't send it again.
     fn send_ack(&mut self) -> Result<(), CashCodeError> {
-        self.port.write_all(ACK)?;
-        Ok(())
+        self.write_frame(&packet(COMMAND_ACK, &[]))
     }
 
-    pub fn reset(&mut self) -> Result<(), CashCodeError> {
-        info!("resetting bill acceptor...");
-        self.send_command(COMMAND_RESET)?;
-
+    /// Reads the answer to a command the device only acknowledges. Anything
+    /// else (ILLEGAL COMMAND, a status in the way) is logged, and
+    /// acknowledged in turn. Returns whether it was the ACK.
+    fn expect_ack(&mut self, what: &str) -> Result<bool, CashCodeError> {
         let response = self.read_response()?;
-        if response == ACK {
-            info!("bill acceptor reset ACK");
-            self.clear_buffer()?;
-        } else {
-            warn!("unexpected response to reset: {:02X?}", response);
+        if is_ack(&response) {
+            return Ok(true);
+        }
+        warn!("unexpected response to {}: {:02X?}", what, response);
+        if !response.is_empty() {
             self.send_ack()?;
-            self.clear_buffer()?;
         }
+        Ok(false)
+    }
+
+    /// Escrow mode: each bill is held until `stack` or `return_bill`, or
+    /// returned after `timeout`. Takes effect on the next `enable`.
+    pub fn set_escrow(&mut self, timeout: Option<Duration>) {
+        self.escrow = timeout;
+    }
+
+    /// Serial timings other than the CCNET defaults; takes effect on the
+    /// next command.
+    pub fn set_timings(&mut self, timings: Timings) {
+        self.timings = timings;
+    }
+
+    /// Dry run: each bill is counted as `Accepted` and handed back instead
+    /// of stacked, so a new deployment can be tried with real bills. Wins
+    /// over escrow mode; takes effect on the next `enable`.
+    pub fn set_dry_run(&mut self, on: bool) {
+        self.dry_run = on;
+    }
+
+    /// Codes of the bills in the table whose value passes `keep`.
+    fn bill_codes(&self, keep: impl Fn(i32) -> bool) -> Vec<u8> {
+        (0..)
+            .zip(&self.bill_types)
+            .filter(|(_, value)| **value > 0 && keep(**value))
+            .map(|(code, _)| code)
+            .collect()
+    }
 
+    fn nominal(&self, code: u8) -> Option<BillNominal> {
+        self.bill_types
+            .get(code as usize)
+            .filter(|value| **value > 0)
+            .map(|value| BillNominal(*value))
+    }
+
+    fn escrow_command(&mut self, command: u8, what: &str) -> Result<(), CashCodeError> {
+        self.send_packet(command, &[])?;
+        self.expect_ack(what)?;
         Ok(())
     }
 
-    pub fn enable(&mut self) -> Result<(), CashCodeError> {
-        info!("enabling bill acceptance...");
-        self.send_command(COMMAND_ENABLE)?;
+    /// ENABLE BILL TYPES: which bill types are taken, and which of those
+    /// are held in escrow. The answer is left for the caller to read.
+    fn enable_bill_types(
+        &mut self,
+        enabled: [u8; 3],
+        escrow: [u8; 3],
+    ) -> Result<(), CashCodeError> {
+        self.enabled_mask = enabled;
+        let mut data = [0u8; 6];
+        data[..3].copy_from_slice(&enabled);
+        data[3..].copy_from_slice(&escrow);
+        self.send_packet(COMMAND_ENABLE_BILL_TYPES, &data)
+    }
 
-        let response = self.read_response()?;
-        if response == ACK {
-            info!("bill acceptance enabled");
-            self.clear_buffer()?;
-        } else {
-            warn!("unexpected response to enable: {:02X?}", response);
-            self.send_ack()?;
-            self.clear_buffer()?;
+    /// ESCROW POSITION in escrow mode: the first poll reports the bill, the
+    /// following ones keep it held until the donor decides or `timeout`.
+    fn escrow_poll(
+        &mut self,
+        nominal_code: u8,
+        timeout: Duration,
+    ) -> Result<Option<BillEvent>, CashCodeError> {
+        let Some(held) = &mut self.held else {
+            let Some(nominal) = self.nominal(nominal_code) else {
+                warn!(
+                    "bill in escrow with unknown nominal: 0x{:02X}, returning",
+                    nominal_code
+                );
+                self.returning = true;
+                self.escrow_command(COMMAND_RETURN, "return")?;
+                return Ok(Some(BillEvent::Error(Message::with(
+                    "bill.unknown_nominal",
+                    &[&format!("{:02X}", nominal_code)],
+                ))));
+            };
+            info!(
+                "bill in escrow: {} {}, waiting for the donor",
+                nominal.value(),
+                money::currency()
+            );
+            let now = clock::instant();
+            self.held = Some(Held {
+                since: now,
+                last_hold: now,
+            });
+            return Ok(Some(BillEvent::Escrow(nominal)));
+        };
+
+        if clock::since(held.since) >= timeout {
+            info!("no answer for the bill in escrow within {:?}", timeout);
+            self.return_bill()?;
+        } else if clock::since(held.last_hold) >= HOLD_INTERVAL {
+            held.last_hold = clock::instant();
+            self.escrow_command(COMMAND_HOLD, "hold")?;
         }
+        Ok(None)
+    }
+}
 
+impl BillAcceptor for CashCode {
+    fn reset(&mut self) -> Result<(), CashCodeError> {
+        info!("resetting bill acceptor...");
+        self.last_status = None;
+        // the device hands back a bill it was holding
+        self.held = None;
+        // the device comes back up with nothing enabled
+        self.enabled_mask = MASK_NONE;
+        self.send_packet(COMMAND_RESET, &[])?;
+        if self.expect_ack("reset")? {
+            info!("bill acceptor reset ACK");
+        }
         Ok(())
     }
 
-    pub fn disable(&mut self) -> Result<(), CashCodeError> {
-        info!("disabling bill acceptance...");
-        self.send_command(COMMAND_DISABLE)?;
+    /// Polls until the device reports it has finished initialising (disabled
+    /// or idling) instead of sleeping a fixed time after a reset. Returns
+    /// `false` if it is still not ready after `timeout`.
+    fn wait_ready(&mut self, timeout: Duration) -> Result<bool, CashCodeError> {
+        let deadline = clock::instant() + timeout;
+        while clock::instant() < deadline {
+            self.poll()?;
+            if matches!(self.last_status, Some(STATUS_DISABLED | STATUS_IDLING)) {
+                return Ok(true);
+            }
+            clock::sleep(Duration::from_millis(200));
+        }
+        Ok(false)
+    }
 
-        let response = self.read_response()?;
-        if response == ACK {
-            info!("bill acceptance disabled");
-            self.clear_buffer()?;
+    /// Bill values by bill type code as the acceptor's firmware numbers
+    /// them, 0 for unused codes (`read_bill_table` has the device's own).
+    /// Takes every bill in the table until `set_accepted_nominals` narrows
+    /// it down.
+    fn set_bill_types(&mut self, values: &[i32]) -> Result<(), CashCodeError> {
+        if values.len() > BILL_TYPES {
+            warn!(
+                "bill_types: only {} bill types exist, the rest are ignored",
+                BILL_TYPES
+            );
+        }
+        self.bill_types = values.iter().take(BILL_TYPES).copied().collect();
+        self.accepted_mask = bill_type_mask(&self.bill_codes(|_| true));
+        self.counters.add_values(&self.bill_types)
+    }
+
+    /// Takes only these bills (in the kiosk's currency) from the next
+    /// `enable` on; the device refuses the others. Values that aren't in
+    /// the bill table are ignored.
+    fn set_accepted_nominals(&mut self, values: &[i32]) {
+        for value in values {
+            if !self.bill_types.contains(value) || *value <= 0 {
+                warn!("accepted_nominals: {} is not a bill, ignored", value);
+            }
+        }
+        let codes = self.bill_codes(|value| values.contains(&value));
+        if codes.is_empty() {
+            warn!("accepted_nominals takes no bill at all, the acceptor stays off");
+        } else if codes.len() < self.bill_codes(|_| true).len() {
+            let accepted: Vec<i32> = codes
+                .iter()
+                .map(|code| self.bill_types[*code as usize])
+                .collect();
+            info!("accepting only {:?} {} bills", accepted, money::currency());
+        }
+        self.accepted_mask = bill_type_mask(&codes);
+    }
+
+    fn enable(&mut self) -> Result<(), CashCodeError> {
+        info!("enabling bill acceptance...");
+        let escrow = if self.escrow.is_some() || self.dry_run {
+            self.accepted_mask
         } else {
-            warn!("unexpected response to disable: {:02X?}", response);
-            self.send_ack()?;
-            self.clear_buffer()?;
+            MASK_NONE
+        };
+        self.enable_bill_types(self.accepted_mask, escrow)?;
+        if self.expect_ack("enable")? {
+            info!("bill acceptance enabled");
+        }
+        Ok(())
+    }
+
+    /// Test-bill mode for checking a serviced validator: bills are
+    /// recognised, reported as `BillEvent::TestBill` and returned, never
+    /// stacked or counted. Turning it off leaves the acceptor disabled.
+    fn set_test_mode(&mut self, on: bool) -> Result<(), CashCodeError> {
+        self.test_mode = on;
+        if !on {
+            info!("test-bill mode off");
+            return self.disable();
+        }
+
+        info!("test-bill mode on, enabling with escrow...");
+        self.enable_bill_types(MASK_ALL, MASK_ALL)?;
+        if self.expect_ack("test-mode enable")? {
+            info!("bill acceptance enabled (test mode)");
         }
+        Ok(())
+    }
 
+    fn disable(&mut self) -> Result<(), CashCodeError> {
+        // the donor left without accepting it
+        if self.held.is_some() {
+            self.return_bill()?;
+        }
+        info!("disabling bill acceptance...");
+        self.enable_bill_types(MASK_NONE, MASK_NONE)?;
+        if self.expect_ack("disable")? {
+            info!("bill acceptance disabled");
+        }
         Ok(())
     }
 
-    pub fn poll(&mut self) -> Result<Option<BillEvent>, CashCodeError> {
-        self.send_command(COMMAND_POLL)?;
+    /// Stacks the bill held in escrow; its `Accepted` follows from a poll.
+    fn stack(&mut self) -> Result<(), CashCodeError> {
+        if self.held.take().is_none() {
+            warn!("stack requested with no bill in escrow");
+            return Ok(());
+        }
+        info!("stacking bill from escrow");
+        self.escrow_command(COMMAND_STACK, "stack")
+    }
 
+    /// Hands the bill held in escrow back; `Returned` follows from a poll.
+    fn return_bill(&mut self) -> Result<(), CashCodeError> {
+        if self.held.take().is_none() {
+            warn!("return requested with no bill in escrow");
+            return Ok(());
+        }
+        info!("returning bill from escrow");
+        self.returning = true;
+        self.escrow_command(COMMAND_RETURN, "return")
+    }
+
+    /// Asks the device which bill types it really has enabled. `None` if
+    /// the answer was missing or malformed.
+    fn get_status(&mut self) -> Result<Option<DeviceStatus>, CashCodeError> {
+        self.send_packet(COMMAND_GET_STATUS, &[])?;
         let response = self.read_response()?;
+        if response.is_empty() {
+            return Ok(None);
+        }
+        self.send_ack()?;
 
-        if response.len() < 2 {
+        // 02 03 0B, 3 bytes enabled, 3 bytes security, 2 bytes CRC
+        if response[1] != ADDRESS_BILL_VALIDATOR || response[2] != 0x0B {
+            debug!("unexpected GET STATUS response: {:02X?}", response);
             return Ok(None);
         }
 
-        // check for CashCode protocol header
-        if response[0] != 0x02 || response[1] != 0x03 {
-            if !response.is_empty() {
-                debug!("unknown message received: {:02X?}", response);
-            }
+        Ok(Some(DeviceStatus {
+            enabled: [response[3], response[4], response[5]],
+            security: [response[6], response[7], response[8]],
+        }))
+    }
+
+    /// Asks the device what it is. `None` when it doesn't answer in kind.
+    fn identify(&mut self) -> Result<Option<Identification>, CashCodeError> {
+        self.send_packet(COMMAND_IDENTIFICATION, &[])?;
+        let response = self.read_response()?;
+        if response.is_empty() {
             return Ok(None);
         }
+        self.send_ack()?;
+        let identification = parse_identification(&response[3..response.len() - 2]);
+        if identification.is_none() {
+            debug!("unexpected IDENTIFICATION response: {:02X?}", response);
+        }
+        Ok(identification)
+    }
+
+    /// Reads the bill table the firmware was set up with, which tells
+    /// what each bill type code reported in a credit is worth. `None` when
+    /// the device doesn't answer in kind.
+    fn read_bill_table(&mut self) -> Result<Option<BillTable>, CashCodeError> {
+        self.send_packet(COMMAND_GET_BILL_TABLE, &[])?;
+        let response = self.read_response()?;
+        if response.is_empty() {
+            return Ok(None);
+        }
+        self.send_ack()?;
+        let table = parse_bill_table(&response[3..response.len() - 2]);
+        if table.is_none() {
+            debug!("unexpected GET BILL TABLE response: {:02X?}", response);
+        }
+        Ok(table)
+    }
+
+    /// Bill types the device should have enabled right now.
+    fn expected_mask(&self) -> [u8; 3] {
+        self.enabled_mask
+    }
+
+    /// Whether the driver has any bill type enabled.
+    fn is_enabled(&self) -> bool {
+        self.enabled_mask != MASK_NONE
+    }
+
+    fn in_test_mode(&self) -> bool {
+        self.test_mode
+    }
+
+    /// Whether the device is between bills, so a status query won't get in
+    /// the way of one being accepted.
+    fn is_idle(&self) -> bool {
+        matches!(self.last_status, Some(STATUS_DISABLED | STATUS_IDLING))
+    }
+
+    /// Whether a bill is being taken in, so a credit for it may follow.
+    fn bill_in_transit(&self) -> bool {
+        matches!(
+            self.last_status,
+            Some(STATUS_ACCEPTING | STATUS_STACKING | STATUS_HOLDING | STATUS_ESCROW)
+        )
+    }
+
+    fn state(&self) -> DeviceState {
+        match self.last_status {
+            None | Some(STATUS_POWER_UP | STATUS_INITIALIZING) => DeviceState::Initializing,
+            Some(STATUS_DISABLED) => DeviceState::Disabled,
+            Some(STATUS_STACKER_REMOVED) => DeviceState::StackerRemoved,
+            Some(STATUS_STACKER_FULL) => DeviceState::StackerFull,
+            Some(STATUS_JAM_IN_ACCEPTOR | STATUS_JAM_IN_STACKER) => DeviceState::Jammed,
+            Some(STATUS_FAILURE) => DeviceState::Offline,
+            // idling, a bill on its way in or out, or a bill's outcome
+            Some(_) => DeviceState::Idle,
+        }
+    }
 
-        if response.len() < 4 {
+    /// Sends the current enable mask again, after the device dropped it.
+    fn reapply_mask(&mut self) -> Result<(), CashCodeError> {
+        if self.enabled_mask == MASK_NONE {
+            self.disable()
+        } else if self.test_mode {
+            self.set_test_mode(true)
+        } else {
+            self.enable()
+        }
+    }
+
+    fn poll(&mut self) -> Result<Option<BillEvent>, CashCodeError> {
+        self.send_packet(COMMAND_POLL, &[])?;
+
+        let response = self.read_response()?;
+        if response.is_empty() {
             return Ok(None);
         }
+        if response[1] != ADDRESS_BILL_VALIDATOR {
+            debug!("unknown message received: {:02X?}", response);
+            return Ok(None);
+        }
+        self.send_ack()?;
 
-        let _length = response[2];
         let status = response[3];
+        // the byte after the status, for the ones that carry one
+        let detail = response[4..response.len() - 2].first().copied();
+        self.last_status = Some(status);
 
         let event = match status {
-            STATUS_INITIALIZING => {
-                self.send_ack()?;
+            STATUS_POWER_UP | STATUS_INITIALIZING => {
                 info!("bill acceptor initialized");
-                self.clear_buffer()?;
                 None
             }
 
             STATUS_DISABLED => {
-                self.send_ack()?;
                 debug!("bill acceptor is disabled");
-                self.clear_buffer()?;
 
                 // check if stacker was recently removed and is now back
                 if self.stacker_removed {
                     info!("stacker replaced, re-enabling bill acceptor...");
                     self.stacker_removed = false;
-                    thread::sleep(Duration::from_millis(500));
+                    clock::sleep(Duration::from_millis(500));
                     self.enable()?;
                     Some(BillEvent::StackerReplaced)
                 } else {
@@ -295,103 +1007,158 @@ impl CashCode {
                 }
             }
 
-            STATUS_IDLING | STATUS_ACCEPTING | STATUS_STACKING => {
-                self.send_ack()?;
-                self.clear_buffer()?;
-                None
+            STATUS_IDLING | STATUS_ACCEPTING | STATUS_STACKING | STATUS_RETURNING
+            | STATUS_HOLDING => None,
+
+            STATUS_STACKER_FULL => {
+                if !self.stacker_full {
+                    self.stacker_full = true;
+                    error!("ERR: stacker full");
+                    Some(BillEvent::StackerFull)
+                } else {
+                    None
+                }
             }
 
             STATUS_STACKER_REMOVED => {
-                self.send_ack()?;
+                self.stacker_full = false;
                 if !self.stacker_removed {
                     self.stacker_removed = true;
                     error!("ERR: stacker removed");
-                    self.clear_buffer()?;
                     Some(BillEvent::StackerRemoved)
                 } else {
-                    self.clear_buffer()?;
                     None
                 }
             }
 
             STATUS_JAM_IN_STACKER => {
-                self.send_ack()?;
                 error!("ERR: bill jam in stacker");
-                self.clear_buffer()?;
-                Some(BillEvent::Jam("Bill jam in stacker".to_string()))
+                Some(BillEvent::Jam(Message::new("bill.jam.stacker")))
             }
 
             STATUS_JAM_IN_ACCEPTOR => {
-                self.send_ack()?;
                 error!("ERR: bill jam in acceptor");
-                self.clear_buffer()?;
-                Some(BillEvent::Jam("Bill jam in acceptor".to_string()))
+                Some(BillEvent::Jam(Message::new("bill.jam.acceptor")))
             }
 
             STATUS_FAILURE => {
-                if response.len() < 5 {
+                let Some(error_code) = detail else {
                     return Ok(None);
-                }
-                let error_code = response[4];
-                self.send_ack()?;
-                self.clear_buffer()?;
+                };
 
                 match error_code {
                     FAILURE_55 => {
                         error!("ERROR: FAILURE 55 (sensor cover opened?)");
-                        Some(BillEvent::Error("FAILURE 55".to_string()))
+                        Some(BillEvent::Error(Message::new("bill.failure.55")))
                     }
                     _ => {
                         error!("FAILURE with unknown code: 0x{:02X}", error_code);
-                        Some(BillEvent::Error(format!("FAILURE 0x{:02X}", error_code)))
+                        Some(BillEvent::Error(Message::with(
+                            "bill.failure",
+                            &[&format!("{:02X}", error_code)],
+                        )))
                     }
                 }
             }
 
             STATUS_REJECTED => {
-                if response.len() < 5 {
+                let Some(reject_code) = detail else {
                     return Ok(None);
-                }
-                let reject_code = response[4];
-                self.send_ack()?;
-                self.clear_buffer()?;
-
-                let reason = match reject_code {
-                    REJECT_INSERTION => "Insertion error",
-                    REJECT_CONVEYING => "Conveying error",
-                    REJECT_IDENTIFICATION => "Identification error",
-                    REJECT_VERIFICATION => "Verification error",
-                    REJECT_INHIBITED => "Denomination inhibited",
-                    REJECT_CAPACITY => "Capacity error",
-                    REJECT_OPERATION => "Operation error",
-                    _ => "Unknown error",
                 };
 
+                let reason = Message::new(match reject_code {
+                    REJECT_INSERTION => "bill.reject.insertion",
+                    REJECT_CONVEYING => "bill.reject.conveying",
+                    REJECT_IDENTIFICATION => "bill.reject.identification",
+                    REJECT_VERIFICATION => "bill.reject.verification",
+                    REJECT_INHIBITED => "bill.reject.inhibited",
+                    REJECT_CAPACITY => "bill.reject.capacity",
+                    REJECT_OPERATION => "bill.reject.operation",
+                    _ => "bill.reject.unknown",
+                });
+
                 warn!("bill rejected: {}", reason);
-                Some(BillEvent::Rejected(reason.to_string()))
+                Some(BillEvent::Rejected(reason))
             }
 
             STATUS_BILL_STACKED => {
-                if response.len() < 5 {
+                let Some(nominal_code) = detail else {
                     return Ok(None);
-                }
-                let nominal_code = response[4];
-                self.send_ack()?;
-                self.clear_buffer()?;
+                };
 
-                if let Some(nominal) = BillNominal::from_code(nominal_code) {
-                    info!("bill accepted: {} dram", nominal.value());
-                    self.record_bill(nominal)?;
+                if let Some(nominal) = self.nominal(nominal_code) {
+                    info!("bill accepted: {} {}", nominal.value(), money::currency());
+                    self.counters.record(nominal)?;
                     Some(BillEvent::Accepted(nominal))
                 } else {
                     warn!("bill accepted with unknown nominal: 0x{:02X}", nominal_code);
-                    Some(BillEvent::Error(format!(
-                        "Unknown nominal: 0x{:02X}",
-                        nominal_code
+                    Some(BillEvent::Error(Message::with(
+                        "bill.unknown_nominal",
+                        &[&format!("{:02X}", nominal_code)],
                     )))
                 }
             }
 
+            STATUS_ESCROW => {
+                let Some(nominal_code) = detail else {
+                    return Ok(None);
+                };
+
+                if self.test_mode {
+                    let nominal = self.nominal(nominal_code);
+                    info!(
+                        "test bill recognised: {:?} (code 0x{:02X}), returning",
+                        nominal, nominal_code
+                    );
+                    self.send_packet(COMMAND_RETURN, &[])?;
+                    let _ = self.read_response()?;
+                    Some(BillEvent::TestBill(nominal, nominal_code))
+                } else if self.dry_run {
+                    self.send_packet(COMMAND_RETURN, &[])?;
+                    let _ = self.read_response()?;
+                    match self.nominal(nominal_code) {
+                        Some(nominal) => {
+                            info!(
+                                "dry run: bill counted and returned: {} {}",
+                                nominal.value(),
+                                money::currency()
+                            );
+                            Some(BillEvent::Accepted(nominal))
+                        }
+                        None => {
+                            warn!(
+                                "dry run: unknown bill type 0x{:02X}, returned",
+                                nominal_code
+                            );
+                            Some(BillEvent::Error(Message::with(
+                                "bill.unknown_nominal",
+                                &[&format!("{:02X}", nominal_code)],
+                            )))
+                        }
+                    }
+                } else if let Some(timeout) = self.escrow {
+                    self.escrow_poll(nominal_code, timeout)?
+                } else {
+                    // escrow wasn't requested; the device returns the bill
+                    // by itself once escrow times out
+                    warn!(
+                        "bill in escrow outside escrow mode (code 0x{:02X})",
+                        nominal_code
+                    );
+                    None
+                }
+            }
+
+            STATUS_BILL_RETURNED => {
+                info!("bill returned");
+                // returned by the device itself, or because we asked
+                if self.held.take().is_some() || std::mem::take(&mut self.returning) {
+                    Some(BillEvent::Returned)
+                } else {
+                    None
+                }
+            }
+
             _ => {
                 warn!(
                     "Unknown status code: 0x{:02X}, response: {:02X?}",
@@ -404,41 +1171,19 @@ impl CashCode {
         Ok(event)
     }
 
-    fn record_bill(&self, nominal: BillNominal) -> Result<(), CashCodeError> {
-        let db = self.db.lock().unwrap();
-        db.execute(
-            "UPDATE accepted_bills SET quantity = quantity + 1 WHERE nominal = ?1",
-            [nominal.value()],
-        )?;
-        Ok(())
+    fn begin_session(&mut self) -> Result<(), CashCodeError> {
+        self.counters.begin_session()
     }
 
-    #[allow(dead_code)]
-    pub fn get_bill_counts(&self) -> Result<Vec<(i32, i32)>, CashCodeError> {
-        let db = self.db.lock().unwrap();
-        let mut stmt =
-            db.prepare("SELECT nominal, quantity FROM accepted_bills ORDER BY nominal")?;
-
-        let rows = stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?;
-
-        let mut results = Vec::new();
-        for row in rows {
-            results.push(row?);
-        }
-
-        Ok(results)
+    fn get_bill_counts(&self) -> Result<Vec<(i32, i32)>, CashCodeError> {
+        self.counters.counts()
     }
 
-    pub fn get_total_amount(&self) -> Result<i32, CashCodeError> {
-        let db = self.db.lock().unwrap();
-        let total: i32 = db
-            .query_row(
-                "SELECT SUM(nominal * quantity) FROM accepted_bills",
-                [],
-                |row| row.get(0),
-            )
-            .unwrap_or(0);
+    fn clear_bill_counts(&self) -> Result<Vec<(i32, i32)>, CashCodeError> {
+        self.counters.clear()
+    }
 
-        Ok(total)
+    fn get_total_amount(&self) -> Result<i32, CashCodeError> {
+        self.counters.total()
     }
 }
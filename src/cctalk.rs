@@ -28,6 +28,8 @@ use tokio::sync::mpsc as tokio_mpsc;
 use tokio::time::timeout;
 use tokio_serial::SerialStream;
 
+use crate::{clock, preflight};
+
 /// Baud rate used by ccTalk devices (fixed by the spec).
 const CCTALK_BAUD: u32 = 9600;
 
@@ -128,11 +130,20 @@ pub fn run(
                                 format!("No device found · retrying in {:?}", RECONNECT_DELAY),
                                 2,
                             ));
-                            tokio::time::sleep(RECONNECT_DELAY).await;
+                            clock::sleep_async(RECONNECT_DELAY).await;
                         }
                     }
                 }
             } else {
+                if let Err(problem) = preflight::check_serial(&serial_port) {
+                    warn!("ccTalk: {}, retrying in {:?}", problem, RECONNECT_DELAY);
+                    let _ = event_tx.send(CoinAcceptorEvent::Status(
+                        format!("{} · retrying in {:?}", problem, RECONNECT_DELAY),
+                        3,
+                    ));
+                    clock::sleep_async(RECONNECT_DELAY).await;
+                    continue;
+                }
                 serial_port.clone()
             };
 
@@ -162,7 +173,7 @@ pub fn run(
                             "Re-enumerating USB · waiting...".to_string(),
                             0,
                         ));
-                        tokio::time::sleep(Duration::from_secs(2)).await;
+                        clock::sleep_async(Duration::from_secs(2)).await;
                     } else {
                         error!(
                             "ccTalk: connection lost ({}), reconnecting in {:?}",
@@ -184,7 +195,7 @@ pub fn run(
                                 }
                             }
                         }
-                        tokio::time::sleep(RECONNECT_DELAY).await;
+                        clock::sleep_async(RECONNECT_DELAY).await;
                     }
                 }
             }
@@ -492,7 +503,7 @@ async fn run_session(
         }
     });
 
-    tokio::time::sleep(Duration::from_millis(100)).await;
+    clock::sleep_async(Duration::from_millis(100)).await;
 
     let address = match Category::CoinAcceptor.default_address() {
         Address::Single(addr) | Address::SingleAndRange(addr, _) => addr,
@@ -507,7 +518,7 @@ async fn run_session(
         .await
         .inspect_err(|e| log::error!("Couldn't reset: {e}"))
         .ok();
-    tokio::time::sleep(Duration::from_millis(250)).await;
+    clock::sleep_async(Duration::from_millis(250)).await;
 
     info!("Connecting to ccTalk coin validator on {}...", serial_port);
     validator.simple_poll().await?;
@@ -680,7 +691,7 @@ async fn run_session(
                 consecutive_errors = 0;
 
                 if poll.event_counter == last_counter {
-                    tokio::time::sleep(delay).await;
+                    clock::sleep_async(delay).await;
                     continue;
                 }
                 last_counter = poll.event_counter;
@@ -715,7 +726,7 @@ async fn run_session(
                 // quiet and the drain_input buffer flush in the transport has
                 // time to clear any stray bytes.
                 if had_credit {
-                    tokio::time::sleep(POST_CREDIT_DELAY).await;
+                    clock::sleep_async(POST_CREDIT_DELAY).await;
                 }
             }
             Err(e) => {
@@ -731,10 +742,10 @@ async fn run_session(
                     )
                     .into());
                 }
-                tokio::time::sleep(Duration::from_secs(1)).await;
+                clock::sleep_async(Duration::from_secs(1)).await;
             }
         }
 
-        tokio::time::sleep(delay).await;
+        clock::sleep_async(delay).await;
     }
 }
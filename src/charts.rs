@@ -0,0 +1,234 @@
+use chrono::{DateTime, Days, Local, NaiveDate, TimeZone};
+use rusqlite::{Connection, Result as SqlResult, params};
+use std::collections::BTreeMap;
+
+use crate::{audit, clock, ledger, money, receipt};
+
+/// Days on every chart, today included.
+pub const DAYS: usize = 30;
+
+/// One plotted value. `x` and `y` are already scaled to 0..=1 (left to
+/// right, bottom to top), so the UI only multiplies by its size.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Point {
+    pub x: f32,
+    pub y: f32,
+    /// Index into `Chart::legend`, for the colour.
+    pub series: i32,
+}
+
+/// A chart ready to draw.
+#[derive(Debug, Clone)]
+pub struct Chart {
+    pub title: String,
+    /// What the top of the chart stands for, e.g. "50,000 AMD".
+    pub max_label: String,
+    /// Dates under the first and last bucket, "MM-DD".
+    pub first_day: String,
+    pub last_day: String,
+    /// Series names; a single-series chart has none.
+    pub legend: Vec<String>,
+    /// Drawn as bars when true, as dots otherwise.
+    pub bars: bool,
+    pub points: Vec<Point>,
+}
+
+/// Daily buckets ending today, local time.
+struct Buckets {
+    first: NaiveDate,
+}
+
+impl Buckets {
+    fn ending_today() -> Self {
+        let today = clock::local().date_naive();
+        Buckets {
+            first: today - Days::new(DAYS as u64 - 1),
+        }
+    }
+
+    /// Unix timestamp of local midnight starting the first bucket.
+    fn start(&self) -> i64 {
+        self.first
+            .and_hms_opt(0, 0, 0)
+            .and_then(|midnight| Local.from_local_datetime(&midnight).earliest())
+            .map(|t| t.timestamp())
+            .unwrap_or(0)
+    }
+
+    /// Bucket of a unix timestamp, if it falls in range.
+    fn index(&self, timestamp: i64) -> Option<usize> {
+        let day = DateTime::from_timestamp(timestamp, 0)?
+            .with_timezone(&Local)
+            .date_naive();
+        let index = (day - self.first).num_days();
+        (0..DAYS as i64).contains(&index).then_some(index as usize)
+    }
+
+    fn label(&self, index: usize) -> String {
+        (self.first + Days::new(index as u64))
+            .format("%m-%d")
+            .to_string()
+    }
+
+    fn x(index: usize) -> f32 {
+        index as f32 / (DAYS - 1) as f32
+    }
+}
+
+/// Smallest 1, 2 or 5 × 10ⁿ at or above `value`, so the top of a chart is
+/// a round number.
+fn nice_ceiling(value: f64) -> f64 {
+    if value <= 0.0 {
+        return 1.0;
+    }
+    let magnitude = 10f64.powf(value.log10().floor());
+    [1.0, 2.0, 5.0, 10.0]
+        .into_iter()
+        .map(|step| step * magnitude)
+        .find(|top| *top >= value)
+        .unwrap_or(10.0 * magnitude)
+}
+
+fn amd(amount: f64) -> String {
+    money::format(amount.round() as i64)
+}
+
+/// Rows of (timestamp, value) from `[start, now)`.
+fn rows(db: &Connection, sql: &str, start: i64) -> SqlResult<Vec<(i64, i64)>> {
+    db.prepare(sql)?
+        .query_map(params![start], |row| Ok((row.get(0)?, row.get(1)?)))?
+        .collect()
+}
+
+fn chart(
+    days: &Buckets,
+    title: &str,
+    legend: Vec<String>,
+    series: &[[f64; DAYS]],
+    top: f64,
+    max_label: String,
+    bars: bool,
+) -> Chart {
+    let points = series
+        .iter()
+        .enumerate()
+        .flat_map(|(s, values)| {
+            values.iter().enumerate().map(move |(i, value)| Point {
+                x: Buckets::x(i),
+                y: (value / top).clamp(0.0, 1.0) as f32,
+                series: s as i32,
+            })
+        })
+        // an empty day is no dot, but still a (flat) bar
+        .filter(|point| bars || point.y > 0.0)
+        .collect();
+    Chart {
+        title: title.to_string(),
+        max_label,
+        first_day: days.label(0),
+        last_day: days.label(DAYS - 1),
+        legend,
+        bars,
+        points,
+    }
+}
+
+/// The Stats page charts over the last [`DAYS`] days: money taken per day,
+/// bills per day by denomination, and the share of bills rejected. Read
+/// from the raw tables; blocking, run it off the UI thread.
+pub fn build(db_path: &str) -> SqlResult<Vec<Chart>> {
+    let db = Connection::open(db_path)?;
+    receipt::init_db(&db)?;
+    ledger::init_db(&db)?;
+    audit::init_db(&db)?;
+    let days = Buckets::ending_today();
+    let start = days.start();
+
+    let mut totals = [0f64; DAYS];
+    for (timestamp, amount) in rows(
+        &db,
+        "SELECT timestamp, amount FROM receipts WHERE timestamp >= ?1",
+        start,
+    )? {
+        if let Some(i) = days.index(timestamp) {
+            totals[i] += amount as f64;
+        }
+    }
+
+    let mut by_nominal: BTreeMap<i64, [f64; DAYS]> = BTreeMap::new();
+    let mut stacked = [0f64; DAYS];
+    for (timestamp, nominal) in rows(
+        &db,
+        "SELECT timestamp, nominal FROM bill_ledger WHERE timestamp >= ?1",
+        start,
+    )? {
+        if let Some(i) = days.index(timestamp) {
+            by_nominal.entry(nominal).or_insert([0.0; DAYS])[i] += 1.0;
+            stacked[i] += 1.0;
+        }
+    }
+
+    let mut rejected = [0f64; DAYS];
+    for (timestamp, _) in rows(
+        &db,
+        "SELECT timestamp, 0 FROM audit_log WHERE event = 'bill_rejected' AND timestamp >= ?1",
+        start,
+    )? {
+        if let Some(i) = days.index(timestamp) {
+            rejected[i] += 1.0;
+        }
+    }
+    let mut reject_rate = [0f64; DAYS];
+    for i in 0..DAYS {
+        let inserted = stacked[i] + rejected[i];
+        if inserted > 0.0 {
+            reject_rate[i] = 100.0 * rejected[i] / inserted;
+        }
+    }
+
+    let max = |series: &[[f64; DAYS]]| {
+        series
+            .iter()
+            .flat_map(|values| values.iter().copied())
+            .fold(0.0, f64::max)
+    };
+
+    let totals_top = nice_ceiling(max(&[totals]));
+    let nominals: Vec<[f64; DAYS]> = by_nominal.values().copied().collect();
+    let bills_top = nice_ceiling(max(&nominals));
+    // At least 1%, so a single reject doesn't fill the chart
+    let reject_top = nice_ceiling(max(&[reject_rate]).max(1.0)).min(100.0);
+
+    Ok(vec![
+        chart(
+            &days,
+            "Donations per day",
+            Vec::new(),
+            &[totals],
+            totals_top,
+            amd(totals_top),
+            true,
+        ),
+        chart(
+            &days,
+            "Bills per day by denomination",
+            by_nominal
+                .keys()
+                .map(|nominal| amd(*nominal as f64))
+                .collect(),
+            &nominals,
+            bills_top,
+            format!("{:.0} bills", bills_top),
+            false,
+        ),
+        chart(
+            &days,
+            "Rejected bills",
+            Vec::new(),
+            &[reject_rate],
+            reject_top,
+            format!("{:.0}%", reject_top),
+            true,
+        ),
+    ])
+}
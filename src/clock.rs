@@ -0,0 +1,192 @@
+use chrono::{DateTime, Local};
+#[cfg(test)]
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/// Where the app gets the time from: wall-clock time for timestamps,
+/// retention and reports, a monotonic clock for timeouts, and sleeping.
+/// Everything goes through the functions below, so in tests a
+/// `ManualClock` can stand in (see `mock`) and time can be moved forward
+/// without waiting.
+pub trait Clock: Send + Sync {
+    fn now(&self) -> SystemTime;
+    fn instant(&self) -> Instant;
+    fn sleep(&self, duration: Duration);
+}
+
+/// The real clock.
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> SystemTime {
+        SystemTime::now()
+    }
+
+    fn instant(&self) -> Instant {
+        Instant::now()
+    }
+
+    fn sleep(&self, duration: Duration) {
+        thread::sleep(duration);
+    }
+}
+
+/// A clock that only moves when told to: by [`ManualClock::advance`], or
+/// by `sleep`, which returns at once with the time moved on.
+#[cfg(test)]
+pub struct ManualClock {
+    start: (SystemTime, Instant),
+    elapsed: Mutex<Duration>,
+}
+
+#[cfg(test)]
+impl ManualClock {
+    /// Starts at `now`.
+    pub fn new(now: SystemTime) -> Self {
+        Self {
+            start: (now, Instant::now()),
+            elapsed: Mutex::new(Duration::ZERO),
+        }
+    }
+
+    pub fn advance(&self, duration: Duration) {
+        *self.elapsed.lock().unwrap() += duration;
+    }
+}
+
+#[cfg(test)]
+impl Clock for ManualClock {
+    fn now(&self) -> SystemTime {
+        self.start.0 + *self.elapsed.lock().unwrap()
+    }
+
+    fn instant(&self) -> Instant {
+        self.start.1 + *self.elapsed.lock().unwrap()
+    }
+
+    fn sleep(&self, duration: Duration) {
+        self.advance(duration);
+    }
+}
+
+#[cfg(test)]
+thread_local! {
+    /// Stands in for the system clock on this thread, see [`mock`].
+    static MOCK: std::cell::RefCell<Option<Arc<ManualClock>>> =
+        const { std::cell::RefCell::new(None) };
+}
+
+/// Puts a [`ManualClock`] starting at `now` in place of the system clock
+/// for the rest of the calling test; each test thread gets its own.
+#[cfg(test)]
+pub fn mock(now: SystemTime) -> Arc<ManualClock> {
+    let clock = Arc::new(ManualClock::new(now));
+    MOCK.with(|mock| *mock.borrow_mut() = Some(clock.clone()));
+    clock
+}
+
+#[cfg(not(test))]
+fn with<R>(f: impl FnOnce(&dyn Clock) -> R) -> R {
+    f(&SystemClock)
+}
+
+#[cfg(test)]
+fn with<R>(f: impl FnOnce(&dyn Clock) -> R) -> R {
+    match MOCK.with(|mock| mock.borrow().clone()) {
+        Some(clock) => f(clock.as_ref()),
+        None => f(&SystemClock),
+    }
+}
+
+/// Current unix timestamp, shared between a donation's log row and its photo
+/// filename (see `camera::photo_filename`) so the two can be re-associated.
+pub fn timestamp() -> u64 {
+    timestamp_ms() / 1000
+}
+
+/// Current unix time in milliseconds.
+pub fn timestamp_ms() -> u64 {
+    (timestamp_ns() / 1_000_000) as u64
+}
+
+/// Current unix time in nanoseconds, for seeds that must differ between
+/// calls close together.
+pub fn timestamp_ns() -> u128 {
+    with(|clock| clock.now())
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_nanos())
+        .unwrap_or(0)
+}
+
+/// Current local time, for days, months and schedules.
+pub fn local() -> DateTime<Local> {
+    DateTime::<Local>::from(with(|clock| clock.now()))
+}
+
+/// Monotonic "now", for timeouts.
+pub fn instant() -> Instant {
+    with(|clock| clock.instant())
+}
+
+/// Time passed since `earlier`, a [`instant()`].
+pub fn since(earlier: Instant) -> Duration {
+    instant().saturating_duration_since(earlier)
+}
+
+pub fn sleep(duration: Duration) {
+    with(|clock| clock.sleep(duration));
+}
+
+/// [`sleep`] for the tokio drivers: the runtime's other tasks carry on
+/// meanwhile. A mocked clock moves on at once, as with `sleep`.
+pub async fn sleep_async(duration: Duration) {
+    #[cfg(test)]
+    {
+        if let Some(clock) = MOCK.with(|mock| mock.borrow().clone()) {
+            clock.advance(duration);
+            return;
+        }
+    }
+    tokio::time::sleep(duration).await;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mock_moves_only_when_told() {
+        let clock = mock(UNIX_EPOCH + Duration::from_secs(1_700_000_000));
+        let start = instant();
+        assert_eq!(timestamp(), 1_700_000_000);
+
+        clock.advance(Duration::from_secs(90));
+        assert_eq!(since(start), Duration::from_secs(90));
+        assert_eq!(timestamp(), 1_700_000_090);
+
+        // sleeping moves the clock instead of waiting
+        sleep(Duration::from_secs(3600));
+        assert_eq!(since(start), Duration::from_secs(3690));
+    }
+
+    #[test]
+    fn async_sleep_moves_a_mock_on() {
+        mock(UNIX_EPOCH);
+        let start = instant();
+        tokio::runtime::Builder::new_current_thread()
+            .enable_time()
+            .build()
+            .unwrap()
+            .block_on(sleep_async(Duration::from_secs(30)));
+        assert_eq!(since(start), Duration::from_secs(30));
+    }
+
+    #[test]
+    fn mock_is_per_thread() {
+        mock(UNIX_EPOCH).advance(Duration::from_secs(5));
+        let elsewhere = thread::spawn(timestamp).join().unwrap();
+        assert!(elsewhere > 1_700_000_000);
+        assert_eq!(timestamp(), 5);
+    }
+}
@@ -0,0 +1,186 @@
+//! Pulse-output coin acceptors (NRI G-13 pulse and the many clones), as
+//! an alternative to ccTalk. The acceptor sends a train of pulses per coin,
+//! one per `pulse_value`, on one of the serial port's status lines; a USB
+//! serial adapter's CTS, DSR, DCD or RI input reads them. Its inhibit input
+//! can hang off RTS or DTR, so disabling the acceptor makes it return
+//! coins instead of only ignoring them.
+//!
+//! Speaks the same `CoinAcceptorCommand` / `CoinAcceptorEvent` as the ccTalk
+//! driver, so the rest of the kiosk doesn't know which one is running.
+
+use log::{error, info, warn};
+use serialport::SerialPort;
+use std::sync::mpsc::{Receiver, Sender, TryRecvError};
+use std::time::{Duration, Instant};
+
+use crate::cctalk::{CoinAcceptorCommand, CoinAcceptorEvent};
+use crate::config::{CoinPulse, InhibitLine, PulseLine};
+use crate::{clock, preflight};
+
+/// How often the pulse line is sampled; pulses are 20-100 ms wide.
+const SAMPLE_INTERVAL: Duration = Duration::from_millis(2);
+
+/// Shorter blips are noise (the solenoid, a loose wire), not pulses.
+const MIN_PULSE: Duration = Duration::from_millis(8);
+
+/// Delay between attempts to open the port.
+const RECONNECT_DELAY: Duration = Duration::from_secs(5);
+
+/// Runs the pulse coin-acceptor driver on the calling thread until the
+/// command channel closes. The port is opened again after an error, with
+/// the enabled state kept.
+pub fn run(
+    settings: CoinPulse,
+    event_tx: Sender<CoinAcceptorEvent>,
+    cmd_rx: Receiver<CoinAcceptorCommand>,
+) {
+    let Some(port_path) = settings.serial_port.clone() else {
+        return;
+    };
+    let mut enabled = false;
+    loop {
+        if let Err(problem) = preflight::check_serial(&port_path) {
+            warn!("Coin pulse: {}, retrying in {:?}", problem, RECONNECT_DELAY);
+            let _ = event_tx.send(CoinAcceptorEvent::Status(
+                format!("{} · retrying in {:?}", problem, RECONNECT_DELAY),
+                3,
+            ));
+            clock::sleep(RECONNECT_DELAY);
+            continue;
+        }
+        let port = match serialport::new(&port_path, 9600).open() {
+            Ok(port) => port,
+            Err(e) => {
+                error!("Coin pulse: failed to open {}: {}", port_path, e);
+                let _ = event_tx.send(CoinAcceptorEvent::Status(
+                    format!(
+                        "Can't open {} · retrying in {:?}",
+                        port_path, RECONNECT_DELAY
+                    ),
+                    3,
+                ));
+                clock::sleep(RECONNECT_DELAY);
+                continue;
+            }
+        };
+        info!(
+            "Coin pulse: listening on {} ({:?})",
+            port_path, settings.line
+        );
+        match session(port, &settings, &event_tx, &cmd_rx, &mut enabled) {
+            Ok(()) => {
+                info!("Coin pulse: command channel closed, exiting");
+                return;
+            }
+            Err(e) => {
+                error!(
+                    "Coin pulse: {} lost ({}), reopening in {:?}",
+                    port_path, e, RECONNECT_DELAY
+                );
+                let _ = event_tx.send(CoinAcceptorEvent::Error(format!(
+                    "Coin acceptor line lost: {}",
+                    e
+                )));
+                clock::sleep(RECONNECT_DELAY);
+            }
+        }
+    }
+}
+
+/// Samples the pulse line until the port fails (`Err`) or the command
+/// channel closes (`Ok`).
+fn session(
+    mut port: Box<dyn SerialPort>,
+    settings: &CoinPulse,
+    event_tx: &Sender<CoinAcceptorEvent>,
+    cmd_rx: &Receiver<CoinAcceptorCommand>,
+    enabled: &mut bool,
+) -> serialport::Result<()> {
+    let train_gap = Duration::from_millis(settings.train_gap_ms);
+    set_inhibit(&mut *port, settings, !*enabled)?;
+    report_state(event_tx, *enabled);
+
+    let mut active_since: Option<Instant> = None;
+    let mut pulses = 0;
+    let mut last_pulse = clock::instant();
+    loop {
+        loop {
+            match cmd_rx.try_recv() {
+                Ok(CoinAcceptorCommand::Enable) => *enabled = true,
+                Ok(CoinAcceptorCommand::Disable) => *enabled = false,
+                // nothing to re-enumerate on a plain serial line; reopen it
+                Ok(CoinAcceptorCommand::Reenumerate) => {
+                    return Err(serialport::Error::new(
+                        serialport::ErrorKind::Unknown,
+                        "reopen requested",
+                    ));
+                }
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => return Ok(()),
+            }
+            set_inhibit(&mut *port, settings, !*enabled)?;
+            report_state(event_tx, *enabled);
+        }
+
+        let active = read_line(&mut *port, settings.line)? != settings.inverted;
+        match (active, active_since) {
+            (true, None) => active_since = Some(clock::instant()),
+            (false, Some(since)) => {
+                active_since = None;
+                if clock::since(since) >= MIN_PULSE {
+                    pulses += 1;
+                    last_pulse = clock::instant();
+                }
+            }
+            _ => {}
+        }
+
+        if pulses > 0 && active_since.is_none() && clock::since(last_pulse) >= train_gap {
+            let value = pulses * settings.pulse_value;
+            if *enabled {
+                info!("Coin pulse: {} pulse(s), coin worth {}", pulses, value);
+                let _ = event_tx.send(CoinAcceptorEvent::Accepted(value));
+            } else {
+                // without an inhibit line the acceptor takes coins anyway
+                warn!(
+                    "Coin pulse: coin worth {} came in while disabled, not counted",
+                    value
+                );
+            }
+            pulses = 0;
+        }
+        clock::sleep(SAMPLE_INTERVAL);
+    }
+}
+
+fn read_line(port: &mut dyn SerialPort, line: PulseLine) -> serialport::Result<bool> {
+    match line {
+        PulseLine::Cts => port.read_clear_to_send(),
+        PulseLine::Dsr => port.read_data_set_ready(),
+        PulseLine::Dcd => port.read_carrier_detect(),
+        PulseLine::Ri => port.read_ring_indicator(),
+    }
+}
+
+fn set_inhibit(
+    port: &mut dyn SerialPort,
+    settings: &CoinPulse,
+    inhibit: bool,
+) -> serialport::Result<()> {
+    match settings.inhibit {
+        Some(InhibitLine::Rts) => port.write_request_to_send(inhibit),
+        Some(InhibitLine::Dtr) => port.write_data_terminal_ready(inhibit),
+        None => Ok(()),
+    }
+}
+
+fn report_state(event_tx: &Sender<CoinAcceptorEvent>, enabled: bool) {
+    let _ = event_tx.send(CoinAcceptorEvent::Status(
+        if enabled {
+            "Pulse line · enabled".to_string()
+        } else {
+            "Pulse line · disabled".to_string()
+        },
+        1,
+    ));
+}
@@ -1,8 +1,18 @@
+use log::{debug, info, warn};
 use serde::Deserialize;
 use std::fs;
 use std::path::Path;
+use std::time::Duration;
 use thiserror::Error;
 
+use crate::cashcode::Timings;
+use crate::config_migration::{self, CONFIG_VERSION, Notice};
+use crate::flow;
+use crate::funds::{Campaign, Fund};
+use crate::money;
+
+const CONFIG_PATH: &str = ".config/dramma.toml";
+
 #[derive(Debug, Error)]
 pub enum ConfigError {
     #[error(
@@ -23,53 +33,978 @@ pub struct GameEntry {
     pub rom: String,
 }
 
+/// Per-deployment switches for whole kiosk features, configured under
+/// `[features]`. Everything is enabled unless turned off explicitly.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct Features {
+    pub home_assistant: bool,
+    /// When off, donations are anonymous-only and the username field is hidden.
+    pub username_entry: bool,
+    pub donation_wall: bool,
+    pub games: bool,
+    pub sound: bool,
+    /// Show a member's dues and sponsorship from the gateway on Donate.
+    pub member_status: bool,
+    /// Let donors set how much they want to give on the insert page, with
+    /// bill combinations for it and their progress towards it.
+    pub donation_intent: bool,
+    /// Let members with one of `service_roles` open the service page with
+    /// their own PIN, looked up on the gateway.
+    pub member_roles: bool,
+    /// Let donors redeem a pre-sold paper voucher on Donate instead of
+    /// inserting money, checked and marked used on the gateway.
+    pub vouchers: bool,
+}
+
+impl Default for Features {
+    fn default() -> Self {
+        Self {
+            home_assistant: true,
+            username_entry: true,
+            donation_wall: true,
+            games: true,
+            sound: true,
+            member_status: false,
+            donation_intent: false,
+            member_roles: false,
+            vouchers: false,
+        }
+    }
+}
+
+/// The donation flow's steps, configured under `[flow]`; see `flow` for
+/// what `steps` may hold. Without a fund step, donations go to `fund_id`,
+/// or to the fallback fund without one.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct Flow {
+    pub steps: Vec<String>,
+    pub fund_id: Option<i32>,
+    pub fund_name: String,
+}
+
+impl Default for Flow {
+    fn default() -> Self {
+        Self {
+            steps: vec!["fund".to_string(), "name".to_string(), "insert".to_string()],
+            fund_id: None,
+            fund_name: "Donations".to_string(),
+        }
+    }
+}
+
+/// Fast anonymous flow for parties, configured under `[event_mode]`. Donate
+/// skips fund and username selection, and once bills stop coming the money
+/// is committed to `fund_id` after `settle_secs`. Without a `fund_id` it goes
+/// to the fallback fund. Can also be toggled at runtime from Diagnostics.
+///
+/// With `batch_secs` set, sessions are added up and sent as one donation
+/// every `batch_secs` (or as soon as they reach `batch_amount`, if set)
+/// rather than one by one; receipts and the bill ledger keep the detail.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct EventMode {
+    pub enabled: bool,
+    pub fund_id: Option<i32>,
+    pub fund_name: String,
+    pub settle_secs: u64,
+    pub batch_secs: u64,
+    pub batch_amount: i32,
+}
+
+impl Default for EventMode {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            fund_id: None,
+            fund_name: "Event donations".to_string(),
+            settle_secs: 20,
+            batch_secs: 0,
+            batch_amount: 0,
+        }
+    }
+}
+
+/// Scheduled self-restart to clear slow leaks on long-running kiosks,
+/// configured under `[reboot]`. Happens once a day at the first idle moment
+/// between `at` ("HH:MM", local time) and `window_mins` later. Without a
+/// `command` the app restarts itself; otherwise the command is run through
+/// `sh -c` (e.g. `"systemctl reboot"` for a full system reboot).
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct Reboot {
+    pub at: Option<String>,
+    pub window_mins: u64,
+    pub command: Option<String>,
+}
+
+impl Default for Reboot {
+    fn default() -> Self {
+        Self {
+            at: None,
+            window_mins: 60,
+            command: None,
+        }
+    }
+}
+
+/// Crash-loop protection, configured under `[safe_mode]`. A start that
+/// doesn't stay up `stable_mins` counts as a crash; after `crashes` of them
+/// within `window_mins` the kiosk starts in safe mode, without its drivers.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct SafeMode {
+    /// 0 turns safe mode off.
+    pub crashes: usize,
+    pub window_mins: u64,
+    pub stable_mins: u64,
+    /// Send the last crash and the log to the gateway on entering it.
+    pub upload_logs: bool,
+}
+
+impl Default for SafeMode {
+    fn default() -> Self {
+        Self {
+            crashes: 4,
+            window_mins: 15,
+            stable_mins: 5,
+            upload_logs: true,
+        }
+    }
+}
+
+/// Paying by QR code, configured under `[qr_payment]`. The QR holds `link`
+/// with `{amount}`, `{fund_id}` and `{reference}` filled in, or without one
+/// the link of an invoice from the gateway.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct QrPayment {
+    pub enabled: bool,
+    /// Amounts offered, in AMD.
+    pub amounts: Vec<i32>,
+    pub link: Option<String>,
+    /// How long to wait for the payment before giving up.
+    pub timeout_secs: u64,
+}
+
+impl Default for QrPayment {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            amounts: vec![2000, 5000, 10000, 20000],
+            link: None,
+            timeout_secs: 300,
+        }
+    }
+}
+
+/// Automatic bill acceptor reset, configured under `[bill_reset]`, for when
+/// it degrades into rejecting everything: daily at `at` ("HH:MM", local
+/// time) and/or after `idle_mins` without a session. Never mid-session.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct BillReset {
+    pub at: Option<String>,
+    pub idle_mins: Option<u64>,
+}
+
+/// Bill stacker fill tracking, configured under `[stacker]`. Without a
+/// `capacity` only the device's own STACKER FULL stops bills.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct Stacker {
+    /// Bills the stacker holds when full.
+    pub capacity: Option<u32>,
+    /// Warn once this few bills or less still fit.
+    pub warn_below: u32,
+}
+
+impl Default for Stacker {
+    fn default() -> Self {
+        Self {
+            capacity: None,
+            warn_below: 50,
+        }
+    }
+}
+
+/// Fundraising boost, configured under `[boost]` and started from
+/// Diagnostics or the listener (which may override any of these). Without
+/// a `fund_id` every donation counts towards the total shown.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct Boost {
+    pub fund_id: Option<i32>,
+    pub fund_name: String,
+    /// How long a boost runs before it ends by itself.
+    pub duration_mins: u64,
+}
+
+impl Default for Boost {
+    fn default() -> Self {
+        Self {
+            fund_id: None,
+            fund_name: "Tonight".to_string(),
+            duration_mins: 180,
+        }
+    }
+}
+
+/// Attract screen, configured under `[attract]`: after `idle_mins` on the
+/// home page with nobody around, the open funds are shown in turn with
+/// their progress, `slide_secs` each, until someone taps. Off without
+/// `idle_mins`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct Attract {
+    pub idle_mins: Option<u64>,
+    pub slide_secs: u64,
+}
+
+impl Default for Attract {
+    fn default() -> Self {
+        Self {
+            idle_mins: None,
+            slide_secs: 8,
+        }
+    }
+}
+
+/// Bill escrow, configured under `[escrow]`: each bill is held in the
+/// acceptor and the donor confirms it or takes it back. Off by default,
+/// when bills are stacked as soon as they are recognised.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct Escrow {
+    pub enabled: bool,
+    /// Seconds a bill is held without an answer before it is returned.
+    pub timeout_secs: u64,
+}
+
+impl Default for Escrow {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            timeout_secs: 30,
+        }
+    }
+}
+
+/// Bill acceptor serial timings, configured under `[serial]`, for USB
+/// serial adapters that need more slack than the CCNET defaults. All in
+/// milliseconds.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct Serial {
+    /// Pause between two polls of the bill acceptor.
+    pub poll_interval_ms: u64,
+    /// How long an answer may take to start before it counts as missing.
+    pub read_timeout_ms: u64,
+    /// Longest gap between two bytes of one answer.
+    pub inter_byte_ms: u64,
+    /// Quiet time on the bus after a frame, before the next command.
+    pub bus_free_ms: u64,
+    /// Allowed on top of the limits above for adapters that pass bytes on
+    /// in batches.
+    pub adapter_latency_ms: u64,
+}
+
+impl Serial {
+    pub fn poll_interval(&self) -> Duration {
+        Duration::from_millis(self.poll_interval_ms)
+    }
+
+    pub fn timings(&self) -> Timings {
+        Timings {
+            response_timeout: Duration::from_millis(self.read_timeout_ms),
+            inter_byte: Duration::from_millis(self.inter_byte_ms),
+            bus_free: Duration::from_millis(self.bus_free_ms),
+            adapter_latency: Duration::from_millis(self.adapter_latency_ms),
+        }
+    }
+}
+
+impl Default for Serial {
+    fn default() -> Self {
+        Self {
+            poll_interval_ms: 400,
+            read_timeout_ms: 100,
+            inter_byte_ms: 5,
+            bus_free_ms: 20,
+            adapter_latency_ms: 16,
+        }
+    }
+}
+
+/// A JSON status file kept up to date for monitoring on the kiosk host,
+/// configured under `[status_file]`. Off without a `path`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct StatusFile {
+    pub path: Option<String>,
+    /// Seconds between rewrites.
+    pub interval_secs: u64,
+}
+
+impl Default for StatusFile {
+    fn default() -> Self {
+        Self {
+            path: None,
+            interval_secs: 5,
+        }
+    }
+}
+
+/// Physical buttons for when the touchscreen fails, configured under
+/// `[softkeys]`: evdev devices (a USB keypad, `gpio-keys` buttons) and the
+/// keys on them for each action, by name ("LEFT", "KPENTER", "KEY_ESC") or
+/// code. Off without `devices`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct SoftKeys {
+    pub devices: Vec<String>,
+    pub previous: Vec<String>,
+    pub next: Vec<String>,
+    pub confirm: Vec<String>,
+    pub cancel: Vec<String>,
+}
+
+impl Default for SoftKeys {
+    fn default() -> Self {
+        let keys = |names: &[&str]| names.iter().map(|name| name.to_string()).collect();
+        Self {
+            devices: Vec::new(),
+            previous: keys(&["LEFT", "UP", "KP4", "KP8"]),
+            next: keys(&["RIGHT", "DOWN", "KP6", "KP2"]),
+            confirm: keys(&["ENTER", "KPENTER", "KP5", "SPACE"]),
+            cancel: keys(&["ESC", "BACKSPACE", "KPMINUS"]),
+        }
+    }
+}
+
+/// Cash box door / tamper switch, configured under `[tamper]`: a switch on
+/// an evdev `device` (gpio-keys) and/or a HASS `binary_sensor`. Opening the
+/// door without an operator shift open is an incident. Off without either.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct Tamper {
+    pub device: Option<String>,
+    /// Key or switch code on `device`, by name ("BTN_0") or number.
+    pub key: String,
+    /// Whether the key reads pressed while the door is open; false for a
+    /// reed switch that closes with the door.
+    pub open_when_pressed: bool,
+    /// Entity watched through `[hass_events]`.
+    pub hass_entity: Option<String>,
+    /// Its state while the door is open.
+    pub hass_open_state: String,
+    /// Put a warning on the kiosk screen until the door is closed.
+    pub show_warning: bool,
+}
+
+impl Default for Tamper {
+    fn default() -> Self {
+        Self {
+            device: None,
+            key: "BTN_0".to_string(),
+            open_when_pressed: true,
+            hass_entity: None,
+            hass_open_state: "on".to_string(),
+            show_warning: true,
+        }
+    }
+}
+
+/// TLS hardening for gateway calls, configured under `[gateway_tls]`. All
+/// paths are PEM files; anything configured but unreadable disables gateway
+/// calls rather than falling back to the system CAs.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct GatewayTls {
+    /// The only CA trusted for the gateway, or the gateway's own certificate
+    /// to pin it.
+    pub ca_file: Option<String>,
+    /// Client certificate for mutual TLS, with its key if not in the same file.
+    pub client_cert: Option<String>,
+    pub client_key: Option<String>,
+}
+
+/// MQTT broker for Home Assistant discovery and state, under `[mqtt]`.
+/// Nothing is published without a `host`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct Mqtt {
+    pub host: Option<String>,
+    pub port: u16,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    pub client_id: String,
+    pub discovery_prefix: String,
+    /// Prefix for the kiosk's own state topics, e.g. `dramma/status`.
+    pub topic_prefix: String,
+    /// Seconds between publishing the status file's figures (`metrics/…`);
+    /// 0 turns them off.
+    pub metrics_interval_secs: u64,
+}
+
+impl Default for Mqtt {
+    fn default() -> Self {
+        Self {
+            host: None,
+            port: 1883,
+            username: None,
+            password: None,
+            client_id: "dramma".to_string(),
+            discovery_prefix: "homeassistant".to_string(),
+            topic_prefix: "dramma".to_string(),
+            metrics_interval_secs: 60,
+        }
+    }
+}
+
+/// Home Assistant events mirrored into the notification bar, configured
+/// under `[hass_events]`. Needs a `ws://` URL to HA's WebSocket API and a
+/// long-lived access token.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct HassEvents {
+    pub url: Option<String>,
+    pub token: Option<String>,
+    pub rules: Vec<HassEventRule>,
+}
+
+/// Which events become notifications (`[[hass_events.rules]]`). With
+/// `to_state`, only a `state_changed` into that state fires, and a sticky
+/// notification is cleared once the entity leaves it.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct HassEventRule {
+    pub event_type: String,
+    pub entity_id: Option<String>,
+    pub to_state: Option<String>,
+    /// Notification level: 0 neutral, 1 ok, 2 warn, 3 error.
+    pub level: i32,
+    /// `{name}`, `{state}`, `{entity}` and `{event}` are filled in.
+    pub text: String,
+    pub sticky: bool,
+}
+
+impl Default for HassEventRule {
+    fn default() -> Self {
+        Self {
+            event_type: "state_changed".to_string(),
+            entity_id: None,
+            to_state: None,
+            level: 0,
+            text: "{name}: {state}".to_string(),
+            sticky: false,
+        }
+    }
+}
+
+/// Monthly treasurer report, configured under `[report]`. Last month's report
+/// is written to `dir` once the month is over, then optionally sent on.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct Report {
+    pub dir: String,
+    /// POST it to the gateway (needs `token`).
+    pub post_to_gateway: bool,
+    /// Send it to a Telegram chat through this bot.
+    pub telegram_bot_token: Option<String>,
+    pub telegram_chat_id: Option<String>,
+}
+
+impl Default for Report {
+    fn default() -> Self {
+        Self {
+            dir: "data/reports".to_string(),
+            post_to_gateway: false,
+            telegram_bot_token: None,
+            telegram_chat_id: None,
+        }
+    }
+}
+
+/// Messages to the admins' chat, configured under `[alerts]`: each
+/// donation sent and each failure reported under an error code. Goes to a
+/// Telegram chat through a bot, a Matrix room, or both; nothing is sent
+/// without one.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct Alerts {
+    pub telegram_bot_token: Option<String>,
+    pub telegram_chat_id: Option<String>,
+    /// e.g. "https://matrix.org"
+    pub matrix_homeserver: Option<String>,
+    /// e.g. "!abcdef:matrix.org"
+    pub matrix_room_id: Option<String>,
+    pub matrix_access_token: Option<String>,
+    pub donations: bool,
+    pub errors: bool,
+    /// The same error code isn't posted again for this long, so a flapping
+    /// device doesn't flood the chat.
+    pub repeat_after_secs: u64,
+}
+
+impl Default for Alerts {
+    fn default() -> Self {
+        Self {
+            telegram_bot_token: None,
+            telegram_chat_id: None,
+            matrix_homeserver: None,
+            matrix_room_id: None,
+            matrix_access_token: None,
+            donations: true,
+            errors: true,
+            repeat_after_secs: 600,
+        }
+    }
+}
+
+/// A pulse-output coin acceptor (NRI G-13 style), configured under
+/// `[coin_pulse]`, used instead of the ccTalk one when `serial_port` is
+/// set. See `coin_pulse`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct CoinPulse {
+    pub serial_port: Option<String>,
+    /// What one pulse is worth; a coin sends value / pulse_value pulses.
+    pub pulse_value: i32,
+    /// Status line the pulse output is wired to.
+    pub line: PulseLine,
+    /// The line reads low while pulsing (open-collector outputs).
+    pub inverted: bool,
+    /// Output wired to the acceptor's inhibit input, raised while coins
+    /// shouldn't be taken. Without one, coins inserted then are swallowed.
+    pub inhibit: Option<InhibitLine>,
+    /// Quiet time that ends a coin's pulse train.
+    pub train_gap_ms: u64,
+}
+
+impl Default for CoinPulse {
+    fn default() -> Self {
+        Self {
+            serial_port: None,
+            pulse_value: 10,
+            line: PulseLine::default(),
+            inverted: false,
+            inhibit: None,
+            train_gap_ms: 300,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PulseLine {
+    #[default]
+    Cts,
+    Dsr,
+    Dcd,
+    Ri,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum InhibitLine {
+    Rts,
+    Dtr,
+}
+
+/// Which make of bill acceptor is on `cashcode_serial_port`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AcceptorType {
+    /// CashCode and other CCNET acceptors.
+    #[default]
+    CashCode,
+    /// Innovative Technology NV9, NV10 and others speaking SSP, see `essp`.
+    Essp,
+}
+
+/// SSP settings for `acceptor_type = "essp"`, under `[essp]`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct Essp {
+    /// SSP address of the validator, 0 unless changed in its setup.
+    pub address: u8,
+    /// Negotiate an eSSP key and encrypt every command after it; needed
+    /// when the validator is set up to insist on it.
+    pub encrypted: bool,
+    /// The validator's fixed eSSP key as 16 hex digits; the ITL default
+    /// unless it was changed with their tools.
+    pub fixed_key: String,
+}
+
+impl Default for Essp {
+    fn default() -> Self {
+        Self {
+            address: 0,
+            encrypted: true,
+            fixed_key: "0123456701234567".to_string(),
+        }
+    }
+}
+
+impl Essp {
+    /// `fixed_key` as a number; `None` when it isn't 16 hex digits.
+    pub fn fixed_key(&self) -> Option<u64> {
+        if self.fixed_key.len() != 16 {
+            return None;
+        }
+        u64::from_str_radix(&self.fixed_key, 16).ok()
+    }
+}
+
+/// Where the bill driver finds its acceptor.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CashCodeMode {
+    /// A CashCode on `cashcode_serial_port`.
+    #[default]
+    Serial,
+    /// A pretend one for development without the hardware, fed from the
+    /// keyboard or a local socket (see `mock_acceptor`).
+    Mock,
+}
+
+/// Which system committed donations are sent to.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BackendKind {
+    /// The hackem gateway, with `token`.
+    #[default]
+    Gateway,
+    /// JSON POST to `url`.
+    Webhook,
+    /// A line appended to `path`.
+    Csv,
+    /// A message on `<mqtt.topic_prefix>/<topic>`.
+    Mqtt,
+}
+
+/// Where donations go, configured under `[backend]`, for spaces that keep
+/// their books somewhere other than the hackem gateway.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct Backend {
+    pub kind: BackendKind,
+    /// webhook: endpoint the donation is POSTed to.
+    pub url: Option<String>,
+    /// webhook: sent as `Authorization: Bearer …` when set.
+    pub token: Option<String>,
+    /// csv: file donations are appended to, with a header when new.
+    pub path: String,
+    /// mqtt: topic under `mqtt.topic_prefix`.
+    pub topic: String,
+    /// Funds offered on the Donate page instead of the gateway's list,
+    /// as `[[backend.funds]]` with `id` and `name`.
+    pub funds: Vec<Fund>,
+}
+
+impl Default for Backend {
+    fn default() -> Self {
+        Self {
+            kind: BackendKind::default(),
+            url: None,
+            token: None,
+            path: "data/donations.csv".to_string(),
+            topic: "donations".to_string(),
+            funds: Vec::new(),
+        }
+    }
+}
+
 #[derive(Debug, Clone, Deserialize)]
 #[serde(default)]
 pub struct Config {
+    /// Format version, see `config_migration`. Older files are migrated
+    /// when loaded.
+    pub config_version: u32,
     pub token: Option<String>,
+    /// File holding the gateway token, for kiosks provisioned with a
+    /// secrets file; `token` wins when both are set. Without either the
+    /// kiosk keeps looking (see `current_token`).
+    pub token_file: Option<String>,
+    /// Gateway base URLs in order of preference; requests move to the next
+    /// one while the current one can't be reached. Empty for the hackem
+    /// gateway.
+    pub gateways: Vec<String>,
+    /// Shared secret for signing gateway requests, once the gateway checks
+    /// signatures.
+    pub gateway_signing_secret: Option<String>,
     pub diagnostics_password: Option<String>,
+    /// PIN for the service page (hold the logo on the main page). Without
+    /// one (or `features.member_roles`) the service page can't be opened.
+    pub admin_pin: Option<String>,
+    /// Gateway roles whose members may open the service page with their own
+    /// PIN, with `features.member_roles`.
+    pub service_roles: Vec<String>,
     pub home_assistant_url: String,
     pub hass_api_port: u16,
+    /// Bearer token Home Assistant must send to the listener on
+    /// `hass_api_port`. Without one the listener is open to the LAN.
+    pub hass_api_token: Option<String>,
     pub cashcode_serial_port: String,
+    pub cashcode_mode: CashCodeMode,
+    pub acceptor_type: AcceptorType,
+    pub essp: Essp,
+    /// Local port the mock acceptor takes commands on.
+    pub mock_acceptor_port: u16,
+    /// Currency the kiosk takes and donations are made in, e.g. "AMD",
+    /// "USD" or "EUR". Amounts everywhere are whole units of it.
+    pub currency: String,
+    /// Value of each bill type the acceptor's firmware reports, by type
+    /// number, 0 where unused. Empty for the usual table of `currency`
+    /// (see `money::bill_table`).
+    pub bill_types: Vec<i32>,
+    /// Bills the acceptor takes, in `currency`; others are inhibited on the
+    /// device. Unknown values are ignored with a warning. Empty takes every
+    /// bill in `bill_types`.
+    pub accepted_nominals: Vec<i32>,
     pub cctalk_serial_port: String,
     pub cctalk_coin_overrides: Vec<[i32; 2]>,
+    pub coin_pulse: CoinPulse,
     pub stats_db_path: String,
     pub photos_dir: String,
     pub retroarch_command: String,
     pub games: Vec<GameEntry>,
+    /// Fund credited when a session ends with money inserted but no fund
+    /// chosen — e.g. cancelling the game screen after feeding it coins.
+    pub fallback_fund_id: Option<i32>,
+    pub fallback_fund_name: String,
+    /// Smallest donation, in AMD; 0, the default, takes any amount. Done
+    /// stays disabled below it, and smaller amounts left behind are audited
+    /// instead of sent to the gateway.
+    pub minimum_donation: i32,
+    /// Donations over this many AMD are confirmed by the donor before
+    /// they're finished, and audited as `large_donation`. Off without one.
+    pub large_donation_threshold: Option<i32>,
+    /// Seconds after Done in which the donor can still change the fund or
+    /// username; the donation is sent once they're up. 0 sends at once.
+    pub donation_edit_secs: u64,
+    /// Seconds without a bill or tap on the insert page before the session
+    /// ends by itself: cancelled with nothing in, committed otherwise.
+    pub session_timeout_secs: u64,
+    pub features: Features,
+    pub event_mode: EventMode,
+    pub flow: Flow,
+    pub boost: Boost,
+    pub attract: Attract,
+    /// Raw CCNET frames kept in memory while frame logging is switched on
+    /// from Diagnostics; exports land in `frame_log_dir`.
+    pub frame_log_capacity: usize,
+    pub frame_log_dir: String,
+    pub bill_reset: BillReset,
+    pub stacker: Stacker,
+    pub escrow: Escrow,
+    pub serial: Serial,
+    /// Where the automounter puts removable drives; a stick mounted below
+    /// one of these is offered for export on the Diagnostics page.
+    pub usb_mount_roots: Vec<String>,
+    pub reboot: Reboot,
+    pub safe_mode: SafeMode,
+    pub qr_payment: QrPayment,
+    /// Trying out a new deployment: donations, MQTT messages and receipts
+    /// are logged and kept locally but not sent, and bills are counted and
+    /// handed back instead of stacked. Coins, vouchers and QR payments are
+    /// off, as they can't be undone.
+    pub dry_run: bool,
+    /// Fund bundles offered as a single entry, on top of any the gateway
+    /// defines.
+    pub campaigns: Vec<Campaign>,
+    /// ESC/POS receipt printer device, e.g. `/dev/usb/lp0`. No printing
+    /// without one.
+    pub printer_device: Option<String>,
+    /// Language for messages produced by the app ("en", "hy", "ru"). Keys
+    /// without a translation fall back to English.
+    pub language: String,
+    pub gateway_tls: GatewayTls,
+    pub mqtt: Mqtt,
+    pub hass_events: HassEvents,
+    pub report: Report,
+    pub alerts: Alerts,
+    pub backend: Backend,
+    pub status_file: StatusFile,
+    pub softkeys: SoftKeys,
+    pub tamper: Tamper,
+    /// Deprecations and gaps found while loading, for the boot screen.
+    #[serde(skip)]
+    pub notices: Vec<Notice>,
 }
 
 impl Default for Config {
     fn default() -> Self {
         Self {
+            config_version: CONFIG_VERSION,
             token: None,
+            token_file: None,
+            gateways: Vec::new(),
+            gateway_signing_secret: None,
             diagnostics_password: None,
+            admin_pin: None,
+            service_roles: vec!["admin".to_string(), "treasurer".to_string()],
             home_assistant_url: "https://ha.hackem.cc/web-dramma/0?BrowserID=dramma".to_string(),
             hass_api_port: 8321,
+            hass_api_token: None,
             cashcode_serial_port:
                 "/dev/serial/by-id/usb-Prolific_Technology_Inc._USB-Serial_Controller_D-if00-port0"
                     .to_string(),
+            cashcode_mode: CashCodeMode::default(),
+            acceptor_type: AcceptorType::default(),
+            essp: Essp::default(),
+            mock_acceptor_port: 8322,
+            currency: "AMD".to_string(),
+            bill_types: Vec::new(),
+            accepted_nominals: Vec::new(),
             cctalk_serial_port: "/dev/ttyUSB0".to_string(),
             cctalk_coin_overrides: Vec::new(),
+            coin_pulse: CoinPulse::default(),
             stats_db_path: "data/Stats.db".to_string(),
             photos_dir: "data/photos".to_string(),
             retroarch_command: "retroarch".to_string(),
             games: Vec::new(),
+            fallback_fund_id: None,
+            fallback_fund_name: "General donations".to_string(),
+            minimum_donation: 0,
+            large_donation_threshold: None,
+            donation_edit_secs: 60,
+            session_timeout_secs: 120,
+            features: Features::default(),
+            event_mode: EventMode::default(),
+            flow: Flow::default(),
+            boost: Boost::default(),
+            attract: Attract::default(),
+            frame_log_capacity: 5000,
+            frame_log_dir: "data/frames".to_string(),
+            bill_reset: BillReset::default(),
+            stacker: Stacker::default(),
+            escrow: Escrow::default(),
+            serial: Serial::default(),
+            usb_mount_roots: vec!["/media".to_string(), "/run/media".to_string()],
+            reboot: Reboot::default(),
+            safe_mode: SafeMode::default(),
+            qr_payment: QrPayment::default(),
+            dry_run: false,
+            campaigns: Vec::new(),
+            printer_device: None,
+            language: "en".to_string(),
+            gateway_tls: GatewayTls::default(),
+            mqtt: Mqtt::default(),
+            hass_events: HassEvents::default(),
+            report: Report::default(),
+            alerts: Alerts::default(),
+            backend: Backend::default(),
+            status_file: StatusFile::default(),
+            softkeys: SoftKeys::default(),
+            tamper: Tamper::default(),
+            notices: Vec::new(),
         }
     }
 }
 
 impl Config {
     pub fn load() -> Result<Self, ConfigError> {
-        let config_path = Path::new(".config/dramma.toml");
+        let config_path = Path::new(CONFIG_PATH);
 
         if !config_path.exists() {
             return Err(ConfigError::NotFound);
         }
 
         let content = fs::read_to_string(config_path)?;
-        let config: Config = toml::from_str(&content)?;
+        let mut table: toml::Table = toml::from_str(&content)?;
+        let migration = config_migration::migrate(&mut table);
+        for notice in &migration.notices {
+            warn!(
+                "⚙️ Config {} key={}: {}",
+                notice.kind(),
+                notice.key(),
+                notice
+            );
+        }
+        if migration.changed {
+            // The original is left alone; it may be managed from elsewhere
+            let copy = config_path.with_extension("toml.migrated");
+            match toml::to_string(&table).map(|text| fs::write(&copy, text)) {
+                Ok(Ok(())) => info!(
+                    "⚙️ Migrated config written to {}, review it and replace {}",
+                    copy.display(),
+                    config_path.display()
+                ),
+                Ok(Err(e)) => warn!("⚙️ Could not write {}: {}", copy.display(), e),
+                Err(e) => warn!("⚙️ Could not serialize migrated config: {}", e),
+            }
+        }
 
+        let mut config: Config = toml::Value::Table(table).try_into()?;
+        config.notices = migration.notices;
+        if config.token.is_none() {
+            config.token = config.token_file.as_deref().and_then(read_token_file);
+        }
+        if config.bill_types().iter().all(|&value| value <= 0) {
+            warn!(
+                "⚙️ Config missing key=bill_types: no bill table for {}",
+                config.currency
+            );
+            config.notices.push(Notice::Missing {
+                key: "bill_types",
+                consequence: "no bill table for this currency, bills are refused unless the acceptor reports its own",
+            });
+        }
+        if let Some(problem) = flow::Flow::check(&config) {
+            warn!("⚙️ Config invalid key=flow.steps: {}", problem);
+            config.notices.push(Notice::Invalid {
+                key: "flow.steps",
+                problem,
+            });
+        }
         Ok(config)
     }
+
+    /// `bill_types`, or the usual bill table for `currency`. The bill
+    /// driver prefers the acceptor's own table to the latter.
+    pub fn bill_types(&self) -> Vec<i32> {
+        if !self.bill_types.is_empty() {
+            return self.bill_types.clone();
+        }
+        money::bill_table(&self.currency.trim().to_uppercase()).to_vec()
+    }
+
+    /// Bills taken, smallest first: `accepted_nominals`, or every bill in
+    /// `bill_types` (the bill table in use).
+    pub fn accepted_bills(&self, bill_types: &[i32]) -> Vec<i32> {
+        let mut bills = if self.accepted_nominals.is_empty() {
+            bill_types.to_vec()
+        } else {
+            self.accepted_nominals.clone()
+        };
+        bills.retain(|&value| value > 0);
+        bills.sort_unstable();
+        bills.dedup();
+        bills
+    }
+}
+
+/// The gateway token as configured right now: `token` in the config file,
+/// or else what `token_file` holds. Read again from disk, so a token pasted
+/// in after startup is found without a restart.
+pub fn current_token() -> Option<String> {
+    let content = fs::read_to_string(CONFIG_PATH).ok()?;
+    let table: toml::Table = toml::from_str(&content).ok()?;
+    if let Some(token) = table.get("token").and_then(toml::Value::as_str) {
+        return Some(token.trim().to_string()).filter(|token| !token.is_empty());
+    }
+    read_token_file(table.get("token_file")?.as_str()?)
+}
+
+fn read_token_file(path: &str) -> Option<String> {
+    match fs::read_to_string(path) {
+        Ok(text) => Some(text.trim().to_string()).filter(|token| !token.is_empty()),
+        Err(e) => {
+            debug!("token_file {}: {}", path, e);
+            None
+        }
+    }
 }
@@ -0,0 +1,160 @@
+use std::fmt;
+use toml::{Table, Value};
+
+/// Format version this build writes and expects. Files without
+/// `config_version` are version 1.
+pub const CONFIG_VERSION: u32 = 2;
+
+/// Something about a config file the operator should fix. Logged at load,
+/// shown on the boot screen and summed up in a notification.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Notice {
+    /// A deprecated key; its value was carried over to the new one.
+    Moved { from: String, to: String },
+    /// A deprecated key next to its replacement; the replacement wins.
+    Shadowed { from: String, to: String },
+    /// A key the kiosk needs is not set.
+    Missing {
+        key: &'static str,
+        consequence: &'static str,
+    },
+    /// Written for a newer dramma; loaded as-is, unknown keys ignored.
+    Newer { version: i64 },
+    /// A value the kiosk can't use; the default is used instead.
+    Invalid { key: &'static str, problem: String },
+}
+
+impl Notice {
+    /// Short machine-friendly kind, for the structured log line.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Notice::Moved { .. } => "deprecated",
+            Notice::Shadowed { .. } => "deprecated",
+            Notice::Missing { .. } => "missing",
+            Notice::Newer { .. } => "newer",
+            Notice::Invalid { .. } => "invalid",
+        }
+    }
+
+    /// The key involved, for the structured log line.
+    pub fn key(&self) -> String {
+        match self {
+            Notice::Moved { from, .. } | Notice::Shadowed { from, .. } => from.clone(),
+            Notice::Missing { key, .. } | Notice::Invalid { key, .. } => key.to_string(),
+            Notice::Newer { .. } => "config_version".to_string(),
+        }
+    }
+}
+
+impl fmt::Display for Notice {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Notice::Moved { from, to } => write!(f, "`{}` is now `{}` (migrated)", from, to),
+            Notice::Shadowed { from, to } => {
+                write!(f, "`{}` is ignored, `{}` is already set", from, to)
+            }
+            Notice::Missing { key, consequence } => write!(f, "`{}` not set: {}", key, consequence),
+            Notice::Invalid { key, problem } => {
+                write!(f, "`{}` ignored, {}; using the default", key, problem)
+            }
+            Notice::Newer { version } => write!(
+                f,
+                "config_version {} is newer than this build ({})",
+                version, CONFIG_VERSION
+            ),
+        }
+    }
+}
+
+/// Outcome of [`migrate`]: what to tell the operator, and whether the table
+/// now differs from the file enough to be worth writing back.
+#[derive(Debug, Default)]
+pub struct Migration {
+    pub notices: Vec<Notice>,
+    pub changed: bool,
+}
+
+type Step = fn(&mut Table, &mut Vec<Notice>);
+
+/// `STEPS[i]` upgrades a file from version `i + 1` to `i + 2`.
+const STEPS: &[Step] = &[v1_to_v2];
+
+/// Bill acceptor reset settings grouped under `[bill_reset]`, like `[reboot]`.
+fn v1_to_v2(table: &mut Table, notices: &mut Vec<Notice>) {
+    move_key(table, "bill_reset_time", "bill_reset", "at", notices);
+    move_key(
+        table,
+        "bill_reset_idle_mins",
+        "bill_reset",
+        "idle_mins",
+        notices,
+    );
+}
+
+/// Moves a top-level key into `[section]`, unless the new key is already set.
+fn move_key(table: &mut Table, from: &str, section: &str, key: &str, notices: &mut Vec<Notice>) {
+    let Some(value) = table.remove(from) else {
+        return;
+    };
+    let to = format!("{}.{}", section, key);
+    let Value::Table(target) = table
+        .entry(section)
+        .or_insert_with(|| Value::Table(Table::new()))
+    else {
+        // `section` isn't a table; leave it for the parser to complain about
+        table.insert(from.to_string(), value);
+        return;
+    };
+    if target.contains_key(key) {
+        notices.push(Notice::Shadowed {
+            from: from.to_string(),
+            to,
+        });
+    } else {
+        target.insert(key.to_string(), value);
+        notices.push(Notice::Moved {
+            from: from.to_string(),
+            to,
+        });
+    }
+}
+
+/// Brings a parsed config file up to [`CONFIG_VERSION`] in place and checks
+/// for settings the kiosk can't do without. Never fails: whatever can't be
+/// migrated is left for the normal parser.
+pub fn migrate(table: &mut Table) -> Migration {
+    let version = table
+        .get("config_version")
+        .and_then(Value::as_integer)
+        .unwrap_or(1);
+    let mut migration = Migration::default();
+
+    if version > CONFIG_VERSION as i64 {
+        migration.notices.push(Notice::Newer { version });
+    } else {
+        let from = version.max(1) as usize - 1;
+        for step in &STEPS[from.min(STEPS.len())..] {
+            step(table, &mut migration.notices);
+        }
+        // A version stamp alone isn't worth a migrated copy
+        migration.changed = !migration.notices.is_empty();
+        table.insert(
+            "config_version".to_string(),
+            Value::Integer(CONFIG_VERSION as i64),
+        );
+    }
+
+    // Only the gateway backend needs it
+    let backend = table
+        .get("backend")
+        .and_then(|backend| backend.get("kind"))
+        .and_then(Value::as_str)
+        .unwrap_or("gateway");
+    if backend == "gateway" && !table.contains_key("token") && !table.contains_key("token_file") {
+        migration.notices.push(Notice::Missing {
+            key: "token",
+            consequence: "donations can't be sent to the gateway",
+        });
+    }
+    migration
+}
@@ -0,0 +1,102 @@
+//! Crash-loop detection. Every start is noted in the kv store and the note
+//! is dropped once the kiosk has stayed up `safe_mode.stable_mins`; starts
+//! that pile up within `window_mins` were crashes (or a kiosk that can't get
+//! its window up). Too many and the next start is in safe mode, so a bad
+//! config or a broken peripheral doesn't restart the kiosk all night.
+//!
+//! A panic also leaves its message and the log tail behind, for safe mode
+//! to show and send on.
+
+use serde::{Deserialize, Serialize};
+use std::panic;
+
+use crate::api;
+use crate::clock;
+use crate::config::SafeMode;
+use crate::diag_logger;
+use crate::error::RequestError;
+use crate::kv;
+
+/// Log lines kept with a crash.
+const CRASH_LOG_LINES: usize = 200;
+
+/// The last panic, as it was left for the next start.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Crash {
+    pub timestamp: u64,
+    pub message: String,
+    pub log: Vec<String>,
+}
+
+/// Keeps the panic message and the log tail in the kv store before the
+/// usual panic output.
+pub fn install_hook(db_path: &str) {
+    let db_path = db_path.to_string();
+    let previous = panic::take_hook();
+    panic::set_hook(Box::new(move |info| {
+        let (entries, _) = diag_logger::recent(log::Level::Info, CRASH_LOG_LINES);
+        let crash = Crash {
+            timestamp: clock::timestamp(),
+            message: info.to_string(),
+            log: entries
+                .into_iter()
+                .map(|entry| format!("{} {} {}", entry.timestamp, entry.level, entry.message))
+                .collect(),
+        };
+        kv::set(&db_path, &kv::LAST_CRASH, &crash);
+        previous(info);
+    }));
+}
+
+/// Notes this start. Returns how many starts before it didn't last, when
+/// that is enough for safe mode.
+pub fn starting(db_path: &str, config: &SafeMode) -> Option<usize> {
+    let now = clock::timestamp();
+    let window = config.window_mins * 60;
+    let mut starts = kv::get(db_path, &kv::RECENT_STARTS).unwrap_or_default();
+    starts.retain(|&at| now.saturating_sub(at) < window);
+    let crashes = starts.len();
+    starts.push(now);
+    kv::set(db_path, &kv::RECENT_STARTS, &starts);
+    (config.crashes > 0 && crashes >= config.crashes).then_some(crashes)
+}
+
+/// The kiosk has stayed up: earlier starts no longer count.
+pub fn stable(db_path: &str) {
+    kv::remove(db_path, &kv::RECENT_STARTS);
+}
+
+/// What safe mode sends on: the last crash, if any, and the log so far.
+pub fn report(db_path: &str, crashes: usize) -> String {
+    let mut text = format!("Safe mode after {} short-lived starts\n", crashes);
+    if let Some(crash) = kv::get(db_path, &kv::LAST_CRASH) {
+        text.push_str(&format!(
+            "\nLast crash at {}: {}\n{}\n",
+            crash.timestamp,
+            crash.message,
+            crash.log.join("\n")
+        ));
+    }
+    let (entries, _) = diag_logger::recent(log::Level::Info, CRASH_LOG_LINES);
+    text.push_str("\nThis start:\n");
+    for entry in entries {
+        text.push_str(&format!(
+            "{} {} {}\n",
+            entry.timestamp, entry.level, entry.message
+        ));
+    }
+    text
+}
+
+/// Hands the report to the gateway, which keeps it for whoever looks after
+/// the kiosk.
+pub async fn upload(token: &str, report: String) -> Result<(), RequestError> {
+    let body = serde_json::to_vec(&serde_json::json!({ "kind": "safe_mode", "log": report }))?;
+    let request = api::post(&api::url("/api/kiosk/logs"), token).body(body)?;
+    let mut response = api::send(request).await?;
+    if response.status().is_success() {
+        Ok(())
+    } else {
+        Err(api::error_for_status(&mut response).await)
+    }
+}
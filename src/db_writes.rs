@@ -0,0 +1,34 @@
+//! Best-effort writes to the stats DB that run on a thread of their own
+//! (the audit trail, transactions, the donation wall), so the UI never
+//! waits on SQLite. They are counted, so a shutdown can wait for the ones
+//! still going instead of cutting them off.
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+use std::time::Duration;
+
+use crate::clock;
+
+static PENDING: AtomicUsize = AtomicUsize::new(0);
+
+/// Runs `write` on a new thread.
+pub fn spawn(write: impl FnOnce() + Send + 'static) {
+    PENDING.fetch_add(1, Ordering::SeqCst);
+    thread::spawn(move || {
+        write();
+        PENDING.fetch_sub(1, Ordering::SeqCst);
+    });
+}
+
+/// Waits up to `timeout` for the writes still running; false if some
+/// didn't finish in time.
+pub fn flush(timeout: Duration) -> bool {
+    let started = clock::instant();
+    while PENDING.load(Ordering::SeqCst) > 0 {
+        if clock::since(started) >= timeout {
+            return false;
+        }
+        clock::sleep(Duration::from_millis(10));
+    }
+    true
+}
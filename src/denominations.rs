@@ -0,0 +1,115 @@
+//! Acceptance and rejection per denomination, for spotting the one bill
+//! path that needs its sensors cleaned. The acceptor doesn't say which bill
+//! it rejected, so a rejection is put down to the bill accepted right after
+//! it: people feed the same bill again until it goes in. Rejections nobody
+//! followed up on stay unattributed.
+
+use rusqlite::{Connection, Result as SqlResult, params};
+use std::collections::BTreeMap;
+
+use crate::{audit, charts, clock, ledger};
+
+/// A rejection this close before the next insert attempt is taken to be
+/// the same bill.
+const RETRY_WINDOW_SECS: i64 = 30;
+/// Fewer attributed rejections than this never count as misbehaving.
+const MIN_REJECTIONS: u32 = 5;
+/// Misbehaving from this reject rate, and only when it is at least
+/// `OUTLIER_FACTOR` times the rate of the other denominations together.
+const MIN_RATE: f64 = 0.15;
+const OUTLIER_FACTOR: f64 = 2.0;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Denomination {
+    pub nominal: i64,
+    pub accepted: u32,
+    /// Rejections put down to this denomination.
+    pub rejected: u32,
+    pub misbehaving: bool,
+}
+
+impl Denomination {
+    /// Share of attempts with this denomination that were rejected.
+    pub fn reject_rate(&self) -> f64 {
+        let attempts = self.accepted + self.rejected;
+        if attempts == 0 {
+            0.0
+        } else {
+            f64::from(self.rejected) / f64::from(attempts)
+        }
+    }
+}
+
+/// Per-denomination counts over the Stats page's days, smallest bill
+/// first, and the number of rejections that couldn't be attributed.
+pub fn build(db_path: &str) -> SqlResult<(Vec<Denomination>, u32)> {
+    let db = Connection::open(db_path)?;
+    ledger::init_db(&db)?;
+    audit::init_db(&db)?;
+    let start = clock::timestamp() as i64 - charts::DAYS as i64 * 86_400;
+
+    // (timestamp, nominal), None for a rejection
+    let mut events: Vec<(i64, Option<i64>)> = db
+        .prepare("SELECT timestamp, nominal FROM bill_ledger WHERE timestamp >= ?1")?
+        .query_map(params![start], |row| Ok((row.get(0)?, Some(row.get(1)?))))?
+        .collect::<SqlResult<_>>()?;
+    events.extend(
+        db.prepare(
+            "SELECT timestamp FROM audit_log WHERE event = 'bill_rejected' AND timestamp >= ?1",
+        )?
+        .query_map(params![start], |row| Ok((row.get(0)?, None)))?
+        .collect::<SqlResult<Vec<_>>>()?,
+    );
+    // a rejection and the acceptance in the same second: rejection first
+    events.sort_by_key(|(timestamp, nominal)| (*timestamp, nominal.is_some()));
+
+    let mut by_nominal: BTreeMap<i64, Denomination> = BTreeMap::new();
+    let mut unattributed = 0;
+    // Walking back in time: the bill the following attempts ended with,
+    // and when the latest of those attempts was
+    let mut next: Option<(i64, i64)> = None;
+    for (timestamp, nominal) in events.into_iter().rev() {
+        match nominal {
+            Some(nominal) => {
+                entry(&mut by_nominal, nominal).accepted += 1;
+                next = Some((nominal, timestamp));
+            }
+            None => match next {
+                Some((nominal, at)) if at - timestamp <= RETRY_WINDOW_SECS => {
+                    entry(&mut by_nominal, nominal).rejected += 1;
+                    next = Some((nominal, timestamp));
+                }
+                _ => {
+                    unattributed += 1;
+                    next = None;
+                }
+            },
+        }
+    }
+
+    let total_accepted: u32 = by_nominal.values().map(|d| d.accepted).sum();
+    let total_rejected: u32 = by_nominal.values().map(|d| d.rejected).sum();
+    for denomination in by_nominal.values_mut() {
+        let others_attempts =
+            (total_accepted - denomination.accepted) + (total_rejected - denomination.rejected);
+        let others_rate = if others_attempts == 0 {
+            0.0
+        } else {
+            f64::from(total_rejected - denomination.rejected) / f64::from(others_attempts)
+        };
+        let rate = denomination.reject_rate();
+        denomination.misbehaving = denomination.rejected >= MIN_REJECTIONS
+            && rate >= MIN_RATE
+            && rate >= OUTLIER_FACTOR * others_rate;
+    }
+    Ok((by_nominal.into_values().collect(), unattributed))
+}
+
+fn entry(by_nominal: &mut BTreeMap<i64, Denomination>, nominal: i64) -> &mut Denomination {
+    by_nominal.entry(nominal).or_insert(Denomination {
+        nominal,
+        accepted: 0,
+        rejected: 0,
+        misbehaving: false,
+    })
+}
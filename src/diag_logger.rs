@@ -1,11 +1,112 @@
+use serde::{Serialize, Serializer};
+use std::collections::VecDeque;
 use std::sync::mpsc::{Receiver, SyncSender, sync_channel};
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::sync::{Condvar, Mutex};
+use std::time::Duration;
+
+use crate::clock;
 
 /// (level, message): level 0 = info · 1 = warn · 2 = error
 pub type LogLine = (u8, String);
 
+/// Entries kept for the remote log tail.
+const RECENT_CAPACITY: usize = 1000;
+
+/// A log line as served by `GET /logs/tail`.
+#[derive(Debug, Clone, Serialize)]
+pub struct Entry {
+    /// Increases by one per line, so a follower knows what it has seen.
+    pub seq: u64,
+    pub timestamp: u64,
+    #[serde(serialize_with = "level_name")]
+    pub level: log::Level,
+    pub target: String,
+    pub message: String,
+}
+
+struct Recent {
+    entries: VecDeque<Entry>,
+    next_seq: u64,
+}
+
+/// The last [`RECENT_CAPACITY`] lines, shared with the listener threads.
+static RECENT: Mutex<Recent> = Mutex::new(Recent {
+    entries: VecDeque::new(),
+    next_seq: 0,
+});
+static NEW_ENTRY: Condvar = Condvar::new();
+
+fn remember(level: log::Level, target: &str, message: String, timestamp: u64) {
+    let Ok(mut recent) = RECENT.lock() else {
+        return;
+    };
+    let seq = recent.next_seq;
+    recent.next_seq += 1;
+    if recent.entries.len() == RECENT_CAPACITY {
+        recent.entries.pop_front();
+    }
+    recent.entries.push_back(Entry {
+        seq,
+        timestamp,
+        level,
+        target: target.to_string(),
+        message,
+    });
+    NEW_ENTRY.notify_all();
+}
+
+/// Up to `limit` of the latest lines at `min_level` or more severe, and
+/// the sequence number the next line will get (for [`after`]).
+pub fn recent(min_level: log::Level, limit: usize) -> (Vec<Entry>, u64) {
+    let Ok(recent) = RECENT.lock() else {
+        return (Vec::new(), 0);
+    };
+    let mut matching: Vec<Entry> = recent
+        .entries
+        .iter()
+        .rev()
+        .filter(|entry| entry.level <= min_level)
+        .take(limit)
+        .cloned()
+        .collect();
+    matching.reverse();
+    (matching, recent.next_seq)
+}
+
+/// Lines from sequence number `next` on at `min_level` or more severe,
+/// waiting up to `timeout` for one to be logged, and where to continue.
+/// Empty when none came in time.
+pub fn after(mut next: u64, min_level: log::Level, timeout: Duration) -> (Vec<Entry>, u64) {
+    let deadline = clock::instant() + timeout;
+    let Ok(mut recent) = RECENT.lock() else {
+        return (Vec::new(), next);
+    };
+    loop {
+        let entries: Vec<Entry> = recent
+            .entries
+            .iter()
+            .filter(|entry| entry.seq >= next && entry.level <= min_level)
+            .cloned()
+            .collect();
+        next = recent.next_seq;
+        let left = deadline.saturating_duration_since(clock::instant());
+        if !entries.is_empty() || left.is_zero() {
+            return (entries, next);
+        }
+        recent = match NEW_ENTRY.wait_timeout(recent, left) {
+            Ok((recent, _)) => recent,
+            Err(_) => return (Vec::new(), next),
+        };
+    }
+}
+
+fn level_name<S: Serializer>(level: &log::Level, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(level.as_str())
+}
+
 /// A logger that writes to stderr (via env_logger) and also sends each line
-/// to an in-memory channel for the diagnostics page.
+/// to an in-memory channel for the diagnostics page and keeps the latest
+/// for the remote log tail.
 struct DiagLogger {
     inner: env_logger::Logger,
     tx: SyncSender<LogLine>,
@@ -24,13 +125,12 @@ impl log::Log for DiagLogger {
                 log::Level::Warn => 1,
                 _ => 0,
             };
-            let ts = SystemTime::now()
-                .duration_since(UNIX_EPOCH)
-                .unwrap_or_default();
-            let secs = ts.as_secs();
+            let secs = clock::timestamp();
             let (h, m, s) = (secs / 3600 % 24, secs / 60 % 60, secs % 60);
-            let text = format!("{:02}:{:02}:{:02} {}", h, m, s, record.args());
+            let message = record.args().to_string();
+            let text = format!("{:02}:{:02}:{:02} {}", h, m, s, message);
             self.tx.try_send((level, text)).ok();
+            remember(record.level(), record.target(), message, secs);
         }
     }
 
@@ -0,0 +1,80 @@
+//! `dramma doctor`: checks the configured hardware the way the drivers will
+//! at startup and prints what to fix, without starting the kiosk.
+
+use crate::config::{AcceptorType, CashCodeMode, Config};
+use crate::preflight::{self, Problem};
+
+pub fn requested() -> bool {
+    std::env::args().nth(1).as_deref() == Some("doctor")
+}
+
+/// Prints one line per check; exits non-zero if any of them failed.
+pub fn run(config: &Config, config_error: Option<&str>) -> i32 {
+    let mut failures = 0;
+    match config_error {
+        None => println!("✅ Configuration"),
+        Some(e) => {
+            println!("❌ Configuration: {}", e);
+            failures += 1;
+        }
+    }
+
+    let mut report = |what: &str, result: Result<(), Problem>| match result {
+        Ok(()) => println!("✅ {}", what),
+        Err(problem) => {
+            println!("❌ {}: {}", what, problem);
+            failures += 1;
+        }
+    };
+
+    if config.cashcode_mode == CashCodeMode::Mock {
+        println!("🧪 Bill acceptor: mock");
+    } else if config.acceptor_type == AcceptorType::Essp {
+        report(
+            &format!("Bill acceptor, eSSP ({})", config.cashcode_serial_port),
+            preflight::check_serial(&config.cashcode_serial_port),
+        );
+    } else {
+        report(
+            &format!("Bill acceptor ({})", config.cashcode_serial_port),
+            preflight::check_serial(&config.cashcode_serial_port),
+        );
+    }
+    if let Some(port) = &config.coin_pulse.serial_port {
+        report(
+            &format!("Coin acceptor, pulse ({})", port),
+            preflight::check_serial(port),
+        );
+    } else if config.cctalk_serial_port != "auto" {
+        report(
+            &format!("Coin acceptor ({})", config.cctalk_serial_port),
+            preflight::check_serial(&config.cctalk_serial_port),
+        );
+    }
+    if let Some(device) = &config.printer_device {
+        report(
+            &format!("Printer ({})", device),
+            preflight::check_device(device, true),
+        );
+    }
+    if let Some(device) = &config.tamper.device {
+        report(
+            &format!("Tamper switch ({})", device),
+            preflight::check_device(device, false),
+        );
+    }
+    for device in &config.softkeys.devices {
+        report(
+            &format!("Soft keys ({})", device),
+            preflight::check_device(device, false),
+        );
+    }
+
+    if failures == 0 {
+        println!("All good.");
+        0
+    } else {
+        println!("{} problem(s) found.", failures);
+        1
+    }
+}
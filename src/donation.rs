@@ -1,9 +1,13 @@
-use http::Request;
-use isahc::prelude::*;
-use log::{error, info};
-use serde::Serialize;
+use log::info;
+use rusqlite::{Connection, OptionalExtension, Result as SqlResult, Row, params};
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
 
+use crate::api;
+use crate::backend::Donation;
+use crate::clock;
 use crate::error::RequestError;
+use crate::money;
 
 #[derive(Debug, Serialize)]
 #[serde(rename_all = "camelCase")]
@@ -12,6 +16,9 @@ struct DonationRequest {
     amount: i32,
     currency: String,
     post_chat: String,
+    /// Kiosk receipt number, so the treasurer can match a donor's query
+    #[serde(skip_serializing_if = "Option::is_none")]
+    receipt: Option<i64>,
 }
 
 /// Sends a donation to the API asynchronously
@@ -20,69 +27,305 @@ pub async fn send_donation(
     fund_id: i32,
     username: &str,
     amount: i32,
+    receipt: Option<i64>,
 ) -> Result<(), RequestError> {
-    let url = format!("https://gateway.hackem.cc/api/funds/{}/donations", fund_id);
+    let url = api::url(&format!("/api/funds/{}/donations", fund_id));
 
     let request_body = DonationRequest {
         username: username.to_string(),
         amount,
-        currency: "AMD".to_string(),
+        currency: money::currency().to_string(),
         post_chat: "main".to_string(),
+        receipt,
     };
 
     info!(
-        "Sending donation: {} AMD from {} to fund {}",
-        amount, username, fund_id
+        "Sending donation: {} {} from {} to fund {}",
+        amount,
+        money::currency(),
+        username,
+        fund_id
     );
 
     let body = serde_json::to_vec(&request_body)?;
 
-    let request = Request::post(&url)
-        .header("Authorization", format!("Bearer {}", token))
-        .header("Content-Type", "application/json")
-        .body(body)?;
+    let request = api::post(&url, token).body(body)?;
 
-    let mut response = isahc::send_async(request).await?;
+    let mut response = api::send(request).await?;
 
-    let status = response.status();
-    if status.is_success() {
+    if response.status().is_success() {
         info!("✅ Donation sent successfully!");
         Ok(())
     } else {
-        let message = response
-            .text()
-            .await
-            .unwrap_or_else(|_| "Unknown error".to_string());
-        error!("❌ API error {}: {}", status.as_u16(), message);
-        Err(RequestError::Api {
-            status: status.as_u16(),
-            message,
-        })
+        Err(api::error_for_status(&mut response).await)
     }
 }
 
-/// Sends a donation to the API asynchronously
-pub async fn fetch_usernames(token: &str) -> Result<Vec<String>, RequestError> {
-    let request = Request::get("https://gateway.hackem.cc/api/usernames")
-        .header("Authorization", format!("Bearer {}", token))
-        .header("Content-Type", "application/json")
-        .body(())?;
-
-    let mut response = isahc::send_async(request).await?;
-
-    let status = response.status();
-    if status.is_success() {
-        let usernames: Vec<String> = response.json().await?;
-        Ok(usernames)
-    } else {
-        let message = response
-            .text()
-            .await
-            .unwrap_or_else(|_| "Unknown error".to_string());
-        error!("❌ API error {}: {}", status.as_u16(), message);
-        Err(RequestError::Api {
-            status: status.as_u16(),
-            message,
+/// Usernames come as plain strings today; objects carrying a `username`
+/// (or `name`) field are accepted too so the endpoint can grow, with an
+/// optional `role` ("member", "guest", …).
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum UsernameEntry {
+    Plain(String),
+    Object {
+        #[serde(alias = "name")]
+        username: String,
+        #[serde(default, alias = "tier", alias = "type")]
+        role: Option<String>,
+    },
+}
+
+/// A known username, with the role the gateway gave it, if any.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Username {
+    pub name: String,
+    pub role: Option<String>,
+}
+
+/// Fetches known usernames for the autocomplete
+pub async fn fetch_usernames(token: &str) -> Result<Vec<Username>, RequestError> {
+    let entries: Vec<UsernameEntry> = api::fetch_all("/api/usernames", token).await?;
+    Ok(entries
+        .into_iter()
+        .map(|entry| match entry {
+            UsernameEntry::Plain(name) => Username { name, role: None },
+            UsernameEntry::Object { username, role } => Username {
+                name: username,
+                role,
+            },
         })
+        .collect())
+}
+
+/// A donation the backend didn't take, or money nobody attributed to a
+/// fund, kept in Stats.db until staff resolve it from Diagnostics.
+#[derive(Debug, Clone)]
+pub struct Queued {
+    pub id: i64,
+    /// When the money went in; the donation is dated this when sent.
+    pub timestamp: u64,
+    pub receipt: Option<i64>,
+    pub username: String,
+    pub amount: i32,
+    /// `None` until a fund is picked for unattributed money.
+    pub fund_id: Option<i32>,
+    pub fund_name: String,
+    pub auto_committed: bool,
+    /// Came in without a fund, whether or not one was picked since.
+    pub unattributed: bool,
+    pub attempts: i64,
+    /// When it was last tried, for the retry backoff.
+    pub last_attempt: Option<u64>,
+    /// The last send error, or why the money was left unattributed.
+    pub note: String,
+}
+
+impl Queued {
+    /// What to hand the backend, once there's a fund.
+    pub fn donation(&self) -> Option<Donation> {
+        Some(Donation {
+            fund_id: self.fund_id?,
+            fund_name: self.fund_name.clone(),
+            username: self.username.clone(),
+            amount: self.amount,
+            currency: money::currency(),
+            receipt: self.receipt,
+            timestamp: self.timestamp,
+            auto_committed: self.auto_committed,
+        })
+    }
+}
+
+pub fn init_db(db: &Connection) -> SqlResult<()> {
+    db.execute(
+        "CREATE TABLE IF NOT EXISTS donation_queue (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            timestamp INTEGER NOT NULL,
+            receipt INTEGER,
+            username TEXT NOT NULL,
+            amount INTEGER NOT NULL,
+            fund_id INTEGER,
+            fund_name TEXT NOT NULL,
+            auto_committed INTEGER NOT NULL,
+            unattributed INTEGER NOT NULL DEFAULT 0,
+            attempts INTEGER NOT NULL DEFAULT 0,
+            note TEXT NOT NULL
+        )",
+        [],
+    )?;
+
+    // Queues created before automatic retries lack this column.
+    let has_last_attempt = db
+        .prepare("SELECT 1 FROM pragma_table_info('donation_queue') WHERE name = 'last_attempt'")?
+        .exists([])?;
+    if !has_last_attempt {
+        db.execute(
+            "ALTER TABLE donation_queue ADD COLUMN last_attempt INTEGER",
+            [],
+        )?;
     }
+    Ok(())
+}
+
+/// Keeps a donation the backend failed to take.
+pub fn enqueue(db_path: &str, donation: &Donation, error: &str) -> SqlResult<i64> {
+    insert(db_path, donation, 1, error)
+}
+
+/// Keeps a donation about to be sent, so one cut off mid-send (a crash, a
+/// restart) goes out again from the queue. Taken out once the backend has
+/// it; a failed send counts as its first attempt.
+pub fn enqueue_sending(db_path: &str, donation: &Donation) -> SqlResult<i64> {
+    insert(db_path, donation, 0, "Sending")
+}
+
+fn insert(db_path: &str, donation: &Donation, attempts: i64, note: &str) -> SqlResult<i64> {
+    let db = Connection::open(db_path)?;
+    init_db(&db)?;
+    db.execute(
+        "INSERT INTO donation_queue
+            (timestamp, receipt, username, amount, fund_id, fund_name, auto_committed, attempts,
+             last_attempt, note)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
+        params![
+            donation.timestamp as i64,
+            donation.receipt,
+            donation.username,
+            donation.amount,
+            donation.fund_id,
+            donation.fund_name,
+            donation.auto_committed,
+            attempts,
+            clock::timestamp() as i64,
+            note
+        ],
+    )?;
+    Ok(db.last_insert_rowid())
+}
+
+/// Keeps money that couldn't go to any fund, so a fund can be picked later.
+pub fn enqueue_unattributed(
+    db_path: &str,
+    receipt: Option<i64>,
+    amount: i32,
+    reason: &str,
+) -> SqlResult<i64> {
+    let db = Connection::open(db_path)?;
+    init_db(&db)?;
+    db.execute(
+        "INSERT INTO donation_queue
+            (timestamp, receipt, username, amount, fund_name, auto_committed, unattributed, note)
+         VALUES (?1, ?2, 'anon', ?3, '', 1, 1, ?4)",
+        params![clock::timestamp() as i64, receipt, amount, reason],
+    )?;
+    Ok(db.last_insert_rowid())
+}
+
+fn queued_from_row(row: &Row) -> SqlResult<Queued> {
+    Ok(Queued {
+        id: row.get(0)?,
+        timestamp: row.get::<_, i64>(1)? as u64,
+        receipt: row.get(2)?,
+        username: row.get(3)?,
+        amount: row.get(4)?,
+        fund_id: row.get(5)?,
+        fund_name: row.get(6)?,
+        auto_committed: row.get(7)?,
+        unattributed: row.get(8)?,
+        attempts: row.get(9)?,
+        last_attempt: row.get::<_, Option<i64>>(10)?.map(|t| t as u64),
+        note: row.get(11)?,
+    })
+}
+
+const QUEUED_COLUMNS: &str = "id, timestamp, receipt, username, amount, fund_id, fund_name, \
+     auto_committed, unattributed, attempts, last_attempt, note";
+
+/// Everything waiting, oldest first.
+pub fn queued(db_path: &str) -> SqlResult<Vec<Queued>> {
+    let db = Connection::open(db_path)?;
+    init_db(&db)?;
+    let mut stmt = db.prepare(&format!(
+        "SELECT {} FROM donation_queue ORDER BY id",
+        QUEUED_COLUMNS
+    ))?;
+    let rows = stmt.query_map([], queued_from_row)?;
+    rows.collect()
+}
+
+/// How long after its `attempts`-th failed send a queued donation is tried
+/// again: a minute, doubling up to an hour.
+pub fn retry_delay(attempts: i64) -> Duration {
+    const FIRST: u64 = 60;
+    const MAX: u64 = 3600;
+    let doublings = attempts.clamp(1, 7) as u32 - 1;
+    Duration::from_secs((FIRST << doublings).min(MAX))
+}
+
+/// Queued donations with a fund whose backoff is over, oldest first.
+pub fn due(db_path: &str) -> SqlResult<Vec<Queued>> {
+    let now = clock::timestamp();
+    Ok(queued(db_path)?
+        .into_iter()
+        .filter(|queued| queued.fund_id.is_some())
+        .filter(|queued| {
+            queued
+                .last_attempt
+                .is_none_or(|last| now >= last + retry_delay(queued.attempts).as_secs())
+        })
+        .collect())
+}
+
+pub fn get_queued(db_path: &str, id: i64) -> SqlResult<Option<Queued>> {
+    let db = Connection::open(db_path)?;
+    init_db(&db)?;
+    db.query_row(
+        &format!(
+            "SELECT {} FROM donation_queue WHERE id = ?1",
+            QUEUED_COLUMNS
+        ),
+        [id],
+        queued_from_row,
+    )
+    .optional()
+}
+
+/// Counts another failed send.
+pub fn retry_failed(db_path: &str, id: i64, error: &str) -> SqlResult<()> {
+    let db = Connection::open(db_path)?;
+    init_db(&db)?;
+    db.execute(
+        "UPDATE donation_queue SET attempts = attempts + 1, last_attempt = ?1, note = ?2
+         WHERE id = ?3",
+        params![clock::timestamp() as i64, error, id],
+    )?;
+    Ok(())
+}
+
+/// Changes who a queued donation is from and where it goes.
+pub fn amend_queued(
+    db_path: &str,
+    id: i64,
+    receipt: Option<i64>,
+    username: &str,
+    fund_id: i32,
+    fund_name: &str,
+) -> SqlResult<()> {
+    let db = Connection::open(db_path)?;
+    init_db(&db)?;
+    db.execute(
+        "UPDATE donation_queue SET receipt = ?1, username = ?2, fund_id = ?3, fund_name = ?4
+         WHERE id = ?5",
+        params![receipt, username, fund_id, fund_name, id],
+    )?;
+    Ok(())
+}
+
+/// Drops a queued donation, once sent or discarded.
+pub fn dequeue(db_path: &str, id: i64) -> SqlResult<()> {
+    let db = Connection::open(db_path)?;
+    init_db(&db)?;
+    db.execute("DELETE FROM donation_queue WHERE id = ?1", [id])?;
+    Ok(())
 }
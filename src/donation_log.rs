@@ -1,7 +1,7 @@
 use log::error;
 use rusqlite::{Connection, Result as SqlResult, params};
-use std::thread;
-use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::db_writes;
 
 /// A single completed donation, as shown on the donation wall.
 #[derive(Debug, Clone)]
@@ -12,7 +12,7 @@ pub struct DonationLogEntry {
     pub fund_name: String,
 }
 
-fn init_db(db: &Connection) -> SqlResult<()> {
+pub fn init_db(db: &Connection) -> SqlResult<()> {
     db.execute(
         "CREATE TABLE IF NOT EXISTS donation_log (
             id INTEGER PRIMARY KEY AUTOINCREMENT,
@@ -23,32 +23,43 @@ fn init_db(db: &Connection) -> SqlResult<()> {
         )",
         [],
     )?;
-    Ok(())
-}
 
-/// Current unix timestamp, shared between a donation's log row and its photo
-/// filename (see `camera::photo_filename`) so the two can be re-associated.
-pub fn now_timestamp() -> u64 {
-    SystemTime::now()
-        .duration_since(UNIX_EPOCH)
-        .map(|d| d.as_secs())
-        .unwrap_or(0)
+    // Databases created before auto-commit tracking lack this column.
+    let has_auto_committed = db
+        .prepare("SELECT 1 FROM pragma_table_info('donation_log') WHERE name = 'auto_committed'")?
+        .exists([])?;
+    if !has_auto_committed {
+        db.execute(
+            "ALTER TABLE donation_log ADD COLUMN auto_committed INTEGER NOT NULL DEFAULT 0",
+            [],
+        )?;
+    }
+    Ok(())
 }
 
 /// Records a completed donation, running on a dedicated thread so it never
 /// blocks the donation flow. Best-effort: a DB hiccup is logged and dropped.
-pub fn record(db_path: &str, timestamp: u64, username: &str, amount: i32, fund_name: &str) {
+/// `auto_committed` marks donations sent on the donor's behalf (inactivity
+/// timeout, abandoned session) rather than confirmed with the Done button.
+pub fn record(
+    db_path: &str,
+    timestamp: u64,
+    username: &str,
+    amount: i32,
+    fund_name: &str,
+    auto_committed: bool,
+) {
     let db_path = db_path.to_string();
     let username = username.to_string();
     let fund_name = fund_name.to_string();
 
-    thread::spawn(move || {
+    db_writes::spawn(move || {
         let result = (|| -> SqlResult<()> {
             let db = Connection::open(&db_path)?;
             init_db(&db)?;
             db.execute(
-                "INSERT INTO donation_log (timestamp, username, amount, fund_name) VALUES (?1, ?2, ?3, ?4)",
-                params![timestamp as i64, username, amount, fund_name],
+                "INSERT INTO donation_log (timestamp, username, amount, fund_name, auto_committed) VALUES (?1, ?2, ?3, ?4, ?5)",
+                params![timestamp as i64, username, amount, fund_name, auto_committed],
             )?;
             Ok(())
         })();
@@ -59,6 +70,22 @@ pub fn record(db_path: &str, timestamp: u64, username: &str, amount: i32, fund_n
     });
 }
 
+/// Usernames that donated here, most recent first, one spelling per name
+/// (the latest). Anonymous donations are left out. One small query.
+pub fn recent_usernames(db_path: &str, limit: i64) -> SqlResult<Vec<String>> {
+    let db = Connection::open(db_path)?;
+    init_db(&db)?;
+
+    let mut stmt = db.prepare(
+        // with MAX(), SQLite takes the bare `username` from the same row
+        "SELECT username, MAX(id) AS last FROM donation_log
+         WHERE username NOT IN ('', 'anon')
+         GROUP BY lower(username) ORDER BY last DESC LIMIT ?1",
+    )?;
+    let rows = stmt.query_map([limit], |row| row.get(0))?;
+    rows.collect()
+}
+
 /// Fetches the most recent donations, newest first. Blocking — call off the UI thread.
 pub fn fetch_recent(db_path: &str, limit: i64) -> SqlResult<Vec<DonationLogEntry>> {
     let db = Connection::open(db_path)?;
@@ -1,5 +1,9 @@
+use isahc::error::ErrorKind;
 use thiserror::Error;
 
+use crate::error_code::ErrorCode;
+use crate::i18n::Message;
+
 #[derive(Debug, Error)]
 pub enum RequestError {
     #[error("HTTP request failed: {0}")]
@@ -12,4 +16,44 @@ pub enum RequestError {
     Json(#[from] serde_json::Error),
     #[error("API returned error status {status}: {message}")]
     Api { status: u16, message: String },
+    #[error("API incompatible, update the kiosk ({0})")]
+    Incompatible(String),
+    #[error("gateway TLS setup failed: {0}")]
+    Tls(String),
+}
+
+impl RequestError {
+    pub fn code(&self) -> ErrorCode {
+        match self {
+            RequestError::Request(e)
+                if matches!(
+                    e.kind(),
+                    ErrorKind::BadServerCertificate
+                        | ErrorKind::BadClientCertificate
+                        | ErrorKind::TlsEngine
+                ) =>
+            {
+                ErrorCode::ApiTls
+            }
+            RequestError::Tls(_) => ErrorCode::ApiTls,
+            RequestError::Request(_) | RequestError::Io(_) => ErrorCode::ApiUnreachable,
+            RequestError::Http(_) | RequestError::Json(_) => ErrorCode::ApiRequest,
+            RequestError::Api { status: 401, .. } => ErrorCode::ApiUnauthorized,
+            RequestError::Api { .. } => ErrorCode::ApiStatus,
+            RequestError::Incompatible(_) => ErrorCode::ApiIncompatible,
+        }
+    }
+
+    /// What a donor is told, with the code for staff.
+    pub fn message(&self) -> Message {
+        if self.code() == ErrorCode::ApiTls {
+            return Message::with("gateway.tls", &[&self.code()]);
+        }
+        let key = match self {
+            RequestError::Incompatible(_) => "gateway.update_required",
+            RequestError::Request(_) | RequestError::Io(_) => "gateway.offline",
+            _ => "gateway.error",
+        };
+        Message::with(key, &[&self.code()])
+    }
 }
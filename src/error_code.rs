@@ -0,0 +1,95 @@
+use log::error;
+use std::fmt;
+
+use crate::transactions::{self, Transaction};
+use crate::{alerts, audit};
+
+/// Short, stable codes for failures a user can see, so a report like
+/// "it shows E-SER-02" points straight at the failing subsystem. Codes are
+/// never renumbered; retired ones are left unused. See the table in README.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorCode {
+    SerialOpen,
+    SerialIo,
+    BillJam,
+    StackerRemoved,
+    BillFailure,
+    CoinAcceptor,
+    StackerFull,
+    Database,
+    ApiUnreachable,
+    ApiUnauthorized,
+    ApiStatus,
+    ApiIncompatible,
+    ApiNoToken,
+    ApiRequest,
+    ApiTls,
+    HassLaunch,
+    HassListener,
+    UsbExport,
+}
+
+impl ErrorCode {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ErrorCode::SerialOpen => "E-SER-01",
+            ErrorCode::SerialIo => "E-SER-02",
+            ErrorCode::BillJam => "E-SER-03",
+            ErrorCode::StackerRemoved => "E-SER-04",
+            ErrorCode::BillFailure => "E-SER-05",
+            ErrorCode::CoinAcceptor => "E-SER-06",
+            ErrorCode::StackerFull => "E-SER-07",
+            ErrorCode::Database => "E-DB-01",
+            ErrorCode::ApiUnreachable => "E-API-01",
+            ErrorCode::ApiUnauthorized => "E-API-02",
+            ErrorCode::ApiStatus => "E-API-03",
+            ErrorCode::ApiIncompatible => "E-API-04",
+            ErrorCode::ApiNoToken => "E-API-05",
+            ErrorCode::ApiRequest => "E-API-06",
+            ErrorCode::ApiTls => "E-API-07",
+            ErrorCode::HassLaunch => "E-HASS-01",
+            ErrorCode::HassListener => "E-HASS-02",
+            ErrorCode::UsbExport => "E-USB-01",
+        }
+    }
+
+    pub fn description(&self) -> &'static str {
+        match self {
+            ErrorCode::SerialOpen => "Bill acceptor serial port could not be opened",
+            ErrorCode::SerialIo => "Bill acceptor serial I/O failed",
+            ErrorCode::BillJam => "Bill jammed in the acceptor or stacker",
+            ErrorCode::StackerRemoved => "Bill stacker removed",
+            ErrorCode::BillFailure => "Bill acceptor reported a hardware failure",
+            ErrorCode::CoinAcceptor => "Coin acceptor error",
+            ErrorCode::StackerFull => "Bill stacker full",
+            ErrorCode::Database => "Local stats database error",
+            ErrorCode::ApiUnreachable => "Gateway API unreachable",
+            ErrorCode::ApiUnauthorized => "Gateway API token invalid or expired",
+            ErrorCode::ApiStatus => "Gateway API returned an error",
+            ErrorCode::ApiIncompatible => "Gateway API incompatible, kiosk needs an update",
+            ErrorCode::ApiNoToken => "No gateway API token configured",
+            ErrorCode::ApiRequest => "Gateway API request could not be built or parsed",
+            ErrorCode::ApiTls => "Gateway certificate rejected or TLS setup failed",
+            ErrorCode::HassLaunch => "Home Assistant browser failed to launch",
+            ErrorCode::HassListener => "Home Assistant close listener failed to start",
+            ErrorCode::UsbExport => "Export to USB stick failed",
+        }
+    }
+}
+
+impl fmt::Display for ErrorCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Logs a user-visible failure under its code and records it in the audit
+/// log and transactions, so the code on screen can be matched to what
+/// happened. Also posted
+/// to the admins' chat when `[alerts]` is set up.
+pub fn report(db_path: &str, code: ErrorCode, details: &str) {
+    error!("🛑 {} ({}): {}", code, code.description(), details);
+    audit::record(db_path, "error", &format!("{} {}", code, details));
+    transactions::record(db_path, &Transaction::Error { code, details });
+    alerts::error(code, details);
+}
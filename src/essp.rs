@@ -0,0 +1,1273 @@
+//! Innovative Technology note validators (NV9, NV10 and their kin) over
+//! SSP, encrypted as eSSP (`acceptor_type = "essp"`). The host syncs with
+//! the validator, negotiates a key, tells it which protocol version it
+//! speaks and reads its channels from SETUP REQUEST. Channel n is bill
+//! type n - 1 to the rest of the kiosk, so `bill_types` and the enable
+//! masks mean the same as with a CashCode.
+//!
+//! Every bill stops in escrow and is reported by a READ event; the next
+//! POLL stacks it, HOLD keeps it there and REJECT hands it back. One POLL
+//! answer can carry several events, handed out one per `poll`.
+
+use aes::Aes128;
+use aes::cipher::generic_array::GenericArray;
+use aes::cipher::{BlockDecrypt, BlockEncrypt, KeyInit};
+use log::{debug, error, info, warn};
+use serialport::{SerialPort, StopBits};
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::{self, Read, Write};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::acceptor::{BillAcceptor, BillCounters};
+use crate::cashcode::{
+    BillEvent, BillNominal, BillTable, CashCodeError, DeviceState, DeviceStatus, Identification,
+    bill_type_mask,
+};
+use crate::config;
+use crate::frame_log::{Direction, FrameLog};
+use crate::i18n::Message;
+use crate::{clock, money, preflight};
+
+// framing
+const STX: u8 = 0x7F;
+// top bit of the address byte, flipped for every new command
+const SEQ_FLAG: u8 = 0x80;
+// first data byte of an encrypted packet
+const ESSP_MARKER: u8 = 0x7E;
+
+// command codes
+const COMMAND_RESET: u8 = 0x01;
+// followed by the channel bits, channel 1 lowest, a set bit takes it
+const COMMAND_SET_INHIBITS: u8 = 0x02;
+// unit type, firmware, country, channel values, protocol version
+const COMMAND_SETUP_REQUEST: u8 = 0x05;
+const COMMAND_HOST_PROTOCOL_VERSION: u8 = 0x06;
+// stacks a bill held in escrow
+const COMMAND_POLL: u8 = 0x07;
+// hands a bill held in escrow back
+const COMMAND_REJECT: u8 = 0x08;
+const COMMAND_DISABLE: u8 = 0x09;
+const COMMAND_ENABLE: u8 = 0x0A;
+// 4 bytes, most significant first
+const COMMAND_GET_SERIAL_NUMBER: u8 = 0x0C;
+// resets the sequence flag; the first command on a line
+const COMMAND_SYNC: u8 = 0x11;
+const COMMAND_LAST_REJECT_CODE: u8 = 0x17;
+// keeps a bill in escrow for another 10 s, sent instead of POLL
+const COMMAND_HOLD: u8 = 0x18;
+// 16 bytes of ASCII
+const COMMAND_GET_FIRMWARE_VERSION: u8 = 0x20;
+// key exchange: generator and modulus (8 bytes each, least significant
+// first), then the host's intermediate key for the validator's
+const COMMAND_SET_GENERATOR: u8 = 0x4A;
+const COMMAND_SET_MODULUS: u8 = 0x4B;
+const COMMAND_REQUEST_KEY_EXCHANGE: u8 = 0x4C;
+
+// generic responses, the first byte of every answer
+const RESPONSE_OK: u8 = 0xF0;
+const RESPONSE_UNKNOWN_COMMAND: u8 = 0xF2;
+const RESPONSE_WRONG_PARAMETERS: u8 = 0xF3;
+const RESPONSE_PARAMETER_OUT_OF_RANGE: u8 = 0xF4;
+const RESPONSE_CANNOT_PROCESS: u8 = 0xF5;
+const RESPONSE_SOFTWARE_ERROR: u8 = 0xF6;
+const RESPONSE_FAIL: u8 = 0xF8;
+const RESPONSE_KEY_NOT_SET: u8 = 0xFA;
+
+// poll events; the ones marked take a channel byte
+const EVENT_SLAVE_RESET: u8 = 0xF1;
+// channel, 0 while the bill is still being read
+const EVENT_READ: u8 = 0xEF;
+// channel
+const EVENT_CREDIT: u8 = 0xEE;
+const EVENT_REJECTING: u8 = 0xED;
+const EVENT_REJECTED: u8 = 0xEC;
+const EVENT_STACKING: u8 = 0xCC;
+const EVENT_STACKED: u8 = 0xEB;
+const EVENT_SAFE_JAM: u8 = 0xEA;
+const EVENT_UNSAFE_JAM: u8 = 0xE9;
+const EVENT_DISABLED: u8 = 0xE8;
+const EVENT_STACKER_FULL: u8 = 0xE7;
+// channel
+const EVENT_FRAUD_ATTEMPT: u8 = 0xE6;
+// cashbox events, named the CCNET way
+const EVENT_STACKER_REMOVED: u8 = 0xE3;
+const EVENT_STACKER_REPLACED: u8 = 0xE4;
+// channel; a bill found in the path at power-up, handed back or stacked
+const EVENT_CLEARED_FROM_FRONT: u8 = 0xE1;
+const EVENT_CLEARED_INTO_STACKER: u8 = 0xE2;
+// enabled, but every channel inhibited
+const EVENT_CHANNEL_DISABLE: u8 = 0xB5;
+const EVENT_INITIALISING: u8 = 0xB6;
+
+// LAST REJECT CODE answers
+const REJECT_CHANNEL_INHIBITED: u8 = 0x06;
+const REJECT_SECOND_NOTE: u8 = 0x07;
+const REJECT_NOTE_TOO_LONG: u8 = 0x0A;
+const REJECT_MECHANISM_SLOW: u8 = 0x0C;
+const REJECT_STRIMMING: u8 = 0x0D;
+const REJECT_FRAUD_CHANNEL: u8 = 0x0E;
+const REJECT_DISABLED: u8 = 0x15;
+
+// the newest SSP protocol version this driver knows; 6 brought channel
+// values too big for one byte
+const PROTOCOL_VERSION: u8 = 6;
+
+// channels one enable mask covers
+const BILL_TYPES: usize = 24;
+const MASK_ALL: [u8; 3] = [0xFF, 0xFF, 0xFF];
+const MASK_NONE: [u8; 3] = [0x00, 0x00, 0x00];
+
+// the validator answers well within this
+const RESPONSE_TIMEOUT: Duration = Duration::from_millis(500);
+// a command goes out this often before the line counts as lost; the
+// validator answers a repeat (same sequence flag) with its last answer
+const ATTEMPTS: usize = 3;
+// HOLD is sent at most this often while a bill waits in escrow
+const HOLD_INTERVAL: Duration = Duration::from_secs(5);
+
+/// The key ITL validators ship with, for when `essp.fixed_key` is broken.
+const DEFAULT_FIXED_KEY: u64 = 0x0123_4567_0123_4567;
+
+/// SSP CRC-16 (polynomial 0x8005, starting at 0xFFFF) of a packet from
+/// the address byte on, without its CRC.
+pub fn crc16(bytes: &[u8]) -> u16 {
+    let mut crc: u16 = 0xFFFF;
+    for byte in bytes {
+        crc ^= u16::from(*byte) << 8;
+        for _ in 0..8 {
+            crc = if crc & 0x8000 != 0 {
+                (crc << 1) ^ 0x8005
+            } else {
+                crc << 1
+            };
+        }
+    }
+    crc
+}
+
+/// A frame as it goes on the wire: STX, address with the sequence flag,
+/// length, data and CRC (low byte first), with every STX byte after the
+/// first sent twice.
+pub fn frame(address: u8, data: &[u8]) -> Vec<u8> {
+    let mut packet = vec![address, data.len() as u8];
+    packet.extend_from_slice(data);
+    packet.extend_from_slice(&crc16(&packet).to_le_bytes());
+    let mut frame = vec![STX];
+    for byte in packet {
+        frame.push(byte);
+        if byte == STX {
+            frame.push(STX);
+        }
+    }
+    frame
+}
+
+/// The first whole packet in `raw` with the stuffing undone (address,
+/// length, data, CRC), or `None` while it is still coming in. A lone STX
+/// starts over.
+fn unstuff(raw: &[u8]) -> Option<Vec<u8>> {
+    let start = raw.iter().position(|byte| *byte == STX)?;
+    let mut packet = Vec::new();
+    let mut i = start + 1;
+    while i < raw.len() {
+        if raw[i] == STX {
+            match raw.get(i + 1) {
+                Some(&STX) => {
+                    packet.push(STX);
+                    i += 2;
+                }
+                Some(_) => {
+                    packet.clear();
+                    i += 1;
+                    continue;
+                }
+                None => return None,
+            }
+        } else {
+            packet.push(raw[i]);
+            i += 1;
+        }
+        if packet.len() >= 2 && packet.len() == usize::from(packet[1]) + 4 {
+            return Some(packet);
+        }
+    }
+    None
+}
+
+fn describe(response: u8) -> &'static str {
+    match response {
+        RESPONSE_OK => "OK",
+        RESPONSE_UNKNOWN_COMMAND => "unknown command",
+        RESPONSE_WRONG_PARAMETERS => "wrong number of parameters",
+        RESPONSE_PARAMETER_OUT_OF_RANGE => "parameter out of range",
+        RESPONSE_CANNOT_PROCESS => "cannot process now",
+        RESPONSE_SOFTWARE_ERROR => "software error",
+        RESPONSE_FAIL => "failed",
+        RESPONSE_KEY_NOT_SET => "encryption key not set",
+        _ => "unknown answer",
+    }
+}
+
+/// Whether poll event `code` is followed by a channel byte.
+fn takes_channel(code: u8) -> bool {
+    matches!(
+        code,
+        EVENT_READ
+            | EVENT_CREDIT
+            | EVENT_FRAUD_ATTEMPT
+            | EVENT_CLEARED_FROM_FRONT
+            | EVENT_CLEARED_INTO_STACKER
+    )
+}
+
+/// Poll events without data that the driver knows.
+fn is_known(code: u8) -> bool {
+    matches!(
+        code,
+        EVENT_SLAVE_RESET
+            | EVENT_REJECTING
+            | EVENT_REJECTED
+            | EVENT_STACKING
+            | EVENT_STACKED
+            | EVENT_SAFE_JAM
+            | EVENT_UNSAFE_JAM
+            | EVENT_DISABLED
+            | EVENT_STACKER_FULL
+            | EVENT_STACKER_REMOVED
+            | EVENT_STACKER_REPLACED
+            | EVENT_CHANNEL_DISABLE
+            | EVENT_INITIALISING
+    )
+}
+
+/// SETUP REQUEST data after the OK: unit type, 4 bytes of firmware
+/// version, country code, value multiplier (3 bytes, most significant
+/// first), number of channels n, n channel values, n security levels,
+/// real value multiplier (3 bytes), protocol version. From protocol 6 on,
+/// n country codes and n 4-byte values (least significant first) follow,
+/// and a value multiplier of 0 says to use those.
+pub fn parse_setup(data: &[u8]) -> Option<BillTable> {
+    let multiplier = u32::from_be_bytes([0, *data.get(8)?, *data.get(9)?, *data.get(10)?]);
+    let channels = usize::from(*data.get(11)?);
+    let values = data.get(12..12 + channels)?;
+    let protocol = *data.get(12 + 2 * channels + 3)?;
+    let extended = 12 + 2 * channels + 4;
+    let countries = data.get(extended..extended + 3 * channels);
+    let wide_values = data.get(extended + 3 * channels..extended + 7 * channels);
+
+    let mut table = BillTable::default();
+    for channel in 0..channels.min(BILL_TYPES) {
+        let value = match wide_values {
+            Some(wide) if protocol >= 6 && (multiplier == 0 || values[channel] == 0) => {
+                let bytes = &wide[channel * 4..channel * 4 + 4];
+                u32::from_le_bytes(bytes.try_into().ok()?)
+            }
+            _ => u32::from(values[channel]) * multiplier,
+        };
+        let value = i32::try_from(value).unwrap_or(0);
+        let country = match countries {
+            Some(countries) => &countries[channel * 3..channel * 3 + 3],
+            None => &data[5..8],
+        };
+        let country = String::from_utf8_lossy(country).trim().to_string();
+        if value > 0 && !table.countries.contains(&country) {
+            table.countries.push(country);
+        }
+        table.values.push(value);
+    }
+    while table.values.last() == Some(&0) {
+        table.values.pop();
+    }
+    Some(table)
+}
+
+/// Bytes from the kernel's random source, for keys and packing.
+fn random_bytes(n: usize) -> io::Result<Vec<u8>> {
+    let mut bytes = vec![0u8; n];
+    File::open("/dev/urandom")?.read_exact(&mut bytes)?;
+    Ok(bytes)
+}
+
+fn random_u32() -> io::Result<u32> {
+    let bytes = random_bytes(4)?;
+    Ok(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+}
+
+fn pow_mod(base: u64, mut exponent: u64, modulus: u64) -> u64 {
+    let modulus = u128::from(modulus);
+    let mut base = u128::from(base) % modulus;
+    let mut result = 1u128;
+    while exponent > 0 {
+        if exponent & 1 == 1 {
+            result = result * base % modulus;
+        }
+        base = base * base % modulus;
+        exponent >>= 1;
+    }
+    result as u64
+}
+
+/// Miller-Rabin with the bases that settle every number below 2^32.
+fn is_prime(n: u64) -> bool {
+    if n < 2 || n.is_multiple_of(2) {
+        return n == 2;
+    }
+    let (mut d, mut s) = (n - 1, 0);
+    while d % 2 == 0 {
+        d /= 2;
+        s += 1;
+    }
+    [2, 7, 61].iter().all(|&a| {
+        if a % n == 0 {
+            return true;
+        }
+        let mut x = pow_mod(a, d, n);
+        if x == 1 || x == n - 1 {
+            return true;
+        }
+        for _ in 1..s {
+            x = pow_mod(x, 2, n);
+            if x == n - 1 {
+                return true;
+            }
+        }
+        false
+    })
+}
+
+/// A random prime below 2^31, which is as big as ITL's own hosts use.
+fn random_prime() -> io::Result<u64> {
+    loop {
+        let candidate = u64::from(random_u32()? | 0x4000_0001) & 0x7FFF_FFFF;
+        if is_prime(candidate) {
+            return Ok(candidate);
+        }
+    }
+}
+
+/// A bill held in escrow until the donor decides.
+struct Held {
+    since: Instant,
+    last_hold: Instant,
+}
+
+pub struct Essp {
+    port: Box<dyn SerialPort>,
+    address: u8,
+    /// Sequence flag of the next new command.
+    seq: u8,
+    /// Negotiate a key and encrypt (`essp.encrypted`).
+    encrypted: bool,
+    fixed_key: u64,
+    /// The negotiated key, until the next SYNC.
+    cipher: Option<Aes128>,
+    /// eSSP packet count: the next packet out carries it, the answer one
+    /// more.
+    count: u32,
+    /// Synced (and keyed) since the line was last lost or reset.
+    connected: bool,
+    /// Channels the validator has, from SETUP REQUEST.
+    channels: usize,
+    state: DeviceState,
+    /// Events from the last POLL answer not handed out yet.
+    pending: VecDeque<BillEvent>,
+    /// A bill is being read or stacked.
+    in_transit: bool,
+    /// Channel of the bill waiting in escrow, reported by READ.
+    escrowed: Option<u8>,
+    stacker_removed: bool,
+    stacker_full: bool,
+    /// Bills are returned from escrow, not stacked.
+    test_mode: bool,
+    /// Dry run: bills are reported as accepted and returned, never stacked
+    /// or recorded in the ledger.
+    dry_run: bool,
+    /// Escrow mode: how long a bill is held for the donor to accept it
+    /// before it is returned. `None` stacks bills straight away.
+    escrow: Option<Duration>,
+    held: Option<Held>,
+    /// A REJECT was sent for the held bill; its return is reported.
+    returning: bool,
+    /// A REJECT was sent in test mode or dry run; the return isn't a reject.
+    handing_back: bool,
+    /// Bill values by bill type (channel - 1), 0 for unused channels.
+    bill_types: Vec<i32>,
+    /// Bill types taken while enabled (`accepted_nominals` in config).
+    accepted_mask: [u8; 3],
+    /// Bill types we last asked the validator to take.
+    enabled_mask: [u8; 3],
+    counters: BillCounters,
+    frame_log: Arc<FrameLog>,
+}
+
+impl Essp {
+    pub fn new(
+        port_path: &str,
+        db_path: &str,
+        settings: &config::Essp,
+        frame_log: Arc<FrameLog>,
+    ) -> Result<Self, CashCodeError> {
+        info!("opening serial port: {} (SSP)", port_path);
+
+        preflight::check_serial(port_path)?;
+        let port = serialport::new(port_path, 9600)
+            .stop_bits(StopBits::Two)
+            .timeout(RESPONSE_TIMEOUT)
+            .open()?;
+
+        let fixed_key = settings.fixed_key().unwrap_or_else(|| {
+            warn!("essp.fixed_key isn't 16 hex digits, using the ITL default");
+            DEFAULT_FIXED_KEY
+        });
+        let counters = BillCounters::open(db_path)?;
+
+        let mut essp = Essp {
+            port,
+            address: settings.address & !SEQ_FLAG,
+            seq: SEQ_FLAG,
+            encrypted: settings.encrypted,
+            fixed_key,
+            cipher: None,
+            count: 0,
+            connected: false,
+            channels: 16,
+            state: DeviceState::Initializing,
+            pending: VecDeque::new(),
+            in_transit: false,
+            escrowed: None,
+            stacker_removed: false,
+            stacker_full: false,
+            test_mode: false,
+            dry_run: false,
+            escrow: None,
+            held: None,
+            returning: false,
+            handing_back: false,
+            bill_types: Vec::new(),
+            accepted_mask: MASK_NONE,
+            enabled_mask: MASK_NONE,
+            counters,
+            frame_log,
+        };
+        essp.set_bill_types(money::bill_table(money::currency()))?;
+        Ok(essp)
+    }
+
+    /// Escrow mode: each bill is held until `stack` or `return_bill`, or
+    /// returned after `timeout`.
+    pub fn set_escrow(&mut self, timeout: Option<Duration>) {
+        self.escrow = timeout;
+    }
+
+    /// Dry run: each bill is counted as `Accepted` and handed back instead
+    /// of stacked. Wins over escrow mode.
+    pub fn set_dry_run(&mut self, on: bool) {
+        self.dry_run = on;
+    }
+
+    /// Sends `data` and reads the answer's data, encrypted both ways once
+    /// a key is set. A lost answer is asked for again with the same
+    /// sequence flag; `None` when none came, and the line is synced again
+    /// before the next command.
+    fn exchange(&mut self, data: &[u8]) -> Result<Option<Vec<u8>>, CashCodeError> {
+        let payload = match &self.cipher {
+            Some(cipher) => {
+                let payload = encrypt(cipher, self.count, data)?;
+                self.count = self.count.wrapping_add(1);
+                payload
+            }
+            None => data.to_vec(),
+        };
+        let frame = frame(self.address | self.seq, &payload);
+        for _ in 0..ATTEMPTS {
+            self.drop_stale()?;
+            self.frame_log.record(Direction::Tx, &frame);
+            self.port.write_all(&frame)?;
+            self.port.flush()?;
+            let Some(packet) = self.read_packet()? else {
+                continue;
+            };
+            if packet[0] != self.address | self.seq {
+                debug!("answer for another address or command: {:02X?}", packet);
+                continue;
+            }
+            self.seq ^= SEQ_FLAG;
+            let data = packet[2..packet.len() - 2].to_vec();
+            return match &self.cipher {
+                Some(cipher) if data.first() == Some(&ESSP_MARKER) => {
+                    match decrypt(cipher, &data[1..]) {
+                        Some((count, data)) if count == self.count => {
+                            self.count = self.count.wrapping_add(1);
+                            Ok(Some(data))
+                        }
+                        Some((count, _)) => {
+                            warn!(
+                                "eSSP count out of step ({} for {}), syncing again",
+                                count, self.count
+                            );
+                            self.connected = false;
+                            Ok(None)
+                        }
+                        None => {
+                            warn!("eSSP answer doesn't decrypt, syncing again");
+                            self.connected = false;
+                            Ok(None)
+                        }
+                    }
+                }
+                _ => {
+                    if data == [RESPONSE_KEY_NOT_SET] {
+                        // it was reset behind our back
+                        self.connected = false;
+                    }
+                    Ok(Some(data))
+                }
+            };
+        }
+        debug!("no answer from the validator to {:02X?}", data.first());
+        self.connected = false;
+        Ok(None)
+    }
+
+    /// Reads one packet with a good CRC, or nothing within
+    /// `RESPONSE_TIMEOUT`.
+    fn read_packet(&mut self) -> Result<Option<Vec<u8>>, CashCodeError> {
+        let started = clock::instant();
+        let mut raw = Vec::new();
+        let mut chunk = [0u8; 256];
+        let packet = loop {
+            let Some(left) = RESPONSE_TIMEOUT.checked_sub(clock::since(started)) else {
+                break None;
+            };
+            self.port.set_timeout(left)?;
+            match self.port.read(&mut chunk) {
+                Ok(0) => break None,
+                Ok(n) => raw.extend_from_slice(&chunk[..n]),
+                Err(e) if e.kind() == io::ErrorKind::TimedOut => break None,
+                Err(e) => return Err(e.into()),
+            }
+            if let Some(packet) = unstuff(&raw) {
+                break Some(packet);
+            }
+        };
+        if !raw.is_empty() {
+            self.frame_log.record(Direction::Rx, &raw);
+        }
+        let Some(packet) = packet else {
+            if !raw.is_empty() {
+                debug!("partial answer: {:02X?}", raw);
+            }
+            return Ok(None);
+        };
+        let length = packet.len();
+        let crc = u16::from_le_bytes([packet[length - 2], packet[length - 1]]);
+        if crc16(&packet[..length - 2]) != crc {
+            debug!("answer with a bad CRC: {:02X?}", packet);
+            return Ok(None);
+        }
+        Ok(Some(packet))
+    }
+
+    /// Drops whatever came in since the last answer was read.
+    fn drop_stale(&mut self) -> Result<(), CashCodeError> {
+        let bytes_available = self.port.bytes_to_read()? as usize;
+        if bytes_available > 0 {
+            let mut buffer = vec![0u8; bytes_available];
+            self.port.read_exact(&mut buffer)?;
+            self.frame_log.record(Direction::Rx, &buffer);
+            debug!("dropped stale bytes: {:02X?}", buffer);
+        }
+        Ok(())
+    }
+
+    /// SYNC, the key exchange when encrypting, and the protocol version.
+    /// False when the validator doesn't answer or refuses.
+    fn connect(&mut self) -> Result<bool, CashCodeError> {
+        self.cipher = None;
+        self.seq = SEQ_FLAG;
+        match self.exchange(&[COMMAND_SYNC])? {
+            Some(answer) if answer.first() == Some(&RESPONSE_OK) => {}
+            Some(answer) => {
+                warn!("validator refused SYNC: {:02X?}", answer);
+                return Ok(false);
+            }
+            None => return Ok(false),
+        }
+        if self.encrypted && !self.negotiate_key()? {
+            return Ok(false);
+        }
+        match self.exchange(&[COMMAND_HOST_PROTOCOL_VERSION, PROTOCOL_VERSION])? {
+            Some(answer) if answer.first() == Some(&RESPONSE_OK) => {}
+            Some(answer) => warn!(
+                "validator doesn't speak SSP protocol {} ({}), carrying on",
+                PROTOCOL_VERSION,
+                answer.first().map_or("no answer", |code| describe(*code))
+            ),
+            None => return Ok(false),
+        }
+        info!(
+            "validator synced{}",
+            if self.encrypted { ", eSSP key set" } else { "" }
+        );
+        self.connected = true;
+        Ok(true)
+    }
+
+    /// Diffie-Hellman over two fresh primes for the second half of the AES
+    /// key; the first half is the fixed key.
+    fn negotiate_key(&mut self) -> Result<bool, CashCodeError> {
+        // the validator wants the generator to be the bigger one
+        let (mut generator, mut modulus) = (random_prime()?, random_prime()?);
+        while generator == modulus {
+            modulus = random_prime()?;
+        }
+        if generator < modulus {
+            std::mem::swap(&mut generator, &mut modulus);
+        }
+        let host_random = u64::from(random_u32()? & 0x7FFF_FFFF).max(2);
+        let host_key = pow_mod(generator, host_random, modulus);
+
+        let mut validator_key = None;
+        for (command, value) in [
+            (COMMAND_SET_GENERATOR, generator),
+            (COMMAND_SET_MODULUS, modulus),
+            (COMMAND_REQUEST_KEY_EXCHANGE, host_key),
+        ] {
+            let mut data = vec![command];
+            data.extend_from_slice(&value.to_le_bytes());
+            let answer = self.exchange(&data)?.unwrap_or_default();
+            if answer.first() != Some(&RESPONSE_OK) {
+                warn!(
+                    "validator refused the key exchange at 0x{:02X}: {}",
+                    command,
+                    answer.first().map_or("no answer", |code| describe(*code))
+                );
+                return Ok(false);
+            }
+            validator_key = answer
+                .get(1..9)
+                .map(|bytes| u64::from_le_bytes(bytes.try_into().unwrap_or_default()));
+        }
+        let Some(validator_key) = validator_key else {
+            warn!("validator sent no key of its own");
+            return Ok(false);
+        };
+
+        let shared = pow_mod(validator_key, host_random, modulus);
+        let mut key = [0u8; 16];
+        key[..8].copy_from_slice(&self.fixed_key.to_le_bytes());
+        key[8..].copy_from_slice(&shared.to_le_bytes());
+        self.cipher = Some(Aes128::new(GenericArray::from_slice(&key)));
+        self.count = 0;
+        Ok(true)
+    }
+
+    /// Sends `command`, syncing first if the line was lost. The answer
+    /// starts with the generic response; `None` when there was none.
+    fn command(&mut self, command: u8, data: &[u8]) -> Result<Option<Vec<u8>>, CashCodeError> {
+        if !self.connected && !self.connect()? {
+            return Ok(None);
+        }
+        let mut packet = vec![command];
+        packet.extend_from_slice(data);
+        self.exchange(&packet)
+    }
+
+    /// Sends a command that is only answered OK or not; logs the refusal.
+    fn expect_ok(&mut self, command: u8, data: &[u8], what: &str) -> Result<bool, CashCodeError> {
+        match self.command(command, data)? {
+            Some(answer) if answer.first() == Some(&RESPONSE_OK) => Ok(true),
+            Some(answer) => {
+                warn!(
+                    "validator refused {}: {}",
+                    what,
+                    answer
+                        .first()
+                        .map_or("empty answer", |code| describe(*code))
+                );
+                Ok(false)
+            }
+            None => {
+                warn!("no answer from the validator to {}", what);
+                Ok(false)
+            }
+        }
+    }
+
+    /// SET INHIBITS: bill types in `mask` are taken once enabled.
+    fn set_inhibits(&mut self, mask: [u8; 3]) -> Result<bool, CashCodeError> {
+        self.enabled_mask = mask;
+        let bits = u32::from_be_bytes([0, mask[0], mask[1], mask[2]]);
+        let bytes = self.channels.div_ceil(8).clamp(2, 3);
+        self.expect_ok(
+            COMMAND_SET_INHIBITS,
+            &bits.to_le_bytes()[..bytes],
+            "set inhibits",
+        )
+    }
+
+    /// Codes of the bills in the table whose value passes `keep`.
+    fn bill_codes(&self, keep: impl Fn(i32) -> bool) -> Vec<u8> {
+        (0..)
+            .zip(&self.bill_types)
+            .filter(|(_, value)| **value > 0 && keep(**value))
+            .map(|(code, _)| code)
+            .collect()
+    }
+
+    fn nominal(&self, channel: u8) -> Option<BillNominal> {
+        let code = usize::from(channel.checked_sub(1)?);
+        self.bill_types
+            .get(code)
+            .filter(|value| **value > 0)
+            .map(|value| BillNominal::new(*value))
+    }
+
+    fn unknown_nominal(channel: u8) -> BillEvent {
+        BillEvent::Error(Message::with(
+            "bill.unknown_nominal",
+            &[&format!("{:02X}", channel)],
+        ))
+    }
+
+    /// REJECT for the bill in escrow; `report` says whether its return is
+    /// news to the donor.
+    fn hand_back(&mut self, report: bool) -> Result<(), CashCodeError> {
+        if report {
+            self.returning = true;
+        } else {
+            self.handing_back = true;
+        }
+        self.expect_ok(COMMAND_REJECT, &[], "reject")?;
+        Ok(())
+    }
+
+    /// Why the last bill was refused, from LAST REJECT CODE.
+    fn reject_reason(&mut self) -> Result<Message, CashCodeError> {
+        let code = match self.command(COMMAND_LAST_REJECT_CODE, &[])? {
+            Some(answer) if answer.first() == Some(&RESPONSE_OK) => answer.get(1).copied(),
+            _ => None,
+        };
+        debug!("last reject code: {:02X?}", code);
+        Ok(Message::new(match code {
+            Some(REJECT_CHANNEL_INHIBITED | REJECT_DISABLED) => "bill.reject.inhibited",
+            Some(REJECT_SECOND_NOTE) => "bill.reject.insertion",
+            Some(REJECT_NOTE_TOO_LONG | REJECT_MECHANISM_SLOW | REJECT_STRIMMING) => {
+                "bill.reject.conveying"
+            }
+            Some(REJECT_FRAUD_CHANNEL) => "bill.reject.verification",
+            // the recognition failures: length, average, coastline, graph…
+            Some(0x01..=0x05 | 0x08 | 0x09 | 0x0B) => "bill.reject.identification",
+            _ => "bill.reject.unknown",
+        }))
+    }
+
+    /// READ with a channel: the bill waits in escrow.
+    fn bill_read(&mut self, channel: u8) -> Result<Option<BillEvent>, CashCodeError> {
+        if self.escrowed == Some(channel) {
+            return Ok(None);
+        }
+        self.escrowed = Some(channel);
+        let nominal = self.nominal(channel);
+        if self.test_mode {
+            info!(
+                "test bill recognised: {:?} (channel {}), returning",
+                nominal, channel
+            );
+            self.hand_back(false)?;
+            return Ok(Some(BillEvent::TestBill(nominal, channel - 1)));
+        }
+        if self.dry_run {
+            self.hand_back(false)?;
+            return Ok(Some(match nominal {
+                Some(nominal) => {
+                    info!(
+                        "dry run: bill counted and returned: {} {}",
+                        nominal.value(),
+                        money::currency()
+                    );
+                    BillEvent::Accepted(nominal)
+                }
+                None => {
+                    warn!("dry run: unknown channel {}, returned", channel);
+                    Self::unknown_nominal(channel)
+                }
+            }));
+        }
+        let Some(nominal) = nominal else {
+            warn!("bill in escrow on unknown channel {}, returning", channel);
+            self.hand_back(false)?;
+            return Ok(Some(Self::unknown_nominal(channel)));
+        };
+        if self.escrow.is_none() {
+            // the next POLL stacks it
+            return Ok(None);
+        }
+        info!(
+            "bill in escrow: {} {}, waiting for the donor",
+            nominal.value(),
+            money::currency()
+        );
+        let now = clock::instant();
+        self.held = Some(Held {
+            since: now,
+            last_hold: now,
+        });
+        Ok(Some(BillEvent::Escrow(nominal)))
+    }
+
+    /// One poll event; `state` is set to what it says about the validator.
+    fn event(
+        &mut self,
+        code: u8,
+        channel: Option<u8>,
+        state: &mut Option<DeviceState>,
+    ) -> Result<Option<BillEvent>, CashCodeError> {
+        let event = match (code, channel) {
+            (EVENT_SLAVE_RESET, _) => {
+                info!("validator reset");
+                *state = Some(DeviceState::Initializing);
+                self.held = None;
+                self.escrowed = None;
+                self.in_transit = false;
+                // it comes back disabled, with the inhibits it was set up with
+                if self.enabled_mask != MASK_NONE {
+                    self.reapply_mask()?;
+                }
+                None
+            }
+            (EVENT_INITIALISING, _) => {
+                *state = Some(DeviceState::Initializing);
+                None
+            }
+            (EVENT_READ, Some(0)) => {
+                self.in_transit = true;
+                None
+            }
+            (EVENT_READ, Some(channel)) => {
+                self.in_transit = true;
+                self.bill_read(channel)?
+            }
+            (EVENT_CREDIT, Some(channel)) => {
+                self.in_transit = false;
+                self.escrowed = None;
+                match self.nominal(channel) {
+                    Some(nominal) => {
+                        info!("bill accepted: {} {}", nominal.value(), money::currency());
+                        self.counters.record(nominal)?;
+                        Some(BillEvent::Accepted(nominal))
+                    }
+                    None => {
+                        warn!("bill accepted on unknown channel {}", channel);
+                        Some(Self::unknown_nominal(channel))
+                    }
+                }
+            }
+            (EVENT_REJECTING | EVENT_STACKING | EVENT_STACKED, _) => None,
+            (EVENT_REJECTED, _) => {
+                self.in_transit = false;
+                self.escrowed = None;
+                if std::mem::take(&mut self.returning) {
+                    info!("bill returned");
+                    Some(BillEvent::Returned)
+                } else if std::mem::take(&mut self.handing_back) {
+                    None
+                } else {
+                    let reason = self.reject_reason()?;
+                    warn!("bill rejected: {}", reason);
+                    Some(BillEvent::Rejected(reason))
+                }
+            }
+            (EVENT_FRAUD_ATTEMPT, channel) => {
+                self.in_transit = false;
+                self.escrowed = None;
+                error!("ERR: fraud attempt (channel {:?})", channel);
+                Some(BillEvent::Rejected(Message::new(
+                    "bill.reject.verification",
+                )))
+            }
+            (EVENT_SAFE_JAM | EVENT_UNSAFE_JAM, _) => {
+                let new = self.state != DeviceState::Jammed;
+                *state = Some(DeviceState::Jammed);
+                if new {
+                    error!(
+                        "ERR: bill jam ({})",
+                        if code == EVENT_SAFE_JAM {
+                            "safe"
+                        } else {
+                            "unsafe"
+                        }
+                    );
+                    Some(BillEvent::Jam(Message::new("bill.jam.acceptor")))
+                } else {
+                    None
+                }
+            }
+            (EVENT_DISABLED | EVENT_CHANNEL_DISABLE, _) => {
+                *state = Some(DeviceState::Disabled);
+                None
+            }
+            (EVENT_STACKER_FULL, _) => {
+                *state = Some(DeviceState::StackerFull);
+                if !self.stacker_full {
+                    self.stacker_full = true;
+                    error!("ERR: stacker full");
+                    Some(BillEvent::StackerFull)
+                } else {
+                    None
+                }
+            }
+            (EVENT_STACKER_REMOVED, _) => {
+                *state = Some(DeviceState::StackerRemoved);
+                self.stacker_full = false;
+                if !self.stacker_removed {
+                    self.stacker_removed = true;
+                    error!("ERR: stacker removed");
+                    Some(BillEvent::StackerRemoved)
+                } else {
+                    None
+                }
+            }
+            (EVENT_STACKER_REPLACED, _) => {
+                info!("stacker replaced");
+                self.stacker_removed = false;
+                Some(BillEvent::StackerReplaced)
+            }
+            (EVENT_CLEARED_FROM_FRONT, channel) => {
+                info!("bill left in the path handed back (channel {:?})", channel);
+                self.in_transit = false;
+                None
+            }
+            (EVENT_CLEARED_INTO_STACKER, channel) => {
+                // taken while the kiosk wasn't looking: not credited
+                warn!(
+                    "bill left in the path stacked without credit (channel {:?})",
+                    channel
+                );
+                self.in_transit = false;
+                None
+            }
+            _ => {
+                warn!("Unknown event: 0x{:02X} {:02X?}", code, channel);
+                None
+            }
+        };
+        Ok(event)
+    }
+}
+
+/// eSSP packet data: the marker, then AES blocks of length, count, data,
+/// random packing and the CRC over all of it.
+fn encrypt(cipher: &Aes128, count: u32, data: &[u8]) -> Result<Vec<u8>, CashCodeError> {
+    let mut plain = vec![data.len() as u8];
+    plain.extend_from_slice(&count.to_le_bytes());
+    plain.extend_from_slice(data);
+    let packing = (plain.len() + 2).next_multiple_of(16) - plain.len() - 2;
+    plain.extend_from_slice(&random_bytes(packing)?);
+    plain.extend_from_slice(&crc16(&plain).to_le_bytes());
+    for block in plain.chunks_exact_mut(16) {
+        cipher.encrypt_block(GenericArray::from_mut_slice(block));
+    }
+    let mut payload = vec![ESSP_MARKER];
+    payload.append(&mut plain);
+    Ok(payload)
+}
+
+/// The count and data of an eSSP answer (after the marker); `None` when
+/// it doesn't decrypt to a good CRC.
+fn decrypt(cipher: &Aes128, encrypted: &[u8]) -> Option<(u32, Vec<u8>)> {
+    if encrypted.is_empty() || !encrypted.len().is_multiple_of(16) {
+        return None;
+    }
+    let mut plain = encrypted.to_vec();
+    for block in plain.chunks_exact_mut(16) {
+        cipher.decrypt_block(GenericArray::from_mut_slice(block));
+    }
+    let length = plain.len();
+    let crc = u16::from_le_bytes([plain[length - 2], plain[length - 1]]);
+    if crc16(&plain[..length - 2]) != crc {
+        return None;
+    }
+    let count = u32::from_le_bytes(plain[1..5].try_into().ok()?);
+    let data = plain.get(5..5 + usize::from(plain[0]))?.to_vec();
+    Some((count, data))
+}
+
+impl BillAcceptor for Essp {
+    fn reset(&mut self) -> Result<(), CashCodeError> {
+        info!("resetting bill validator...");
+        if self.expect_ok(COMMAND_RESET, &[], "reset")? {
+            info!("bill validator reset OK");
+        }
+        // it reboots; everything from the key on starts over
+        self.connected = false;
+        self.state = DeviceState::Initializing;
+        self.pending.clear();
+        self.held = None;
+        self.escrowed = None;
+        self.in_transit = false;
+        self.enabled_mask = MASK_NONE;
+        Ok(())
+    }
+
+    /// Syncs and polls until the validator reports it is up (disabled or
+    /// idle). Returns `false` if it still isn't after `timeout`.
+    fn wait_ready(&mut self, timeout: Duration) -> Result<bool, CashCodeError> {
+        let deadline = clock::instant() + timeout;
+        while clock::instant() < deadline {
+            self.poll()?;
+            if self.connected && matches!(self.state, DeviceState::Disabled | DeviceState::Idle) {
+                return Ok(true);
+            }
+            clock::sleep(Duration::from_millis(200));
+        }
+        Ok(false)
+    }
+
+    /// Firmware version and serial number. `None` when the validator
+    /// doesn't answer in kind.
+    fn identify(&mut self) -> Result<Option<Identification>, CashCodeError> {
+        let firmware = match self.command(COMMAND_GET_FIRMWARE_VERSION, &[])? {
+            Some(answer) if answer.first() == Some(&RESPONSE_OK) => {
+                String::from_utf8_lossy(&answer[1..])
+                    .trim_matches(|c: char| c == ' ' || c == '\0')
+                    .to_string()
+            }
+            _ => return Ok(None),
+        };
+        let serial = match self.command(COMMAND_GET_SERIAL_NUMBER, &[])? {
+            Some(answer) if answer.len() >= 5 && answer[0] == RESPONSE_OK => {
+                u32::from_be_bytes([answer[1], answer[2], answer[3], answer[4]])
+            }
+            _ => return Ok(None),
+        };
+        Ok(Some(Identification {
+            part_number: firmware,
+            serial_number: serial.to_string(),
+            asset_number: [0; 7],
+        }))
+    }
+
+    /// The channel values from SETUP REQUEST. `None` when the validator
+    /// doesn't answer in kind.
+    fn read_bill_table(&mut self) -> Result<Option<BillTable>, CashCodeError> {
+        let answer = match self.command(COMMAND_SETUP_REQUEST, &[])? {
+            Some(answer) if answer.first() == Some(&RESPONSE_OK) => answer,
+            _ => return Ok(None),
+        };
+        let table = parse_setup(&answer[1..]);
+        match &table {
+            Some(_) => self.channels = usize::from(answer[12]),
+            None => debug!("unexpected SETUP REQUEST response: {:02X?}", answer),
+        }
+        Ok(table)
+    }
+
+    /// Bill values by channel - 1, 0 for unused channels. Takes every
+    /// bill in the table until `set_accepted_nominals` narrows it down.
+    fn set_bill_types(&mut self, values: &[i32]) -> Result<(), CashCodeError> {
+        if values.len() > BILL_TYPES {
+            warn!(
+                "bill_types: only {} channels are driven, the rest are ignored",
+                BILL_TYPES
+            );
+        }
+        self.bill_types = values.iter().take(BILL_TYPES).copied().collect();
+        self.accepted_mask = bill_type_mask(&self.bill_codes(|_| true));
+        self.counters.add_values(&self.bill_types)
+    }
+
+    fn set_accepted_nominals(&mut self, values: &[i32]) {
+        for value in values {
+            if !self.bill_types.contains(value) || *value <= 0 {
+                warn!("accepted_nominals: {} is not a bill, ignored", value);
+            }
+        }
+        let codes = self.bill_codes(|value| values.contains(&value));
+        if codes.is_empty() {
+            warn!("accepted_nominals takes no bill at all, the acceptor stays off");
+        }
+        self.accepted_mask = bill_type_mask(&codes);
+    }
+
+    fn begin_session(&mut self) -> Result<(), CashCodeError> {
+        self.counters.begin_session()
+    }
+
+    fn enable(&mut self) -> Result<(), CashCodeError> {
+        info!("enabling bill acceptance...");
+        self.set_inhibits(self.accepted_mask)?;
+        if self.expect_ok(COMMAND_ENABLE, &[], "enable")? {
+            info!("bill acceptance enabled");
+        }
+        Ok(())
+    }
+
+    fn disable(&mut self) -> Result<(), CashCodeError> {
+        // the donor left without accepting it
+        if self.held.is_some() {
+            self.return_bill()?;
+        }
+        info!("disabling bill acceptance...");
+        self.enabled_mask = MASK_NONE;
+        if self.expect_ok(COMMAND_DISABLE, &[], "disable")? {
+            info!("bill acceptance disabled");
+        }
+        Ok(())
+    }
+
+    /// Test-bill mode: every channel is taken into escrow, reported as
+    /// `BillEvent::TestBill` and handed back. Turning it off leaves the
+    /// validator disabled.
+    fn set_test_mode(&mut self, on: bool) -> Result<(), CashCodeError> {
+        self.test_mode = on;
+        if !on {
+            info!("test-bill mode off");
+            return self.disable();
+        }
+        info!("test-bill mode on, enabling every channel...");
+        self.set_inhibits(MASK_ALL)?;
+        if self.expect_ok(COMMAND_ENABLE, &[], "test-mode enable")? {
+            info!("bill acceptance enabled (test mode)");
+        }
+        Ok(())
+    }
+
+    /// Lets the bill held in escrow go; the next POLL stacks it and its
+    /// `Accepted` follows.
+    fn stack(&mut self) -> Result<(), CashCodeError> {
+        if self.held.take().is_none() {
+            warn!("stack requested with no bill in escrow");
+            return Ok(());
+        }
+        info!("stacking bill from escrow");
+        Ok(())
+    }
+
+    /// Hands the bill held in escrow back; `Returned` follows from a poll.
+    fn return_bill(&mut self) -> Result<(), CashCodeError> {
+        if self.held.take().is_none() {
+            warn!("return requested with no bill in escrow");
+            return Ok(());
+        }
+        info!("returning bill from escrow");
+        self.hand_back(true)
+    }
+
+    fn poll(&mut self) -> Result<Option<BillEvent>, CashCodeError> {
+        if let Some(event) = self.pending.pop_front() {
+            return Ok(Some(event));
+        }
+
+        // a POLL would stack the held bill
+        if let Some(held) = &mut self.held {
+            let timeout = self.escrow.unwrap_or_default();
+            if clock::since(held.since) >= timeout {
+                info!("no answer for the bill in escrow within {:?}", timeout);
+                self.return_bill()?;
+            } else if clock::since(held.last_hold) >= HOLD_INTERVAL {
+                held.last_hold = clock::instant();
+                self.expect_ok(COMMAND_HOLD, &[], "hold")?;
+            }
+            return Ok(None);
+        }
+
+        let Some(answer) = self.command(COMMAND_POLL, &[])? else {
+            self.state = DeviceState::Offline;
+            return Ok(None);
+        };
+        if answer.first() != Some(&RESPONSE_OK) {
+            warn!(
+                "validator refused POLL: {}",
+                answer
+                    .first()
+                    .map_or("empty answer", |code| describe(*code))
+            );
+            return Ok(None);
+        }
+
+        let mut state = None;
+        let mut i = 1;
+        while i < answer.len() {
+            let code = answer[i];
+            i += 1;
+            let channel = if takes_channel(code) {
+                i += 1;
+                answer.get(i - 1).copied()
+            } else {
+                None
+            };
+            if let Some(event) = self.event(code, channel, &mut state)? {
+                self.pending.push_back(event);
+            }
+            if !takes_channel(code) && !is_known(code) {
+                // its data length is unknown, so is where the next one starts
+                debug!("rest of the poll answer skipped: {:02X?}", &answer[i..]);
+                break;
+            }
+        }
+        self.state = state.unwrap_or(if self.stacker_removed {
+            DeviceState::StackerRemoved
+        } else if self.enabled_mask == MASK_NONE {
+            DeviceState::Disabled
+        } else {
+            DeviceState::Idle
+        });
+        Ok(self.pending.pop_front())
+    }
+
+    /// SSP has no way to read the inhibits back.
+    fn get_status(&mut self) -> Result<Option<DeviceStatus>, CashCodeError> {
+        Ok(None)
+    }
+
+    fn expected_mask(&self) -> [u8; 3] {
+        self.enabled_mask
+    }
+
+    fn reapply_mask(&mut self) -> Result<(), CashCodeError> {
+        if self.enabled_mask == MASK_NONE {
+            self.disable()
+        } else if self.test_mode {
+            self.set_test_mode(true)
+        } else {
+            self.enable()
+        }
+    }
+
+    fn is_enabled(&self) -> bool {
+        self.enabled_mask != MASK_NONE
+    }
+
+    fn in_test_mode(&self) -> bool {
+        self.test_mode
+    }
+
+    fn is_idle(&self) -> bool {
+        matches!(self.state, DeviceState::Disabled | DeviceState::Idle)
+            && !self.in_transit
+            && self.escrowed.is_none()
+    }
+
+    fn bill_in_transit(&self) -> bool {
+        self.in_transit || self.escrowed.is_some()
+    }
+
+    fn state(&self) -> DeviceState {
+        self.state
+    }
+
+    fn get_bill_counts(&self) -> Result<Vec<(i32, i32)>, CashCodeError> {
+        self.counters.counts()
+    }
+
+    fn clear_bill_counts(&self) -> Result<Vec<(i32, i32)>, CashCodeError> {
+        self.counters.clear()
+    }
+
+    fn get_total_amount(&self) -> Result<i32, CashCodeError> {
+        self.counters.total()
+    }
+}
@@ -0,0 +1,125 @@
+//! The steps of a donation and their order, from `[flow] steps`: picking a
+//! fund, entering a name and inserting money. Fund and name share the
+//! Donate page and come either before the money (the usual flow) or after
+//! it, in which case the donation is held while the donor picks (see
+//! `donation_edit_secs`). Without either the kiosk goes straight to the
+//! insert page with `[flow] fund_id`, like event mode.
+
+use crate::config::Config;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Step {
+    Fund,
+    Name,
+    Insert,
+}
+
+impl Step {
+    fn parse(name: &str) -> Option<Step> {
+        match name.trim().to_lowercase().as_str() {
+            "fund" => Some(Step::Fund),
+            "name" | "username" => Some(Step::Name),
+            "insert" => Some(Step::Insert),
+            _ => None,
+        }
+    }
+}
+
+/// A checked `[flow] steps`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Flow {
+    steps: Vec<Step>,
+}
+
+impl Default for Flow {
+    fn default() -> Self {
+        Flow {
+            steps: vec![Step::Fund, Step::Name, Step::Insert],
+        }
+    }
+}
+
+impl Flow {
+    /// The flow `steps` describe, or what is wrong with them: an unknown or
+    /// repeated step, no `insert`, or fund and name on different sides of
+    /// it (they share a page).
+    pub fn parse(steps: &[String]) -> Result<Flow, String> {
+        let mut parsed = Vec::new();
+        for name in steps {
+            let step = Step::parse(name).ok_or_else(|| format!("unknown step \"{}\"", name))?;
+            if parsed.contains(&step) {
+                return Err(format!("\"{}\" is listed twice", name));
+            }
+            parsed.push(step);
+        }
+        let flow = Flow { steps: parsed };
+        let Some(insert) = flow.position(Step::Insert) else {
+            return Err("there is no \"insert\" step".to_string());
+        };
+        let sides: Vec<bool> = [Step::Fund, Step::Name]
+            .into_iter()
+            .filter_map(|step| flow.position(step))
+            .map(|at| at < insert)
+            .collect();
+        if sides.windows(2).any(|pair| pair[0] != pair[1]) {
+            return Err("fund and name must both come before or both after insert".to_string());
+        }
+        Ok(flow)
+    }
+
+    /// The configured flow, or the usual one when it is invalid (already
+    /// reported as a config notice, see `check`).
+    pub fn from_config(config: &Config) -> Flow {
+        let flow = Flow::parse(&config.flow.steps).unwrap_or_default();
+        if flow.details_after() && config.donation_edit_secs == 0 {
+            return Flow::default();
+        }
+        flow
+    }
+
+    /// What is wrong with `[flow]` in `config`, for the config notices.
+    pub fn check(config: &Config) -> Option<String> {
+        match Flow::parse(&config.flow.steps) {
+            Err(problem) => Some(problem),
+            Ok(flow) if flow.details_after() && config.donation_edit_secs == 0 => {
+                Some("asking for fund or name after insert needs donation_edit_secs".to_string())
+            }
+            Ok(_) => None,
+        }
+    }
+
+    fn position(&self, step: Step) -> Option<usize> {
+        self.steps.iter().position(|&s| s == step)
+    }
+
+    pub fn asks(&self, step: Step) -> bool {
+        self.position(step).is_some()
+    }
+
+    /// Whether the Donate page comes after the money rather than before.
+    pub fn details_after(&self) -> bool {
+        let insert = self.position(Step::Insert);
+        [Step::Fund, Step::Name]
+            .into_iter()
+            .filter_map(|step| self.position(step))
+            .any(|at| Some(at) > insert)
+    }
+
+    /// Whether the Donate page is shown before the money.
+    pub fn details_first(&self) -> bool {
+        (self.asks(Step::Fund) || self.asks(Step::Name)) && !self.details_after()
+    }
+
+    /// Step names as configured, for the log.
+    pub fn describe(&self) -> String {
+        self.steps
+            .iter()
+            .map(|step| match step {
+                Step::Fund => "fund",
+                Step::Name => "name",
+                Step::Insert => "insert",
+            })
+            .collect::<Vec<_>>()
+            .join(" → ")
+    }
+}
@@ -0,0 +1,93 @@
+use std::collections::VecDeque;
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use crate::clock;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    Tx,
+    Rx,
+}
+
+#[derive(Debug, Clone)]
+struct Frame {
+    timestamp_ms: u64,
+    direction: Direction,
+    bytes: Vec<u8>,
+}
+
+/// Bounded in-memory log of raw serial frames, toggled at runtime from the
+/// diagnostics page. Shared between the driver thread (which records) and
+/// the UI (which toggles and exports). Recording is a no-op while disabled.
+pub struct FrameLog {
+    enabled: AtomicBool,
+    capacity: usize,
+    frames: Mutex<VecDeque<Frame>>,
+}
+
+impl FrameLog {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            enabled: AtomicBool::new(false),
+            capacity,
+            frames: Mutex::new(VecDeque::with_capacity(capacity)),
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled.load(Ordering::Relaxed)
+    }
+
+    pub fn set_enabled(&self, enabled: bool) {
+        self.enabled.store(enabled, Ordering::Relaxed);
+    }
+
+    pub fn record(&self, direction: Direction, bytes: &[u8]) {
+        if !self.is_enabled() || bytes.is_empty() || self.capacity == 0 {
+            return;
+        }
+        let timestamp_ms = clock::timestamp_ms();
+
+        let mut frames = self.frames.lock().unwrap();
+        if frames.len() == self.capacity {
+            frames.pop_front();
+        }
+        frames.push_back(Frame {
+            timestamp_ms,
+            direction,
+            bytes: bytes.to_vec(),
+        });
+    }
+
+    /// Writes the buffered frames to a new file in `dir`, one
+    /// `<unix ms> <TX|RX> <hex bytes>` line per frame, oldest first.
+    /// Returns the file path and the number of frames written.
+    pub fn export(&self, dir: &str, name: &str) -> io::Result<(PathBuf, usize)> {
+        let frames: Vec<Frame> = self.frames.lock().unwrap().iter().cloned().collect();
+
+        fs::create_dir_all(dir)?;
+        let path =
+            Path::new(dir).join(format!("{}-frames-{}.log", name, crate::clock::timestamp()));
+        let mut file = io::BufWriter::new(fs::File::create(&path)?);
+        for frame in &frames {
+            let hex: Vec<String> = frame.bytes.iter().map(|b| format!("{:02X}", b)).collect();
+            writeln!(
+                file,
+                "{} {} {}",
+                frame.timestamp_ms,
+                match frame.direction {
+                    Direction::Tx => "TX",
+                    Direction::Rx => "RX",
+                },
+                hex.join(" ")
+            )?;
+        }
+        file.flush()?;
+
+        Ok((path, frames.len()))
+    }
+}
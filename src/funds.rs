@@ -1,49 +1,117 @@
-use http::Request;
-use isahc::prelude::*;
-use log::{error, info};
-use serde::Deserialize;
+use log::info;
+use serde::{Deserialize, Serialize};
 
+use crate::api;
 use crate::error::RequestError;
 
-#[derive(Debug, Clone, Deserialize)]
+/// An open fund. Only `id` and `name` are required; everything else has a
+/// default so added or dropped fields on the gateway side do not break the
+/// fund list.
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Fund {
     pub id: i32,
+    #[serde(alias = "title")]
     pub name: String,
-    #[allow(dead_code)]
+    #[serde(default, alias = "targetValue")]
     pub target_value: i32,
-    #[allow(dead_code)]
+    /// Raised so far, for the attract screen's progress bars.
+    #[serde(default, alias = "collectedValue", alias = "collected")]
+    pub collected_value: i32,
+    #[serde(default, alias = "targetCurrency", alias = "currency")]
     pub target_currency: String,
     #[allow(dead_code)]
+    #[serde(default)]
     pub status: String,
 }
 
-/// Fetches available open funds from the API asynchronously
-pub async fn fetch_funds(token: &str) -> Result<Vec<Fund>, RequestError> {
-    let url = "https://gateway.hackem.cc/api/funds?status=open";
+impl Fund {
+    /// Raised so far and the target (0 without one), for a progress bar.
+    /// `None` when the fund counts in another currency than `currency`, as
+    /// its amounts would read as ours.
+    pub fn progress(&self, currency: &str) -> Option<(i32, i32)> {
+        if !self.target_currency.is_empty() && !self.target_currency.eq_ignore_ascii_case(currency)
+        {
+            return None;
+        }
+        Some((self.collected_value.max(0), self.target_value.max(0)))
+    }
+}
 
+/// Fetches available open funds from the API asynchronously, every page
+pub async fn fetch_funds(token: &str) -> Result<Vec<Fund>, RequestError> {
     info!("Fetching open funds from API...");
 
-    let request = Request::get(url)
-        .header("Authorization", format!("Bearer {}", token))
-        .body(())?;
-
-    let mut response = isahc::send_async(request).await?;
-
-    let status = response.status();
-    if status.is_success() {
-        let funds: Vec<Fund> = response.json().await?;
-        info!("✅ Fetched {} open funds", funds.len());
-        Ok(funds)
-    } else {
-        let message = response
-            .text()
-            .await
-            .unwrap_or_else(|_| "Unknown error".to_string());
-
-        error!("❌ API error {}: {}", status.as_u16(), message);
-        Err(RequestError::Api {
-            status: status.as_u16(),
-            message,
-        })
+    let funds: Vec<Fund> = api::fetch_all("/api/funds?status=open", token).await?;
+    info!("✅ Fetched {} open funds", funds.len());
+    Ok(funds)
+}
+
+/// What the gateway offers, fetched and cached together: its open funds
+/// and campaigns.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Offer {
+    pub funds: Vec<Fund>,
+    pub campaigns: Vec<Campaign>,
+}
+
+/// Several funds shown as one entry (e.g. "Winter bills" = electricity +
+/// heating). A donation to it is split between the funds by weight. Comes
+/// from `[[campaigns]]` in config or from the gateway, same shape.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Campaign {
+    #[serde(alias = "title")]
+    pub name: String,
+    pub funds: Vec<CampaignFund>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CampaignFund {
+    #[serde(alias = "fund_id", alias = "fundId")]
+    pub id: i32,
+    #[serde(default = "default_weight")]
+    pub weight: u32,
+}
+
+fn default_weight() -> u32 {
+    1
+}
+
+impl Campaign {
+    /// Splits `amount` between the funds by weight, in whole drams. The
+    /// rounding remainder goes to the first fund; zero shares are dropped.
+    pub fn split(&self, amount: i32) -> Vec<(i32, i32)> {
+        let total_weight: i64 = self.funds.iter().map(|f| f.weight as i64).sum();
+        if total_weight == 0 {
+            return Vec::new();
+        }
+        let mut parts: Vec<(i32, i32)> = self
+            .funds
+            .iter()
+            .map(|f| {
+                (
+                    f.id,
+                    (amount as i64 * f.weight as i64 / total_weight) as i32,
+                )
+            })
+            .collect();
+        let remainder = amount - parts.iter().map(|(_, share)| share).sum::<i32>();
+        if let Some((_, share)) = parts.first_mut() {
+            *share += remainder;
+        }
+        parts.retain(|(_, share)| *share > 0);
+        parts
+    }
+}
+
+/// Fetches campaigns defined on the gateway. A gateway without campaign
+/// support (404) just has none.
+pub async fn fetch_campaigns(token: &str) -> Result<Vec<Campaign>, RequestError> {
+    match api::fetch_all::<Campaign>("/api/campaigns?status=open", token).await {
+        Ok(campaigns) => {
+            info!("✅ Fetched {} campaigns", campaigns.len());
+            Ok(campaigns)
+        }
+        Err(RequestError::Api { status: 404, .. }) => Ok(Vec::new()),
+        Err(e) => Err(e),
     }
 }
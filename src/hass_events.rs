@@ -0,0 +1,419 @@
+use log::{debug, info, warn};
+use serde_json::Value;
+use std::collections::HashSet;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::sync::mpsc::{RecvTimeoutError, Sender, channel};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use crate::clock;
+use crate::config::{HassEventRule, HassEvents};
+
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+/// Quiet this long and we ping; quiet for twice as long and we reconnect.
+const IDLE: Duration = Duration::from_secs(60);
+const MIN_BACKOFF: Duration = Duration::from_secs(5);
+const MAX_BACKOFF: Duration = Duration::from_secs(300);
+/// HA state objects are small; anything bigger is not something we asked for.
+const MAX_MESSAGE: usize = 1 << 20;
+
+/// What a matching Home Assistant event does to the notification center.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HassNotice {
+    /// Sticky when `key` is set (cleared by `Clear`), transient otherwise.
+    Show {
+        key: Option<String>,
+        level: i32,
+        text: String,
+    },
+    Clear(String),
+    /// A watched entity (the tamper switch) changed state.
+    State {
+        entity: String,
+        state: String,
+    },
+}
+
+/// Handle for reconnecting the bridge on demand.
+#[derive(Clone)]
+pub struct Restarter {
+    tx: Sender<()>,
+    /// The open connection, shut down to interrupt a blocking read.
+    socket: Arc<Mutex<Option<TcpStream>>>,
+}
+
+impl Restarter {
+    /// Drops the connection (or the wait before the next attempt) and
+    /// connects again now.
+    pub fn restart(&self) {
+        let _ = self.tx.send(());
+        if let Some(socket) = self.socket.lock().ok().and_then(|mut s| s.take()) {
+            let _ = socket.shutdown(std::net::Shutdown::Both);
+        }
+    }
+}
+
+/// Starts the event bridge on its own thread, also reporting every state
+/// change of `watch`. Does nothing without a URL and token.
+pub fn start(
+    config: &HassEvents,
+    watch: Option<String>,
+    tx: Sender<HassNotice>,
+) -> Option<Restarter> {
+    let (Some(url), Some(token)) = (config.url.clone(), config.token.clone()) else {
+        return None;
+    };
+    if config.rules.is_empty() && watch.is_none() {
+        warn!("🏠 hass_events.url set but no rules, not connecting");
+        return None;
+    }
+    let rules = config.rules.clone();
+    let (restart_tx, restart_rx) = channel();
+    let restarter = Restarter {
+        tx: restart_tx,
+        socket: Arc::default(),
+    };
+    let socket = restarter.socket.clone();
+    thread::spawn(move || {
+        let mut backoff = MIN_BACKOFF;
+        loop {
+            // a restart asked for while connected has done its job
+            while restart_rx.try_recv().is_ok() {}
+            match run(&url, &token, &rules, watch.as_deref(), &tx, &socket) {
+                // the UI is gone
+                Ok(()) => return,
+                Err(e) => warn!("🏠 HASS event bridge: {}", e),
+            }
+            match restart_rx.recv_timeout(backoff) {
+                Ok(()) => {
+                    info!("🏠 HASS event bridge restarting");
+                    backoff = MIN_BACKOFF;
+                }
+                Err(RecvTimeoutError::Timeout) => backoff = (backoff * 2).min(MAX_BACKOFF),
+                Err(RecvTimeoutError::Disconnected) => {
+                    clock::sleep(backoff);
+                    backoff = (backoff * 2).min(MAX_BACKOFF);
+                }
+            }
+        }
+    });
+    Some(restarter)
+}
+
+/// One connection: handshake, auth, subscribe, then forward matching events
+/// until the socket fails. `Ok` only when the receiver is gone.
+fn run(
+    url: &str,
+    token: &str,
+    rules: &[HassEventRule],
+    watch: Option<&str>,
+    tx: &Sender<HassNotice>,
+    open: &Mutex<Option<TcpStream>>,
+) -> io::Result<()> {
+    let mut socket = WebSocket::connect(url)?;
+    if let Ok(mut open) = open.lock() {
+        *open = socket.writer.try_clone().ok();
+    }
+
+    let hello = socket.read_json()?;
+    if hello["type"] != "auth_required" {
+        return Err(protocol(format!("unexpected greeting {}", hello["type"])));
+    }
+    socket.send_json(&serde_json::json!({ "type": "auth", "access_token": token }))?;
+    let auth = socket.read_json()?;
+    if auth["type"] != "auth_ok" {
+        return Err(protocol(format!(
+            "authentication failed: {}",
+            auth["message"].as_str().unwrap_or("no reason given")
+        )));
+    }
+
+    let mut event_types: HashSet<&str> = rules.iter().map(|r| r.event_type.as_str()).collect();
+    if watch.is_some() {
+        event_types.insert("state_changed");
+    }
+    for (id, event_type) in event_types.iter().enumerate() {
+        socket.send_json(&serde_json::json!({
+            "id": id + 1,
+            "type": "subscribe_events",
+            "event_type": event_type,
+        }))?;
+    }
+    info!(
+        "🏠 HASS event bridge connected, subscribed to {:?}",
+        event_types
+    );
+
+    let mut next_id = event_types.len() + 1;
+    let mut pinged = false;
+    loop {
+        let message = match socket.read_json() {
+            Ok(message) => message,
+            Err(e) if is_timeout(&e) && !pinged => {
+                socket.send_json(&serde_json::json!({ "id": next_id, "type": "ping" }))?;
+                next_id += 1;
+                pinged = true;
+                continue;
+            }
+            Err(e) => return Err(e),
+        };
+        pinged = false;
+        if message["type"] == "result" && message["success"] == false {
+            warn!("🏠 HASS subscription failed: {}", message["error"]);
+            continue;
+        }
+        if message["type"] != "event" {
+            continue;
+        }
+        for notice in notices(rules, watch, &message["event"]) {
+            debug!("🏠 HASS event → {:?}", notice);
+            if tx.send(notice).is_err() {
+                return Ok(());
+            }
+        }
+    }
+}
+
+/// The notices an event produces under `rules` and for `watch`.
+fn notices(rules: &[HassEventRule], watch: Option<&str>, event: &Value) -> Vec<HassNotice> {
+    let event_type = event["event_type"].as_str().unwrap_or_default();
+    let data = &event["data"];
+    let entity = data["entity_id"].as_str().unwrap_or_default();
+    let state = data["new_state"]["state"].as_str().unwrap_or_default();
+    let old_state = data["old_state"]["state"].as_str().unwrap_or_default();
+    let name = data["new_state"]["attributes"]["friendly_name"]
+        .as_str()
+        .unwrap_or(entity);
+
+    let mut out = Vec::new();
+    if event_type == "state_changed" && watch == Some(entity) && state != old_state {
+        out.push(HassNotice::State {
+            entity: entity.to_string(),
+            state: state.to_string(),
+        });
+    }
+    for rule in rules {
+        if rule.event_type != event_type || rule.entity_id.as_deref().is_some_and(|id| id != entity)
+        {
+            continue;
+        }
+        let key = rule
+            .sticky
+            .then(|| format!("hass:{}", rule.entity_id.as_deref().unwrap_or(event_type)));
+        match rule.to_state.as_deref() {
+            // only the transition counts, not attribute updates while in it
+            Some(to) if to == state && to != old_state => {}
+            Some(to) if to != state => {
+                if let Some(key) = key {
+                    out.push(HassNotice::Clear(key));
+                }
+                continue;
+            }
+            Some(_) => continue,
+            None => {}
+        }
+        let text = rule
+            .text
+            .replace("{name}", name)
+            .replace("{state}", state)
+            .replace("{entity}", entity)
+            .replace("{event}", event_type);
+        out.push(HassNotice::Show {
+            key,
+            level: rule.level,
+            text,
+        });
+    }
+    out
+}
+
+fn is_timeout(e: &io::Error) -> bool {
+    matches!(
+        e.kind(),
+        io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+    )
+}
+
+fn mid_message(e: io::Error) -> io::Error {
+    if is_timeout(&e) {
+        io::Error::new(io::ErrorKind::UnexpectedEof, "timed out mid-message")
+    } else {
+        e
+    }
+}
+
+fn protocol(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+/// Just enough of a WebSocket client (RFC 6455) for HA's API: plain `ws://`,
+/// text messages, pings answered.
+struct WebSocket {
+    reader: BufReader<TcpStream>,
+    writer: TcpStream,
+}
+
+impl WebSocket {
+    fn connect(url: &str) -> io::Result<Self> {
+        let rest = url.strip_prefix("ws://").ok_or_else(|| {
+            protocol(format!(
+                "{} is not a ws:// URL (wss:// is not supported, use the LAN address)",
+                url
+            ))
+        })?;
+        let (authority, path) = match rest.find('/') {
+            Some(i) => (&rest[..i], &rest[i..]),
+            None => (rest, "/api/websocket"),
+        };
+        let address = if authority.contains(':') {
+            authority.to_string()
+        } else {
+            format!("{}:80", authority)
+        };
+        let address = address
+            .to_socket_addrs()?
+            .next()
+            .ok_or_else(|| protocol(format!("cannot resolve {}", authority)))?;
+        let stream = TcpStream::connect_timeout(&address, CONNECT_TIMEOUT)?;
+        // HA is often quiet for long stretches; the idle timeout lets us ping
+        // it and notice a half-open connection
+        stream.set_read_timeout(Some(IDLE))?;
+        let mut writer = stream.try_clone()?;
+        let mut reader = BufReader::new(stream);
+
+        write!(
+            writer,
+            "GET {} HTTP/1.1\r\nHost: {}\r\nUpgrade: websocket\r\nConnection: Upgrade\r\n\
+             Sec-WebSocket-Key: {}\r\nSec-WebSocket-Version: 13\r\n\r\n",
+            path,
+            authority,
+            handshake_key()
+        )?;
+        let mut status = String::new();
+        reader.read_line(&mut status)?;
+        if !status.starts_with("HTTP/1.1 101") {
+            return Err(protocol(format!("upgrade refused: {}", status.trim())));
+        }
+        // The accept hash isn't checked: we know who we're talking to
+        loop {
+            let mut header = String::new();
+            if reader.read_line(&mut header)? == 0 || header == "\r\n" {
+                break;
+            }
+        }
+        Ok(WebSocket { reader, writer })
+    }
+
+    fn send_json(&mut self, value: &Value) -> io::Result<()> {
+        self.send(0x1, value.to_string().as_bytes())
+    }
+
+    /// Sends one masked frame, as clients must.
+    fn send(&mut self, opcode: u8, payload: &[u8]) -> io::Result<()> {
+        let mut frame = vec![0x80 | opcode];
+        match payload.len() {
+            n if n < 126 => frame.push(0x80 | n as u8),
+            n if n <= u16::MAX as usize => {
+                frame.push(0x80 | 126);
+                frame.extend_from_slice(&(n as u16).to_be_bytes());
+            }
+            n => {
+                frame.push(0x80 | 127);
+                frame.extend_from_slice(&(n as u64).to_be_bytes());
+            }
+        }
+        let mask = nonce().to_be_bytes();
+        let mask = [mask[12], mask[13], mask[14], mask[15]];
+        frame.extend_from_slice(&mask);
+        frame.extend(payload.iter().enumerate().map(|(i, b)| b ^ mask[i % 4]));
+        self.writer.write_all(&frame)
+    }
+
+    /// Next text message, reassembled from fragments, as JSON.
+    fn read_json(&mut self) -> io::Result<Value> {
+        let mut message = Vec::new();
+        loop {
+            let mut head = [0u8; 2];
+            // Only a timeout between messages is idle; inside one it's fatal
+            self.reader.read_exact(&mut head).map_err(|e| {
+                if message.is_empty() {
+                    e
+                } else {
+                    mid_message(e)
+                }
+            })?;
+            let fin = head[0] & 0x80 != 0;
+            let opcode = head[0] & 0x0F;
+            let len = match head[1] & 0x7F {
+                126 => {
+                    let mut n = [0u8; 2];
+                    self.reader.read_exact(&mut n).map_err(mid_message)?;
+                    u16::from_be_bytes(n) as usize
+                }
+                127 => {
+                    let mut n = [0u8; 8];
+                    self.reader.read_exact(&mut n).map_err(mid_message)?;
+                    u64::from_be_bytes(n) as usize
+                }
+                n => n as usize,
+            };
+            if message.len() + len > MAX_MESSAGE {
+                return Err(protocol(format!("message over {} bytes", MAX_MESSAGE)));
+            }
+            // servers never mask
+            let mut payload = vec![0u8; len];
+            self.reader.read_exact(&mut payload).map_err(mid_message)?;
+
+            match opcode {
+                0x0 | 0x1 => {
+                    message.extend_from_slice(&payload);
+                    if fin {
+                        return serde_json::from_slice(&message)
+                            .map_err(|e| protocol(format!("bad JSON from HA: {}", e)));
+                    }
+                }
+                0x8 => {
+                    let _ = self.send(0x8, &[]);
+                    return Err(io::Error::new(
+                        io::ErrorKind::ConnectionAborted,
+                        "closed by Home Assistant",
+                    ));
+                }
+                0x9 => self.send(0xA, &payload)?,
+                _ => {}
+            }
+        }
+    }
+}
+
+/// Not cryptographic; the handshake key and frame masks only need to vary.
+fn nonce() -> u128 {
+    let nanos = clock::timestamp_ns();
+    // splitmix-style scramble so consecutive calls differ in every byte
+    let mut x = nanos ^ 0x9E37_79B9_7F4A_7C15_F39C_C060_5CED_C834;
+    x = (x ^ (x >> 67)).wrapping_mul(0xBF58_476D_1CE4_E5B9_94D0_49BB_1331_11EB);
+    x ^ (x >> 61)
+}
+
+/// 16 random bytes, base64-encoded.
+fn handshake_key() -> String {
+    const ALPHABET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let bytes = nonce().to_be_bytes();
+    let mut key = String::new();
+    for chunk in bytes.chunks(3) {
+        let n = chunk
+            .iter()
+            .enumerate()
+            .fold(0u32, |n, (i, &b)| n | (b as u32) << (16 - 8 * i));
+        for i in 0..4 {
+            if i <= chunk.len() {
+                key.push(ALPHABET[(n >> (18 - 6 * i) & 0x3F) as usize] as char);
+            } else {
+                key.push('=');
+            }
+        }
+    }
+    key
+}
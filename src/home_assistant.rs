@@ -1,9 +1,56 @@
-use log::{error, info};
+use log::{error, info, warn};
 use std::io::{Read, Write};
-use std::net::TcpListener;
+use std::net::{TcpListener, TcpStream};
 use std::process::{Child, Command};
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::mpsc::Sender;
 use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use crate::supervisor::Subsystem;
+use crate::{boost, diag_logger};
+
+/// Log tails streaming at once; more are turned away.
+const MAX_LOG_TAILS: usize = 4;
+/// A tail with nothing new gets a blank line this often, which also
+/// notices a client that went away.
+const LOG_TAIL_KEEPALIVE: Duration = Duration::from_secs(15);
+/// Lines of backlog a tail starts with unless `lines=` says otherwise.
+const LOG_TAIL_BACKLOG: usize = 100;
+
+static LOG_TAILS: AtomicUsize = AtomicUsize::new(0);
+
+/// Browsers tried in order (the name differs between Debian versions).
+const CHROMIUM: [&str; 2] = ["chromium", "chromium-browser"];
+
+/// Chromium locked down for the HASS page: kiosk mode can't be minimized,
+/// left with F11 or closed from a title bar, and links can't open new
+/// windows or navigate back past the dashboard with a swipe, so the
+/// desktop never shows.
+fn chromium_args(url: &str) -> Vec<String> {
+    vec![
+        format!("--app={}", url),
+        "--kiosk".to_string(),
+        "--start-fullscreen".to_string(),
+        "--window-position=0,0".to_string(),
+        "--block-new-web-contents".to_string(),
+        "--overscroll-history-navigation=0".to_string(),
+        "--disable-infobars".to_string(),
+        "--noerrdialogs".to_string(),
+        "--disable-session-crashed-bubble".to_string(),
+        "--disable-pinch".to_string(),
+        "--no-first-run".to_string(),
+        "--no-default-browser-check".to_string(),
+        "--enable-native-gpu-memory-buffers".to_string(),
+        "--ozone-platform-hint=auto".to_string(),
+        "--enable-features=AcceleratedVideoEncoder,VaapiOnNvidiaGPUs,VaapiIgnoreDriverChecks,Vulkan,DefaultANGLEVulkan,VulkanFromANGLE,VaapiVideoDecoder,PlatformHEVCDecoderSupport,UseMultiPlaneFormatForHardwareVideo,OverlayScrollbar".to_string(),
+        "--ignore-gpu-blocklist".to_string(),
+        "--enable-zero-copy".to_string(),
+        "--autoplay-policy=no-user-gesture-required".to_string(),
+        "--disable-restore-session-state".to_string(),
+    ]
+}
 
 /// Manages a Chromium subprocess for displaying Home Assistant
 pub struct ChromiumManager {
@@ -17,7 +64,7 @@ impl ChromiumManager {
         }
     }
 
-    /// Launch Chromium in app mode with the given URL
+    /// Launch Chromium in kiosk mode with the given URL
     pub fn launch(&self, url: &str) -> Result<(), String> {
         let mut process_guard = self.process.lock().unwrap();
 
@@ -30,46 +77,14 @@ impl ChromiumManager {
 
         info!("Launching Chromium with URL: {}", url);
 
-        // Try chromium first, then chromium-browser as fallback (different Debian versions)
-        let command_result = Command::new("chromium")
-            .arg("--app=".to_string() + url)
-            .arg("--start-fullscreen")
-            .arg("--window-position=0,0")
-            .arg("--disable-infobars")
-            .arg("--noerrdialogs")
-            .arg("--disable-session-crashed-bubble")
-            .arg("--disable-pinch")
-            .arg("--no-first-run")
-            .arg("--no-default-browser-check")
-            .arg("--enable-native-gpu-memory-buffers")
-            .arg("--ozone-platform-hint=auto")
-            .arg("--enable-features=AcceleratedVideoEncoder,VaapiOnNvidiaGPUs,VaapiIgnoreDriverChecks,Vulkan,DefaultANGLEVulkan,VulkanFromANGLE,VaapiVideoDecoder,PlatformHEVCDecoderSupport,UseMultiPlaneFormatForHardwareVideo,OverlayScrollbar")
-            .arg("--ignore-gpu-blocklist")
-            .arg("--enable-zero-copy")
-            .arg("--autoplay-policy=no-user-gesture-required")
-            .arg("--disable-restore-session-state")
-            .spawn()
-            .or_else(|_| {
-                // Fallback to chromium-browser
-                Command::new("chromium-browser")
-                    .arg("--app=".to_string() + url)
-                    .arg("--start-fullscreen")
-                    .arg("--window-position=0,0")
-                    .arg("--disable-infobars")
-                    .arg("--noerrdialogs")
-                    .arg("--disable-session-crashed-bubble")
-                    .arg("--disable-pinch")
-                    .arg("--no-first-run")
-                    .arg("--no-default-browser-check")
-                    .arg("--enable-native-gpu-memory-buffers")
-                    .arg("--ozone-platform-hint=auto")
-                    .arg("--enable-features=AcceleratedVideoEncoder,VaapiOnNvidiaGPUs,VaapiIgnoreDriverChecks,Vulkan,DefaultANGLEVulkan,VulkanFromANGLE,VaapiVideoDecoder,PlatformHEVCDecoderSupport,UseMultiPlaneFormatForHardwareVideo,OverlayScrollbar")
-                    .arg("--ignore-gpu-blocklist")
-                    .arg("--enable-zero-copy")
-                    .arg("--autoplay-policy=no-user-gesture-required")
-                    .arg("--disable-restore-session-state")
-                    .spawn()
-            });
+        let args = chromium_args(url);
+        let mut command_result = Err(std::io::Error::other("no browser tried"));
+        for browser in CHROMIUM {
+            command_result = Command::new(browser).args(&args).spawn();
+            if command_result.is_ok() {
+                break;
+            }
+        }
 
         match command_result {
             Ok(child) => {
@@ -78,6 +93,7 @@ impl ChromiumManager {
                 Ok(())
             }
             Err(e) => {
+                *process_guard = None;
                 let err_msg = format!(
                     "Failed to launch Chromium. Make sure chromium is installed: {}",
                     e
@@ -88,6 +104,28 @@ impl ChromiumManager {
         }
     }
 
+    /// Whether a launched Chromium exited by itself (crashed, or closed in
+    /// a way the flags didn't prevent), collecting it if so. False when
+    /// none was launched.
+    pub fn exited(&self) -> bool {
+        let mut process_guard = self.process.lock().unwrap();
+        let Some(child) = process_guard.as_mut() else {
+            return false;
+        };
+        match child.try_wait() {
+            Ok(Some(status)) => {
+                warn!("Chromium exited by itself: {}", status);
+                *process_guard = None;
+                true
+            }
+            Ok(None) => false,
+            Err(e) => {
+                error!("Failed to check on Chromium: {}", e);
+                false
+            }
+        }
+    }
+
     /// Close the Chromium process
     pub fn close(&self) {
         let mut process_guard = self.process.lock().unwrap();
@@ -112,45 +150,193 @@ impl Drop for ChromiumManager {
     }
 }
 
-/// Starts a simple HTTP listener for remote control from Home Assistant.
-/// When a `POST /close-hass` request is received, sends a signal through `tx`.
-#[allow(dead_code)]
-pub fn start_close_listener(port: u16, tx: Sender<()>) {
-    let addr = format!("0.0.0.0:{}", port);
-    let listener = match TcpListener::bind(&addr) {
-        Ok(l) => l,
-        Err(e) => {
-            error!("Failed to bind HASS close listener on {}: {}", addr, e);
-            return;
-        }
-    };
-    info!("🏠 Home Assistant close listener on port {}", port);
+/// Requests Home Assistant can make over the listener.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RemoteCommand {
+    /// `POST /close-hass`: dismiss the HASS page.
+    CloseHass,
+    /// `POST /acceptors/disable`: stop taking money until re-enabled, e.g.
+    /// when the space is closed or an alarm is armed. The body, if any, is
+    /// the reason shown on the kiosk.
+    DisableAcceptors(String),
+    /// `POST /acceptors/enable`: lift the above.
+    EnableAcceptors,
+    /// `POST /restart/<name>`: restart one subsystem (`bill_acceptor`,
+    /// `coin_acceptor`, `mqtt`, `hass_events`, `gateway`).
+    Restart(Subsystem),
+    /// `POST /boost/start`: start a fundraising boost. The body, if any,
+    /// is JSON overriding `[boost]` (see `boost::Request`).
+    StartBoost(boost::Request),
+    /// `POST /boost/stop`: end it early.
+    StopBoost,
+    /// `POST /collection`: the stacker was emptied; the body is the name of
+    /// whoever took the cash. Snapshots and zeroes the bill counters.
+    RecordCollection(String),
+}
+
+/// Binds the listener for remote control from Home Assistant.
+pub fn bind_listener(port: u16) -> std::io::Result<TcpListener> {
+    let listener = TcpListener::bind(format!("0.0.0.0:{}", port))?;
+    info!("🏠 Home Assistant listener on port {}", port);
+    Ok(listener)
+}
 
+/// Serves a simple HTTP API on `listener` and forwards each recognised
+/// request through `tx`; `GET /logs/tail` is streamed from its own thread.
+/// With a `token`, requests must carry `Authorization: Bearer <token>`.
+/// Runs as long as the listener does.
+pub fn serve_listener(listener: TcpListener, token: Option<String>, tx: Sender<RemoteCommand>) {
     for stream in listener.incoming() {
         let Ok(mut stream) = stream else {
             continue;
         };
-        let mut buf = [0u8; 512];
+        let mut buf = [0u8; 1024];
         let Ok(n) = stream.read(&mut buf) else {
             continue;
         };
         let request = String::from_utf8_lossy(&buf[..n]);
-        let first_line = request.lines().next().unwrap_or("");
+        let (head, body) = request.split_once("\r\n\r\n").unwrap_or((&request, ""));
+        let first_line = head.lines().next().unwrap_or("");
 
-        if first_line.starts_with("POST /close-hass") {
-            info!("🏠 Received remote close-hass request");
-            let _ = tx.send(());
-            let _ = stream.write_all(
-                b"HTTP/1.1 200 OK\r\nAccess-Control-Allow-Origin: *\r\nContent-Length: 2\r\n\r\nOK",
-            );
-        } else if first_line.starts_with("OPTIONS") {
+        if first_line.starts_with("OPTIONS") {
             // CORS preflight
             let _ = stream.write_all(
-                b"HTTP/1.1 204 No Content\r\nAccess-Control-Allow-Origin: *\r\nAccess-Control-Allow-Methods: POST, OPTIONS\r\nAccess-Control-Allow-Headers: Content-Type\r\n\r\n",
+                b"HTTP/1.1 204 No Content\r\nAccess-Control-Allow-Origin: *\r\nAccess-Control-Allow-Methods: GET, POST, OPTIONS\r\nAccess-Control-Allow-Headers: Content-Type, Authorization\r\n\r\n",
             );
+            continue;
+        }
+
+        let log_tail = first_line.starts_with("GET /logs/tail");
+        let command = if first_line.starts_with("POST /close-hass") {
+            Some(RemoteCommand::CloseHass)
+        } else if first_line.starts_with("POST /acceptors/disable") {
+            Some(RemoteCommand::DisableAcceptors(body.trim().to_string()))
+        } else if first_line.starts_with("POST /acceptors/enable") {
+            Some(RemoteCommand::EnableAcceptors)
+        } else if let Some(target) = first_line.strip_prefix("POST /restart/") {
+            let name = target.split_whitespace().next().unwrap_or("");
+            Subsystem::parse(name).map(RemoteCommand::Restart)
+        } else if first_line.starts_with("POST /boost/start") {
+            let Some(request) = boost::Request::parse(body) else {
+                let _ = stream.write_all(
+                    b"HTTP/1.1 400 Bad Request\r\nAccess-Control-Allow-Origin: *\r\nContent-Length: 11\r\n\r\nBad Request",
+                );
+                continue;
+            };
+            Some(RemoteCommand::StartBoost(request))
+        } else if first_line.starts_with("POST /boost/stop") {
+            Some(RemoteCommand::StopBoost)
+        } else if first_line.starts_with("POST /collection") {
+            let collector = body.trim();
+            if collector.is_empty() {
+                let _ = stream.write_all(
+                    b"HTTP/1.1 400 Bad Request\r\nAccess-Control-Allow-Origin: *\r\nContent-Length: 11\r\n\r\nBad Request",
+                );
+                continue;
+            }
+            Some(RemoteCommand::RecordCollection(collector.to_string()))
         } else {
+            None
+        };
+        if command.is_none() && !log_tail {
             let _ =
                 stream.write_all(b"HTTP/1.1 404 Not Found\r\nContent-Length: 9\r\n\r\nNot Found");
+            continue;
+        }
+
+        if let Some(ref token) = token {
+            let authorized = head.lines().any(|line| {
+                line.split_once(':').is_some_and(|(name, value)| {
+                    name.eq_ignore_ascii_case("authorization")
+                        && value.trim().split_once(' ').is_some_and(|(scheme, given)| {
+                            scheme.eq_ignore_ascii_case("bearer") && given.trim() == token
+                        })
+                })
+            });
+            if !authorized {
+                warn!("🏠 Rejected unauthorized request: {}", first_line);
+                let _ = stream.write_all(
+                    b"HTTP/1.1 401 Unauthorized\r\nAccess-Control-Allow-Origin: *\r\nContent-Length: 12\r\n\r\nUnauthorized",
+                );
+                continue;
+            }
+        }
+
+        let Some(command) = command else {
+            let query = first_line
+                .split_whitespace()
+                .nth(1)
+                .and_then(|target| target.split_once('?'))
+                .map(|(_, query)| query.to_string())
+                .unwrap_or_default();
+            start_log_tail(stream, query);
+            continue;
+        };
+
+        info!("🏠 Received remote request: {:?}", command);
+        let _ = tx.send(command);
+        let _ = stream.write_all(
+            b"HTTP/1.1 200 OK\r\nAccess-Control-Allow-Origin: *\r\nContent-Length: 2\r\n\r\nOK",
+        );
+    }
+}
+
+/// Streams log lines as JSON, one per line: the latest `lines` (default
+/// 100) at `level` (`error`, `warn`, `info` or `debug`; default `info`) or
+/// more severe, then new ones as they are logged until the client hangs up.
+fn start_log_tail(mut stream: TcpStream, query: String) {
+    let mut min_level = log::Level::Info;
+    let mut backlog = LOG_TAIL_BACKLOG;
+    for pair in query.split('&').filter(|pair| !pair.is_empty()) {
+        let parsed = match pair.split_once('=') {
+            Some(("level", value)) => value.parse().map(|level| min_level = level).is_ok(),
+            Some(("lines", value)) => value.parse().map(|lines| backlog = lines).is_ok(),
+            _ => false,
+        };
+        if !parsed {
+            let _ = stream.write_all(
+                b"HTTP/1.1 400 Bad Request\r\nAccess-Control-Allow-Origin: *\r\nContent-Length: 11\r\n\r\nBad Request",
+            );
+            return;
         }
     }
+
+    if LOG_TAILS.fetch_add(1, Ordering::SeqCst) >= MAX_LOG_TAILS {
+        LOG_TAILS.fetch_sub(1, Ordering::SeqCst);
+        let _ = stream.write_all(
+            b"HTTP/1.1 503 Service Unavailable\r\nContent-Length: 14\r\n\r\nToo many tails",
+        );
+        return;
+    }
+    let peer = stream
+        .peer_addr()
+        .map(|addr| addr.to_string())
+        .unwrap_or_default();
+    info!("🏠 Log tail ({}) opened by {}", min_level, peer);
+
+    thread::spawn(move || {
+        let _ = stream.set_write_timeout(Some(LOG_TAIL_KEEPALIVE));
+        let _ = stream.write_all(
+            b"HTTP/1.1 200 OK\r\nContent-Type: application/x-ndjson\r\nCache-Control: no-cache\r\nAccess-Control-Allow-Origin: *\r\nConnection: close\r\n\r\n",
+        );
+        let (mut entries, mut next) = diag_logger::recent(min_level, backlog);
+        loop {
+            let mut chunk = String::new();
+            for entry in &entries {
+                if let Ok(line) = serde_json::to_string(entry) {
+                    chunk += &line;
+                    chunk.push('\n');
+                }
+            }
+            if chunk.is_empty() {
+                chunk.push('\n');
+            }
+            if stream.write_all(chunk.as_bytes()).is_err() {
+                break;
+            }
+            (entries, next) = diag_logger::after(next, min_level, LOG_TAIL_KEEPALIVE);
+        }
+        LOG_TAILS.fetch_sub(1, Ordering::SeqCst);
+        info!("🏠 Log tail closed for {}", peer);
+    });
 }
@@ -0,0 +1,211 @@
+use std::fmt;
+use std::sync::OnceLock;
+
+/// Language messages are shown in; logs and audits always use English.
+static LANGUAGE: OnceLock<String> = OnceLock::new();
+
+const FALLBACK: &str = "en";
+
+/// English strings by key. `{0}`, `{1}`, … are replaced by the message's
+/// arguments, so a translation can reorder them.
+const EN: &[(&str, &str)] = &[
+    // bill acceptor
+    ("bill.rejected", "Rejected: {0}"),
+    ("bill.reject.insertion", "Insertion error"),
+    ("bill.reject.conveying", "Conveying error"),
+    ("bill.reject.identification", "Identification error"),
+    ("bill.reject.verification", "Verification error"),
+    ("bill.reject.inhibited", "Denomination inhibited"),
+    ("bill.reject.capacity", "Capacity error"),
+    ("bill.reject.operation", "Operation error"),
+    ("bill.reject.unknown", "Unknown error"),
+    ("bill.jam", "{0} Jam: {1}"),
+    ("bill.jam.acceptor", "Bill jam in acceptor"),
+    ("bill.jam.stacker", "Bill jam in stacker"),
+    ("bill.jammed", "{0} Bill jammed"),
+    ("bill.error", "{0} Error: {1}"),
+    ("bill.failure.55", "FAILURE 55"),
+    ("bill.failure", "FAILURE 0x{0}"),
+    ("bill.unknown_nominal", "Unknown nominal: 0x{0}"),
+    ("bill.stacker_removed", "{0} Bill stacker removed"),
+    (
+        "bill.stacker_full",
+        "{0} Bill stacker full, no more bills for now",
+    ),
+    (
+        "bill.stacker_low",
+        "Bill stacker nearly full, room for {0} bills",
+    ),
+    (
+        "bill.offline",
+        "{0} Bill acceptor offline, retrying in {1} s",
+    ),
+    (
+        "bill.enable_failed",
+        "{0} Bill acceptor didn't start, please try again",
+    ),
+    // vouchers
+    ("voucher.valid", "Valid voucher"),
+    ("voucher.used", "This voucher was already redeemed"),
+    ("voucher.unknown", "Unknown voucher code, please check it"),
+    ("voucher.redeemed", "Voucher for {0} redeemed, thank you!"),
+    (
+        "dry_run.banner",
+        "Dry run: bills are returned and nothing is sent",
+    ),
+    ("qr.expired", "The payment was cancelled, please try again"),
+    ("qr.timeout", "No payment arrived in time, please try again"),
+    ("qr.too_long", "The payment link doesn't fit in a QR code"),
+    // safe mode
+    (
+        "safe_mode.banner",
+        "Safe mode: {0} crashes in a row, drivers are off",
+    ),
+    // gateway
+    ("gateway.update_required", "{0} Kiosk update required"),
+    ("gateway.offline", "{0} Gateway offline"),
+    ("gateway.error", "{0} Gateway error"),
+    ("gateway.tls", "{0} Gateway certificate rejected"),
+    ("funds.cached", "{0}, showing saved funds"),
+    // donation flow
+    (
+        "donation.amount_corrected",
+        "Amount corrected to {0}, please check and confirm",
+    ),
+    ("donation.below_minimum", "Minimum donation is {0}"),
+    (
+        "donation.edit_expired",
+        "Too late to change that donation, please ask a treasurer",
+    ),
+    ("campaign.sending", "🎯 {0}: sending {1}/{2}…"),
+    ("campaign.sent", "🎯 {0}: {1}/{2} parts sent"),
+    ("queue.needs_fund", "Pick a fund for this money first"),
+    ("queue.campaign", "Pick a fund, not a campaign"),
+    ("queue.no_backend", "No donation backend configured"),
+    ("queue.sent", "✅ {0} to {1} sent"),
+    ("queue.failed", "Sending {0} to {1} failed again"),
+    ("queue.in_flight", "That donation is being sent right now"),
+    // restarting one subsystem
+    ("restart.started", "🔄 Restarting {0}"),
+    (
+        "restart.busy",
+        "Not restarting {0} while money is being inserted",
+    ),
+    ("restart.unavailable", "{0} is not running on this kiosk"),
+    ("printer.stalled", "🖨️ Printer: {0} ({1} waiting)"),
+    ("printer.paper_out", "out of paper"),
+    ("printer.offline", "printer offline"),
+    (
+        "usb.detected",
+        "💾 USB stick detected, export from Diagnostics",
+    ),
+    (
+        "acceptance.paused",
+        "Money acceptance paused by Home Assistant",
+    ),
+    ("acceptance.paused_reason", "Money acceptance paused: {0}"),
+    (
+        "tamper.opened",
+        "🚨 Cash box opened outside a shift, staff have been alerted",
+    ),
+    (
+        "config.outdated",
+        "⚙️ Config needs attention ({0}), see Diagnostics log",
+    ),
+    // boost mode
+    ("boost.started", "🚀 Boost for {0}, {1} min"),
+    ("boost.ended", "🚀 Boost over: {0} raised for {1}"),
+    // member status on Donate
+    (
+        "member.dues_outstanding",
+        "🏠 This month's rent share: {0} outstanding",
+    ),
+    ("member.dues_paid", "✅ This month's rent share is paid"),
+    ("member.tier", "⭐ {0}"),
+    // donor's intent on the insert page
+    ("intent.to_go", "{0} to go: {1}"),
+    ("intent.to_go_over", "{0} to go: {1}, or coins"),
+    ("intent.reached", "🎯 That's the {0} you wanted to give"),
+    ("intent.over", "⚠️ {0} over the {1} you wanted to give"),
+    // read out by a screen reader
+    ("a11y.counted", "{0} counted, {1} in total"),
+    ("a11y.rejected", "Bill rejected: {0}. Please try again"),
+    ("a11y.escrow", "{0} held. Accept or return?"),
+    ("a11y.returned", "Bill returned, please take it"),
+];
+
+fn catalog(language: &str) -> Option<&'static [(&'static str, &'static str)]> {
+    match language {
+        "en" => Some(EN),
+        _ => None,
+    }
+}
+
+/// Selects the display language (`language` in config). Languages without
+/// a catalog, or keys missing from one, fall back to English.
+pub fn set_language(language: &str) {
+    let _ = LANGUAGE.set(language.to_lowercase());
+}
+
+/// The display language, e.g. "en".
+pub fn language() -> &'static str {
+    LANGUAGE.get().map(String::as_str).unwrap_or(FALLBACK)
+}
+
+fn lookup(language: &str, key: &str) -> Option<&'static str> {
+    catalog(language)?
+        .iter()
+        .find(|(k, _)| *k == key)
+        .map(|(_, text)| *text)
+}
+
+fn resolve(language: &str, key: &str, args: &[String]) -> String {
+    let Some(template) = lookup(language, key).or_else(|| lookup(FALLBACK, key)) else {
+        // a missing key should be visible, not silently blank
+        return key.to_string();
+    };
+    let mut text = template.to_string();
+    for (i, arg) in args.iter().enumerate() {
+        text = text.replace(&format!("{{{}}}", i), arg);
+    }
+    text
+}
+
+/// A user-facing message kept as a catalog key and its arguments until it
+/// is shown, so hardware and gateway messages are translated at the edge
+/// instead of leaking whatever the driver produced.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Message {
+    pub key: &'static str,
+    pub args: Vec<String>,
+}
+
+impl Message {
+    pub fn new(key: &'static str) -> Self {
+        Message { key, args: vec![] }
+    }
+
+    pub fn with(key: &'static str, args: &[&dyn fmt::Display]) -> Self {
+        Message {
+            key,
+            args: args.iter().map(|a| a.to_string()).collect(),
+        }
+    }
+
+    /// The message in the display language.
+    pub fn text(&self) -> String {
+        resolve(language(), self.key, &self.args)
+    }
+}
+
+/// English, for logs and the audit trail.
+impl fmt::Display for Message {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&resolve(FALLBACK, self.key, &self.args))
+    }
+}
+
+/// Shorthand for `Message::with(key, args).text()`.
+pub fn tr(key: &'static str, args: &[&dyn fmt::Display]) -> String {
+    Message::with(key, args).text()
+}
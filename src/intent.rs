@@ -0,0 +1,194 @@
+//! A donor's intent on the insert page ("I want to give 7000"): the bill
+//! combinations that make it up, and how far what went in so far is from
+//! it. Only sums of accepted bills can be picked, so an intent is always an
+//! amount the acceptor can take exactly.
+
+use crate::money;
+
+/// Highest intent the stepper goes up to.
+pub const MAX: i64 = 200_000;
+/// Combinations suggested for one intent.
+const SUGGESTIONS: usize = 3;
+/// Suggestions may take this many bills more than the fewest possible.
+const EXTRA_BILLS: u32 = 2;
+
+/// `count` bills of each nominal, largest nominal first.
+pub type Combination = Vec<(i64, u32)>;
+
+/// How the money inserted so far compares to the intent.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Progress {
+    /// `left` to go; `next` is the fewest bills that make it up, or that
+    /// come closest above it when no bills do (after a coin).
+    Short {
+        left: i64,
+        next: Combination,
+    },
+    Reached,
+    Over(i64),
+}
+
+/// Bill combinations over the accepted nominals (`accepted_nominals`).
+pub struct Planner {
+    /// Largest first.
+    nominals: Vec<i64>,
+}
+
+impl Planner {
+    pub fn new(accepted: &[i32]) -> Planner {
+        let mut nominals: Vec<i64> = accepted
+            .iter()
+            .filter(|&&nominal| nominal > 0)
+            .map(|&nominal| i64::from(nominal))
+            .collect();
+        nominals.sort_unstable_by(|a, b| b.cmp(a));
+        nominals.dedup();
+        Planner { nominals }
+    }
+
+    /// Every reachable amount is a multiple of this.
+    fn unit(&self) -> i64 {
+        self.nominals.iter().fold(0, |a, &b| gcd(a, b)).max(1)
+    }
+
+    /// Fewest bills adding up to exactly `amount`, `None` when none do.
+    fn fewest(&self, amount: i64) -> Option<u32> {
+        let unit = self.unit();
+        if self.nominals.is_empty() || amount <= 0 || amount % unit != 0 {
+            return None;
+        }
+        let steps = (amount / unit) as usize;
+        let mut best: Vec<Option<u32>> = vec![None; steps + 1];
+        best[0] = Some(0);
+        for i in 1..=steps {
+            best[i] = self
+                .nominals
+                .iter()
+                .filter_map(|&nominal| {
+                    let size = (nominal / unit) as usize;
+                    let rest = best[i.checked_sub(size)?]?;
+                    Some(rest + 1)
+                })
+                .min();
+        }
+        best[steps]
+    }
+
+    pub fn reachable(&self, amount: i64) -> bool {
+        self.fewest(amount).is_some()
+    }
+
+    /// The next intent above `amount` for the stepper's plus, `None` past
+    /// `MAX`.
+    pub fn step_up(&self, amount: i64) -> Option<i64> {
+        let unit = self.unit();
+        let mut next = (amount.max(0) / unit + 1) * unit;
+        while next <= MAX {
+            if self.reachable(next) {
+                return Some(next);
+            }
+            next += unit;
+        }
+        None
+    }
+
+    /// The next intent below `amount` for the stepper's minus; 0 (no
+    /// intent) below the smallest bill.
+    pub fn step_down(&self, amount: i64) -> i64 {
+        let unit = self.unit();
+        let mut next = (amount - 1).div_euclid(unit) * unit;
+        while next > 0 {
+            if self.reachable(next) {
+                return next;
+            }
+            next -= unit;
+        }
+        0
+    }
+
+    /// A few ways to make up `amount` in bills, fewest bills first.
+    pub fn combinations(&self, amount: i64) -> Vec<Combination> {
+        let Some(fewest) = self.fewest(amount) else {
+            return Vec::new();
+        };
+        let mut found = Vec::new();
+        self.collect(amount, 0, fewest + EXTRA_BILLS, &mut Vec::new(), &mut found);
+        // stable: among equal counts, the ones with larger bills stay first
+        found.sort_by_key(bills);
+        found.truncate(SUGGESTIONS);
+        found
+    }
+
+    fn collect(
+        &self,
+        left: i64,
+        from: usize,
+        budget: u32,
+        current: &mut Combination,
+        found: &mut Vec<Combination>,
+    ) {
+        if left == 0 {
+            found.push(current.clone());
+            return;
+        }
+        let Some(&nominal) = self.nominals.get(from) else {
+            return;
+        };
+        // not even the budget in this bill (the largest left) gets there
+        if left > i64::from(budget) * nominal {
+            return;
+        }
+        let most = (left / nominal).min(i64::from(budget)) as u32;
+        for count in (0..=most).rev() {
+            if count > 0 {
+                current.push((nominal, count));
+            }
+            self.collect(
+                left - nominal * i64::from(count),
+                from + 1,
+                budget - count,
+                current,
+                found,
+            );
+            if count > 0 {
+                current.pop();
+            }
+        }
+    }
+
+    pub fn progress(&self, intent: i64, inserted: i64) -> Progress {
+        if inserted == intent {
+            return Progress::Reached;
+        }
+        if inserted > intent {
+            return Progress::Over(inserted - intent);
+        }
+        let left = intent - inserted;
+        let next = Some(left)
+            .filter(|&left| self.reachable(left))
+            .or_else(|| self.step_up(left))
+            .and_then(|target| self.combinations(target).into_iter().next())
+            .unwrap_or_default();
+        Progress::Short { left, next }
+    }
+}
+
+fn bills(combination: &Combination) -> u32 {
+    combination.iter().map(|&(_, count)| count).sum()
+}
+
+/// "2 × 2,000 ֏ + 1,000 ֏"
+pub fn describe(combination: &Combination) -> String {
+    combination
+        .iter()
+        .map(|&(nominal, count)| match count {
+            1 => money::format(nominal),
+            _ => format!("{} × {}", count, money::format(nominal)),
+        })
+        .collect::<Vec<_>>()
+        .join(" + ")
+}
+
+fn gcd(a: i64, b: i64) -> i64 {
+    if b == 0 { a } else { gcd(b, a % b) }
+}
@@ -0,0 +1,163 @@
+use log::error;
+use rusqlite::{Connection, OptionalExtension, Result as SqlResult, params};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::marker::PhantomData;
+
+use crate::boost::Boost;
+use crate::cache::Entry;
+use crate::clock;
+use crate::crash_loop::Crash;
+use crate::donation::Username;
+use crate::funds::Offer;
+
+/// A runtime setting kept in Stats.db; `T` is the type stored under it.
+/// Keys are declared below so each name and type lives in one place.
+pub struct Key<T> {
+    pub name: &'static str,
+    value: PhantomData<fn() -> T>,
+}
+
+impl<T> Key<T> {
+    const fn new(name: &'static str) -> Self {
+        Key {
+            name,
+            value: PhantomData,
+        }
+    }
+}
+
+/// Event mode as last toggled from Diagnostics; overrides
+/// `event_mode.enabled` from config once set.
+pub const EVENT_MODE: Key<bool> = Key::new("event_mode");
+/// The boost running now, if any; dropped when it ends.
+pub const BOOST: Key<Boost> = Key::new("boost");
+/// Reason Home Assistant paused money acceptance, while it is paused.
+pub const ACCEPTANCE_LOCK: Key<String> = Key::new("acceptance_lock");
+/// Unix time the bill stacker was last put back, taken as emptied; bills
+/// stacked since count against `stacker.capacity`.
+pub const STACKER_EMPTIED: Key<u64> = Key::new("stacker_emptied");
+/// The donation session in progress, kept up to date as money comes in,
+/// so one cut short by a crash, a lost window or a shutdown is resumed once
+/// the app is back up.
+pub const INTERRUPTED_SESSION: Key<InterruptedSession> = Key::new("interrupted_session");
+/// A donation held back for corrections after Done, sent once the grace
+/// period is over; one left over from a restart is sent at startup.
+pub const HELD_DONATION: Key<HeldDonation> = Key::new("held_donation");
+/// Event-mode sessions added up but not sent yet (`event_mode.batch_secs`);
+/// picked up again after a restart.
+pub const EVENT_BATCH: Key<EventBatch> = Key::new("event_batch");
+/// Unix time of the last successful funds fetch from the gateway.
+pub const LAST_FUNDS_SYNC: Key<u64> = Key::new("last_funds_sync");
+/// Unix time of the last successful usernames fetch from the gateway.
+pub const LAST_USERNAMES_SYNC: Key<u64> = Key::new("last_usernames_sync");
+/// The gateway's funds and campaigns as last fetched, see `cache`.
+pub const FUNDS_CACHE: Key<Entry<Offer>> = Key::new("funds_cache");
+/// The gateway's usernames as last fetched.
+pub const USERNAMES_CACHE: Key<Entry<Vec<Username>>> = Key::new("usernames_cache");
+/// Unix times of recent starts that haven't stayed up yet, see `crash_loop`.
+pub const RECENT_STARTS: Key<Vec<u64>> = Key::new("recent_starts");
+/// The last panic, with the log leading up to it.
+pub const LAST_CRASH: Key<Crash> = Key::new("last_crash");
+
+/// What the donor had chosen and inserted so far.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InterruptedSession {
+    pub username: String,
+    pub fund_id: i32,
+    pub fund_name: String,
+    /// Credited apart from the current ledger session's bills, which are
+    /// read back from the ledger: coins, and bills from before a resume.
+    pub coins: i32,
+}
+
+/// A donation whose receipt is issued but whose send waits out
+/// `donation_edit_secs`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HeldDonation {
+    pub receipt: Option<i64>,
+    /// When Done was tapped; the donor photo is taken then.
+    pub timestamp: u64,
+    pub username: String,
+    pub fund_id: i32,
+    pub fund_name: String,
+    pub amount: i32,
+}
+
+/// Event-mode sessions for one fund, sent together as one donation.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EventBatch {
+    pub fund_id: i32,
+    pub fund_name: String,
+    pub amount: i32,
+    pub sessions: u32,
+    pub receipts: Vec<i64>,
+    /// When the first session went in; the donation is dated then.
+    pub started: u64,
+    /// Every session in it was committed by the timeout, not Done.
+    pub auto_committed: bool,
+}
+
+fn init_db(db: &Connection) -> SqlResult<()> {
+    db.execute(
+        "CREATE TABLE IF NOT EXISTS kv (
+            key TEXT PRIMARY KEY,
+            value TEXT NOT NULL,
+            updated INTEGER NOT NULL
+        )",
+        [],
+    )?;
+    Ok(())
+}
+
+fn read(db_path: &str, name: &str) -> SqlResult<Option<String>> {
+    let db = Connection::open(db_path)?;
+    init_db(&db)?;
+    db.query_row("SELECT value FROM kv WHERE key = ?1", [name], |row| {
+        row.get(0)
+    })
+    .optional()
+}
+
+/// The stored value, or `None` when unset. A read error or a value of the
+/// wrong type is logged and treated as unset. Blocking, one small query.
+pub fn get<T: DeserializeOwned>(db_path: &str, key: &Key<T>) -> Option<T> {
+    let text = read(db_path, key.name)
+        .inspect_err(|e| error!("Failed to read {} from the kv store: {}", key.name, e))
+        .ok()??;
+    serde_json::from_str(&text)
+        .inspect_err(|e| error!("Ignoring stored {} ({:?}): {}", key.name, text, e))
+        .ok()
+}
+
+fn write(db_path: &str, name: &str, value: Option<&str>) -> SqlResult<()> {
+    let db = Connection::open(db_path)?;
+    init_db(&db)?;
+    match value {
+        Some(value) => db.execute(
+            "INSERT INTO kv (key, value, updated) VALUES (?1, ?2, ?3)
+             ON CONFLICT(key) DO UPDATE SET value = excluded.value, updated = excluded.updated",
+            params![name, value, clock::timestamp() as i64],
+        )?,
+        None => db.execute("DELETE FROM kv WHERE key = ?1", [name])?,
+    };
+    Ok(())
+}
+
+/// Stores a value. Blocking, a single upsert, so quick toggles land in
+/// order; best-effort, a failure is only logged.
+pub fn set<T: Serialize>(db_path: &str, key: &Key<T>, value: &T) {
+    let result = serde_json::to_string(value)
+        .map_err(|e| e.to_string())
+        .and_then(|text| write(db_path, key.name, Some(&text)).map_err(|e| e.to_string()));
+    if let Err(e) = result {
+        error!("Failed to write {} to the kv store: {}", key.name, e);
+    }
+}
+
+/// Unsets a key, so `get` returns `None` again.
+pub fn remove<T>(db_path: &str, key: &Key<T>) {
+    if let Err(e) = write(db_path, key.name, None) {
+        error!("Failed to remove {} from the kv store: {}", key.name, e);
+    }
+}
@@ -0,0 +1,91 @@
+use rusqlite::{Connection, Result as SqlResult, params};
+
+use crate::{clock, shift};
+
+/// The bill driver's own record of what it stacked, one row per bill,
+/// grouped into sessions (one per enable request from the UI). It is written
+/// before the UI hears about a bill, so it is the authoritative amount when
+/// the two disagree.
+pub fn init_db(db: &Connection) -> SqlResult<()> {
+    db.execute(
+        "CREATE TABLE IF NOT EXISTS ledger_sessions (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            started INTEGER NOT NULL
+        )",
+        [],
+    )?;
+    db.execute(
+        "CREATE TABLE IF NOT EXISTS bill_ledger (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            session INTEGER NOT NULL,
+            timestamp INTEGER NOT NULL,
+            nominal INTEGER NOT NULL
+        )",
+        [],
+    )?;
+
+    // Databases created before shift tracking lack this column.
+    let has_shift = db
+        .prepare("SELECT 1 FROM pragma_table_info('ledger_sessions') WHERE name = 'shift'")?
+        .exists([])?;
+    if !has_shift {
+        db.execute("ALTER TABLE ledger_sessions ADD COLUMN shift INTEGER", [])?;
+    }
+    Ok(())
+}
+
+/// Opens a new ledger session under the open shift, if any, and returns
+/// its id.
+pub fn begin_session(db: &Connection) -> SqlResult<i64> {
+    let shift = shift::current(db)?;
+    db.execute(
+        "INSERT INTO ledger_sessions (started, shift) VALUES (?1, ?2)",
+        params![clock::timestamp() as i64, shift.map(|shift| shift.id)],
+    )?;
+    Ok(db.last_insert_rowid())
+}
+
+pub fn record(db: &Connection, session: i64, nominal: i32) -> SqlResult<()> {
+    db.execute(
+        "INSERT INTO bill_ledger (session, timestamp, nominal) VALUES (?1, ?2, ?3)",
+        params![session, clock::timestamp() as i64, nominal],
+    )?;
+    Ok(())
+}
+
+/// Total stacked in the most recent session. Blocking, one small query.
+pub fn current_session_total(db_path: &str) -> SqlResult<i32> {
+    let db = Connection::open(db_path)?;
+    init_db(&db)?;
+    db.query_row(
+        "SELECT COALESCE(SUM(nominal), 0) FROM bill_ledger
+         WHERE session = (SELECT MAX(id) FROM ledger_sessions)",
+        [],
+        |row| row.get(0),
+    )
+}
+
+/// Bills stacked in the most recent session as (nominal, count), largest
+/// first.
+pub fn current_session_bills(db_path: &str) -> SqlResult<Vec<(i32, i32)>> {
+    let db = Connection::open(db_path)?;
+    init_db(&db)?;
+    let mut stmt = db.prepare(
+        "SELECT nominal, COUNT(*) FROM bill_ledger
+         WHERE session = (SELECT MAX(id) FROM ledger_sessions)
+         GROUP BY nominal ORDER BY nominal DESC",
+    )?;
+    stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+        .collect()
+}
+
+/// Bills stacked since a unix time, across sessions. Blocking, one query.
+pub fn bills_since(db_path: &str, since: u64) -> SqlResult<i64> {
+    let db = Connection::open(db_path)?;
+    init_db(&db)?;
+    db.query_row(
+        "SELECT COUNT(*) FROM bill_ledger WHERE timestamp >= ?1",
+        [since as i64],
+        |row| row.get(0),
+    )
+}
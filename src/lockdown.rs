@@ -0,0 +1,58 @@
+//! Key combinations from a physical keyboard that the kiosk swallows: the
+//! ones that close, switch away from or open things over the kiosk on a
+//! desktop session. Typing (a keyboard used for usernames) passes through.
+//! Slint's key text uses private-use characters for special keys.
+
+const META: char = '\u{0017}';
+const META_R: char = '\u{0018}';
+const TAB: char = '\t';
+const F1: u32 = 0xF704;
+const F24: u32 = 0xF71B;
+
+/// The modifiers held with a key press, as Slint reports them.
+#[derive(Debug, Clone, Copy)]
+pub struct Modifiers {
+    pub alt: bool,
+    pub control: bool,
+    pub meta: bool,
+}
+
+/// "Ctrl+Alt+F2" when the press is one to swallow, `None` otherwise.
+pub fn blocked(text: &str, modifiers: Modifiers) -> Option<String> {
+    let key = text.chars().next()?;
+    let function = (F1..=F24)
+        .contains(&u32::from(key))
+        .then(|| u32::from(key) - F1 + 1);
+    let super_key = key == META || key == META_R;
+
+    let dangerous = modifiers.meta
+        || super_key
+        || (modifiers.control && modifiers.alt)
+        || (modifiers.alt && (function.is_some() || key == TAB));
+    if !dangerous {
+        return None;
+    }
+
+    let mut combo = Vec::new();
+    if modifiers.control {
+        combo.push("Ctrl".to_string());
+    }
+    if modifiers.alt {
+        combo.push("Alt".to_string());
+    }
+    if modifiers.meta && !super_key {
+        combo.push("Super".to_string());
+    }
+    combo.push(match (function, key) {
+        (Some(n), _) => format!("F{}", n),
+        (None, META | META_R) => "Super".to_string(),
+        (None, TAB) => "Tab".to_string(),
+        (None, '\u{7f}') => "Delete".to_string(),
+        (None, '\u{8}') => "Backspace".to_string(),
+        (None, key) if key.is_control() || ('\u{E000}'..='\u{F8FF}').contains(&key) => {
+            format!("U+{:04X}", u32::from(key))
+        }
+        (None, key) => key.to_uppercase().to_string(),
+    });
+    Some(combo.join("+"))
+}
@@ -3,54 +3,150 @@
 
 slint::include_modules!();
 
+mod acceptor;
+mod admin;
+mod alerts;
+mod api;
+mod audit;
+mod backend;
+mod boost;
+mod cache;
 mod camera;
 mod cashcode;
 mod cctalk;
+mod charts;
+mod clock;
+mod coin_pulse;
 mod config;
+mod config_migration;
+mod crash_loop;
+mod db_writes;
+mod denominations;
 mod diag_logger;
+mod doctor;
 mod donation;
 mod donation_log;
 mod error;
+mod error_code;
+mod essp;
+mod flow;
+mod frame_log;
 mod funds;
+mod hass_events;
 mod home_assistant;
+mod i18n;
+mod intent;
+mod kv;
+mod ledger;
+mod lockdown;
+mod member;
+mod mock_acceptor;
+mod money;
+mod mqtt;
+mod notifications;
+mod preflight;
+mod printer;
+mod qr;
+mod receipt;
+mod reconcile;
+mod report;
 mod retroarch;
+mod router;
+mod shift;
+mod shutdown;
+mod signing;
+mod soak;
+mod softkeys;
 mod sound;
+mod status_file;
+mod supervisor;
+mod tamper;
+mod transactions;
+mod usb_export;
+mod usernames;
+mod version;
+mod voucher;
 
-use cashcode::{BillEvent, CashCode};
+use acceptor::BillAcceptor;
+use cashcode::{BillEvent, DeviceState, DeviceStatus};
 use config::Config;
+use error_code::ErrorCode;
+use frame_log::FrameLog;
+use funds::Campaign;
 use log::{error, info, warn};
 use slint::Model;
 use std::cell::RefCell;
 use std::rc::Rc;
+use std::sync::Arc;
 use std::sync::mpsc::Sender;
 use std::thread;
 use std::time::Duration;
+use transactions::Transaction;
 
 pub fn main() {
     let log_rx = diag_logger::init();
 
-    info!("Starting :3");
-
-    sound::init();
+    info!(
+        "Starting :3 v{} ({}, {})",
+        version::VERSION,
+        version::GIT_HASH,
+        version::COMMIT_DATE
+    );
 
-    // Test
-    for _ in 0..5 {
-        sound::play_yippee();
+    if let Some(args) = soak::requested() {
+        std::process::exit(soak::run(&args));
     }
 
     // Load config
-    let config = match Config::load() {
-        Ok(config) => config,
+    let (mut config, config_error) = match Config::load() {
+        Ok(config) => (config, None),
         Err(e) => {
             error!(
                 "Failed to load configuration, falling back to defaults: {}",
                 e
             );
-            Config::default()
+            (Config::default(), Some(e.to_string()))
         }
     };
+    i18n::set_language(&config.language);
+    money::set_currency(&config.currency);
+    if doctor::requested() {
+        std::process::exit(doctor::run(&config, config_error.as_deref()));
+    }
+    api::configure(
+        &config.gateways,
+        &config.gateway_tls,
+        config.gateway_signing_secret.as_deref(),
+    );
+    crash_loop::install_hook(&config.stats_db_path);
+    if let Some(crashes) = crash_loop::starting(&config.stats_db_path, &config.safe_mode) {
+        safe_mode::run(&config, log_rx, crashes);
+        return;
+    }
+    let publisher = mqtt::start(&config.mqtt, config.dry_run);
+    alerts::start(&config.alerts, config.dry_run);
+    if config.cashcode_mode == config::CashCodeMode::Mock {
+        match mock_acceptor::start(config.bill_types(), config.mock_acceptor_port) {
+            Ok(port) => config.cashcode_serial_port = port,
+            Err(e) => error!("🧪 Failed to start the mock bill acceptor: {}", e),
+        }
+    }
+    let features = &config.features;
+
+    if features.sound {
+        sound::init();
 
+        // Test
+        for _ in 0..5 {
+            sound::play_yippee();
+        }
+    } else {
+        info!("🔇 Sound disabled in config");
+    }
+
+    window_recovery::wait_for_compositor();
     let main_window = MainWindow::new().unwrap();
+    window_recovery::init(&main_window);
 
     // Enable fullscreen mode for kiosk deployment
     main_window.window().set_fullscreen(true);
@@ -63,40 +159,514 @@ pub fn main() {
             .into(),
     );
 
+    main_window.set_feature_home_assistant(features.home_assistant);
+    let flow = flow::Flow::from_config(&config);
+    info!("🧭 Donation flow: {}", flow.describe());
+    main_window.set_feature_username_entry(features.username_entry && flow.asks(flow::Step::Name));
+    main_window.set_flow_details_first(flow.details_first());
+    main_window.set_flow_fund_entry(flow.asks(flow::Step::Fund));
+    main_window.set_flow_fund_id(config.flow.fund_id.or(config.fallback_fund_id).unwrap_or(0));
+    main_window.set_flow_fund_name(
+        match config.flow.fund_id {
+            Some(_) => &config.flow.fund_name,
+            None => &config.fallback_fund_name,
+        }
+        .into(),
+    );
+    main_window.set_feature_donation_wall(features.donation_wall);
+    main_window.set_feature_games(features.games);
+    main_window.set_feature_sound(features.sound);
+    main_window.set_diag_version(version::describe().into());
+    let changelog: Vec<slint::SharedString> = version::CHANGELOG.lines().map(Into::into).collect();
+    main_window.set_diag_changelog(slint::ModelRc::new(slint::VecModel::from(changelog)));
+    let config_notices: Vec<LogEntry> = config
+        .notices
+        .iter()
+        .map(|notice| LogEntry {
+            level: 2,
+            text: notice.to_string().into(),
+        })
+        .collect();
+    main_window.set_config_notices(slint::ModelRc::new(slint::VecModel::from(config_notices)));
+
+    notification_handler::init(&main_window);
+    boot_checklist::init(&main_window, &config, config_error.as_deref());
+    if !config.notices.is_empty() {
+        notification_handler::set(
+            &main_window,
+            "config",
+            2,
+            &i18n::tr("config.outdated", &[&config.notices.len()]),
+        );
+    }
+    if config.dry_run {
+        warn!("🧪 Dry run: nothing is sent, printed or kept");
+        notification_handler::set(&main_window, "dry-run", 2, &i18n::tr("dry_run.banner", &[]));
+    }
+    navigation_handler::init(&main_window);
     virtual_keyboard::init(&main_window);
     autocomplete_handler::init(&main_window);
-    let cashcode_tx = bill_acceptor::init(&main_window, &config);
+    lockdown_handler::init(&main_window, &config);
+    main_window
+        .global::<Money>()
+        .on_format(|amount| money::format(amount.into()).into());
+    let frame_log = Arc::new(FrameLog::new(config.frame_log_capacity));
+    let cashcode_tx = bill_acceptor::init(&main_window, &config, frame_log.clone());
     let cctalk_tx = coin_acceptor::init(&main_window, &config, cashcode_tx.clone());
-    fund_fetcher::init(&main_window, &config);
+    let campaigns = Rc::new(RefCell::new(Vec::new()));
+    fund_fetcher::init(&main_window, &config, campaigns.clone());
+    member_status_handler::init(&main_window, &config);
+    voucher_handler::init(&main_window, &config);
+    qr_payment_handler::init(&main_window, &config);
+    intent_handler::init(&main_window, &config);
     diagnostics_handler::init(
         &main_window,
+        &config,
         log_rx,
         cashcode_tx.clone(),
         cctalk_tx.clone(),
-        config.token.clone(),
+        frame_log,
+    );
+    usb_export_handler::init(&main_window, &config);
+    shift_handler::init(&main_window, &config);
+    admin_handler::init(&main_window, &config, cashcode_tx.clone());
+    let tamper_tx = tamper_handler::init(&main_window, &config, publisher.as_ref());
+    let print_wake = printer_handler::init(&main_window, &config);
+    attract_handler::init(&main_window, &config);
+    let booster = boost_handler::init(&main_window, &config, publisher.as_ref());
+    let backend = donation_handler::init(
+        &main_window,
+        &config,
+        cashcode_tx.clone(),
+        cctalk_tx.clone(),
+        campaigns.clone(),
+        print_wake,
+        publisher.as_ref(),
+    );
+    token_watch::init(&main_window, &config, campaigns, backend, publisher.clone());
+    let mut hass_events = None;
+    let mut chromium = None;
+    if features.home_assistant {
+        chromium = Some(home_assistant_handler::init(
+            &main_window,
+            &config,
+            cashcode_tx.clone(),
+            cctalk_tx.clone(),
+            booster,
+        ));
+        hass_events = hass_events_handler::init(&main_window, &config, tamper_tx);
+    }
+    restart_handler::init(
+        &main_window,
+        &config,
+        cashcode_tx.clone(),
+        cctalk_tx,
+        publisher.clone(),
+        hass_events,
+    );
+    if features.games {
+        game_handler::init(&main_window, &config);
+    }
+    if features.donation_wall {
+        logs_handler::init(&main_window, &config);
+    }
+    stats_handler::init(&main_window, &config);
+    reboot_handler::init(&main_window, &config);
+    report_handler::init(&config);
+    status_file_handler::init(&main_window, &config);
+    softkey_handler::init(&main_window, &config);
+    shutdown_handler::init(&main_window, &config);
+    let shutdown_tx = cashcode_tx.clone();
+    startup_handler::init(&main_window, &config, cashcode_tx, publisher.clone());
+    if let Some(publisher) = &publisher {
+        status_file_handler::init_mqtt(&main_window, &config, publisher.clone());
+        status_light_handler::init(&main_window, publisher.clone());
+    }
+    window_recovery::resume(&main_window, &config);
+    window_recovery::persist(&main_window, &config);
+    // Up for long enough: the starts before this one weren't a crash loop
+    let stats_db_path = config.stats_db_path.clone();
+    slint::Timer::single_shot(
+        Duration::from_secs(config.safe_mode.stable_mins * 60),
+        move || crash_loop::stable(&stats_db_path),
     );
-    donation_handler::init(&main_window, &config, cashcode_tx, cctalk_tx);
-    home_assistant_handler::init(&main_window, &config);
-    game_handler::init(&main_window, &config);
-    logs_handler::init(&main_window, &config);
 
-    main_window.run().unwrap();
+    let result = main_window.run();
+    let restart = reboot_handler::restart_requested();
+    if shutdown::requested() || restart {
+        shutdown_handler::finish(&shutdown_tx, chromium.as_deref());
+        if restart {
+            reboot_handler::restart_self();
+        }
+        return;
+    }
+    window_recovery::relaunch(&main_window, &config, result);
+}
+
+mod safe_mode {
+    use super::*;
+    use std::sync::mpsc::channel;
+
+    /// Runs the kiosk without its drivers, MQTT or Home Assistant, after
+    /// too many starts that didn't last (see `crash_loop`): donations are
+    /// off and Diagnostics opens, with the log and a way out. The last crash
+    /// and the log go to the gateway once.
+    pub fn run(
+        config: &Config,
+        log_rx: std::sync::mpsc::Receiver<diag_logger::LogLine>,
+        crashes: usize,
+    ) {
+        error!(
+            "🛟 {} starts within {} min, starting in safe mode",
+            crashes, config.safe_mode.window_mins
+        );
+        audit::record_now(
+            &config.stats_db_path,
+            "safe_mode",
+            &format!("{} starts", crashes),
+        );
+
+        window_recovery::wait_for_compositor();
+        let window = MainWindow::new().unwrap();
+        window_recovery::init(&window);
+        window.window().set_fullscreen(true);
+        let password = config.diagnostics_password.clone().unwrap_or_default();
+        window.set_diagnostics_password(password.as_str().into());
+        window.set_diag_version(version::describe().into());
+        window.set_feature_home_assistant(false);
+        window.set_feature_games(false);
+        window.set_feature_donation_wall(false);
+        window.set_bill_state(BillState::Offline);
+
+        notification_handler::init(&window);
+        navigation_handler::init(&window);
+        virtual_keyboard::init(&window);
+        let reason = i18n::tr("safe_mode.banner", &[&crashes]);
+        notification_handler::set(&window, "safe-mode", 3, &reason);
+        window.set_safe_mode(reason.into());
+
+        // Nothing listens: the buttons for the drivers only log a failure
+        let (cashcode_tx, _) = channel();
+        let (cctalk_tx, _) = channel();
+        let frame_log = Arc::new(FrameLog::new(config.frame_log_capacity));
+        diagnostics_handler::init(&window, config, log_rx, cashcode_tx, cctalk_tx, frame_log);
+
+        let stats_db_path = config.stats_db_path.clone();
+        window.on_leave_safe_mode(move || {
+            info!("🛟 Leaving safe mode");
+            audit::record_now(&stats_db_path, "safe_mode_left", "");
+            crash_loop::stable(&stats_db_path);
+            reboot_handler::restart_self();
+        });
+
+        if config.safe_mode.upload_logs
+            && let Some(token) = config.token.clone()
+        {
+            let report = crash_loop::report(&config.stats_db_path, crashes);
+            slint::spawn_local(async move {
+                match crash_loop::upload(&token, report).await {
+                    Ok(()) => info!("🛟 Crash report sent to the gateway"),
+                    Err(e) => warn!("🛟 Failed to send the crash report: {}", e),
+                }
+            })
+            .unwrap();
+        }
+
+        window.invoke_force_navigate(if password.is_empty() {
+            Page::Diagnostics
+        } else {
+            Page::DiagnosticsAuth
+        });
+        if let Err(e) = window.run() {
+            error!("🛟 Safe mode window failed: {}", e);
+        }
+    }
+}
+
+mod notification_handler {
+    use super::*;
+    use crate::notifications::NotificationCenter;
+    use slint::{ModelRc, Timer, TimerMode, VecModel};
+
+    /// Owns the notification center and mirrors it into the `notifications`
+    /// model. Other handlers raise and clear notifications through the
+    /// window's `notify` / `clear-notification` callbacks (see `set`,
+    /// `push` and `clear` below).
+    pub fn init(app: &MainWindow) {
+        let center = Rc::new(RefCell::new(NotificationCenter::default()));
+        let model = Rc::new(VecModel::<LogEntry>::default());
+        app.set_notifications(ModelRc::from(model.clone()));
+
+        let sync = {
+            let center = center.clone();
+            move || {
+                let items: Vec<LogEntry> = center
+                    .borrow()
+                    .visible()
+                    .into_iter()
+                    .map(|n| LogEntry {
+                        level: n.level,
+                        text: n.text.clone().into(),
+                    })
+                    .collect();
+                model.set_vec(items);
+            }
+        };
+        let sync = Rc::new(sync);
+
+        let center_notify = center.clone();
+        let sync_notify = sync.clone();
+        let weak = app.as_weak();
+        app.on_notify(move |key, level, text| {
+            let new = if key.is_empty() {
+                center_notify.borrow_mut().push(level, &text);
+                true
+            } else {
+                center_notify.borrow_mut().set(&key, level, &text)
+            };
+            sync_notify();
+            // errors are read out once, not on every refresh of the same one
+            if new
+                && level >= 3
+                && let Some(window) = weak.upgrade()
+            {
+                announce_handler::say(&window, &text);
+            }
+        });
+
+        let center_clear = center.clone();
+        let sync_clear = sync.clone();
+        app.on_clear_notification(move |key| {
+            if center_clear.borrow_mut().clear(&key) {
+                sync_clear();
+            }
+        });
+
+        let timer = Timer::default();
+        timer.start(TimerMode::Repeated, Duration::from_secs(1), move || {
+            if center.borrow_mut().expire(clock::instant()) {
+                sync();
+            }
+        });
+        std::mem::forget(timer);
+    }
+
+    /// Raises (or updates) the sticky notification for a condition.
+    pub fn set(window: &MainWindow, key: &str, level: i32, text: &str) {
+        window.invoke_notify(key.into(), level, text.into());
+    }
+
+    /// Shows a short-lived notification.
+    pub fn push(window: &MainWindow, level: i32, text: &str) {
+        window.invoke_notify("".into(), level, text.into());
+    }
+
+    /// Clears the sticky notification for a condition once it's resolved.
+    pub fn clear(window: &MainWindow, key: &str) {
+        window.invoke_clear_notification(key.into());
+    }
+}
+
+mod announce_handler {
+    use super::*;
+
+    /// Reads `text` out through the screen reader, see announcer.slint.
+    pub fn say(window: &MainWindow, text: &str) {
+        let announcer = window.global::<Announcer>();
+        announcer.set_text(text.into());
+        announcer.set_serial(announcer.get_serial().wrapping_add(1));
+    }
+}
+
+mod session_breakdown {
+    use super::*;
+    use slint::{ModelRc, VecModel};
+
+    /// Per-denomination list of what was counted this session, shown on the
+    /// insert page so a donor can see exactly which bills went in. Bills
+    /// come first, largest first, then coins.
+    fn set(window: &MainWindow, mut items: Vec<Denomination>) {
+        items.sort_by_key(|d| (d.coin, -d.nominal));
+        window.set_session_breakdown(ModelRc::new(VecModel::from(items)));
+    }
+
+    pub fn reset(window: &MainWindow) {
+        set(window, Vec::new());
+    }
+
+    pub fn add(window: &MainWindow, nominal: i32, coin: bool) {
+        let mut items: Vec<Denomination> = window.get_session_breakdown().iter().collect();
+        match items
+            .iter_mut()
+            .find(|d| d.nominal == nominal && d.coin == coin)
+        {
+            Some(item) => item.count += 1,
+            None => items.push(Denomination {
+                nominal,
+                count: 1,
+                coin,
+            }),
+        }
+        set(window, items);
+    }
+
+    /// Swaps the bills for the ledger's after a reconciliation, keeping the
+    /// coins (the ledger only knows bills).
+    pub fn replace_bills(window: &MainWindow, bills: &[(i32, i32)]) {
+        let mut items: Vec<Denomination> = window
+            .get_session_breakdown()
+            .iter()
+            .filter(|d| d.coin)
+            .collect();
+        items.extend(bills.iter().map(|&(nominal, count)| Denomination {
+            nominal,
+            count,
+            coin: false,
+        }));
+        set(window, items);
+    }
+}
+
+mod intent_handler {
+    use super::*;
+    use intent::{Planner, Progress};
+    use slint::{ModelRc, VecModel};
+
+    /// The "I want to give" stepper on the insert page, over the bills the
+    /// acceptor takes.
+    pub fn init(app: &MainWindow, config: &Config) {
+        if !config.features.donation_intent {
+            return;
+        }
+        app.set_feature_donation_intent(true);
+        let planner = Rc::new(Planner::new(&config.accepted_bills(&config.bill_types())));
+
+        let weak = app.as_weak();
+        let planner_step = planner.clone();
+        app.on_intent_step(move |up| {
+            let Some(w) = weak.upgrade() else {
+                return;
+            };
+            let intent = i64::from(w.get_session_intent());
+            let intent = if up {
+                planner_step.step_up(intent).unwrap_or(intent)
+            } else {
+                planner_step.step_down(intent)
+            };
+            w.set_session_intent(intent as i32);
+            update(&w, &planner_step);
+        });
+
+        let weak = app.as_weak();
+        app.on_intent_progress(move || {
+            if let Some(w) = weak.upgrade() {
+                update(&w, &planner);
+            }
+        });
+    }
+
+    fn update(window: &MainWindow, planner: &Planner) {
+        let intent = i64::from(window.get_session_intent());
+        let inserted = i64::from(window.get_session_amount());
+        let mut suggestions = Vec::new();
+        let (status, level) = if intent == 0 {
+            (String::new(), 0)
+        } else if inserted == 0 {
+            suggestions = planner
+                .combinations(intent)
+                .iter()
+                .map(|combination| intent::describe(combination).into())
+                .collect();
+            (String::new(), 0)
+        } else {
+            match planner.progress(intent, inserted) {
+                Progress::Short { left, next } => {
+                    let key = if next.iter().map(|&(n, c)| n * i64::from(c)).sum::<i64>() > left {
+                        "intent.to_go_over"
+                    } else {
+                        "intent.to_go"
+                    };
+                    let next = intent::describe(&next);
+                    (i18n::tr(key, &[&money::format(left), &next]), 0)
+                }
+                Progress::Reached => (i18n::tr("intent.reached", &[&money::format(intent)]), 1),
+                Progress::Over(over) => {
+                    info!("🎯 Donor went {} over their intent of {}", over, intent);
+                    (
+                        i18n::tr(
+                            "intent.over",
+                            &[&money::format(over), &money::format(intent)],
+                        ),
+                        2,
+                    )
+                }
+            }
+        };
+        window.set_intent_suggestions(ModelRc::new(VecModel::from(suggestions)));
+        window.set_intent_status(status.into());
+        window.set_intent_status_level(level);
+    }
 }
 
 mod bill_acceptor {
     use super::*;
     use slint::{Timer, TimerMode};
-    use std::sync::mpsc::channel;
+    use std::panic::{self, AssertUnwindSafe};
+    use std::sync::mpsc::{RecvTimeoutError, TryRecvError, channel};
+
+    /// First wait before a failed driver is started again; doubles up to
+    /// `MAX_BACKOFF` while it keeps failing.
+    const MIN_BACKOFF: Duration = Duration::from_secs(5);
+    const MAX_BACKOFF: Duration = Duration::from_secs(300);
+    /// A driver that ran this long before failing starts over from
+    /// `MIN_BACKOFF`.
+    const STABLE_RUN: Duration = Duration::from_secs(600);
+    /// How long the driver gets to answer an `Enable`. It normally answers
+    /// within one poll cycle; a reset in progress can hold it up longer.
+    const ENABLE_TIMEOUT: Duration = Duration::from_secs(5);
+    /// How often the driver is told whether it should be taking bills, so
+    /// a missed ACK or a lost command doesn't leave the two apart for long.
+    const RECONCILE_INTERVAL: Duration = Duration::from_secs(30);
+
+    /// Where the driver reports whether an `Enable` actually took effect.
+    pub type EnableAck = Sender<Result<(), String>>;
+    /// Where the driver sends its (nominal, count) bill counters.
+    pub type CountsReply = Sender<Result<Vec<(i32, i32)>, String>>;
 
     /// Commands to control the CashCode bill acceptor
     #[derive(Debug, Clone)]
     pub enum CashCodeCommand {
-        Enable,
+        Enable(EnableAck),
         Disable,
+        /// Back on within the session already open, after Done was blocked
+        Resume,
         Reset,
+        /// Test-bill mode on/off: bills are recognised and returned, not counted
+        TestMode(bool),
+        /// Close the port and start the driver over
+        Restart,
+        /// Escrow mode: take the bill waiting for the donor
+        Stack,
+        /// Escrow mode: hand the bill waiting for the donor back
+        Return,
+        /// Bill counters, for the service page
+        Counts(CountsReply),
+        /// Zero the bill counters after a cash collection; replies with
+        /// the counts they had
+        ClearCounts(CountsReply),
+        /// Whether the UI expects bills to be taken right now; the driver
+        /// re-sends enable or disable when it believes otherwise
+        Reconcile(bool),
+        /// The app is exiting: disable the acceptor, let a bill on its way
+        /// to the stacker finish, then stop the driver and reply
+        Shutdown(Sender<()>),
     }
 
-    pub fn init(app: &MainWindow, config: &Config) -> Sender<CashCodeCommand> {
+    pub fn init(
+        app: &MainWindow,
+        config: &Config,
+        frame_log: Arc<FrameLog>,
+    ) -> Sender<CashCodeCommand> {
         let weak = app.as_weak();
 
         // Create a channel for bill events (from CashCode to UI)
@@ -105,21 +675,84 @@ mod bill_acceptor {
         // Create a channel for control commands (from UI to CashCode)
         let (cmd_tx, cmd_rx) = channel::<CashCodeCommand>();
 
-        // Start CashCode driver in a separate thread
+        // Start CashCode driver in a separate thread, supervised: when it
+        // fails (or panics) it is started again with backoff, or at once on
+        // a Reset (Diagnostics, boot screen Retry).
         thread::spawn({
             let config = config.clone();
-            move || match init_cashcode(&config, event_tx, cmd_rx) {
-                Ok(_) => info!("CashCode driver stopped"),
-                Err(e) => error!("CashCode driver error: {}", e),
+            move || {
+                let mut backoff = MIN_BACKOFF;
+                loop {
+                    let started = clock::instant();
+                    let run = panic::catch_unwind(AssertUnwindSafe(|| {
+                        init_cashcode(&config, event_tx.clone(), &cmd_rx, frame_log.clone())
+                    }));
+                    let (code, problem) = match run {
+                        Ok(Ok(DriverExit::Restart)) => {
+                            info!("🔄 Restarting CashCode driver...");
+                            backoff = MIN_BACKOFF;
+                            continue;
+                        }
+                        Ok(Ok(DriverExit::Stopped)) => {
+                            info!("CashCode driver stopped");
+                            return;
+                        }
+                        Ok(Err(e)) => (e.code(), e.to_string()),
+                        Err(_) => (ErrorCode::BillFailure, "driver panicked".to_string()),
+                    };
+                    error_code::report(
+                        &config.stats_db_path,
+                        code,
+                        &format!("CashCode driver error: {}", problem),
+                    );
+                    let _ = event_tx.send(BillEvent::Status(format!("{} {}", code, problem), 3));
+                    if clock::since(started) >= STABLE_RUN {
+                        backoff = MIN_BACKOFF;
+                    }
+                    let _ = event_tx.send(BillEvent::Offline(backoff));
+                    warn!("CashCode driver offline, restarting in {:?}", backoff);
+                    let retry_at = clock::instant() + backoff;
+                    loop {
+                        let wait = retry_at.saturating_duration_since(clock::instant());
+                        match cmd_rx.recv_timeout(wait) {
+                            Ok(CashCodeCommand::Reset | CashCodeCommand::Restart) => {
+                                backoff = MIN_BACKOFF;
+                                break;
+                            }
+                            Ok(CashCodeCommand::Enable(ack)) => {
+                                let _ = ack.send(Err("driver offline, restarting".into()));
+                            }
+                            Ok(CashCodeCommand::Shutdown(done)) => {
+                                let _ = done.send(());
+                                return;
+                            }
+                            Ok(
+                                CashCodeCommand::Counts(reply)
+                                | CashCodeCommand::ClearCounts(reply),
+                            ) => {
+                                let _ = reply.send(Err("driver offline, restarting".into()));
+                            }
+                            Ok(_) => {}
+                            Err(RecvTimeoutError::Timeout) => {
+                                backoff = (backoff * 2).min(MAX_BACKOFF);
+                                break;
+                            }
+                            Err(RecvTimeoutError::Disconnected) => return,
+                        }
+                    }
+                    info!("🔄 Restarting CashCode driver...");
+                }
             }
         });
 
         // Set up callbacks for page transitions
         let cmd_tx_start = cmd_tx.clone();
+        let weak_start = app.as_weak();
+        let stats_db_path = config.stats_db_path.clone();
         app.on_start_accepting_money(move || {
             info!("📥 UI: Start accepting money");
-            if cmd_tx_start.send(CashCodeCommand::Enable).is_err() {
-                error!("Failed to send enable command to CashCode");
+            if let Some(w) = weak_start.upgrade() {
+                enable(&w, &cmd_tx_start, &stats_db_path);
             }
         });
 
@@ -131,7 +764,34 @@ mod bill_acceptor {
             }
         });
 
+        let cmd_tx_escrow = cmd_tx.clone();
+        app.on_escrow_accept(move || {
+            info!("📥 UI: Bill in escrow accepted");
+            if cmd_tx_escrow.send(CashCodeCommand::Stack).is_err() {
+                error!("Failed to send stack command to CashCode");
+            }
+        });
+        let cmd_tx_escrow = cmd_tx.clone();
+        app.on_escrow_return(move || {
+            info!("📤 UI: Bill in escrow returned");
+            if cmd_tx_escrow.send(CashCodeCommand::Return).is_err() {
+                error!("Failed to send return command to CashCode");
+            }
+        });
+
+        spawn_reset_schedule(app, config, cmd_tx.clone());
+        spawn_reconcile(app, cmd_tx.clone());
+
+        app.set_stacker_headroom(stacker_headroom(config));
+        app.set_stacker_warn_below(config.stacker.warn_below as i32);
+        show_headroom(app);
+
         // Poll for bill events and update UI
+        let stats_db_path = config.stats_db_path.clone();
+        let capacity = config.stacker.capacity;
+        // Dry-run bills go back to the donor: nothing in the ledger or stacker
+        let dry_run = config.dry_run;
+        let cmd_tx_events = cmd_tx.clone();
         let timer = Timer::default();
         timer.start(
             TimerMode::Repeated,
@@ -142,26 +802,82 @@ mod bill_acceptor {
                     while let Ok(event) = event_rx.try_recv() {
                         match event {
                             BillEvent::Accepted(nominal) => {
-                                info!("💵 Bill accepted in UI: {} dram", nominal as i32);
+                                info!("💵 Bill accepted in UI: {}", nominal.value());
+                                window.set_escrow_nominal(0);
+                                // Taking bills again means any jam is cleared
+                                notification_handler::clear(&window, "bill-jam");
                                 let current = window.get_session_amount();
-                                window.set_session_amount(current + nominal as i32);
-                                window.set_last_added_amount(nominal as i32);
+                                window.set_session_amount(current + nominal.value());
+                                if !dry_run {
+                                    window.set_session_bill_amount(
+                                        window.get_session_bill_amount() + nominal.value(),
+                                    );
+                                }
+                                session_breakdown::add(&window, nominal.value(), false);
+                                window.set_last_added_amount(nominal.value());
+                                announce_handler::say(
+                                    &window,
+                                    &i18n::tr(
+                                        "a11y.counted",
+                                        &[
+                                            &money::format(nominal.value() as i64),
+                                            &money::format(window.get_session_amount().into()),
+                                        ],
+                                    ),
+                                );
+
+                                let headroom = window.get_stacker_headroom();
+                                if headroom > 0 && !dry_run {
+                                    window.set_stacker_headroom(headroom - 1);
+                                    if headroom == 1 {
+                                        // Stop before the device jams on a full stacker; the
+                                        // session keeps what it has, coins still go in
+                                        warn!("📦 Stacker full by count, no more bills");
+                                        audit::record(
+                                            &stats_db_path,
+                                            "stacker_full",
+                                            &format!("{} bills counted", capacity.unwrap_or(0)),
+                                        );
+                                        let _ = cmd_tx_events.send(CashCodeCommand::Disable);
+                                    }
+                                    show_headroom(&window);
+                                }
                             }
                             BillEvent::Rejected(reason) => {
                                 info!("❌ Bill rejected: {}", reason);
+                                // counted for the reject rate on the Stats page
+                                audit::record(&stats_db_path, "bill_rejected", &reason.to_string());
                                 window.set_diag_bill_status(LogEntry {
                                     level: 2,
-                                    text: format!("Rejected: {}", reason).into(),
+                                    text: i18n::tr("bill.rejected", &[&reason.text()]).into(),
                                 });
+                                announce_handler::say(
+                                    &window,
+                                    &i18n::tr("a11y.rejected", &[&reason.text()]),
+                                );
                                 // Rejected bill still counts as insert-page activity
                                 window.invoke_activity_on_insert_money();
                             }
                             BillEvent::StackerRemoved => {
-                                error!("⚠️  Stacker removed!");
+                                error_code::report(
+                                    &stats_db_path,
+                                    ErrorCode::StackerRemoved,
+                                    "Stacker removed",
+                                );
                                 window.set_diag_bill_status(LogEntry {
                                     level: 2,
-                                    text: "Stacker removed!".into(),
+                                    text: format!("{} Stacker removed!", ErrorCode::StackerRemoved)
+                                        .into(),
                                 });
+                                notification_handler::set(
+                                    &window,
+                                    "stacker",
+                                    2,
+                                    &i18n::tr(
+                                        "bill.stacker_removed",
+                                        &[&ErrorCode::StackerRemoved],
+                                    ),
+                                );
                             }
                             BillEvent::StackerReplaced => {
                                 info!("✅ Stacker replaced");
@@ -169,19 +885,76 @@ mod bill_acceptor {
                                     level: 1,
                                     text: "Stacker replaced".into(),
                                 });
+                                // Taken as emptied; counting starts over
+                                let since = kv::get(&stats_db_path, &kv::STACKER_EMPTIED);
+                                match shift::record_collection(&stats_db_path, since.unwrap_or(0)) {
+                                    Ok((bills, amount)) => audit::record(
+                                        &stats_db_path,
+                                        "collection",
+                                        &format!(
+                                            "{} bills, {} {currency}",
+                                            bills,
+                                            amount,
+                                            currency = money::currency()
+                                        ),
+                                    ),
+                                    Err(e) => error_code::report(
+                                        &stats_db_path,
+                                        ErrorCode::Database,
+                                        &format!("Failed to record collection: {}", e),
+                                    ),
+                                }
+                                kv::set(&stats_db_path, &kv::STACKER_EMPTIED, &clock::timestamp());
+                                window.set_stacker_headroom(
+                                    capacity.map_or(-1, |capacity| capacity as i32),
+                                );
+                                show_headroom(&window);
+                            }
+                            BillEvent::StackerFull => {
+                                error_code::report(
+                                    &stats_db_path,
+                                    ErrorCode::StackerFull,
+                                    "Stacker full",
+                                );
+                                window.set_diag_bill_status(LogEntry {
+                                    level: 3,
+                                    text: format!("{} Stacker full!", ErrorCode::StackerFull)
+                                        .into(),
+                                });
+                                window.set_stacker_headroom(0);
+                                show_headroom(&window);
                             }
                             BillEvent::Jam(msg) => {
-                                error!("🚫 Jam: {}", msg);
+                                error_code::report(
+                                    &stats_db_path,
+                                    ErrorCode::BillJam,
+                                    &msg.to_string(),
+                                );
                                 window.set_diag_bill_status(LogEntry {
                                     level: 3,
-                                    text: format!("Jam: {}", msg).into(),
+                                    text: i18n::tr("bill.jam", &[&ErrorCode::BillJam, &msg.text()])
+                                        .into(),
                                 });
+                                notification_handler::set(
+                                    &window,
+                                    "bill-jam",
+                                    3,
+                                    &i18n::tr("bill.jammed", &[&ErrorCode::BillJam]),
+                                );
                             }
                             BillEvent::Error(msg) => {
-                                error!("⚠️  Error: {}", msg);
+                                error_code::report(
+                                    &stats_db_path,
+                                    ErrorCode::BillFailure,
+                                    &msg.to_string(),
+                                );
                                 window.set_diag_bill_status(LogEntry {
                                     level: 3,
-                                    text: format!("Error: {}", msg).into(),
+                                    text: i18n::tr(
+                                        "bill.error",
+                                        &[&ErrorCode::BillFailure, &msg.text()],
+                                    )
+                                    .into(),
                                 });
                             }
                             BillEvent::Status(text, level) => {
@@ -190,6 +963,71 @@ mod bill_acceptor {
                                     text: text.into(),
                                 });
                             }
+                            BillEvent::Ready => {
+                                info!("✅ Bill acceptor ready");
+                                window.set_bills_ready(true);
+                                notification_handler::clear(&window, "bill-offline");
+                            }
+                            BillEvent::State(state) => {
+                                window.set_bill_state(bill_state(state));
+                            }
+                            BillEvent::Offline(retry) => {
+                                window.set_bills_ready(false);
+                                window.set_bill_state(BillState::Offline);
+                                window.set_bill_in_transit(false);
+                                window.set_escrow_nominal(0);
+                                notification_handler::set(
+                                    &window,
+                                    "bill-offline",
+                                    3,
+                                    &i18n::tr(
+                                        "bill.offline",
+                                        &[&ErrorCode::BillFailure, &retry.as_secs()],
+                                    ),
+                                );
+                            }
+                            BillEvent::InTransit(in_transit) => {
+                                window.set_bill_in_transit(in_transit);
+                            }
+                            BillEvent::Escrow(nominal) => {
+                                info!("💵 Bill in escrow: {}", nominal.value());
+                                window.set_escrow_nominal(nominal.value());
+                                announce_handler::say(
+                                    &window,
+                                    &i18n::tr(
+                                        "a11y.escrow",
+                                        &[&money::format(nominal.value() as i64)],
+                                    ),
+                                );
+                                window.invoke_activity_on_insert_money();
+                            }
+                            BillEvent::Returned => {
+                                info!("↩️ Bill returned to the donor");
+                                window.set_escrow_nominal(0);
+                                window.set_diag_bill_status(LogEntry {
+                                    level: 0,
+                                    text: "Bill returned".into(),
+                                });
+                                announce_handler::say(&window, &i18n::tr("a11y.returned", &[]));
+                                window.invoke_activity_on_insert_money();
+                            }
+                            BillEvent::TestBill(nominal, code) => {
+                                let text = match nominal {
+                                    Some(nominal) => format!(
+                                        "Test: recognised {} (0x{:02X}) — returned",
+                                        money::format(nominal.value() as i64),
+                                        code
+                                    ),
+                                    None => {
+                                        format!("Test: unknown bill type 0x{:02X} — returned", code)
+                                    }
+                                };
+                                info!("🧪 {}", text);
+                                window.set_diag_bill_status(LogEntry {
+                                    level: if nominal.is_some() { 1 } else { 2 },
+                                    text: text.into(),
+                                });
+                            }
                         }
                     }
                 }
@@ -201,60 +1039,363 @@ mod bill_acceptor {
 
         cmd_tx
     }
-}
 
-fn init_cashcode(
-    config: &Config,
-    tx: Sender<BillEvent>,
-    cmd_rx: std::sync::mpsc::Receiver<bill_acceptor::CashCodeCommand>,
-) -> Result<(), cashcode::CashCodeError> {
-    use bill_acceptor::CashCodeCommand;
+    /// Tells the driver every `RECONCILE_INTERVAL` whether a session is
+    /// taking money and not paused by Home Assistant. Skipped while an
+    /// enable is waiting for its answer and in test-bill mode, when the
+    /// driver is on without a session.
+    fn spawn_reconcile(app: &MainWindow, cmd_tx: Sender<CashCodeCommand>) {
+        let weak = app.as_weak();
+        let timer = Timer::default();
+        timer.start(TimerMode::Repeated, RECONCILE_INTERVAL, move || {
+            let Some(w) = weak.upgrade() else {
+                return;
+            };
+            if w.get_acceptors_starting() || w.get_diag_test_bills() {
+                return;
+            }
+            let wanted = w.get_accepting_money() && !w.get_acceptance_locked();
+            if cmd_tx.send(CashCodeCommand::Reconcile(wanted)).is_err() {
+                error!("Failed to send Reconcile to bill acceptor");
+            }
+        });
+        std::mem::forget(timer);
+    }
 
-    info!("Initializing CashCode driver...");
-    let mut cashcode = match CashCode::new(&config.cashcode_serial_port, &config.stats_db_path) {
-        Ok(c) => c,
-        Err(e) => {
-            let _ = tx.send(BillEvent::Status(e.to_string(), 3));
-            return Err(e);
+    /// Asks the driver to enable the acceptor and waits, on the UI thread,
+    /// for it to confirm. The outcome goes to the window's
+    /// `acceptors-enabled` callback; on failure everything is switched off
+    /// again and the donor is told.
+    pub fn enable(window: &MainWindow, cmd_tx: &Sender<CashCodeCommand>, stats_db_path: &str) {
+        if window.get_stacker_headroom() == 0 {
+            warn!("📦 Stacker full, not starting a session");
+            window.invoke_stop_accepting_money();
+            notification_handler::push(
+                window,
+                3,
+                &i18n::tr("bill.stacker_full", &[&ErrorCode::StackerFull]),
+            );
+            window.invoke_acceptors_enabled(false);
+            return;
+        }
+        window.set_acceptors_starting(true);
+        let (ack_tx, ack_rx) = channel();
+        if cmd_tx.send(CashCodeCommand::Enable(ack_tx)).is_err() {
+            enabled(
+                window,
+                stats_db_path,
+                Err("driver is not running".to_string()),
+            );
+            return;
         }
-    };
 
-    let _ = tx.send(BillEvent::Status("Resetting...".to_string(), 0));
-    info!("Resetting bill acceptor...");
-    cashcode.reset()?;
-    thread::sleep(Duration::from_secs(5));
+        let started = clock::instant();
+        let stats_db_path = stats_db_path.to_string();
+        let weak = window.as_weak();
+        let timer = Rc::new(Timer::default());
+        let timer_weak = Rc::downgrade(&timer);
+        timer.start(TimerMode::Repeated, Duration::from_millis(100), move || {
+            let result = match ack_rx.try_recv() {
+                Ok(result) => result,
+                Err(TryRecvError::Empty) if clock::since(started) < ENABLE_TIMEOUT => return,
+                Err(TryRecvError::Empty) => Err(format!("no answer within {:?}", ENABLE_TIMEOUT)),
+                Err(TryRecvError::Disconnected) => Err("driver stopped".to_string()),
+            };
+            if let Some(timer) = timer_weak.upgrade() {
+                timer.stop();
+            }
+            if let Some(window) = weak.upgrade() {
+                enabled(&window, &stats_db_path, result);
+            }
+        });
+        // Stopped from inside the callback once the driver answered
+        std::mem::forget(timer);
+    }
 
-    info!("Polling for initializing status...");
-    cashcode.poll()?;
-    thread::sleep(Duration::from_millis(200));
+    fn enabled(window: &MainWindow, stats_db_path: &str, result: Result<(), String>) {
+        window.set_acceptors_starting(false);
+        match result {
+            Ok(()) => {
+                info!("✅ Bill acceptor confirmed enabled");
+                window.set_accepting_money(true);
+                window.invoke_acceptors_enabled(true);
+            }
+            Err(e) => {
+                error_code::report(
+                    stats_db_path,
+                    ErrorCode::BillFailure,
+                    &format!("Bill acceptor could not be enabled: {}", e),
+                );
+                // The coin acceptor may have been switched on alongside
+                window.invoke_stop_accepting_money();
+                notification_handler::push(
+                    window,
+                    3,
+                    &i18n::tr("bill.enable_failed", &[&ErrorCode::BillFailure]),
+                );
+                window.invoke_acceptors_enabled(false);
+            }
+        }
+    }
 
-    info!("Polling for disabled status...");
-    cashcode.poll()?;
-    thread::sleep(Duration::from_millis(200));
+    /// Bills the stacker can still take by the count since it was last
+    /// emptied, or -1 when that isn't tracked.
+    fn stacker_headroom(config: &Config) -> i32 {
+        let Some(capacity) = config.stacker.capacity else {
+            return -1;
+        };
+        let Some(emptied) = kv::get(&config.stats_db_path, &kv::STACKER_EMPTIED) else {
+            info!("📦 Stacker fill unknown until it is next emptied");
+            return -1;
+        };
+        match ledger::bills_since(&config.stats_db_path, emptied) {
+            Ok(stacked) => {
+                let headroom = (capacity as i64 - stacked).max(0) as i32;
+                info!(
+                    "📦 Stacker: {} bills counted, room for {}",
+                    stacked, headroom
+                );
+                headroom
+            }
+            Err(e) => {
+                error!("Failed to count stacked bills: {}", e);
+                -1
+            }
+        }
+    }
 
-    let total = cashcode.get_total_amount().unwrap_or(0);
+    fn bill_state(state: DeviceState) -> BillState {
+        match state {
+            DeviceState::Initializing => BillState::Initializing,
+            DeviceState::Idle => BillState::Idle,
+            DeviceState::Disabled => BillState::Disabled,
+            DeviceState::StackerRemoved => BillState::StackerRemoved,
+            DeviceState::StackerFull => BillState::StackerFull,
+            DeviceState::Jammed => BillState::Jammed,
+            DeviceState::Offline => BillState::Offline,
+        }
+    }
+
+    /// Raises, updates or clears the stacker notice for the current headroom.
+    fn show_headroom(window: &MainWindow) {
+        let headroom = window.get_stacker_headroom();
+        if headroom == 0 {
+            notification_handler::set(
+                window,
+                "stacker",
+                3,
+                &i18n::tr("bill.stacker_full", &[&ErrorCode::StackerFull]),
+            );
+        } else if headroom > 0 && headroom <= window.get_stacker_warn_below() {
+            notification_handler::set(
+                window,
+                "stacker",
+                2,
+                &i18n::tr("bill.stacker_low", &[&headroom]),
+            );
+        } else {
+            notification_handler::clear(window, "stacker");
+        }
+    }
+
+    /// Resets the bill acceptor on the schedule from config. Checked every
+    /// 30 s on the UI thread; a due reset waits until the kiosk is
+    /// `reboot_handler::unattended`.
+    fn spawn_reset_schedule(app: &MainWindow, config: &Config, cmd_tx: Sender<CashCodeCommand>) {
+        use chrono::NaiveTime;
+        use std::cell::Cell;
+
+        let daily_at = config.bill_reset.at.as_deref().and_then(|s| {
+            NaiveTime::parse_from_str(s, "%H:%M")
+                .inspect_err(|e| warn!("Ignoring invalid bill_reset.at {:?}: {}", s, e))
+                .ok()
+        });
+        let idle_after = config
+            .bill_reset
+            .idle_mins
+            .map(|mins| Duration::from_secs(mins * 60));
+        if daily_at.is_none() && idle_after.is_none() {
+            return;
+        }
+        info!(
+            "🔄 Bill acceptor reset schedule: daily at {:?}, after idle {:?}",
+            daily_at, idle_after
+        );
+
+        // The startup reset counts as today's if we boot after the reset time
+        let now = clock::local();
+        let last_daily = Cell::new(
+            daily_at
+                .filter(|at| now.time() >= *at)
+                .map(|_| now.date_naive()),
+        );
+        let idle_since = Cell::new(clock::instant());
+        let stats_db_path = config.stats_db_path.clone();
+        let weak = app.as_weak();
+
+        let timer = Timer::default();
+        timer.start(TimerMode::Repeated, Duration::from_secs(30), move || {
+            let Some(window) = weak.upgrade() else {
+                return;
+            };
+            if !reboot_handler::unattended(&window, &stats_db_path) {
+                idle_since.set(clock::instant());
+                return;
+            }
+
+            let now = clock::local();
+            let reason = if daily_at
+                .is_some_and(|at| now.time() >= at && last_daily.get() != Some(now.date_naive()))
+            {
+                last_daily.set(Some(now.date_naive()));
+                "daily"
+            } else if idle_after.is_some_and(|idle| clock::since(idle_since.get()) >= idle) {
+                "idle"
+            } else {
+                return;
+            };
+
+            info!("🔄 Scheduled bill acceptor reset ({})", reason);
+            idle_since.set(clock::instant());
+            if cmd_tx.send(CashCodeCommand::Reset).is_err() {
+                error!("Failed to send scheduled Reset to bill acceptor");
+                return;
+            }
+            audit::record(&stats_db_path, "bill_reset", reason);
+        });
+        std::mem::forget(timer);
+    }
+}
+
+/// Why the CashCode driver returned without an error.
+enum DriverExit {
+    /// The UI is gone, or the app is shutting down.
+    Stopped,
+    /// Asked to start over (`CashCodeCommand::Restart`).
+    Restart,
+}
+
+/// What each bill type is worth: `bill_types` from config when set, else
+/// the acceptor's own bill table, else the usual table for `currency`.
+fn bill_table(cashcode: &mut dyn BillAcceptor, config: &Config) -> Vec<i32> {
+    if !config.bill_types.is_empty() {
+        info!("💵 Bill table from config: {:?}", config.bill_types);
+        return config.bill_types.clone();
+    }
+    match cashcode.read_bill_table() {
+        Ok(Some(table)) if table.values.iter().any(|&value| value > 0) => {
+            info!(
+                "💵 Bill table from the acceptor: {:?} ({})",
+                table.values,
+                table.countries.join(", ")
+            );
+            return table.values;
+        }
+        Ok(_) => warn!(
+            "Bill acceptor reported no bill table, using the usual one for {}",
+            money::currency()
+        ),
+        Err(e) => warn!(
+            "Failed to read the bill table ({}), using the usual one for {}",
+            e,
+            money::currency()
+        ),
+    }
+    config.bill_types()
+}
+
+fn init_cashcode(
+    config: &Config,
+    tx: Sender<BillEvent>,
+    cmd_rx: &std::sync::mpsc::Receiver<bill_acceptor::CashCodeCommand>,
+    frame_log: Arc<FrameLog>,
+) -> Result<DriverExit, cashcode::CashCodeError> {
+    use bill_acceptor::CashCodeCommand;
+
+    // Upper bound for the device to come back after a reset; it is usually
+    // ready in 2-4 s.
+    const READY_TIMEOUT: Duration = Duration::from_secs(15);
+    // How often the device is asked which bill types it has enabled
+    const HEALTH_CHECK_INTERVAL: Duration = Duration::from_secs(60);
+
+    info!("Initializing CashCode driver...");
+    let mut cashcode = acceptor::open(config, frame_log)?;
+
+    let _ = tx.send(BillEvent::State(DeviceState::Initializing));
+    let _ = tx.send(BillEvent::Status("Resetting...".to_string(), 0));
+    info!("Resetting bill acceptor...");
+    cashcode.reset()?;
+
+    info!("Waiting for bill acceptor to initialise...");
+    if !cashcode.wait_ready(READY_TIMEOUT)? {
+        warn!(
+            "Bill acceptor not ready after {:?}, continuing anyway",
+            READY_TIMEOUT
+        );
+    }
+
+    match cashcode.identify() {
+        Ok(Some(identification)) => info!("💵 Bill acceptor: {}", identification),
+        Ok(None) => warn!("Bill acceptor didn't identify itself"),
+        Err(e) => warn!("Failed to identify the bill acceptor: {}", e),
+    }
+    let bill_types = bill_table(cashcode.as_mut(), config);
+    cashcode.set_bill_types(&bill_types)?;
+    cashcode.set_accepted_nominals(&config.accepted_bills(&bill_types));
+
+    let total = cashcode.get_total_amount().unwrap_or(0);
     let _ = tx.send(BillEvent::Status(
-        format!("Disabled · {} ֏ total", total),
+        format!("Disabled · {} total", money::format(total.into())),
         1,
     ));
+    let _ = tx.send(BillEvent::Ready);
 
     // Keep bill acceptor disabled until UI requests to enable it
     info!("Bill acceptor initialized, waiting for enable command...");
     info!("Starting polling loop...");
+    let mut last_health_check = clock::instant();
+    let mut last_device_status = None;
+    let mut last_state = None;
+    let mut bill_in_transit = false;
+    let mut shutting_down: Option<Sender<()>> = None;
     loop {
         // Check for enable/disable commands from UI
         while let Ok(cmd) = cmd_rx.try_recv() {
             match cmd {
-                CashCodeCommand::Enable => {
+                CashCodeCommand::Shutdown(done) => {
+                    info!("🛑 Disabling bill acceptor for shutdown...");
+                    if let Err(e) = cashcode.disable() {
+                        error!("Failed to disable bill acceptor for shutdown: {}", e);
+                    }
+                    shutting_down = Some(done);
+                }
+                CashCodeCommand::Enable(ack) => {
                     info!("📥 Enabling bill acceptor...");
+                    if let Err(e) = cashcode.begin_session() {
+                        error_code::report(
+                            &config.stats_db_path,
+                            ErrorCode::Database,
+                            &format!("Failed to start ledger session: {}", e),
+                        );
+                    }
                     if let Err(e) = cashcode.enable() {
                         error!("Failed to enable bill acceptor: {}", e);
                         let _ = tx.send(BillEvent::Status(format!("Enable failed: {}", e), 3));
+                        let _ = ack.send(Err(e.to_string()));
                     } else {
                         info!("✅ Bill acceptor enabled");
                         let total = cashcode.get_total_amount().unwrap_or(0);
-                        let _ =
-                            tx.send(BillEvent::Status(format!("Enabled · {} ֏ total", total), 1));
+                        let _ = tx.send(BillEvent::Status(
+                            format!("Enabled · {} total", money::format(total.into())),
+                            1,
+                        ));
+                        let _ = ack.send(Ok(()));
+                    }
+                }
+                CashCodeCommand::Resume => {
+                    info!("📥 Re-enabling bill acceptor, same session...");
+                    if let Err(e) = cashcode.enable() {
+                        error!("Failed to re-enable bill acceptor: {}", e);
                     }
                 }
                 CashCodeCommand::Disable => {
@@ -265,10 +1406,63 @@ fn init_cashcode(
                         info!("✅ Bill acceptor disabled");
                         let total = cashcode.get_total_amount().unwrap_or(0);
                         let _ = tx.send(BillEvent::Status(
-                            format!("Disabled · {} ֏ total", total),
+                            format!("Disabled · {} total", money::format(total.into())),
+                            1,
+                        ));
+                    }
+                }
+                CashCodeCommand::TestMode(on) => {
+                    if let Err(e) = cashcode.set_test_mode(on) {
+                        error!("Failed to switch test-bill mode: {}", e);
+                        let _ = tx.send(BillEvent::Status(format!("Test mode failed: {}", e), 3));
+                    } else if on {
+                        let _ = tx.send(BillEvent::Status(
+                            "Test mode — insert bills, they are returned".to_string(),
+                            2,
+                        ));
+                    } else {
+                        let total = cashcode.get_total_amount().unwrap_or(0);
+                        let _ = tx.send(BillEvent::Status(
+                            format!("Disabled · {} total", money::format(total.into())),
+                            1,
+                        ));
+                    }
+                }
+                CashCodeCommand::Restart => {
+                    info!("🔄 Closing the bill acceptor port to restart the driver");
+                    let _ = tx.send(BillEvent::Status("Restarting...".to_string(), 0));
+                    if let Err(e) = cashcode.disable() {
+                        warn!("Failed to disable bill acceptor before restart: {}", e);
+                    }
+                    return Ok(DriverExit::Restart);
+                }
+                CashCodeCommand::Stack => {
+                    if let Err(e) = cashcode.stack() {
+                        error!("Failed to stack bill from escrow: {}", e);
+                        let _ = tx.send(BillEvent::Status(format!("Stack failed: {}", e), 3));
+                    }
+                }
+                CashCodeCommand::Return => {
+                    if let Err(e) = cashcode.return_bill() {
+                        error!("Failed to return bill from escrow: {}", e);
+                        let _ = tx.send(BillEvent::Status(format!("Return failed: {}", e), 3));
+                    }
+                }
+                CashCodeCommand::Reconcile(wanted) => {
+                    reconcile_enable(cashcode.as_mut(), wanted, &config.stats_db_path);
+                }
+                CashCodeCommand::Counts(reply) => {
+                    let _ = reply.send(cashcode.get_bill_counts().map_err(|e| e.to_string()));
+                }
+                CashCodeCommand::ClearCounts(reply) => {
+                    let result = cashcode.clear_bill_counts();
+                    if result.is_ok() {
+                        let _ = tx.send(BillEvent::Status(
+                            format!("Disabled · {} total", money::format(0)),
                             1,
                         ));
                     }
+                    let _ = reply.send(result.map_err(|e| e.to_string()));
                 }
                 CashCodeCommand::Reset => {
                     info!("🔄 Resetting bill acceptor from diagnostics...");
@@ -278,14 +1472,13 @@ fn init_cashcode(
                         let _ = tx.send(BillEvent::Status(format!("Reset failed: {}", e), 3));
                     } else {
                         info!("✅ Reset sent, waiting for device to reinitialise...");
-                        thread::sleep(Duration::from_secs(3));
-                        cashcode.poll().ok();
-                        thread::sleep(Duration::from_millis(200));
-                        cashcode.poll().ok();
+                        if !cashcode.wait_ready(READY_TIMEOUT).unwrap_or(false) {
+                            warn!("Bill acceptor not ready after {:?}", READY_TIMEOUT);
+                        }
                         info!("✅ Bill acceptor re-initialised after reset");
                         let total = cashcode.get_total_amount().unwrap_or(0);
                         let _ = tx.send(BillEvent::Status(
-                            format!("Disabled · {} ֏ total", total),
+                            format!("Disabled · {} total", money::format(total.into())),
                             1,
                         ));
                     }
@@ -293,6 +1486,15 @@ fn init_cashcode(
             }
         }
 
+        if clock::since(last_health_check) >= HEALTH_CHECK_INTERVAL && cashcode.is_idle() {
+            last_health_check = clock::instant();
+            check_bill_health(
+                cashcode.as_mut(),
+                &config.stats_db_path,
+                &mut last_device_status,
+            );
+        }
+
         match cashcode.poll() {
             Ok(Some(event)) => {
                 // Send event to UI thread
@@ -305,7 +1507,10 @@ fn init_cashcode(
                     && let Ok(total) = cashcode.get_total_amount()
                 {
                     info!("Total collected in DB: {} dram", total);
-                    let _ = tx.send(BillEvent::Status(format!("Enabled · {} ֏ total", total), 1));
+                    let _ = tx.send(BillEvent::Status(
+                        format!("Enabled · {} total", money::format(total.into())),
+                        1,
+                    ));
                 }
             }
             Ok(_none) => {
@@ -314,14 +1519,113 @@ fn init_cashcode(
             Err(e) => {
                 error!("poll error: {}", e);
                 let _ = tx.send(BillEvent::Status(format!("Poll error: {}", e), 3));
-                thread::sleep(Duration::from_secs(1));
+                clock::sleep(Duration::from_secs(1));
             }
         }
 
-        thread::sleep(Duration::from_millis(400));
+        if cashcode.bill_in_transit() != bill_in_transit {
+            bill_in_transit = !bill_in_transit;
+            let _ = tx.send(BillEvent::InTransit(bill_in_transit));
+        }
+        let state = cashcode.state();
+        if last_state != Some(state) {
+            last_state = Some(state);
+            let _ = tx.send(BillEvent::State(state));
+        }
+        if !bill_in_transit && let Some(done) = shutting_down.take() {
+            let _ = done.send(());
+            return Ok(DriverExit::Stopped);
+        }
+
+        clock::sleep(config.serial.poll_interval());
+    }
+
+    Ok(DriverExit::Stopped)
+}
+
+/// Compares the bill types the device reports as enabled with what we last
+/// told it. After some faults it silently falls back to another mask (all
+/// disabled, typically) and would otherwise reject everything until the next
+/// reset; this puts it back. Changes in the reported state are audited.
+fn check_bill_health(
+    cashcode: &mut dyn BillAcceptor,
+    db_path: &str,
+    last_seen: &mut Option<DeviceStatus>,
+) {
+    let status = match cashcode.get_status() {
+        Ok(Some(status)) => status,
+        Ok(None) => return,
+        Err(e) => {
+            warn!("🩺 Bill acceptor GET STATUS failed: {}", e);
+            return;
+        }
+    };
+    let hex = |mask: [u8; 3]| format!("{:02X}{:02X}{:02X}", mask[0], mask[1], mask[2]);
+
+    if *last_seen != Some(status) {
+        info!(
+            "🩺 Bill acceptor reports enabled {}, security {}",
+            hex(status.enabled),
+            hex(status.security)
+        );
+        audit::record(
+            db_path,
+            "bill_status",
+            &format!(
+                "enabled {} security {}",
+                hex(status.enabled),
+                hex(status.security)
+            ),
+        );
+        *last_seen = Some(status);
     }
 
-    Ok(())
+    let expected = cashcode.expected_mask();
+    if status.enabled != expected {
+        warn!(
+            "🩺 Bill acceptor reverted its bill types: expected {}, device has {}; reapplying",
+            hex(expected),
+            hex(status.enabled)
+        );
+        audit::record(
+            db_path,
+            "bill_mask_reverted",
+            &format!("expected {} device {}", hex(expected), hex(status.enabled)),
+        );
+        if let Err(e) = cashcode.reapply_mask() {
+            error!("Failed to reapply bill types: {}", e);
+        }
+    }
+}
+
+/// Puts the driver back in line with the UI when they drifted apart, e.g.
+/// after an ACK went missing or a disable failed. Left alone in test-bill
+/// mode and while a bill is on its way in; whether the device itself has
+/// what the driver believes is `check_bill_health`'s job.
+fn reconcile_enable(cashcode: &mut dyn BillAcceptor, wanted: bool, db_path: &str) {
+    let enabled = cashcode.is_enabled();
+    if enabled == wanted || cashcode.in_test_mode() || !cashcode.is_idle() {
+        return;
+    }
+    let state = |on: bool| if on { "enabled" } else { "disabled" };
+    warn!(
+        "🩺 Bill acceptor is {} but the kiosk expects it {}; resending",
+        state(enabled),
+        state(wanted)
+    );
+    audit::record(
+        db_path,
+        "bill_enable_drift",
+        &format!("driver {} expected {}", state(enabled), state(wanted)),
+    );
+    let result = if wanted {
+        cashcode.enable()
+    } else {
+        cashcode.disable()
+    };
+    if let Err(e) = result {
+        error!("Failed to resend bill enable state: {}", e);
+    }
 }
 
 mod coin_acceptor {
@@ -340,32 +1644,54 @@ mod coin_acceptor {
         let (event_tx, event_rx) = channel::<CoinAcceptorEvent>();
         let (cmd_tx, cmd_rx) = channel::<CoinAcceptorCommand>();
 
-        thread::spawn({
+        if config.coin_pulse.serial_port.is_some() {
+            let settings = config.coin_pulse.clone();
+            thread::spawn(move || coin_pulse::run(settings, event_tx, cmd_rx));
+        } else {
             let serial_port = config.cctalk_serial_port.clone();
             let coin_overrides = config.cctalk_coin_overrides.clone();
-            move || cctalk::run(serial_port, event_tx, cmd_rx, coin_overrides)
-        });
+            thread::spawn(move || cctalk::run(serial_port, event_tx, cmd_rx, coin_overrides));
+        }
 
         // Override start/stop callbacks to drive both bill and coin acceptors.
         let cmd_tx_start = cmd_tx.clone();
         let cashcode_tx_start = cashcode_tx.clone();
+        let weak_start = app.as_weak();
+        let stats_db_path = config.stats_db_path.clone();
+        // Coins can't be handed back, so a dry run takes bills only
+        let coins = !config.dry_run;
+        if !coins {
+            info!("🧪 Dry run, the coin acceptor stays off");
+        }
         app.on_start_accepting_money(move || {
             info!("📥 UI: Start accepting money (bills + coins)");
-            if cashcode_tx_start
-                .send(bill_acceptor::CashCodeCommand::Enable)
-                .is_err()
-            {
-                error!("Failed to send enable command to CashCode");
+            let Some(w) = weak_start.upgrade() else {
+                return;
+            };
+            if w.get_acceptance_locked() {
+                warn!("📥 Money acceptance is locked, not enabling acceptors");
+                w.invoke_acceptors_enabled(false);
+                return;
             }
-            if cmd_tx_start.send(CoinAcceptorCommand::Enable).is_err() {
-                error!("Failed to send enable command to ccTalk coin acceptor");
+            // The bill driver opens a new ledger session on Enable
+            w.set_session_bill_amount(0);
+            session_breakdown::reset(&w);
+            // Coins aren't waited for: the coin acceptor is optional and
+            // picks the enable up whenever it (re)connects
+            if coins && cmd_tx_start.send(CoinAcceptorCommand::Enable).is_err() {
+                error!("Failed to send enable command to the coin acceptor");
             }
+            bill_acceptor::enable(&w, &cashcode_tx_start, &stats_db_path);
         });
 
         let cmd_tx_stop = cmd_tx.clone();
         let cashcode_tx_stop = cashcode_tx;
+        let weak_stop = app.as_weak();
         app.on_stop_accepting_money(move || {
             info!("📤 UI: Stop accepting money (bills + coins)");
+            if let Some(w) = weak_stop.upgrade() {
+                w.set_accepting_money(false);
+            }
             if cashcode_tx_stop
                 .send(bill_acceptor::CashCodeCommand::Disable)
                 .is_err()
@@ -373,11 +1699,12 @@ mod coin_acceptor {
                 error!("Failed to send disable command to CashCode");
             }
             if cmd_tx_stop.send(CoinAcceptorCommand::Disable).is_err() {
-                error!("Failed to send disable command to ccTalk coin acceptor");
+                error!("Failed to send disable command to the coin acceptor");
             }
         });
 
         // Poll for coin events on the slint timer and add to session amount.
+        let stats_db_path = config.stats_db_path.clone();
         let timer = Timer::default();
         timer.start(
             TimerMode::Repeated,
@@ -387,16 +1714,35 @@ mod coin_acceptor {
                     while let Ok(event) = event_rx.try_recv() {
                         match event {
                             CoinAcceptorEvent::Accepted(value) => {
-                                info!("🪙 Coin accepted in UI: {} AMD", value);
+                                info!(
+                                    "🪙 Coin accepted in UI: {} {currency}",
+                                    value,
+                                    currency = money::currency()
+                                );
+                                transactions::record(
+                                    &stats_db_path,
+                                    &Transaction::CoinAccepted { value },
+                                );
                                 let current = window.get_session_amount();
                                 window.set_session_amount(current + value);
+                                session_breakdown::add(&window, value, true);
                                 window.set_last_added_amount(value);
+                                announce_handler::say(
+                                    &window,
+                                    &i18n::tr(
+                                        "a11y.counted",
+                                        &[
+                                            &money::format(value.into()),
+                                            &money::format(window.get_session_amount().into()),
+                                        ],
+                                    ),
+                                );
                             }
                             CoinAcceptorEvent::Error(msg) => {
-                                error!("⚠️ {}", msg);
+                                error_code::report(&stats_db_path, ErrorCode::CoinAcceptor, &msg);
                                 window.set_diag_coin_status(LogEntry {
                                     level: 2,
-                                    text: msg.into(),
+                                    text: format!("{} {}", ErrorCode::CoinAcceptor, msg).into(),
                                 });
                             }
                             CoinAcceptorEvent::Status(text, level) => {
@@ -416,6 +1762,192 @@ mod coin_acceptor {
     }
 }
 
+mod navigation_handler {
+    use super::*;
+    use crate::router::{Navigation, Router};
+    use std::cell::Cell;
+
+    /// A move to an insert page waiting for the bill driver to confirm the
+    /// acceptor is on. The router already points at `to`; the window still
+    /// shows `from`.
+    type Pending = Rc<Cell<Option<(Page, Page)>>>;
+
+    /// Connects the UI's navigation callbacks to the `Router` and runs the
+    /// enter/leave hooks, so acceptors, timers and the HASS browser follow
+    /// the page instead of every button remembering to switch them.
+    pub fn init(app: &MainWindow) {
+        let router = Rc::new(RefCell::new(Router::new(app.get_current_page())));
+        let pending: Pending = Rc::new(Cell::new(None));
+
+        app.on_navigate({
+            let router = router.clone();
+            let pending = pending.clone();
+            let weak = app.as_weak();
+            move |page| {
+                if let Some(w) = weak.upgrade() {
+                    // double taps while the acceptor is starting
+                    if pending.get().is_some() {
+                        return;
+                    }
+                    let nav = router.borrow_mut().navigate(page, w.get_accepting_money());
+                    apply(&w, &pending, nav);
+                }
+            }
+        });
+
+        app.on_force_navigate({
+            let router = router.clone();
+            let pending = pending.clone();
+            let weak = app.as_weak();
+            move |page| {
+                if let Some(w) = weak.upgrade() {
+                    // The page never changed; put the router back first
+                    if let Some((from, _)) = pending.take() {
+                        router.borrow_mut().force(from);
+                    }
+                    let nav = router.borrow_mut().force(page);
+                    apply(&w, &pending, nav);
+                }
+            }
+        });
+
+        app.on_back({
+            let router = router.clone();
+            let pending = pending.clone();
+            let weak = app.as_weak();
+            move || {
+                if let Some(w) = weak.upgrade() {
+                    if pending.get().is_some() {
+                        return;
+                    }
+                    let nav = router.borrow_mut().back(w.get_accepting_money());
+                    apply(&w, &pending, nav);
+                }
+            }
+        });
+
+        app.on_confirm_navigation({
+            let router = router.clone();
+            let pending = pending.clone();
+            let weak = app.as_weak();
+            move || {
+                if let Some(w) = weak.upgrade() {
+                    let nav = router.borrow_mut().confirm();
+                    apply(&w, &pending, nav);
+                }
+            }
+        });
+
+        app.on_acceptors_enabled({
+            let router = router.clone();
+            let weak = app.as_weak();
+            move |ok| {
+                let Some(w) = weak.upgrade() else {
+                    return;
+                };
+                // Home Assistant may have paused acceptance while starting
+                let ok = ok && !w.get_acceptance_locked();
+                match pending.take() {
+                    Some((from, to)) if ok => {
+                        info!("🧭 {:?} → {:?}", from, to);
+                        leave(&w, from);
+                        w.set_current_page(to);
+                        enter(&w, to);
+                        return;
+                    }
+                    Some((from, to)) => {
+                        warn!(
+                            "🧭 Staying on {:?}, acceptors for {:?} did not start",
+                            from, to
+                        );
+                        router.borrow_mut().force(from);
+                    }
+                    // The move was overridden meanwhile
+                    None => {}
+                }
+                if w.get_accepting_money() {
+                    w.invoke_stop_accepting_money();
+                }
+            }
+        });
+
+        let weak = app.as_weak();
+        app.on_cancel_navigation(move || {
+            router.borrow_mut().cancel();
+            if let Some(w) = weak.upgrade() {
+                w.set_nav_confirm_open(false);
+            }
+        });
+    }
+
+    fn apply(window: &MainWindow, pending: &Pending, nav: Navigation) {
+        match nav {
+            // Money pages only show once the acceptor is confirmed on,
+            // see `acceptors-enabled`
+            Navigation::Go { from, to } if matches!(to, Page::InsertMoney | Page::InsertCoins) => {
+                info!("🧭 {:?} → {:?}, waiting for the acceptors", from, to);
+                window.set_nav_confirm_open(false);
+                pending.set(Some((from, to)));
+                window.invoke_start_accepting_money();
+            }
+            Navigation::Go { from, to } => {
+                info!("🧭 {:?} → {:?}", from, to);
+                window.set_nav_confirm_open(false);
+                leave(window, from);
+                window.set_current_page(to);
+                enter(window, to);
+            }
+            Navigation::Confirm(to) => {
+                info!("🧭 Leaving for {:?} needs confirmation", to);
+                window.set_nav_confirm_open(true);
+            }
+            Navigation::Stay => {}
+        }
+    }
+
+    /// Runs once the page is showing; for the insert pages that is after
+    /// the acceptors were enabled.
+    fn enter(window: &MainWindow, page: Page) {
+        match page {
+            Page::InsertMoney => window.invoke_enter_insert_money(), // start inactivity timer
+            Page::HomeAssistant => window.invoke_show_home_assistant(),
+            _ => {}
+        }
+    }
+
+    fn leave(window: &MainWindow, page: Page) {
+        match page {
+            Page::InsertMoney | Page::InsertCoins => {
+                if page == Page::InsertMoney {
+                    window.invoke_leave_insert_money(); // stop inactivity timers
+                }
+                if window.get_accepting_money() {
+                    window.invoke_stop_accepting_money();
+                }
+                // Money still on the page was never committed: it goes to the
+                // fund picked for it, or the fallback fund, rather than vanishing
+                let amount = window.get_session_amount();
+                if amount > 0 {
+                    window.invoke_session_abandoned(amount);
+                }
+                window.set_session_amount(0);
+                window.set_session_intent(0);
+                window.invoke_intent_progress();
+                window.set_session_username(slint::SharedString::default());
+                window.set_session_fund_id(0);
+            }
+            Page::HomeAssistant => window.invoke_hide_home_assistant(),
+            // the PIN is asked again on every visit
+            Page::Admin => window.set_admin_unlocked(false),
+            // never leave the acceptor in test-bill mode unattended
+            Page::Diagnostics if window.get_diag_test_bills() => {
+                window.invoke_diag_toggle_test_bills();
+            }
+            _ => {}
+        }
+    }
+}
+
 mod virtual_keyboard {
     use super::*;
     use slint::platform::Key;
@@ -445,33 +1977,107 @@ mod virtual_keyboard {
     }
 }
 
-mod autocomplete_handler {
+mod member_status_handler {
     use super::*;
+    use std::cell::Cell;
 
-    pub fn init(app: &MainWindow) {
-        app.global::<AutocompleteHandler>()
-            .on_find_suggestion(|input, suggestions| {
-                if input.is_empty() {
-                    return slint::SharedString::default();
-                }
-
-                let input_lower = input.to_lowercase();
+    /// Looks up the member picked on Donate and shows their dues and
+    /// sponsorship under the username. Off unless `features.member_status`.
+    pub fn init(app: &MainWindow, config: &Config) {
+        let (true, Some(token)) = (config.features.member_status, config.token.clone()) else {
+            return;
+        };
+        // Bumped for every pick, so a slow answer for an earlier name is dropped
+        let generation = Rc::new(Cell::new(0u64));
+        let weak = app.as_weak();
+        app.on_member_chosen(move |username| {
+            let Some(window) = weak.upgrade() else {
+                return;
+            };
+            generation.set(generation.get() + 1);
+            window.set_member_status("".into());
+            window.set_member_status_level(0);
+            if username.is_empty() {
+                return;
+            }
 
-                // Find the first suggestion that starts with the input (case-insensitive)
-                for suggestion in suggestions.iter() {
-                    let suggestion_lower = suggestion.to_lowercase();
-                    if suggestion_lower.starts_with(&input_lower) && suggestion_lower != input_lower
-                    {
-                        return suggestion;
+            let asked = generation.get();
+            let generation = generation.clone();
+            let token = token.clone();
+            let weak = weak.clone();
+            slint::spawn_local(async move {
+                let status = match member::fetch_status(&token, &username).await {
+                    Ok(status) => status,
+                    Err(e) => {
+                        warn!("⚠️  Failed to fetch member status for {}: {}", username, e);
+                        None
                     }
+                };
+                let Some(window) = weak.upgrade() else {
+                    return;
+                };
+                if generation.get() != asked {
+                    return;
                 }
+                let Some(status) = status else {
+                    return;
+                };
+                let (text, level) = describe(&status);
+                window.set_member_status(text.into());
+                window.set_member_status_level(level);
+            })
+            .unwrap();
+        });
+    }
+
+    /// The line under the username and its level: 1 when the dues are
+    /// settled, 2 while some are outstanding.
+    fn describe(status: &member::MemberStatus) -> (String, i32) {
+        let mut parts = Vec::new();
+        let mut level = 0;
+        if let Some(dues) = &status.dues {
+            if dues.outstanding() > 0 {
+                parts.push(i18n::tr(
+                    "member.dues_outstanding",
+                    &[&money::format(dues.outstanding().into())],
+                ));
+                level = 2;
+            } else {
+                parts.push(i18n::tr("member.dues_paid", &[]));
+                level = 1;
+            }
+        }
+        if let Some(tier) = &status.tier {
+            parts.push(i18n::tr("member.tier", &[tier]));
+        }
+        (parts.join(" · "), level)
+    }
+}
+
+mod autocomplete_handler {
+    use super::*;
+    use slint::{ModelRc, SharedString, VecModel};
 
-                slint::SharedString::default()
+    /// Most names offered under the username field at once.
+    const MAX_SUGGESTIONS: usize = 5;
+
+    pub fn init(app: &MainWindow) {
+        app.global::<AutocompleteHandler>()
+            .on_find_suggestions(|input, suggestions| {
+                let names: Vec<SharedString> = suggestions.iter().map(|s| s.name).collect();
+                let names: Vec<&str> = names.iter().map(SharedString::as_str).collect();
+                let found: Vec<SharedString> = usernames::matches(&input, &names, MAX_SUGGESTIONS)
+                    .into_iter()
+                    .map(|i| names[i].into())
+                    .collect();
+                ModelRc::new(VecModel::from(found))
             });
 
         app.global::<AutocompleteHandler>()
             .on_get_suggestion_suffix(|typed, suggestion| {
-                if suggestion.is_empty() {
+                // Only a suggestion that goes on from what was typed can be
+                // shown after it; the others are listed under the field
+                if !suggestion.to_lowercase().starts_with(&typed.to_lowercase()) {
                     return slint::SharedString::default();
                 }
 
@@ -492,106 +2098,141 @@ mod autocomplete_handler {
                 let input_lower = input.to_lowercase();
 
                 // Check if input exactly matches any suggestion (case-insensitive)
-                suggestions.iter().any(|s| s.to_lowercase() == input_lower)
+                suggestions
+                    .iter()
+                    .any(|s| s.name.to_lowercase() == input_lower)
+            });
+
+        app.global::<AutocompleteHandler>()
+            .on_tier_of(|name, suggestions| {
+                let name_lower = name.to_lowercase();
+                suggestions
+                    .iter()
+                    .find(|s| !name.is_empty() && s.name.to_lowercase() == name_lower)
+                    .map(|s| s.tier)
+                    .unwrap_or_default()
             });
     }
 }
 
-mod fund_fetcher {
+mod voucher_handler {
     use super::*;
-    use crate::funds;
-    use slint::*;
+    use crate::voucher::{self, Redeemed};
 
+    /// Checks and redeems paper vouchers from the Voucher page, for the
+    /// fund and name picked on Donate (`features.vouchers`).
     pub fn init(app: &MainWindow, config: &Config) {
-        let app_handle = app.clone_strong();
-
-        let Some(ref token) = config.token else {
-            warn!("⚠️  No token loaded, donation functions unavailable");
-            app_handle.set_available_funds(slint::ModelRc::new(slint::VecModel::<
-                slint::SharedString,
-            >::default()));
-            app_handle
-                .set_available_fund_ids(slint::ModelRc::new(slint::VecModel::<i32>::default()));
-
+        if !config.features.vouchers {
+            return;
+        }
+        if config.dry_run {
+            info!("🧪 Dry run, vouchers off");
+            return;
+        }
+        let Some(token) = config.token.clone() else {
+            warn!("🎟 features.vouchers needs a gateway token, vouchers off");
             return;
         };
+        app.set_feature_vouchers(true);
 
-        let token = token.clone();
-        let token_usernames = token.clone();
-        app.on_fetch_funds(move || {
-            info!("🔍 Fetching funds from API...");
-            let app = app_handle.clone_strong();
-            let token = token.clone();
-
+        let weak = app.as_weak();
+        let check_token = token.clone();
+        app.on_voucher_check(move |code| {
+            let code = voucher::normalize(&code);
+            let Some(window) = weak.upgrade() else {
+                return;
+            };
+            if code.is_empty() {
+                return;
+            }
+            window.set_voucher_busy(true);
+            window.set_voucher_amount(0);
+            window.set_voucher_status(slint::SharedString::default());
+            let token = check_token.clone();
+            let weak = weak.clone();
             slint::spawn_local(async move {
-                match funds::fetch_funds(&token).await {
-                    Ok(value) => {
-                        info!("✅ Fetched {} funds", value.len());
-
-                        // Convert funds to string array for ComboBox
-                        let model_data: Vec<slint::SharedString> = value
-                            .iter()
-                            .map(|fund| {
-                                slint::SharedString::from(std::format!(
-                                    "{} (ID: {})",
-                                    fund.name,
-                                    fund.id
-                                ))
-                            })
-                            .collect();
-
-                        // Also store fund IDs separately for lookup
-                        let fund_ids: Vec<i32> = value.iter().map(|f| f.id).collect();
-
-                        // Set the properties on MainWindow
-                        app.set_available_funds(slint::ModelRc::new(slint::VecModel::from(
-                            model_data,
-                        )));
-                        app.set_available_fund_ids(slint::ModelRc::new(slint::VecModel::from(
-                            fund_ids,
-                        )));
+                let result = voucher::check(&token, &code).await;
+                let Some(window) = weak.upgrade() else {
+                    return;
+                };
+                window.set_voucher_busy(false);
+                let (level, status, amount) = match result {
+                    Ok(Some(found)) if found.used => (2, i18n::tr("voucher.used", &[]), 0),
+                    Ok(Some(found)) if found.amount > 0 => {
+                        (1, i18n::tr("voucher.valid", &[]), found.amount)
                     }
+                    Ok(_) => (2, i18n::tr("voucher.unknown", &[]), 0),
                     Err(e) => {
-                        error!("❌ Failed to fetch funds: {}", e);
-                        app.set_available_funds(slint::ModelRc::new(slint::VecModel::<
-                            slint::SharedString,
-                        >::default(
-                        )));
-                        app.set_available_fund_ids(slint::ModelRc::new(
-                            slint::VecModel::<i32>::default(),
-                        ));
+                        warn!("🎟 Voucher check failed: {}", e);
+                        (3, e.message().text(), 0)
                     }
-                }
+                };
+                window.set_voucher_status_level(level);
+                window.set_voucher_status(status.into());
+                window.set_voucher_amount(amount);
             })
             .unwrap();
         });
 
-        let app_handle = app.clone_strong();
-        app.on_fetch_usernames(move || {
-            info!("🔍 Fetching usernames from API...");
-            let app = app_handle.clone_strong();
-            let token = token_usernames.clone();
-
+        let weak = app.as_weak();
+        let stats_db_path = config.stats_db_path.clone();
+        app.on_voucher_redeem(move |code| {
+            let code = voucher::normalize(&code);
+            let Some(window) = weak.upgrade() else {
+                return;
+            };
+            let amount = window.get_voucher_amount();
+            let fund_id = window.get_session_fund_id();
+            let fund_name = window.get_session_fund_name().to_string();
+            let username = window.get_session_username().to_string();
+            window.set_voucher_busy(true);
+            let token = token.clone();
+            let stats_db_path = stats_db_path.clone();
+            let weak = weak.clone();
             slint::spawn_local(async move {
-                match donation::fetch_usernames(&token).await {
-                    Ok(value) => {
-                        info!("✅ Fetched {} usernames", value.len());
-
-                        // Convert usernames to string array for the input autocomplete
-                        let model_data: Vec<slint::SharedString> = value
-                            .iter()
-                            .map(|username| slint::SharedString::from(username.to_string()))
-                            .collect();
-
-                        // Set the properties on MainWindow
-                        app.set_usernames(slint::ModelRc::new(slint::VecModel::from(model_data)));
+                let result = voucher::redeem(&token, &code, fund_id, &username).await;
+                let Some(window) = weak.upgrade() else {
+                    return;
+                };
+                window.set_voucher_busy(false);
+                match result {
+                    Ok(Redeemed::Done) => {
+                        info!(
+                            "🎟 Voucher {} redeemed: {} {currency}",
+                            code,
+                            amount,
+                            currency = money::currency()
+                        );
+                        audit::record(
+                            &stats_db_path,
+                            "voucher_redeemed",
+                            &format!(
+                                "{}: {} {currency} to {} ({}) from {}",
+                                code,
+                                amount,
+                                fund_name,
+                                fund_id,
+                                username,
+                                currency = money::currency()
+                            ),
+                        );
+                        notification_handler::push(
+                            &window,
+                            1,
+                            &i18n::tr("voucher.redeemed", &[&money::format(amount.into())]),
+                        );
+                        window.invoke_show_confetti_after_auto_approve();
+                    }
+                    Ok(Redeemed::AlreadyUsed) => {
+                        warn!("🎟 Voucher {} was already redeemed", code);
+                        window.set_voucher_amount(0);
+                        window.set_voucher_status_level(2);
+                        window.set_voucher_status(i18n::tr("voucher.used", &[]).into());
                     }
                     Err(e) => {
-                        error!("❌ Failed to fetch usernames: {}", e);
-                        app.set_available_funds(slint::ModelRc::new(slint::VecModel::<
-                            slint::SharedString,
-                        >::default(
-                        )));
+                        warn!("🎟 Voucher redemption failed: {}", e);
+                        window.set_voucher_status_level(3);
+                        window.set_voucher_status(e.message().text().into());
                     }
                 }
             })
@@ -600,578 +2241,5285 @@ mod fund_fetcher {
     }
 }
 
-mod donation_handler {
+mod qr_payment_handler {
     use super::*;
+    use crate::qr::{self, Payment, Status};
+    use slint::{ModelRc, VecModel};
+    use std::time::Instant;
 
-    const INACTIVITY_TIMEOUT: Duration = Duration::from_mins(2); // 2 minutes
+    /// How often the gateway is asked whether the shown QR has been paid.
+    const POLL_INTERVAL: Duration = Duration::from_secs(3);
+    /// Pixels per QR module.
+    const QR_SCALE: usize = 8;
 
-    /// Spawns a single-shot inactivity timer. Returns the Timer (must be kept alive).
-    fn spawn_inactivity_timer(
-        weak: slint::Weak<MainWindow>,
-        cashcode_tx: Sender<bill_acceptor::CashCodeCommand>,
-        token: Option<String>,
-        photos_dir: String,
-        stats_db_path: String,
-    ) -> slint::Timer {
-        let timer = slint::Timer::default();
-        timer.start(
-            slint::TimerMode::SingleShot,
-            INACTIVITY_TIMEOUT,
-            move || {
-                if let Some(window) = weak.upgrade() {
-                    // Guard: only act if we're still on the InsertMoney page
-                    if !window.get_on_insert_money_page() {
-                        info!(
-                            "⏱️  Inactivity timeout fired but not on InsertMoney page — ignoring"
-                        );
-                        return;
-                    }
-                    let amount = window.get_session_amount();
-                    if amount == 0 {
-                        // No money inserted — auto-cancel
-                        info!("⏱️  Inactivity timeout: auto-cancelling (no money inserted)");
-                        if cashcode_tx
-                            .send(bill_acceptor::CashCodeCommand::Disable)
-                            .is_err()
-                        {
-                            error!("Failed to send disable command on inactivity cancel");
-                        }
-                        window.set_session_amount(0);
-                        window.set_session_username(slint::SharedString::default());
-                        window.invoke_cancel_insert_money();
-                    } else {
-                        // Money inserted — auto-approve
-                        info!("⏱️  Inactivity timeout: auto-approving {} AMD", amount);
-                        if cashcode_tx
-                            .send(bill_acceptor::CashCodeCommand::Disable)
-                            .is_err()
-                        {
-                            error!("Failed to send disable command on inactivity approve");
-                        }
-                        if let Some(ref tok) = token {
-                            let username = window.get_session_username().to_string();
-                            let fund_id = window.get_session_fund_id();
-                            let fund_name = window.get_session_fund_name().to_string();
-                            let tok = tok.clone();
-                            let photos_dir = photos_dir.clone();
-                            let stats_db_path = stats_db_path.clone();
-                            slint::spawn_local(async move {
-                                match donation::send_donation(&tok, fund_id, &username, amount)
-                                    .await
-                                {
-                                    Ok(_) => {
-                                        sound::play_yippee();
-                                        info!("✅ Auto-approved donation sent successfully!");
-                                        let timestamp = donation_log::now_timestamp();
-                                        if username != "anon" {
-                                            camera::capture_donation_photo(
-                                                &photos_dir,
-                                                &username,
-                                                timestamp,
-                                            );
-                                        }
-                                        donation_log::record(
-                                            &stats_db_path,
-                                            timestamp,
-                                            &username,
-                                            amount,
-                                            &fund_name,
-                                        );
-                                    }
-                                    Err(e) => {
-                                        error!("❌ Auto-approve: failed to send donation: {}", e)
-                                    }
-                                }
-                            })
-                            .unwrap();
-                        } else {
-                            warn!("⚠️  No token — auto-approved donation not sent to server");
-                        }
-                        window.set_session_amount(0);
-                        window.set_session_username(slint::SharedString::default());
-                        window.set_session_fund_id(0);
-                        window.invoke_show_confetti_after_auto_approve();
-                    }
-                }
-            },
-        );
-        timer
+    /// The QR on screen.
+    struct Pending {
+        payment: Payment,
+        amount: i32,
+        started: Instant,
+        /// A status request is on its way.
+        checking: bool,
     }
 
-    pub fn init(
-        app: &MainWindow,
-        config: &Config,
-        cashcode_tx: Sender<bill_acceptor::CashCodeCommand>,
-        cctalk_tx: Sender<cctalk::CoinAcceptorCommand>,
-    ) {
-        // Shared timer slots — replaced on each entry to InsertMoney page or bill insertion
-        // Using Rc<RefCell<>> because all callbacks run on the single Slint event-loop thread.
-        let inactivity_timer: Rc<RefCell<Option<slint::Timer>>> = Rc::new(RefCell::new(None));
-        let countdown_ticker: Rc<RefCell<Option<slint::Timer>>> = Rc::new(RefCell::new(None));
+    /// Shows a QR code for the amount picked on the PayQr page and polls
+    /// the gateway until it's paid, then hands the donation to
+    /// `donation_handler` through `qr-paid` (`qr_payment.enabled`).
+    pub fn init(app: &MainWindow, config: &Config) {
+        let settings = config.qr_payment.clone();
+        if !settings.enabled {
+            return;
+        }
+        if config.dry_run {
+            info!("🧪 Dry run, paying by QR off");
+            return;
+        }
+        let Some(token) = config.token.clone() else {
+            warn!("📱 qr_payment needs a gateway token, paying by QR off");
+            return;
+        };
+        if settings.amounts.is_empty() {
+            warn!("📱 qr_payment.amounts is empty, paying by QR off");
+            return;
+        }
+        app.set_feature_qr_payment(true);
+        app.set_qr_amounts(ModelRc::new(VecModel::from(settings.amounts.clone())));
+        let timeout = Duration::from_secs(settings.timeout_secs);
+        let pending: Rc<RefCell<Option<Pending>>> = Rc::new(RefCell::new(None));
 
-        app.on_done_clicked({
-            let cashcode_tx = cashcode_tx.clone();
-            let cctalk_tx = cctalk_tx.clone();
-            let token = config.token.clone();
-            let photos_dir = config.photos_dir.clone();
-            let stats_db_path = config.stats_db_path.clone();
-            let weak = app.as_weak();
-            move |username, fund_id, amount| {
+        let weak = app.as_weak();
+        let start_token = token.clone();
+        let start_pending = pending.clone();
+        app.on_qr_start(move |amount| {
+            let Some(window) = weak.upgrade() else {
+                return;
+            };
+            let fund_id = window.get_session_fund_id();
+            let username = window.get_session_username().to_string();
+            window.set_qr_busy(true);
+            window.set_qr_status(slint::SharedString::default());
+            let link = settings.link.clone();
+            let token = start_token.clone();
+            let pending = start_pending.clone();
+            let weak = weak.clone();
+            slint::spawn_local(async move {
+                let payment = match &link {
+                    Some(template) => Ok(qr::from_link(template, amount, fund_id)),
+                    None => qr::invoice(&token, amount, fund_id, &username).await,
+                };
+                let Some(window) = weak.upgrade() else {
+                    return;
+                };
+                window.set_qr_busy(false);
+                let payment = match payment {
+                    Ok(payment) => payment,
+                    Err(e) => {
+                        warn!("📱 Couldn't create a QR payment: {}", e);
+                        window.set_qr_status_level(3);
+                        window.set_qr_status(e.message().text().into());
+                        return;
+                    }
+                };
+                let (side, pixels) = match qr::render(&payment.url, QR_SCALE) {
+                    Ok(rendered) => rendered,
+                    Err(e) => {
+                        warn!("📱 Payment link doesn't fit in a QR code: {}", e);
+                        window.set_qr_status_level(3);
+                        window.set_qr_status(i18n::tr("qr.too_long", &[]).into());
+                        return;
+                    }
+                };
+                let buffer = slint::SharedPixelBuffer::<slint::Rgb8Pixel>::clone_from_slice(
+                    &pixels, side, side,
+                );
+                window.set_qr_image(slint::Image::from_rgb8(buffer));
+                window.set_qr_seconds_left(timeout.as_secs() as i32);
+                window.set_qr_waiting(true);
                 info!(
-                    "💰 Processing donation: {} AMD from {} to fund {}",
-                    amount, username, fund_id
+                    "📱 Waiting for QR payment {}: {} {currency} to fund {}",
+                    payment.reference,
+                    amount,
+                    fund_id,
+                    currency = money::currency()
                 );
+                *pending.borrow_mut() = Some(Pending {
+                    payment,
+                    amount,
+                    started: clock::instant(),
+                    checking: false,
+                });
+            })
+            .unwrap();
+        });
 
-                // Stop accepting money immediately
-                if cashcode_tx
-                    .send(bill_acceptor::CashCodeCommand::Disable)
-                    .is_err()
-                {
-                    error!("Failed to send disable command to CashCode on done click");
-                }
-                if cctalk_tx
-                    .send(cctalk::CoinAcceptorCommand::Disable)
-                    .is_err()
-                {
-                    error!("Failed to send disable command to ccTalk coin acceptor on done click");
-                }
-                if let Some(ref token) = token {
-                    // Send donation asynchronously using slint::spawn_local
-                    let token = token.clone();
-                    let username_str = username.to_string();
-                    let photos_dir = photos_dir.clone();
-                    let stats_db_path = stats_db_path.clone();
-                    let fund_name = weak
-                        .upgrade()
-                        .map(|w| w.get_session_fund_name().to_string())
-                        .unwrap_or_default();
-                    slint::spawn_local(async move {
-                        match donation::send_donation(&token, fund_id, &username_str, amount).await
-                        {
-                            Ok(_) => {
-                                sound::play_yippee();
-                                info!("✅ Donation sent successfully!");
-                                let timestamp = donation_log::now_timestamp();
-                                if username_str != "anon" {
-                                    camera::capture_donation_photo(
-                                        &photos_dir,
-                                        &username_str,
-                                        timestamp,
-                                    );
-                                }
-                                donation_log::record(
-                                    &stats_db_path,
-                                    timestamp,
-                                    &username_str,
-                                    amount,
-                                    &fund_name,
-                                );
-                            }
-                            Err(e) => error!("❌ Failed to send donation: {}", e),
-                        }
-                    })
-                    .unwrap();
+        let weak = app.as_weak();
+        let stats_db_path = config.stats_db_path.clone();
+        let poll = slint::Timer::default();
+        poll.start(slint::TimerMode::Repeated, POLL_INTERVAL, move || {
+            let Some(window) = weak.upgrade() else {
+                return;
+            };
+            let mut slot = pending.borrow_mut();
+            let Some(current) = slot.as_mut() else {
+                return;
+            };
+            let elapsed = clock::since(current.started);
+            if !window.get_on_pay_qr_page() || elapsed >= timeout {
+                let reason = if window.get_on_pay_qr_page() {
+                    window.set_qr_status_level(2);
+                    window.set_qr_status(i18n::tr("qr.timeout", &[]).into());
+                    "timed out"
                 } else {
-                    warn!("⚠️  No token loaded, donation not sent to server");
-                }
+                    "left the page"
+                };
+                info!(
+                    "📱 QR payment {} abandoned: {}",
+                    current.payment.reference, reason
+                );
+                audit::record(
+                    &stats_db_path,
+                    "qr_payment_abandoned",
+                    &format!(
+                        "{}: {} {currency}, {}",
+                        current.payment.reference,
+                        current.amount,
+                        reason,
+                        currency = money::currency()
+                    ),
+                );
+                *slot = None;
+                window.set_qr_waiting(false);
+                return;
             }
-        });
-
-        // enter-insert-money: start 3-minute inactivity timer + countdown ticker
-        let weak_enter = app.as_weak();
-        let cashcode_tx_enter = cashcode_tx.clone();
-        let token_enter = config.token.clone();
-        let photos_dir_enter = config.photos_dir.clone();
-        let stats_db_path_enter = config.stats_db_path.clone();
-        let timer_enter = inactivity_timer.clone();
-        let ticker_enter = countdown_ticker.clone();
-        app.on_enter_insert_money(move || {
-            info!(
-                "⏱️  InsertMoney entered — starting {:?} inactivity timer",
-                INACTIVITY_TIMEOUT
-            );
-            // Reset the countdown display
-            if let Some(w) = weak_enter.upgrade() {
-                w.set_inactivity_seconds_left(INACTIVITY_TIMEOUT.as_secs() as _);
-            }
-            // Main timeout timer
-            let timer = spawn_inactivity_timer(
-                weak_enter.clone(),
-                cashcode_tx_enter.clone(),
-                token_enter.clone(),
-                photos_dir_enter.clone(),
-                stats_db_path_enter.clone(),
-            );
-            *timer_enter.borrow_mut() = Some(timer);
-            // Countdown ticker (1-second decrement)
-            let weak_tick = weak_enter.clone();
-            let ticker = slint::Timer::default();
-            ticker.start(
-                slint::TimerMode::Repeated,
-                Duration::from_secs(1),
-                move || {
-                    if let Some(w) = weak_tick.upgrade() {
-                        let current = w.get_inactivity_seconds_left();
-                        if current > 0 {
-                            w.set_inactivity_seconds_left(current - 1);
-                        }
+            window.set_qr_seconds_left(timeout.saturating_sub(elapsed).as_secs() as i32);
+            if current.checking {
+                return;
+            }
+            current.checking = true;
+            let reference = current.payment.reference.clone();
+            let token = token.clone();
+            let pending = pending.clone();
+            let weak = weak.clone();
+            slint::spawn_local(async move {
+                let result = qr::status(&token, &reference).await;
+                let Some(window) = weak.upgrade() else {
+                    return;
+                };
+                let mut slot = pending.borrow_mut();
+                // Abandoned (or replaced) while the request was out
+                let Some(current) = slot.as_mut().filter(|p| p.payment.reference == reference)
+                else {
+                    return;
+                };
+                current.checking = false;
+                match result {
+                    Ok(state) if state.status == Status::Paid => {
+                        let amount = state.amount.unwrap_or(current.amount);
+                        info!(
+                            "📱 QR payment {} received: {} {currency}",
+                            reference,
+                            amount,
+                            currency = money::currency()
+                        );
+                        *slot = None;
+                        drop(slot);
+                        window.set_qr_waiting(false);
+                        window.invoke_qr_paid(amount);
                     }
-                },
-            );
-            *ticker_enter.borrow_mut() = Some(ticker);
-        });
-
-        // activity-on-insert-money: reset both timers when a bill is inserted
-        let weak_activity = app.as_weak();
-        let cashcode_tx_activity = cashcode_tx.clone();
-        let token_activity = config.token.clone();
-        let photos_dir_activity = config.photos_dir.clone();
-        let stats_db_path_activity = config.stats_db_path.clone();
-        let timer_activity = inactivity_timer.clone();
-        let ticker_activity = countdown_ticker.clone();
-        app.on_activity_on_insert_money(move || {
-            info!("⏱️  Bill inserted — resetting inactivity timer");
-            // Reset countdown display
-            if let Some(w) = weak_activity.upgrade() {
-                w.set_inactivity_seconds_left(INACTIVITY_TIMEOUT.as_secs() as _);
-            }
-            // Replace main timeout timer
-            let timer = spawn_inactivity_timer(
-                weak_activity.clone(),
-                cashcode_tx_activity.clone(),
-                token_activity.clone(),
-                photos_dir_activity.clone(),
-                stats_db_path_activity.clone(),
-            );
-            *timer_activity.borrow_mut() = Some(timer);
-            // Replace countdown ticker
-            let weak_tick = weak_activity.clone();
-            let ticker = slint::Timer::default();
-            ticker.start(
-                slint::TimerMode::Repeated,
-                Duration::from_secs(1),
-                move || {
-                    if let Some(w) = weak_tick.upgrade() {
-                        let current = w.get_inactivity_seconds_left();
-                        if current > 0 {
-                            w.set_inactivity_seconds_left(current - 1);
-                        }
+                    Ok(state) if state.status == Status::Expired => {
+                        warn!("📱 QR payment {} expired", reference);
+                        *slot = None;
+                        window.set_qr_waiting(false);
+                        window.set_qr_status_level(2);
+                        window.set_qr_status(i18n::tr("qr.expired", &[]).into());
                     }
-                },
-            );
-            *ticker_activity.borrow_mut() = Some(ticker);
+                    Ok(_) => {}
+                    Err(e) => warn!("📱 QR payment status check failed: {}", e),
+                }
+            })
+            .unwrap();
         });
+        std::mem::forget(poll);
+    }
+}
 
-        // leave-insert-money: stop both timers when user exits normally (cancel or done)
-        let timer_leave = inactivity_timer.clone();
-        let ticker_leave = countdown_ticker.clone();
-        app.on_leave_insert_money(move || {
-            info!("⏱️  InsertMoney left — stopping inactivity timers");
-            *timer_leave.borrow_mut() = None; // drops Timer → cancels it
-            *ticker_leave.borrow_mut() = None; // drops Timer → cancels it
-        });
+mod printer_handler {
+    use super::*;
+    use crate::printer::{self, PrinterStatus};
+    use slint::{Timer, TimerMode};
+    use std::sync::mpsc::channel;
 
-        // Drive confetti animation from Rust with a two-step approach:
-        // 1. show-confetti is already set to true by the Slint side (overlay is created)
-        // 2. After a brief delay, set confetti-falling = true (triggers the animations)
-        // 3. After animation completes, reset both properties
-        let weak = app.as_weak();
-        app.on_confetti_started(move || {
-            crate::sound::play_yippee();
-            // Step 1: trigger falling after a short delay so the component is fully rendered
-            let weak_fall = weak.clone();
-            slint::Timer::single_shot(std::time::Duration::from_millis(50), move || {
-                if let Some(window) = weak_fall.upgrade() {
-                    window.set_confetti_falling(true);
-                }
-            });
+    /// Starts the print worker when a printer is configured and surfaces
+    /// its state as a sticky notification. Returns the sender used to wake
+    /// the worker after queueing a job.
+    pub fn init(app: &MainWindow, config: &Config) -> Option<Sender<()>> {
+        let device = config.printer_device.clone()?;
+        let (wake_tx, wake_rx) = channel::<()>();
+        let (status_tx, status_rx) = channel::<PrinterStatus>();
+        let db_path = config.stats_db_path.clone();
+        let dry_run = config.dry_run;
+        thread::spawn(move || printer::run(device, db_path, dry_run, wake_rx, status_tx));
 
-            // Step 2: dismiss everything after animations complete
-            let weak_dismiss = weak.clone();
-            slint::Timer::single_shot(std::time::Duration::from_millis(2500), move || {
-                if let Some(window) = weak_dismiss.upgrade() {
-                    window.set_confetti_falling(false);
-                    window.set_show_confetti(false);
+        let weak = app.as_weak();
+        let timer = Timer::default();
+        timer.start(TimerMode::Repeated, Duration::from_millis(500), move || {
+            let Some(window) = weak.upgrade() else {
+                return;
+            };
+            while let Ok(status) = status_rx.try_recv() {
+                match status {
+                    PrinterStatus::Ready => notification_handler::clear(&window, "printer"),
+                    PrinterStatus::Stalled { reason, pending } => notification_handler::set(
+                        &window,
+                        "printer",
+                        2,
+                        &i18n::tr("printer.stalled", &[&reason.text(), &pending]),
+                    ),
                 }
-            });
+            }
         });
+        std::mem::forget(timer);
 
-        // Warmup: run the animation once at startup (no sound) so all SVGs are
-        // rasterized and cached before the first real donation triggers it.
-        let weak_warmup = app.as_weak();
-        slint::Timer::single_shot(std::time::Duration::from_millis(500), move || {
-            if let Some(window) = weak_warmup.upgrade() {
-                info!("🎉 Warming up confetti cache...");
-                window.set_show_confetti(true);
-                window.set_confetti_falling(true);
-
-                let weak_done = weak_warmup.clone();
-                slint::Timer::single_shot(std::time::Duration::from_millis(1000), move || {
-                    if let Some(window) = weak_done.upgrade() {
-                        window.set_confetti_falling(false);
-                        window.set_show_confetti(false);
-                    }
-                });
+        app.set_diag_printer_enabled(true);
+        let stats_db_path = config.stats_db_path.clone();
+        let wake_reprint = wake_tx.clone();
+        app.on_diag_reprint_receipt(move || match printer::reprint_last(&stats_db_path) {
+            Ok(true) => {
+                info!("🖨️  Diagnostics: reprinting last receipt");
+                let _ = wake_reprint.send(());
             }
+            Ok(false) => info!("🖨️  Diagnostics: nothing to reprint"),
+            Err(e) => error_code::report(
+                &stats_db_path,
+                ErrorCode::Database,
+                &format!("Failed to queue reprint: {}", e),
+            ),
         });
+
+        Some(wake_tx)
     }
 }
 
-mod logs_handler {
+mod fund_fetcher {
+    use super::*;
+    use crate::cache::{Cache, Lookup};
+    use crate::donation::Username;
+    use crate::error::RequestError;
+    use crate::funds::{self, Offer};
+    use slint::*;
+    use std::cell::Cell;
+
+    /// How many of the latest donors here are put first in the suggestions.
+    const RECENT_USERNAMES: i64 = 50;
+    /// Funds and usernames fetched this recently aren't fetched again when
+    /// Donate opens; older ones are shown while a new fetch runs.
+    const CACHE_FRESH: Duration = Duration::from_secs(60);
+    /// Beyond this a cached list is too old to offer during an outage.
+    const CACHE_KEEP: Duration = Duration::from_secs(7 * 24 * 3600);
+    /// While a fetch is failing it is tried again this often, so the lists
+    /// are fresh soon after the gateway is back rather than on the next
+    /// Donate.
+    const OFFLINE_RETRY: Duration = Duration::from_secs(60);
+
+    pub fn init(app: &MainWindow, config: &Config, campaigns: Rc<RefCell<Vec<Campaign>>>) {
+        let app_handle = app.clone_strong();
+
+        // Funds listed under [backend] stand in for the gateway's
+        let static_funds = !config.backend.funds.is_empty();
+        if static_funds {
+            let funds = config.backend.funds.clone();
+            let configured_campaigns = config.campaigns.clone();
+            let campaigns = campaigns.clone();
+            app.on_fetch_funds(move || {
+                info!("📋 Offering {} funds from config", funds.len());
+                boot_checklist::set(
+                    &app_handle,
+                    boot_checklist::Check::Gateway,
+                    1,
+                    &std::format!("{} funds from config", funds.len()),
+                );
+                let offered = open_campaigns(configured_campaigns.clone(), &funds);
+                show_funds(&app_handle, &campaigns, offered, &funds);
+            });
+        }
+        let app_handle = app.clone_strong();
+
+        let Some(ref token) = config.token else {
+            if static_funds {
+                return;
+            }
+            warn!("⚠️  No token loaded, donation functions unavailable");
+            app_handle.set_funds_error_code(ErrorCode::ApiNoToken.as_str().into());
+            boot_checklist::set(
+                app,
+                boot_checklist::Check::Gateway,
+                3,
+                &std::format!("{} No token configured", ErrorCode::ApiNoToken),
+            );
+            update_funds(&app_handle, Vec::new(), Vec::new(), Vec::new());
+
+            return;
+        };
+
+        let token = token.clone();
+        let token_usernames = token.clone();
+        let configured_campaigns = config.campaigns.clone();
+        let funds_db_path = config.stats_db_path.clone();
+        // The last fetch of each failed, see `OFFLINE_RETRY`
+        let funds_failed = Rc::new(Cell::new(false));
+        let usernames_failed = Rc::new(Cell::new(false));
+        let retry = Timer::default();
+        retry.start(TimerMode::Repeated, OFFLINE_RETRY, {
+            let weak = app.as_weak();
+            let funds_failed = funds_failed.clone();
+            let usernames_failed = usernames_failed.clone();
+            move || {
+                let Some(window) = weak.upgrade() else {
+                    return;
+                };
+                if funds_failed.get() {
+                    window.invoke_fetch_funds();
+                }
+                if usernames_failed.get() {
+                    window.invoke_fetch_usernames();
+                }
+            }
+        });
+        std::mem::forget(retry);
+        if !static_funds {
+            let cache = Rc::new(RefCell::new(
+                Cache::<Offer>::new(CACHE_FRESH, CACHE_KEEP)
+                    .persisted(&config.stats_db_path, &kv::FUNDS_CACHE),
+            ));
+            app.on_fetch_funds(move || {
+                let show_offer = {
+                    let app = app_handle.clone_strong();
+                    let campaigns = campaigns.clone();
+                    let configured_campaigns = configured_campaigns.clone();
+                    move |offer: &Offer| {
+                        let mut offered = configured_campaigns.clone();
+                        offered.extend(offer.campaigns.iter().cloned());
+                        let offered = open_campaigns(offered, &offer.funds);
+                        show_funds(&app, &campaigns, offered, &offer.funds);
+                    }
+                };
+                match cache.borrow().get(clock::timestamp()) {
+                    Lookup::Fresh(offer) => {
+                        show_offer(offer);
+                        return;
+                    }
+                    Lookup::Stale(offer, _) => show_offer(offer),
+                    Lookup::Missing => {}
+                }
+
+                info!("🔍 Fetching funds from API...");
+                let app = app_handle.clone_strong();
+                let token = token.clone();
+                let cache = cache.clone();
+                let stats_db_path = funds_db_path.clone();
+                let failed = funds_failed.clone();
+
+                slint::spawn_local(async move {
+                    match funds::fetch_funds(&token).await {
+                        Ok(value) => {
+                            info!("✅ Fetched {} funds", value.len());
+                            failed.set(false);
+                            boot_checklist::set(
+                                &app,
+                                boot_checklist::Check::Gateway,
+                                1,
+                                &std::format!("{} funds", value.len()),
+                            );
+                            let now = clock::timestamp();
+                            kv::set(&stats_db_path, &kv::LAST_FUNDS_SYNC, &now);
+                            let campaigns = match funds::fetch_campaigns(&token).await {
+                                Ok(remote) => remote,
+                                Err(e) => {
+                                    warn!("⚠️  Failed to fetch campaigns: {}", e);
+                                    Vec::new()
+                                }
+                            };
+                            let offer = Offer {
+                                funds: value,
+                                campaigns,
+                            };
+                            show_offer(&offer);
+                            cache.borrow_mut().put(offer, now);
+                        }
+                        Err(e) => {
+                            error!("❌ Failed to fetch funds: {}", e);
+                            let level = match e.code() {
+                                ErrorCode::ApiIncompatible | ErrorCode::ApiTls => 3,
+                                _ => 2,
+                            };
+                            // An incompatible gateway won't get better by itself
+                            failed.set(!matches!(e, RequestError::Incompatible(_)));
+                            // The last funds fetched stay on offer through an
+                            // outage, under a warning; an incompatible
+                            // gateway takes them down
+                            let cached = match e {
+                                RequestError::Incompatible(_) => None,
+                                _ => match cache.borrow().get(clock::timestamp()) {
+                                    Lookup::Fresh(_) => Some(0),
+                                    Lookup::Stale(_, age) => Some(age),
+                                    Lookup::Missing => None,
+                                },
+                            };
+                            if let Some(age) = cached {
+                                warn!("📋 Offering the funds fetched {} s ago", age);
+                                // Enough to start with, no need to hold the boot screen
+                                boot_checklist::set(
+                                    &app,
+                                    boot_checklist::Check::Gateway,
+                                    2,
+                                    &std::format!(
+                                        "{} {}, using funds from {} min ago",
+                                        e.code(),
+                                        e,
+                                        age / 60
+                                    ),
+                                );
+                                notification_handler::set(
+                                    &app,
+                                    "gateway",
+                                    2,
+                                    &i18n::tr("funds.cached", &[&e.message().text()]),
+                                );
+                                return;
+                            }
+                            boot_checklist::set(
+                                &app,
+                                boot_checklist::Check::Gateway,
+                                3,
+                                &std::format!("{} {}", e.code(), e),
+                            );
+                            app.set_api_incompatible(matches!(e, RequestError::Incompatible(_)));
+                            app.set_funds_error_code(e.code().as_str().into());
+                            notification_handler::set(&app, "gateway", level, &e.message().text());
+                            update_funds(&app, Vec::new(), Vec::new(), Vec::new());
+                        }
+                    }
+                })
+                .unwrap();
+            });
+        }
+
+        let app_handle = app.clone_strong();
+        let stats_db_path = config.stats_db_path.clone();
+        let cache = Rc::new(RefCell::new(
+            Cache::<Vec<Username>>::new(CACHE_FRESH, CACHE_KEEP)
+                .persisted(&config.stats_db_path, &kv::USERNAMES_CACHE),
+        ));
+        app.on_fetch_usernames(move || {
+            match cache.borrow().get(clock::timestamp()) {
+                Lookup::Fresh(value) => {
+                    show_usernames(&app_handle, &stats_db_path, value.clone());
+                    return;
+                }
+                Lookup::Stale(value, _) => {
+                    show_usernames(&app_handle, &stats_db_path, value.clone())
+                }
+                Lookup::Missing => {}
+            }
+
+            info!("🔍 Fetching usernames from API...");
+            let app = app_handle.clone_strong();
+            let token = token_usernames.clone();
+            let stats_db_path = stats_db_path.clone();
+            let cache = cache.clone();
+            let failed = usernames_failed.clone();
+
+            slint::spawn_local(async move {
+                let result = donation::fetch_usernames(&token).await;
+                failed.set(result.is_err());
+                match result {
+                    Ok(value) => {
+                        info!("✅ Fetched {} usernames", value.len());
+                        let now = clock::timestamp();
+                        kv::set(&stats_db_path, &kv::LAST_USERNAMES_SYNC, &now);
+                        show_usernames(&app, &stats_db_path, value.clone());
+                        cache.borrow_mut().put(value, now);
+                    }
+                    // the cached names, if any, stay in the suggestions
+                    Err(e) if cache.borrow().get(clock::timestamp()).value().is_some() => {
+                        warn!(
+                            "⚠️  Failed to fetch usernames, keeping the cached ones: {}",
+                            e
+                        );
+                    }
+                    Err(e) => {
+                        error!("❌ Failed to fetch usernames: {}", e);
+                        app.set_usernames(slint::ModelRc::new(
+                            slint::VecModel::<UsernameSuggestion>::default(),
+                        ));
+                    }
+                }
+            })
+            .unwrap();
+        });
+    }
+
+    /// Puts the known usernames in the Donate page's suggestions, regulars
+    /// (names donated with here recently) first. The regulars are read off
+    /// the UI thread; the model is built back on it.
+    fn show_usernames(app: &MainWindow, stats_db_path: &str, value: Vec<Username>) {
+        let weak = app.as_weak();
+        let stats_db_path = stats_db_path.to_string();
+        thread::spawn(move || {
+            let recent = donation_log::recent_usernames(&stats_db_path, RECENT_USERNAMES)
+                .unwrap_or_else(|e| {
+                    error!("Failed to read recent usernames: {}", e);
+                    Vec::new()
+                });
+            let suggestions = usernames::suggestions(value, &recent);
+            let _ = weak.upgrade_in_event_loop(move |app| {
+                let model_data: Vec<UsernameSuggestion> = suggestions
+                    .into_iter()
+                    .map(|s| UsernameSuggestion {
+                        name: s.name.into(),
+                        tier: s.tier.tag().into(),
+                    })
+                    .collect();
+                app.set_usernames(slint::ModelRc::new(slint::VecModel::from(model_data)));
+            });
+        });
+    }
+
+    /// Puts the campaigns and funds on the Donate page and clears any
+    /// gateway error.
+    fn show_funds(
+        app: &MainWindow,
+        campaigns: &RefCell<Vec<Campaign>>,
+        offered: Vec<Campaign>,
+        funds: &[funds::Fund],
+    ) {
+        app.set_api_incompatible(false);
+        app.set_funds_error_code(slint::SharedString::default());
+        notification_handler::clear(app, "gateway");
+
+        // Convert funds to string array for ComboBox, campaigns first
+        let model_data: Vec<slint::SharedString> = offered
+            .iter()
+            .map(|campaign| campaign_label(campaign, funds).into())
+            .chain(funds.iter().map(|fund| {
+                slint::SharedString::from(std::format!("{} (ID: {})", fund.name, fund.id))
+            }))
+            .collect();
+
+        // Also store fund IDs separately for lookup; campaigns
+        // get negative ids, -1 being the first
+        let fund_ids: Vec<i32> = (1..=offered.len() as i32)
+            .map(|i| -i)
+            .chain(funds.iter().map(|f| f.id))
+            .collect();
+
+        // And how far along each one is, for the progress bars
+        let currency = money::currency();
+        let fund_progress: Vec<FundProgress> = funds
+            .iter()
+            .map(|fund| progress_row(&fund.name, fund.progress(currency)))
+            .collect();
+        let progress: Vec<FundProgress> = offered
+            .iter()
+            .map(|campaign| {
+                progress_row(
+                    &campaign_label(campaign, funds),
+                    campaign_progress(campaign, funds, currency),
+                )
+            })
+            .chain(fund_progress.iter().cloned())
+            .collect();
+        *campaigns.borrow_mut() = offered;
+
+        update_funds(app, model_data, fund_ids, progress);
+        attract_handler::show_funds(app, fund_progress);
+    }
+
+    /// A row for the progress bars from what's raised and the target, if
+    /// known.
+    fn progress_row(name: &str, progress: Option<(i32, i32)>) -> FundProgress {
+        let (collected, target) = progress.unwrap_or_default();
+        FundProgress {
+            name: name.into(),
+            collected,
+            target,
+            percent: if target > 0 {
+                (collected as i64 * 100 / target as i64) as i32
+            } else {
+                0
+            },
+        }
+    }
+
+    /// A campaign's progress is its funds' together; it has a target only
+    /// if each of them does.
+    fn campaign_progress(
+        campaign: &Campaign,
+        open: &[funds::Fund],
+        currency: &str,
+    ) -> Option<(i32, i32)> {
+        let mut collected = 0;
+        let mut target = Some(0);
+        for part in &campaign.funds {
+            let (fund_collected, fund_target) = open
+                .iter()
+                .find(|fund| fund.id == part.id)
+                .and_then(|fund| fund.progress(currency))?;
+            collected += fund_collected;
+            target = target
+                .filter(|_| fund_target > 0)
+                .map(|sum| sum + fund_target);
+        }
+        Some((collected, target.unwrap_or(0)))
+    }
+
+    /// Brings the fund models up to date row by row, matched on fund id, so
+    /// a refresh landing while someone is picking a fund doesn't reset the
+    /// picker under their finger. The Donate page's pick follows its fund;
+    /// if the fund went away, nothing is picked.
+    fn update_funds(
+        app: &MainWindow,
+        labels: Vec<SharedString>,
+        ids: Vec<i32>,
+        progress: Vec<FundProgress>,
+    ) {
+        let label_rc = app.get_available_funds();
+        let id_rc = app.get_available_fund_ids();
+        let progress_rc = app.get_available_fund_progress();
+        let (Some(label_model), Some(id_model), Some(progress_model)) = (
+            label_rc.as_any().downcast_ref::<VecModel<SharedString>>(),
+            id_rc.as_any().downcast_ref::<VecModel<i32>>(),
+            progress_rc
+                .as_any()
+                .downcast_ref::<VecModel<FundProgress>>(),
+        ) else {
+            // first load: the window still has its empty literal models
+            app.set_donate_fund_index(if ids.is_empty() { -1 } else { 0 });
+            app.set_available_funds(ModelRc::new(VecModel::from(labels)));
+            app.set_available_fund_ids(ModelRc::new(VecModel::from(ids)));
+            app.set_available_fund_progress(ModelRc::new(VecModel::from(progress)));
+            return;
+        };
+
+        let picked = usize::try_from(app.get_donate_fund_index())
+            .ok()
+            .and_then(|row| id_model.row_data(row));
+
+        let rows = labels.into_iter().zip(ids.iter().copied()).zip(progress);
+        for (row, ((label, id), progress)) in rows.enumerate() {
+            let found = (row..id_model.row_count()).find(|&r| id_model.row_data(r) == Some(id));
+            match found {
+                Some(at) => {
+                    // rows in between are gone or come later; drop them
+                    for _ in row..at {
+                        id_model.remove(row);
+                        label_model.remove(row);
+                        progress_model.remove(row);
+                    }
+                    if label_model.row_data(row).as_ref() != Some(&label) {
+                        label_model.set_row_data(row, label);
+                    }
+                    if progress_model.row_data(row).as_ref() != Some(&progress) {
+                        progress_model.set_row_data(row, progress);
+                    }
+                }
+                None => {
+                    id_model.insert(row, id);
+                    label_model.insert(row, label);
+                    progress_model.insert(row, progress);
+                }
+            }
+        }
+        while id_model.row_count() > ids.len() {
+            id_model.remove(ids.len());
+            label_model.remove(ids.len());
+            progress_model.remove(ids.len());
+        }
+
+        let index = match picked {
+            Some(id) => ids.iter().position(|&i| i == id).map_or(-1, |i| i as i32),
+            None if ids.is_empty() => -1,
+            None => 0,
+        };
+        app.set_donate_fund_index(index);
+    }
+
+    /// Keeps only campaigns whose funds are all open, so a donation is never
+    /// split towards a closed fund.
+    fn open_campaigns(campaigns: Vec<Campaign>, open: &[funds::Fund]) -> Vec<Campaign> {
+        campaigns
+            .into_iter()
+            .filter(|campaign| {
+                let all_open = !campaign.funds.is_empty()
+                    && campaign
+                        .funds
+                        .iter()
+                        .all(|f| open.iter().any(|fund| fund.id == f.id));
+                if !all_open {
+                    warn!(
+                        "⚠️  Campaign {:?} skipped: not all of its funds are open",
+                        campaign.name
+                    );
+                }
+                all_open
+            })
+            .collect()
+    }
+
+    fn campaign_label(campaign: &Campaign, open: &[funds::Fund]) -> String {
+        let names: Vec<&str> = campaign
+            .funds
+            .iter()
+            .filter_map(|f| open.iter().find(|fund| fund.id == f.id))
+            .map(|fund| fund.name.as_str())
+            .collect();
+        std::format!("🎯 {} ({})", campaign.name, names.join(" + "))
+    }
+}
+
+mod token_watch {
+    use super::*;
+    use crate::backend;
+    use crate::config::BackendKind;
+    use crate::mqtt::Publisher;
+    use slint::{Timer, TimerMode};
+
+    /// How often the config file and `token_file` are read again while the
+    /// kiosk has no gateway token.
+    const CHECK_INTERVAL: Duration = Duration::from_secs(10);
+
+    /// On a kiosk started without a gateway token, keeps looking for one
+    /// and, once it turns up, starts fetching funds and usernames and
+    /// sending donations without a restart. Member status, service-page
+    /// roles and the gateway report still need one.
+    pub fn init(
+        app: &MainWindow,
+        config: &Config,
+        campaigns: Rc<RefCell<Vec<Campaign>>>,
+        backend_slot: donation_handler::BackendSlot,
+        publisher: Option<Publisher>,
+    ) {
+        let gateway_backend = config.backend.kind == BackendKind::Gateway;
+        let gateway_funds = config.backend.funds.is_empty();
+        if config.token.is_some() || !(gateway_backend || gateway_funds) {
+            return;
+        }
+        info!(
+            "🔑 No gateway token yet, checking for one every {:?}",
+            CHECK_INTERVAL
+        );
+
+        let weak = app.as_weak();
+        let config = config.clone();
+        let timer = Rc::new(Timer::default());
+        let timer_weak = Rc::downgrade(&timer);
+        timer.start(TimerMode::Repeated, CHECK_INTERVAL, move || {
+            let Some(window) = weak.upgrade() else {
+                return;
+            };
+            let Some(token) = crate::config::current_token() else {
+                return;
+            };
+            if let Some(timer) = timer_weak.upgrade() {
+                timer.stop();
+            }
+            info!("🔑 Gateway token found, enabling donations");
+            audit::record(&config.stats_db_path, "token_loaded", "");
+            let mut config = config.clone();
+            config.token = Some(token);
+            fund_fetcher::init(&window, &config, campaigns.clone());
+            if gateway_backend {
+                *backend_slot.borrow_mut() = backend::from_config(&config, publisher.as_ref());
+            }
+            window.invoke_fetch_funds();
+        });
+        std::mem::forget(timer);
+    }
+}
+
+mod attract_handler {
+    use super::*;
+    use slint::{ModelRc, Timer, TimerMode, VecModel};
+    use std::cell::Cell;
+
+    /// How often idleness is checked and the slides move on.
+    const TICK: Duration = Duration::from_secs(1);
+
+    /// Puts the open funds on the attract screen, in the Donate page's
+    /// order.
+    pub fn show_funds(app: &MainWindow, funds: Vec<FundProgress>) {
+        if app.get_attract_index() as usize >= funds.len() {
+            app.set_attract_index(0);
+        }
+        app.set_attract_funds(ModelRc::new(VecModel::from(funds)));
+    }
+
+    /// Shows the attract screen once the main page has sat `[attract]
+    /// idle_mins` with nobody around, moving to the next fund every
+    /// `slide_secs`. Anything else on screen (another page, the edit
+    /// banner, the thank-you), a softkey press or a tap puts the idle count
+    /// back to zero; so do paused donations, as there'd be nothing to tap
+    /// for.
+    pub fn init(app: &MainWindow, config: &Config) {
+        let Some(idle_mins) = config.attract.idle_mins.filter(|&mins| mins > 0) else {
+            return;
+        };
+        let idle_after = Duration::from_secs(idle_mins * 60);
+        let slide = Duration::from_secs(config.attract.slide_secs.max(1));
+        info!(
+            "✨ Attract screen after {} min idle, {} s a fund",
+            idle_mins,
+            slide.as_secs()
+        );
+
+        let idle_since = Rc::new(Cell::new(clock::instant()));
+        app.on_attract_touched({
+            let idle_since = idle_since.clone();
+            move || {
+                info!("✨ Attract screen tapped");
+                idle_since.set(clock::instant());
+            }
+        });
+
+        let last_page = Cell::new(app.get_current_page());
+        let last_presses = Cell::new(app.global::<SoftKeys>().get_presses());
+        let slide_since = Cell::new(clock::instant());
+        let weak = app.as_weak();
+        let timer = Timer::default();
+        timer.start(TimerMode::Repeated, TICK, move || {
+            let Some(window) = weak.upgrade() else {
+                return;
+            };
+            let page = window.get_current_page();
+            let presses = window.global::<SoftKeys>().get_presses();
+            let busy = page != Page::Main
+                || window.get_edit_seconds_left() > 0
+                || window.get_show_confetti()
+                || window.get_nav_confirm_open()
+                || window.get_acceptance_locked()
+                || !window.get_bills_ready();
+            if busy || page != last_page.get() || presses != last_presses.get() {
+                last_page.set(page);
+                last_presses.set(presses);
+                idle_since.set(clock::instant());
+                if window.get_attract_active() {
+                    info!("✨ Attract screen off");
+                    window.set_attract_active(false);
+                }
+                return;
+            }
+
+            if !window.get_attract_active() {
+                if clock::since(idle_since.get()) >= idle_after {
+                    info!("✨ Attract screen on");
+                    window.set_attract_index(0);
+                    window.set_attract_active(true);
+                    slide_since.set(clock::instant());
+                    // fresh totals, unless the cached ones are recent
+                    window.invoke_fetch_funds();
+                }
+                return;
+            }
+            if clock::since(slide_since.get()) < slide {
+                return;
+            }
+            slide_since.set(clock::instant());
+            let count = window.get_attract_funds().row_count();
+            if count == 0 {
+                return;
+            }
+            let next = (window.get_attract_index() as usize + 1) % count;
+            window.set_attract_index(next as i32);
+            if next == 0 {
+                window.invoke_fetch_funds();
+            }
+        });
+        std::mem::forget(timer);
+    }
+}
+
+mod boost_handler {
+    use super::*;
+    use crate::boost::{Boost, Request};
+    use crate::mqtt::Publisher;
+    use slint::{Timer, TimerMode};
+
+    /// How often the time left on screen is brought up to date, and a
+    /// boost whose time is up is ended.
+    const TICK: Duration = Duration::from_secs(15);
+
+    /// Starts, ends and counts towards the boost. Cheap to clone and can be
+    /// handed to the listener thread, but only used on the event loop.
+    #[derive(Clone)]
+    pub struct Booster {
+        window: slint::Weak<MainWindow>,
+        stats_db_path: String,
+        config: config::Boost,
+        /// Gets each donation and the retained boost state; `None` without
+        /// a broker.
+        publisher: Option<Publisher>,
+    }
+
+    impl Booster {
+        /// Starts a boost from `[boost]` with the request's overrides. One
+        /// already running is replaced, its total starting over.
+        pub fn start(&self, request: &Request) {
+            let Some(window) = self.window.upgrade() else {
+                return;
+            };
+            let now = clock::timestamp();
+            let boost = Boost::start(&self.config, request, now);
+            let minutes = boost.minutes_left(now);
+            info!(
+                "🚀 Boost for {} (fund {:?}) for {} min",
+                boost.fund_name, boost.fund_id, minutes
+            );
+            kv::set(&self.stats_db_path, &kv::BOOST, &boost);
+            audit::record(
+                &self.stats_db_path,
+                "boost_started",
+                &format!("{} for {} min", boost.fund_name, minutes),
+            );
+            notification_handler::push(
+                &window,
+                1,
+                &i18n::tr("boost.started", &[&boost.fund_name, &minutes]),
+            );
+            self.show(&window, Some(&boost));
+        }
+
+        pub fn stop(&self) {
+            let Some(boost) = kv::get(&self.stats_db_path, &kv::BOOST) else {
+                return;
+            };
+            kv::remove(&self.stats_db_path, &kv::BOOST);
+            info!(
+                "🚀 Boost over: {} {currency} for {}",
+                boost.total,
+                boost.fund_name,
+                currency = money::currency()
+            );
+            audit::record(
+                &self.stats_db_path,
+                "boost_ended",
+                &format!(
+                    "{} {currency} for {}",
+                    boost.total,
+                    boost.fund_name,
+                    currency = money::currency()
+                ),
+            );
+            if let Some(window) = self.window.upgrade() {
+                notification_handler::push(
+                    &window,
+                    1,
+                    &i18n::tr(
+                        "boost.ended",
+                        &[&money::format(boost.total), &boost.fund_name],
+                    ),
+                );
+                self.show(&window, None);
+            }
+        }
+
+        /// Adds a donation to the running boost's total, if it's for the
+        /// boost's fund, and sends it to the overlay.
+        fn donated(&self, fund_id: i32, username: &str, amount: i32) {
+            let Some(mut boost) = kv::get(&self.stats_db_path, &kv::BOOST) else {
+                return;
+            };
+            if !boost.counts(fund_id) {
+                return;
+            }
+            boost.total += i64::from(amount);
+            kv::set(&self.stats_db_path, &kv::BOOST, &boost);
+            if let Some(publisher) = &self.publisher {
+                publisher.publish(
+                    &publisher.topic("boost/donation"),
+                    &boost.event(username, amount, clock::timestamp()),
+                    false,
+                );
+            }
+            if let Some(window) = self.window.upgrade() {
+                self.show(&window, Some(&boost));
+            }
+        }
+
+        /// Puts the boost (or its absence) on screen and in the retained
+        /// `boost` topic.
+        fn show(&self, window: &MainWindow, boost: Option<&Boost>) {
+            window.set_boost_active(boost.is_some());
+            if let Some(boost) = boost {
+                window.set_boost_fund_name(boost.fund_name.clone().into());
+                window.set_boost_total(boost.total as i32);
+                window.set_boost_minutes_left(boost.minutes_left(clock::timestamp()) as i32);
+            }
+            if let Some(publisher) = &self.publisher {
+                publisher.publish(&publisher.topic("boost"), &Boost::state(boost), true);
+            }
+        }
+
+        fn tick(&self) {
+            let Some(boost) = kv::get(&self.stats_db_path, &kv::BOOST) else {
+                return;
+            };
+            let minutes = boost.minutes_left(clock::timestamp());
+            if minutes == 0 {
+                self.stop();
+            } else if let Some(window) = self.window.upgrade() {
+                window.set_boost_minutes_left(minutes as i32);
+            }
+        }
+    }
+
+    pub fn init(app: &MainWindow, config: &Config, publisher: Option<&Publisher>) -> Booster {
+        let booster = Booster {
+            window: app.as_weak(),
+            stats_db_path: config.stats_db_path.clone(),
+            config: config.boost.clone(),
+            publisher: publisher.cloned(),
+        };
+        // Running when the app went down: carry on, or end it if its time
+        // ran out meanwhile
+        let running = kv::get(&config.stats_db_path, &kv::BOOST);
+        if let Some(boost) = &running {
+            info!(
+                "🚀 Boost for {} carries on after the restart",
+                boost.fund_name
+            );
+        }
+        booster.show(app, running.as_ref());
+        booster.tick();
+
+        app.on_boost_donated({
+            let booster = booster.clone();
+            move |fund_id, username, amount| booster.donated(fund_id, &username, amount)
+        });
+
+        app.on_boost_toggled({
+            let booster = booster.clone();
+            move |enabled| {
+                if enabled {
+                    booster.start(&Request::default());
+                } else {
+                    booster.stop();
+                }
+            }
+        });
+
+        let timer = Timer::default();
+        timer.start(TimerMode::Repeated, TICK, {
+            let booster = booster.clone();
+            move || booster.tick()
+        });
+        std::mem::forget(timer);
+        booster
+    }
+}
+
+mod donation_handler {
+    use super::*;
+    use crate::backend::{self, Donation, DonationBackend};
+    use crate::error::RequestError;
+    use crate::kv::{EventBatch, HeldDonation};
+    use crate::mqtt::Publisher;
+    use log::debug;
+    use std::cell::Cell;
+    use std::collections::HashSet;
+    use std::time::Instant;
+
+    /// The inactivity timeout and its countdown ticker, replaced on entering
+    /// the insert page and on every bill or tap there.
+    type InactivitySlot = Rc<RefCell<Option<slint::Timer>>>;
+
+    /// The backend donations go to, `None` while the configured one can't be
+    /// used; filled in later once a missing token turns up (`token_watch`).
+    pub type BackendSlot = Rc<RefCell<Option<Rc<dyn DonationBackend>>>>;
+
+    thread_local! {
+        /// Sessions `DonationSink::send` is sending right now.
+        static SENDING: Cell<usize> = const { Cell::new(0) };
+    }
+
+    /// Whether a session's donation is on its way to the backend.
+    pub fn sending() -> bool {
+        SENDING.get() > 0
+    }
+
+    /// Done waits at least this long before the amount is final: one driver
+    /// poll plus a UI tick, so a bill that went in as Done was tapped is seen.
+    const SETTLE_MIN: Duration = Duration::from_millis(600);
+    /// ...and at most this long for a bill in transit to be credited; one
+    /// that takes longer is probably jammed.
+    const SETTLE_MAX: Duration = Duration::from_secs(5);
+
+    /// How often the donation queue is checked for donations due another try
+    /// (see `donation::retry_delay`).
+    const QUEUE_RETRY_CHECK: Duration = Duration::from_secs(30);
+
+    /// Everything needed to send a donation and record it locally once the
+    /// amount and attribution are known. Cheap to clone into callbacks.
+    #[derive(Clone)]
+    struct DonationSink {
+        backend: BackendSlot,
+        photos_dir: String,
+        stats_db_path: String,
+        fallback_fund_id: Option<i32>,
+        fallback_fund_name: String,
+        /// Smaller amounts are never sent to the gateway.
+        minimum_donation: i32,
+        /// Larger amounts are confirmed by the donor, see `record_large`.
+        large_threshold: Option<i32>,
+        /// Campaigns currently offered; fund id -1 is the first.
+        campaigns: Rc<RefCell<Vec<Campaign>>>,
+        window: slint::Weak<MainWindow>,
+        /// Wakes the print worker; `None` without a printer.
+        print_wake: Option<Sender<()>>,
+        /// How long a donor's donation is held for corrections.
+        edit_grace: Duration,
+        /// `[flow]` asks for fund and name after the money, see `flow`.
+        details_after: bool,
+        /// The donation being held, and when it goes out.
+        held: Rc<RefCell<Option<(HeldDonation, Instant)>>>,
+        /// Event-mode sessions are sent together this often; zero sends
+        /// each one.
+        batch_every: Duration,
+        /// ...or as soon as they add up to this much, when positive.
+        batch_amount: i32,
+        batch: Rc<RefCell<Option<EventBatch>>>,
+        /// Queued donations being sent right now, so a retry from
+        /// Diagnostics and the automatic one never send the same one twice.
+        in_flight: Rc<RefCell<HashSet<i64>>>,
+        /// The last receipt number handed out; `None` when it couldn't be
+        /// read at startup, and no receipts are issued then.
+        last_receipt: Rc<Cell<Option<i64>>>,
+    }
+
+    /// A session's donation, receipt issued, on its way to the backend.
+    struct Outgoing {
+        receipt: Option<i64>,
+        fund_id: i32,
+        fund_name: String,
+        username: String,
+        amount: i32,
+        auto_committed: bool,
+        /// For a donation sent after the fact (held, batched): when it was
+        /// made. It's dated then, and its photo was taken then.
+        dated: Option<u64>,
+    }
+
+    impl From<HeldDonation> for Outgoing {
+        fn from(held: HeldDonation) -> Self {
+            Outgoing {
+                receipt: held.receipt,
+                fund_id: held.fund_id,
+                fund_name: held.fund_name,
+                username: held.username,
+                amount: held.amount,
+                auto_committed: false,
+                dated: Some(held.timestamp),
+            }
+        }
+    }
+
+    impl DonationSink {
+        fn new(
+            config: &Config,
+            app: &MainWindow,
+            campaigns: Rc<RefCell<Vec<Campaign>>>,
+            print_wake: Option<Sender<()>>,
+            publisher: Option<&Publisher>,
+        ) -> Self {
+            Self {
+                backend: Rc::new(RefCell::new(backend::from_config(config, publisher))),
+                photos_dir: config.photos_dir.clone(),
+                stats_db_path: config.stats_db_path.clone(),
+                fallback_fund_id: config.fallback_fund_id,
+                fallback_fund_name: config.fallback_fund_name.clone(),
+                minimum_donation: config.minimum_donation,
+                large_threshold: config.large_donation_threshold,
+                campaigns,
+                window: app.as_weak(),
+                print_wake,
+                edit_grace: Duration::from_secs(config.donation_edit_secs),
+                details_after: flow::Flow::from_config(config).details_after(),
+                held: Rc::new(RefCell::new(None)),
+                batch_every: Duration::from_secs(config.event_mode.batch_secs),
+                batch_amount: config.event_mode.batch_amount,
+                // Left over from before a restart: carry on with it
+                batch: Rc::new(RefCell::new(kv::get(
+                    &config.stats_db_path,
+                    &kv::EVENT_BATCH,
+                ))),
+                in_flight: Rc::new(RefCell::new(HashSet::new())),
+                last_receipt: Rc::new(Cell::new(
+                    receipt::last(&config.stats_db_path)
+                        .inspect_err(|e| {
+                            error_code::report(
+                                &config.stats_db_path,
+                                ErrorCode::Database,
+                                &format!("Failed to read the last receipt number: {}", e),
+                            )
+                        })
+                        .ok(),
+                )),
+            }
+        }
+
+        /// Issues the session's receipt number, then sends the donation
+        /// asynchronously (see `send`). Returns the receipt number for the
+        /// thank-you screen. Amounts below the minimum are only audited.
+        fn submit(
+            &self,
+            fund_id: i32,
+            fund_name: String,
+            username: String,
+            amount: i32,
+            auto_committed: bool,
+        ) -> Option<i64> {
+            if amount < self.minimum_donation {
+                self.record_below_minimum(amount, fund_id);
+                return None;
+            }
+            let receipt = self.issue(&username, amount, fund_id, &fund_name);
+            if self.batching() && username == "anon" && fund_id >= 0 {
+                self.add_to_batch(receipt, fund_id, fund_name, amount, auto_committed);
+                return receipt;
+            }
+            self.send(Outgoing {
+                receipt,
+                fund_id,
+                fund_name,
+                username,
+                amount,
+                auto_committed,
+                dated: None,
+            });
+            receipt
+        }
+
+        /// Whether sessions go into the event batch rather than out one by one.
+        fn batching(&self) -> bool {
+            !self.batch_every.is_zero()
+                && self
+                    .window
+                    .upgrade()
+                    .is_some_and(|window| window.get_event_mode())
+        }
+
+        /// Adds an anonymous event-mode session to the batch for its fund.
+        /// A session for another fund sends the batch so far first.
+        fn add_to_batch(
+            &self,
+            receipt: Option<i64>,
+            fund_id: i32,
+            fund_name: String,
+            amount: i32,
+            auto_committed: bool,
+        ) {
+            if self
+                .batch
+                .borrow()
+                .as_ref()
+                .is_some_and(|batch| batch.fund_id != fund_id)
+            {
+                self.flush_batch();
+            }
+            let full = {
+                let mut slot = self.batch.borrow_mut();
+                let batch = slot.get_or_insert_with(|| EventBatch {
+                    fund_id,
+                    fund_name,
+                    amount: 0,
+                    sessions: 0,
+                    receipts: Vec::new(),
+                    started: clock::timestamp(),
+                    auto_committed: true,
+                });
+                batch.amount += amount;
+                batch.sessions += 1;
+                batch.receipts.extend(receipt);
+                batch.auto_committed &= auto_committed;
+                kv::set(&self.stats_db_path, &kv::EVENT_BATCH, batch);
+                info!(
+                    "📦 {} {currency} added to the event batch, now {} {currency} from {} sessions",
+                    amount,
+                    batch.amount,
+                    batch.sessions,
+                    currency = money::currency()
+                );
+                self.batch_amount > 0 && batch.amount >= self.batch_amount
+            };
+            if full {
+                self.flush_batch();
+            }
+        }
+
+        /// Sends the event batch as one donation, if there is one.
+        fn flush_batch(&self) {
+            let Some(batch) = self.batch.borrow_mut().take() else {
+                return;
+            };
+            kv::remove(&self.stats_db_path, &kv::EVENT_BATCH);
+            info!(
+                "📤 Sending the event batch: {} {currency} from {} sessions",
+                batch.amount,
+                batch.sessions,
+                currency = money::currency()
+            );
+            let receipts: Vec<String> =
+                batch.receipts.iter().map(|&r| receipt::format(r)).collect();
+            audit::record(
+                &self.stats_db_path,
+                "event_batch",
+                &format!(
+                    "{} sessions to fund {}, receipts {}",
+                    batch.sessions,
+                    batch.fund_id,
+                    receipts.join(", ")
+                ),
+            );
+            self.send(Outgoing {
+                receipt: None,
+                fund_id: batch.fund_id,
+                fund_name: batch.fund_name,
+                username: "anon".to_string(),
+                amount: batch.amount,
+                auto_committed: batch.auto_committed,
+                dated: Some(batch.started),
+            });
+        }
+
+        /// Sends the event batch once it's `batch_every` old, or as soon as
+        /// batching is off (event mode ended, or not configured any more).
+        fn tick_batch(&self) {
+            let due = match &*self.batch.borrow() {
+                Some(batch) => {
+                    !self.batching()
+                        || clock::timestamp().saturating_sub(batch.started)
+                            >= self.batch_every.as_secs()
+                }
+                None => false,
+            };
+            if due {
+                self.flush_batch();
+            }
+        }
+
+        /// Issues the receipt number and queues the printed receipt. There's
+        /// no number without a backend; the donation only waits in the queue.
+        fn issue(&self, username: &str, amount: i32, fund_id: i32, fund_name: &str) -> Option<i64> {
+            let receipt = if self.backend.borrow().is_some() {
+                self.next_receipt(username, amount, fund_id, fund_name)
+            } else {
+                None
+            };
+            if let Some(number) = receipt {
+                info!("🧾 Receipt {} issued", receipt::format(number));
+            }
+            self.print(receipt, username, amount, fund_name);
+            if let Some(window) = self.window.upgrade() {
+                window.invoke_boost_donated(fund_id, username.into(), amount);
+            }
+            receipt
+        }
+
+        /// Hands out the next receipt number; what it was for is recorded
+        /// in the background.
+        fn next_receipt(
+            &self,
+            username: &str,
+            amount: i32,
+            fund_id: i32,
+            fund_name: &str,
+        ) -> Option<i64> {
+            let number = self.last_receipt.get()? + 1;
+            self.last_receipt.set(Some(number));
+            let db_path = self.stats_db_path.clone();
+            let username = username.to_string();
+            let fund_name = fund_name.to_string();
+            db_writes::spawn(move || {
+                if let Err(e) =
+                    receipt::issue(&db_path, number, &username, amount, fund_id, &fund_name)
+                {
+                    error_code::report(
+                        &db_path,
+                        ErrorCode::Database,
+                        &format!("Failed to issue receipt {}: {}", receipt::format(number), e),
+                    );
+                }
+            });
+            Some(number)
+        }
+
+        /// Queues the printed receipt, if there is a printer.
+        fn print(&self, receipt: Option<i64>, username: &str, amount: i32, fund_name: &str) {
+            let Some(print_wake) = self.print_wake.clone() else {
+                return;
+            };
+            let body =
+                printer::format_receipt(receipt, username, amount, fund_name, clock::timestamp());
+            let db_path = self.stats_db_path.clone();
+            db_writes::spawn(move || match printer::enqueue(&db_path, receipt, &body) {
+                Ok(_) => {
+                    let _ = print_wake.send(());
+                }
+                Err(e) => error_code::report(
+                    &db_path,
+                    ErrorCode::Database,
+                    &format!("Failed to queue receipt for printing: {}", e),
+                ),
+            });
+        }
+
+        /// Like `submit` for a donor's own donation, but the send is held
+        /// for `edit_grace` so the fund or username can still be corrected
+        /// from the main page (see `edit`). The donor photo is taken now,
+        /// while they're still there. Returns the receipt number.
+        fn hold(
+            &self,
+            fund_id: i32,
+            fund_name: String,
+            username: String,
+            amount: i32,
+        ) -> Option<i64> {
+            // One at a time; a new session sends the last one anyway
+            self.release();
+            let receipt = self.issue(&username, amount, fund_id, &fund_name);
+            let held = HeldDonation {
+                receipt,
+                timestamp: clock::timestamp(),
+                username,
+                fund_id,
+                fund_name,
+                amount,
+            };
+            if held.username != "anon" {
+                camera::capture_donation_photo(&self.photos_dir, &held.username, held.timestamp);
+            }
+            kv::set(&self.stats_db_path, &kv::HELD_DONATION, &held);
+            info!(
+                "✋ Holding the donation for {:?} for corrections",
+                self.edit_grace
+            );
+            if let Some(window) = self.window.upgrade() {
+                show_held(&window, &held, self.edit_grace);
+            }
+            *self.held.borrow_mut() = Some((held, clock::instant() + self.edit_grace));
+            receipt
+        }
+
+        /// Applies a donor's correction to the held donation: the receipt
+        /// is amended and printed again, the photo follows the new name.
+        fn edit(&self, username: String, fund_id: i32, fund_name: String) {
+            let mut slot = self.held.borrow_mut();
+            let Some((held, deadline)) = slot.as_mut() else {
+                // Sent while the donor was still choosing
+                if let Some(window) = self.window.upgrade() {
+                    notification_handler::push(&window, 2, &i18n::tr("donation.edit_expired", &[]));
+                }
+                return;
+            };
+            let receipt = held.receipt.map(receipt::format).unwrap_or_default();
+            info!(
+                "✏️  Donation {} changed: {} to fund {} → {} to fund {}",
+                receipt, held.username, held.fund_id, username, fund_id
+            );
+            audit::record(
+                &self.stats_db_path,
+                "donation_edited",
+                &format!(
+                    "receipt {}: {} to fund {} -> {} to fund {}",
+                    receipt, held.username, held.fund_id, username, fund_id
+                ),
+            );
+            if let Some(number) = held.receipt
+                && let Err(e) =
+                    receipt::amend(&self.stats_db_path, number, &username, fund_id, &fund_name)
+            {
+                error_code::report(
+                    &self.stats_db_path,
+                    ErrorCode::Database,
+                    &format!("Failed to amend receipt {}: {}", receipt, e),
+                );
+            }
+            self.print(held.receipt, &username, held.amount, &fund_name);
+            if username != held.username {
+                if held.username == "anon" {
+                    camera::capture_donation_photo(&self.photos_dir, &username, held.timestamp);
+                } else {
+                    camera::rename_donation_photo(
+                        &self.photos_dir,
+                        held.timestamp,
+                        &held.username,
+                        &username,
+                    );
+                }
+            }
+            held.username = username;
+            held.fund_id = fund_id;
+            held.fund_name = fund_name;
+            kv::set(&self.stats_db_path, &kv::HELD_DONATION, held);
+            if let Some(window) = self.window.upgrade() {
+                show_held(
+                    &window,
+                    held,
+                    deadline.saturating_duration_since(clock::instant()),
+                );
+            }
+        }
+
+        /// Sends the held donation now, if there is one.
+        fn release(&self) {
+            let Some((held, _)) = self.held.borrow_mut().take() else {
+                return;
+            };
+            kv::remove(&self.stats_db_path, &kv::HELD_DONATION);
+            if let Some(window) = self.window.upgrade() {
+                window.set_edit_seconds_left(0);
+                window.invoke_edit_expired();
+            }
+            info!(
+                "📤 Sending held donation {}",
+                held.receipt.map(receipt::format).unwrap_or_default()
+            );
+            self.send(held.into());
+        }
+
+        /// Counts the held donation down on the main page; it goes out when
+        /// the time is up, or as soon as the kiosk moves on to anything but
+        /// correcting it.
+        fn tick(&self) {
+            let Some(window) = self.window.upgrade() else {
+                return;
+            };
+            let due = match &*self.held.borrow() {
+                Some((_, deadline)) => {
+                    let left = deadline.saturating_duration_since(clock::instant());
+                    window.set_edit_seconds_left(left.as_secs_f32().ceil() as i32);
+                    left.is_zero() || (!window.get_editing_donation() && !window.get_on_main_page())
+                }
+                None => false,
+            };
+            if due {
+                self.release();
+            }
+        }
+
+        /// Sends the donation asynchronously; on success plays the sound,
+        /// takes the donor photo (unless already taken) and records it in
+        /// the donation log and audit trail. A campaign (negative `fund_id`)
+        /// is split into one donation per fund, all under the same receipt.
+        fn send(&self, outgoing: Outgoing) {
+            let Outgoing {
+                receipt,
+                fund_id,
+                fund_name,
+                username,
+                amount,
+                auto_committed,
+                dated,
+            } = outgoing;
+            let campaign = (fund_id < 0)
+                .then(|| {
+                    self.campaigns
+                        .borrow()
+                        .get((-fund_id - 1) as usize)
+                        .cloned()
+                })
+                .flatten();
+            let parts = match &campaign {
+                Some(campaign) => {
+                    let parts = campaign.split(amount);
+                    info!(
+                        "🎯 Splitting {} {currency} for campaign {:?}: {:?}",
+                        amount,
+                        campaign.name,
+                        parts,
+                        currency = money::currency()
+                    );
+                    parts
+                }
+                None if fund_id < 0 => {
+                    // The campaign list changed under us; don't lose the money
+                    warn!(
+                        "⚠️  Campaign {} no longer offered, using the fallback fund",
+                        fund_id
+                    );
+                    match self.fallback_fund_id {
+                        Some(fallback) => vec![(fallback, amount)],
+                        None => {
+                            let reason = format!("Campaign {} gone", fund_id);
+                            self.leave_unattributed(receipt, amount, &reason);
+                            Vec::new()
+                        }
+                    }
+                }
+                None => vec![(fund_id, amount)],
+            };
+
+            let backend = self.backend.borrow().clone();
+            let campaign_name = campaign.as_ref().map(|c| c.name.clone());
+            let timestamp = dated.unwrap_or_else(clock::timestamp);
+            // In the queue before they go out, so a crash or restart mid-send
+            // doesn't lose them; `retry_due` sends what's left over once
+            // there's a backend
+            let donations: Vec<(Option<i64>, Donation)> = parts
+                .iter()
+                .map(|&(part_fund_id, part_amount)| {
+                    let donation = Donation {
+                        fund_id: part_fund_id,
+                        fund_name: match &campaign_name {
+                            Some(name) => format!("{} (fund {})", name, part_fund_id),
+                            None => fund_name.clone(),
+                        },
+                        username: username.clone(),
+                        amount: part_amount,
+                        currency: money::currency(),
+                        receipt,
+                        timestamp,
+                        auto_committed,
+                    };
+                    // Without a backend yet (no token) it waits there for one
+                    let id = match backend {
+                        Some(_) => donation::enqueue_sending(&self.stats_db_path, &donation),
+                        None => {
+                            donation::enqueue(&self.stats_db_path, &donation, "No donation backend")
+                        }
+                    }
+                    .inspect_err(|e| error!("Failed to queue the donation: {}", e))
+                    .ok();
+                    (id, donation)
+                })
+                .collect();
+            let Some(backend) = backend else {
+                warn!(
+                    "⚠️  No donation backend yet, {} donation(s) queued until there is one",
+                    donations.len()
+                );
+                return;
+            };
+            self.in_flight
+                .borrow_mut()
+                .extend(donations.iter().filter_map(|(id, _)| *id));
+
+            let photos_dir = self.photos_dir.clone();
+            let stats_db_path = self.stats_db_path.clone();
+            let window = self.window.clone();
+            let in_flight = self.in_flight.clone();
+            SENDING.set(SENDING.get() + 1);
+            slint::spawn_local(async move {
+                let mut sent = 0;
+                for (i, (id, donation)) in donations.iter().enumerate() {
+                    if let (Some(name), Some(w)) = (&campaign_name, window.upgrade()) {
+                        notification_handler::set(
+                            &w,
+                            "campaign",
+                            0,
+                            &i18n::tr("campaign.sending", &[name, &(i + 1), &parts.len()]),
+                        );
+                    }
+                    let taken = match *id {
+                        Some(id) => {
+                            let taken =
+                                send_queued(backend.as_ref(), &stats_db_path, id, donation).await;
+                            in_flight.borrow_mut().remove(&id);
+                            taken
+                        }
+                        // Couldn't be queued: send it anyway and queue it if that fails
+                        None => match backend.send(donation).await {
+                            Ok(_) => {
+                                record_sent(&stats_db_path, donation);
+                                true
+                            }
+                            Err(e) => {
+                                report_failed(&stats_db_path, donation, backend.name(), &e);
+                                if let Err(e) =
+                                    donation::enqueue(&stats_db_path, donation, &e.to_string())
+                                {
+                                    error!("Failed to queue the unsent donation: {}", e);
+                                }
+                                false
+                            }
+                        },
+                    };
+                    if taken {
+                        sent += 1;
+                    }
+                }
+
+                if sent > 0 {
+                    // A late one had its sound with the thank-you
+                    if dated.is_none() {
+                        sound::play_yippee();
+                    }
+                    info!("✅ Donation sent successfully!");
+                    if username != "anon" && dated.is_none() {
+                        camera::capture_donation_photo(&photos_dir, &username, clock::timestamp());
+                    }
+                }
+                if let (Some(name), Some(w)) = (campaign_name, window.upgrade()) {
+                    notification_handler::clear(&w, "campaign");
+                    notification_handler::push(
+                        &w,
+                        if sent == parts.len() { 1 } else { 3 },
+                        &i18n::tr("campaign.sent", &[&name, &sent, &parts.len()]),
+                    );
+                }
+                SENDING.set(SENDING.get() - 1);
+            })
+            .unwrap();
+        }
+
+        /// Audits money too little to send, which stays in the box.
+        fn record_below_minimum(&self, amount: i32, fund_id: i32) {
+            warn!(
+                "⚠️  {} {currency} is below the {} {currency} minimum, not sending it to fund {}",
+                amount,
+                self.minimum_donation,
+                fund_id,
+                currency = money::currency()
+            );
+            audit::record(
+                &self.stats_db_path,
+                "below_minimum",
+                &format!(
+                    "{} {currency} for fund {}",
+                    amount,
+                    fund_id,
+                    currency = money::currency()
+                ),
+            );
+            transactions::record(
+                &self.stats_db_path,
+                &Transaction::DonationCancelled {
+                    amount,
+                    fund_id: Some(fund_id),
+                    reason: "below the minimum donation",
+                },
+            );
+        }
+
+        /// Audits a session over `large_threshold` and what became of it:
+        /// confirmed or declined by the donor, or committed on a timeout
+        /// without anyone confirming.
+        fn record_large(&self, amount: i32, fund_id: i32, outcome: &str) {
+            if self
+                .large_threshold
+                .is_none_or(|threshold| amount <= threshold)
+            {
+                return;
+            }
+            warn!(
+                "💰 Large donation: {} {currency} for fund {}, {}",
+                amount,
+                fund_id,
+                outcome,
+                currency = money::currency()
+            );
+            audit::record(
+                &self.stats_db_path,
+                "large_donation",
+                &format!(
+                    "{} {currency} for fund {}: {}",
+                    amount,
+                    fund_id,
+                    outcome,
+                    currency = money::currency()
+                ),
+            );
+        }
+
+        /// Commits money nobody attributed to a fund to the configured fallback
+        /// fund, anonymously. Without a fallback fund the amount is only audited.
+        fn submit_unattributed(&self, amount: i32, reason: &str) -> Option<i64> {
+            match self.fallback_fund_id {
+                Some(fund_id) => {
+                    info!(
+                        "💰 {}: committing {} {currency} to fallback fund {}",
+                        reason,
+                        amount,
+                        fund_id,
+                        currency = money::currency()
+                    );
+                    self.submit(
+                        fund_id,
+                        self.fallback_fund_name.clone(),
+                        "anon".to_string(),
+                        amount,
+                        true,
+                    )
+                }
+                None => {
+                    warn!(
+                        "⚠️  {}: {} {currency} left unattributed (no fallback_fund_id configured)",
+                        reason,
+                        amount,
+                        currency = money::currency()
+                    );
+                    self.leave_unattributed(None, amount, reason);
+                    None
+                }
+            }
+        }
+
+        /// Audits money no fund gets and queues it, so staff can pick one
+        /// from Diagnostics → Donation Queue.
+        fn leave_unattributed(&self, receipt: Option<i64>, amount: i32, reason: &str) {
+            audit::record(
+                &self.stats_db_path,
+                "unattributed",
+                &format!(
+                    "{}: {} {currency}",
+                    reason,
+                    amount,
+                    currency = money::currency()
+                ),
+            );
+            transactions::record(
+                &self.stats_db_path,
+                &Transaction::DonationCancelled {
+                    amount,
+                    fund_id: None,
+                    reason,
+                },
+            );
+            if let Err(e) =
+                donation::enqueue_unattributed(&self.stats_db_path, receipt, amount, reason)
+            {
+                error!("Failed to queue unattributed money: {}", e);
+            }
+        }
+
+        /// Sends a queued donation again, now. Unattributed money needs a
+        /// fund first (see `edit_queued`).
+        fn retry_queued(&self, id: i64) {
+            let Some(window) = self.window.upgrade() else {
+                return;
+            };
+            let queued = match donation::get_queued(&self.stats_db_path, id) {
+                Ok(Some(queued)) => queued,
+                Ok(None) => return,
+                Err(e) => {
+                    error!("Failed to read queued donation {}: {}", id, e);
+                    return;
+                }
+            };
+            let Some(donation) = queued.donation() else {
+                notification_handler::push(&window, 2, &i18n::tr("queue.needs_fund", &[]));
+                return;
+            };
+            let Some(backend) = self.backend.borrow().clone() else {
+                notification_handler::push(&window, 2, &i18n::tr("queue.no_backend", &[]));
+                return;
+            };
+            if !self.in_flight.borrow_mut().insert(id) {
+                notification_handler::push(&window, 2, &i18n::tr("queue.in_flight", &[]));
+                return;
+            }
+            info!("🔁 Retrying queued donation {}", id);
+            let stats_db_path = self.stats_db_path.clone();
+            let in_flight = self.in_flight.clone();
+            let weak = self.window.clone();
+            slint::spawn_local(async move {
+                let sent = send_queued(backend.as_ref(), &stats_db_path, id, &donation).await;
+                in_flight.borrow_mut().remove(&id);
+                if let Some(window) = weak.upgrade() {
+                    notification_handler::push(
+                        &window,
+                        if sent { 1 } else { 3 },
+                        &i18n::tr(
+                            if sent { "queue.sent" } else { "queue.failed" },
+                            &[&money::format(donation.amount.into()), &donation.fund_name],
+                        ),
+                    );
+                    show_queue(&window, &stats_db_path);
+                }
+            })
+            .unwrap();
+        }
+
+        /// Sends the queued donations whose backoff is over, one after the
+        /// other, until the backend confirms them.
+        fn retry_due(&self) {
+            let Some(backend) = self.backend.borrow().clone() else {
+                return;
+            };
+            let due: Vec<(i64, Donation)> = match donation::due(&self.stats_db_path) {
+                Ok(due) => due
+                    .into_iter()
+                    .filter(|queued| !self.in_flight.borrow().contains(&queued.id))
+                    .filter_map(|queued| Some((queued.id, queued.donation()?)))
+                    .collect(),
+                Err(e) => {
+                    error!("Failed to read the donation queue: {}", e);
+                    return;
+                }
+            };
+            if due.is_empty() {
+                return;
+            }
+            self.in_flight
+                .borrow_mut()
+                .extend(due.iter().map(|(id, _)| *id));
+            info!("🔁 Retrying {} queued donation(s)", due.len());
+            let backend = backend.clone();
+            let stats_db_path = self.stats_db_path.clone();
+            let in_flight = self.in_flight.clone();
+            let weak = self.window.clone();
+            slint::spawn_local(async move {
+                for (id, donation) in due {
+                    send_queued(backend.as_ref(), &stats_db_path, id, &donation).await;
+                    in_flight.borrow_mut().remove(&id);
+                }
+                if let Some(window) = weak.upgrade() {
+                    show_queue(&window, &stats_db_path);
+                }
+            })
+            .unwrap();
+        }
+
+        /// Whether a queued donation is being sent; staff can't change it
+        /// until that's over.
+        fn busy(&self, id: i64) -> bool {
+            let busy = self.in_flight.borrow().contains(&id);
+            if busy && let Some(window) = self.window.upgrade() {
+                notification_handler::push(&window, 2, &i18n::tr("queue.in_flight", &[]));
+            }
+            busy
+        }
+
+        /// Changes who a queued donation is from and which fund it goes to.
+        /// Picking a fund for unattributed money issues its receipt.
+        fn edit_queued(&self, id: i64, username: &str, fund_id: i32, fund_name: &str) {
+            if fund_id < 0 {
+                // A campaign's split happens when a session is sent
+                if let Some(window) = self.window.upgrade() {
+                    notification_handler::push(&window, 2, &i18n::tr("queue.campaign", &[]));
+                }
+                return;
+            }
+            if self.busy(id) {
+                return;
+            }
+            let queued = match donation::get_queued(&self.stats_db_path, id) {
+                Ok(Some(queued)) => queued,
+                Ok(None) => return,
+                Err(e) => {
+                    error!("Failed to read queued donation {}: {}", id, e);
+                    return;
+                }
+            };
+            let receipt = match queued.receipt {
+                Some(number) => {
+                    if let Err(e) =
+                        receipt::amend(&self.stats_db_path, number, username, fund_id, fund_name)
+                    {
+                        error!("Failed to amend receipt {}: {}", receipt::format(number), e);
+                    }
+                    Some(number)
+                }
+                None => self.next_receipt(username, queued.amount, fund_id, fund_name),
+            };
+            if queued.unattributed && queued.fund_id.is_none() {
+                audit::record(
+                    &self.stats_db_path,
+                    "unattributed_resolved",
+                    &format!(
+                        "{} {currency} to fund {}",
+                        queued.amount,
+                        fund_id,
+                        currency = money::currency()
+                    ),
+                );
+            }
+            audit::record(
+                &self.stats_db_path,
+                "queue_edited",
+                &format!(
+                    "{} {currency}, {} to fund {:?} -> {} to fund {}",
+                    queued.amount,
+                    queued.username,
+                    queued.fund_id,
+                    username,
+                    fund_id,
+                    currency = money::currency()
+                ),
+            );
+            if let Err(e) = donation::amend_queued(
+                &self.stats_db_path,
+                id,
+                receipt,
+                username,
+                fund_id,
+                fund_name,
+            ) {
+                error!("Failed to edit queued donation {}: {}", id, e);
+            }
+            if let Some(window) = self.window.upgrade() {
+                show_queue(&window, &self.stats_db_path);
+            }
+        }
+
+        /// Gives up on a queued donation, for the reason staff give.
+        fn discard_queued(&self, id: i64, reason: &str) {
+            if self.busy(id) {
+                return;
+            }
+            let queued = match donation::get_queued(&self.stats_db_path, id) {
+                Ok(Some(queued)) => queued,
+                Ok(None) => return,
+                Err(e) => {
+                    error!("Failed to read queued donation {}: {}", id, e);
+                    return;
+                }
+            };
+            warn!(
+                "🗑️  Discarding queued donation {}: {} {currency} ({})",
+                id,
+                queued.amount,
+                reason,
+                currency = money::currency()
+            );
+            audit::record(
+                &self.stats_db_path,
+                "queue_discarded",
+                &format!(
+                    "{}: {} {currency} from {} to fund {:?}, receipt {}",
+                    reason,
+                    queued.amount,
+                    queued.username,
+                    queued.fund_id,
+                    queued.receipt.map(receipt::format).unwrap_or_default(),
+                    currency = money::currency()
+                ),
+            );
+            if let Err(e) = donation::dequeue(&self.stats_db_path, id) {
+                error!("Failed to discard queued donation {}: {}", id, e);
+            }
+            if let Some(window) = self.window.upgrade() {
+                show_queue(&window, &self.stats_db_path);
+            }
+        }
+    }
+
+    /// Records a donation the backend took: the donation log (the wall and
+    /// reports) and the audit trail.
+    fn record_sent(stats_db_path: &str, donation: &Donation) {
+        donation_log::record(
+            stats_db_path,
+            donation.timestamp,
+            &donation.username,
+            donation.amount,
+            &donation.fund_name,
+            donation.auto_committed,
+        );
+        audit::record(
+            stats_db_path,
+            if donation.auto_committed {
+                "donation_auto_committed"
+            } else {
+                "donation_committed"
+            },
+            &format!(
+                "{} {currency} from {} to fund {}, receipt {}",
+                donation.amount,
+                donation.username,
+                donation.fund_id,
+                donation.receipt.map(receipt::format).unwrap_or_default(),
+                currency = money::currency()
+            ),
+        );
+        transactions::record(stats_db_path, &Transaction::DonationCommitted(donation));
+        alerts::donation(donation);
+    }
+
+    /// Sends a queued donation. Once taken it leaves the queue; otherwise it
+    /// stays with one more attempt counted. Returns whether it was taken.
+    async fn send_queued(
+        backend: &dyn DonationBackend,
+        stats_db_path: &str,
+        id: i64,
+        donation: &Donation,
+    ) -> bool {
+        match backend.send(donation).await {
+            Ok(_) => {
+                record_sent(stats_db_path, donation);
+                if let Err(e) = donation::dequeue(stats_db_path, id) {
+                    error!(
+                        "Failed to remove sent donation {} from the queue: {}",
+                        id, e
+                    );
+                }
+                true
+            }
+            Err(e) => {
+                report_failed(stats_db_path, donation, backend.name(), &e);
+                if let Err(e) = donation::retry_failed(stats_db_path, id, &e.to_string()) {
+                    error!("Failed to update queued donation {}: {}", id, e);
+                }
+                false
+            }
+        }
+    }
+
+    fn report_failed(stats_db_path: &str, donation: &Donation, backend: &str, e: &RequestError) {
+        error_code::report(
+            stats_db_path,
+            e.code(),
+            &format!(
+                "Failed to send donation of {} {currency} from {} to fund {} via {}: {}",
+                donation.amount,
+                donation.username,
+                donation.fund_id,
+                backend,
+                e,
+                currency = money::currency()
+            ),
+        );
+    }
+
+    /// Lists the donation queue for Diagnostics → Donation Queue.
+    fn show_queue(window: &MainWindow, stats_db_path: &str) {
+        use chrono::TimeZone;
+        let queued = match donation::queued(stats_db_path) {
+            Ok(queued) => queued,
+            Err(e) => {
+                error!("Failed to read the donation queue: {}", e);
+                Vec::new()
+            }
+        };
+        let fund_ids = window.get_available_fund_ids();
+        let items: Vec<QueueItem> = queued
+            .into_iter()
+            .map(|queued| QueueItem {
+                fund_index: queued
+                    .fund_id
+                    .and_then(|id| fund_ids.iter().position(|offered| offered == id))
+                    .map_or(-1, |i| i as i32),
+                id: queued.id as i32,
+                when: chrono::Local
+                    .timestamp_opt(queued.timestamp as i64, 0)
+                    .single()
+                    .map(|t| t.format("%Y-%m-%d %H:%M").to_string())
+                    .unwrap_or_default()
+                    .into(),
+                receipt: queued
+                    .receipt
+                    .map(receipt::format)
+                    .unwrap_or_default()
+                    .into(),
+                username: queued.username.into(),
+                amount: queued.amount,
+                fund_name: queued.fund_name.into(),
+                has_fund: queued.fund_id.is_some(),
+                attempts: queued.attempts as i32,
+                note: queued.note.into(),
+            })
+            .collect();
+        window.set_queue_items(slint::ModelRc::new(slint::VecModel::from(items)));
+    }
+
+    /// Puts the held donation on the main page's correction banner.
+    fn show_held(window: &MainWindow, held: &HeldDonation, left: Duration) {
+        window.set_held_username(held.username.clone().into());
+        window.set_held_fund_name(held.fund_name.clone().into());
+        window.set_held_amount(held.amount);
+        let fund_index = window
+            .get_available_fund_ids()
+            .iter()
+            .position(|id| id == held.fund_id);
+        window.set_held_fund_index(fund_index.map_or(-1, |index| index as i32));
+        window.set_edit_seconds_left(left.as_secs() as i32);
+    }
+
+    /// Cross-checks the bills credited this session against the driver's
+    /// ledger before anything is submitted. On a mismatch (a missed or
+    /// duplicated event) the ledger wins: the session amount is corrected,
+    /// the incident audited, and the corrected amount returned. `None` when
+    /// they agree, or when the ledger can't be read — that is reported but
+    /// doesn't hold up the donation.
+    fn reconcile(window: &MainWindow, stats_db_path: &str) -> Option<i32> {
+        let credited = window.get_session_bill_amount();
+        let ledger = match ledger::current_session_total(stats_db_path) {
+            Ok(total) => total,
+            Err(e) => {
+                error_code::report(
+                    stats_db_path,
+                    ErrorCode::Database,
+                    &format!("Failed to read bill ledger: {}", e),
+                );
+                return None;
+            }
+        };
+        if ledger == credited {
+            return None;
+        }
+
+        let shown = window.get_session_amount();
+        let corrected = shown - credited + ledger;
+        warn!(
+            "⚖️  Session amount mismatch: UI credited {} {currency} in bills, ledger has {} {currency}; correcting {} → {} {currency}",
+            credited,
+            ledger,
+            shown,
+            corrected,
+            currency = money::currency()
+        );
+        audit::record(
+            stats_db_path,
+            "reconciliation",
+            &format!(
+                "bills credited {} {currency}, ledger {} {currency}, session {} -> {} {currency}",
+                credited,
+                ledger,
+                shown,
+                corrected,
+                currency = money::currency()
+            ),
+        );
+        window.set_session_bill_amount(ledger);
+        window.set_session_amount(corrected);
+        match ledger::current_session_bills(stats_db_path) {
+            Ok(bills) => session_breakdown::replace_bills(window, &bills),
+            Err(e) => warn!("Failed to read ledger bills for the breakdown: {}", e),
+        }
+        Some(corrected)
+    }
+
+    /// What Done does with the settled session.
+    #[derive(Debug, PartialEq)]
+    enum Done {
+        Submit,
+        /// The ledger disagreed; the session now holds this amount
+        Corrected(i32),
+        /// Stale amount: the button is disabled below the minimum
+        BelowMinimum,
+    }
+
+    impl Done {
+        fn check(corrected: Option<i32>, amount: i32, minimum: i32) -> Self {
+            match corrected {
+                Some(corrected) => Done::Corrected(corrected),
+                None if amount < minimum => Done::BelowMinimum,
+                None => Done::Submit,
+            }
+        }
+
+        /// Whether the acceptors go back on: a blocked donor stays on the
+        /// insert page and may well insert more.
+        fn accepting_money(&self) -> bool {
+            *self != Done::Submit
+        }
+    }
+
+    /// Finishes the session once Done has settled. It is checked against
+    /// the ledger and the minimum first; if either check fails the donor
+    /// stays on the page with a notification, `submitting` is cleared so
+    /// Done works again, and the check is returned for the caller to switch
+    /// the acceptors back on. Otherwise the session is sent, or held for
+    /// corrections or the details, and the thank-you shown.
+    fn finish_donation(
+        window: &MainWindow,
+        sink: &DonationSink,
+        username: String,
+        fund_id: i32,
+    ) -> Done {
+        let corrected = reconcile(window, &sink.stats_db_path);
+        let amount = window.get_session_amount();
+        let done = Done::check(corrected, amount, sink.minimum_donation);
+        let blocked = match done {
+            Done::Submit => None,
+            Done::Corrected(corrected) => Some(i18n::tr(
+                "donation.amount_corrected",
+                &[&money::format(corrected.into())],
+            )),
+            Done::BelowMinimum => Some(i18n::tr(
+                "donation.below_minimum",
+                &[&money::format(sink.minimum_donation.into())],
+            )),
+        };
+        if let Some(message) = blocked {
+            notification_handler::push(window, 2, &message);
+            window.set_submitting(false);
+            return done;
+        }
+
+        info!(
+            "💰 Processing donation: {} {currency} from {} to fund {}",
+            amount,
+            username,
+            fund_id,
+            currency = money::currency()
+        );
+        let fund_name = window.get_session_fund_name().to_string();
+        // Event mode has nothing to pick, so nothing to correct
+        let event_mode = window.get_event_mode();
+        // The flow asks for fund and name now that the money is in; the
+        // held donation is finished from Donate (`Flow::from_config` makes
+        // sure there is an edit grace)
+        let details_after = !event_mode && sink.details_after;
+        let receipt = if !details_after && (sink.edit_grace.is_zero() || event_mode) {
+            sink.submit(fund_id, fund_name, username, amount, false)
+        } else {
+            sink.hold(fund_id, fund_name, username, amount)
+        };
+        window.set_last_receipt(receipt.map(receipt::format).unwrap_or_default().into());
+        window.set_session_amount(0);
+        if details_after {
+            window.invoke_ask_details();
+        } else {
+            window.invoke_show_confetti_after_auto_approve();
+        }
+        done
+    }
+
+    /// Switches the acceptors back on for the session still open, after
+    /// `finish_donation` blocked it. Unlike `start-accepting-money` this
+    /// doesn't start a new ledger session.
+    fn resume_accepting(
+        window: &MainWindow,
+        cashcode_tx: &Sender<bill_acceptor::CashCodeCommand>,
+        cctalk_tx: Option<&Sender<cctalk::CoinAcceptorCommand>>,
+    ) {
+        if window.get_acceptance_locked() {
+            return;
+        }
+        info!("📥 Done was blocked, taking money again");
+        window.set_accepting_money(true);
+        if cashcode_tx
+            .send(bill_acceptor::CashCodeCommand::Resume)
+            .is_err()
+        {
+            error!("Failed to send resume command to CashCode");
+        }
+        if let Some(cctalk_tx) = cctalk_tx
+            && cctalk_tx.send(cctalk::CoinAcceptorCommand::Enable).is_err()
+        {
+            error!("Failed to send enable command to the coin acceptor");
+        }
+    }
+
+    /// In event mode, once money is in, the session commits after the short
+    /// settle period instead of waiting out the full inactivity timeout.
+    fn session_timeout(
+        window: &MainWindow,
+        inactivity: Duration,
+        event_settle: Duration,
+    ) -> Duration {
+        if window.get_event_mode() && window.get_session_amount() > 0 {
+            event_settle
+        } else {
+            inactivity
+        }
+    }
+
+    /// Starts the inactivity timeout over, with its on-screen countdown.
+    fn restart_inactivity(
+        window: &MainWindow,
+        cashcode_tx: &Sender<bill_acceptor::CashCodeCommand>,
+        sink: &DonationSink,
+        (timer_slot, ticker_slot): (&InactivitySlot, &InactivitySlot),
+        timeout: Duration,
+    ) {
+        window.set_inactivity_seconds_left(timeout.as_secs() as _);
+        let timer =
+            spawn_inactivity_timer(window.as_weak(), cashcode_tx.clone(), sink.clone(), timeout);
+        *timer_slot.borrow_mut() = Some(timer);
+
+        let weak_tick = window.as_weak();
+        let ticker = slint::Timer::default();
+        ticker.start(
+            slint::TimerMode::Repeated,
+            Duration::from_secs(1),
+            move || {
+                if let Some(w) = weak_tick.upgrade() {
+                    let current = w.get_inactivity_seconds_left();
+                    if current > 0 {
+                        w.set_inactivity_seconds_left(current - 1);
+                    }
+                }
+            },
+        );
+        *ticker_slot.borrow_mut() = Some(ticker);
+    }
+
+    /// Spawns a single-shot inactivity timer. Returns the Timer (must be kept alive).
+    fn spawn_inactivity_timer(
+        weak: slint::Weak<MainWindow>,
+        cashcode_tx: Sender<bill_acceptor::CashCodeCommand>,
+        sink: DonationSink,
+        timeout: Duration,
+    ) -> slint::Timer {
+        let timer = slint::Timer::default();
+        timer.start(slint::TimerMode::SingleShot, timeout, move || {
+            if let Some(window) = weak.upgrade() {
+                // Guard: only act if we're still on the InsertMoney page
+                if !window.get_on_insert_money_page() {
+                    info!("⏱️  Inactivity timeout fired but not on InsertMoney page — ignoring");
+                    return;
+                }
+                // Done was tapped; the settle timer finishes the session
+                if window.get_submitting() {
+                    return;
+                }
+                if cashcode_tx
+                    .send(bill_acceptor::CashCodeCommand::Disable)
+                    .is_err()
+                {
+                    error!("Failed to send disable command on inactivity timeout");
+                }
+                // Nobody is there to confirm a correction, so the ledger
+                // amount is committed directly
+                let amount = reconcile(&window, &sink.stats_db_path)
+                    .unwrap_or_else(|| window.get_session_amount());
+                if amount < sink.minimum_donation {
+                    // No money inserted, or too little to send — auto-cancel
+                    if amount > 0 {
+                        sink.record_below_minimum(amount, window.get_session_fund_id());
+                    }
+                    info!(
+                        "⏱️  Inactivity timeout: auto-cancelling ({} {currency} inserted)",
+                        amount,
+                        currency = money::currency()
+                    );
+                    window.set_session_amount(0);
+                    window.set_session_username(slint::SharedString::default());
+                    window.invoke_cancel_insert_money();
+                } else {
+                    // Money inserted — auto-approve
+                    info!(
+                        "⏱️  Inactivity timeout: auto-approving {} {currency}",
+                        amount,
+                        currency = money::currency()
+                    );
+                    let fund_id = window.get_session_fund_id();
+                    sink.record_large(amount, fund_id, "committed on timeout, not confirmed");
+                    window.set_large_confirm_open(false);
+                    let receipt = if fund_id == 0 {
+                        sink.submit_unattributed(amount, "Inactivity timeout")
+                    } else {
+                        sink.submit(
+                            fund_id,
+                            window.get_session_fund_name().to_string(),
+                            window.get_session_username().to_string(),
+                            amount,
+                            true,
+                        )
+                    };
+                    window
+                        .set_last_receipt(receipt.map(receipt::format).unwrap_or_default().into());
+                    window.set_session_amount(0);
+                    window.set_session_username(slint::SharedString::default());
+                    window.set_session_fund_id(0);
+                    window.invoke_show_confetti_after_auto_approve();
+                }
+            }
+        });
+        timer
+    }
+
+    pub fn init(
+        app: &MainWindow,
+        config: &Config,
+        cashcode_tx: Sender<bill_acceptor::CashCodeCommand>,
+        cctalk_tx: Sender<cctalk::CoinAcceptorCommand>,
+        campaigns: Rc<RefCell<Vec<Campaign>>>,
+        print_wake: Option<Sender<()>>,
+        publisher: Option<&Publisher>,
+    ) -> BackendSlot {
+        // Shared timer slots — replaced on each entry to InsertMoney page or bill insertion
+        // Using Rc<RefCell<>> because all callbacks run on the single Slint event-loop thread.
+        let inactivity_timer: InactivitySlot = Rc::new(RefCell::new(None));
+        let countdown_ticker: InactivitySlot = Rc::new(RefCell::new(None));
+        let sink = DonationSink::new(config, app, campaigns, print_wake, publisher);
+        let backend = sink.backend.clone();
+        let inactivity = Duration::from_secs(config.session_timeout_secs);
+        let event_settle = Duration::from_secs(config.event_mode.settle_secs);
+
+        // Held when the app went down: the donor is long gone, send it
+        if let Some(held) = kv::get(&config.stats_db_path, &kv::HELD_DONATION) {
+            kv::remove(&config.stats_db_path, &kv::HELD_DONATION);
+            info!(
+                "📤 Sending donation {} held before the restart",
+                held.receipt.map(receipt::format).unwrap_or_default()
+            );
+            sink.send(held.into());
+        }
+        // Held donation countdown, event batch schedule
+        let ticker = slint::Timer::default();
+        ticker.start(slint::TimerMode::Repeated, Duration::from_secs(1), {
+            let sink = sink.clone();
+            move || {
+                sink.tick();
+                sink.tick_batch();
+            }
+        });
+        std::mem::forget(ticker);
+        // Donations the backend didn't take, retried with backoff
+        let queue_retry = slint::Timer::default();
+        queue_retry.start(slint::TimerMode::Repeated, QUEUE_RETRY_CHECK, {
+            let sink = sink.clone();
+            move || sink.retry_due()
+        });
+        std::mem::forget(queue_retry);
+        // Paid by QR code (qr_payment_handler), recorded like cash
+        app.on_qr_paid({
+            let sink = sink.clone();
+            let weak = app.as_weak();
+            move |amount| {
+                let Some(window) = weak.upgrade() else {
+                    return;
+                };
+                let username = window.get_session_username().to_string();
+                let fund_id = window.get_session_fund_id();
+                let fund_name = window.get_session_fund_name().to_string();
+                info!(
+                    "📱 Processing QR donation: {} {currency} from {} to fund {}",
+                    amount,
+                    username,
+                    fund_id,
+                    currency = money::currency()
+                );
+                let receipt = if sink.edit_grace.is_zero() {
+                    sink.submit(fund_id, fund_name, username, amount, false)
+                } else {
+                    sink.hold(fund_id, fund_name, username, amount)
+                };
+                window.set_last_receipt(receipt.map(receipt::format).unwrap_or_default().into());
+                window.invoke_show_confetti_after_auto_approve();
+            }
+        });
+        app.on_donation_edited({
+            let sink = sink.clone();
+            move |username, fund_id, fund_name| {
+                sink.edit(username.to_string(), fund_id, fund_name.to_string())
+            }
+        });
+
+        // Diagnostics → Donation Queue
+        app.on_fetch_queue({
+            let weak = app.as_weak();
+            let stats_db_path = config.stats_db_path.clone();
+            move || {
+                if let Some(window) = weak.upgrade() {
+                    show_queue(&window, &stats_db_path);
+                }
+            }
+        });
+        app.on_queue_retry({
+            let sink = sink.clone();
+            move |id| sink.retry_queued(id as i64)
+        });
+        app.on_queue_edit({
+            let sink = sink.clone();
+            move |id, username, fund_id, fund_name| {
+                sink.edit_queued(id as i64, &username, fund_id, &fund_name)
+            }
+        });
+        app.on_queue_discard({
+            let sink = sink.clone();
+            move |id, reason| sink.discard_queued(id as i64, &reason)
+        });
+
+        app.set_minimum_donation(config.minimum_donation);
+        app.set_large_donation_threshold(config.large_donation_threshold.unwrap_or(0));
+        app.on_large_donation({
+            let sink = sink.clone();
+            let weak = app.as_weak();
+            move |amount, confirmed| {
+                let Some(window) = weak.upgrade() else {
+                    return;
+                };
+                let outcome = if confirmed {
+                    "confirmed"
+                } else {
+                    "declined, still on the insert page"
+                };
+                sink.record_large(amount, window.get_session_fund_id(), outcome);
+            }
+        });
+        // The Diagnostics toggle outlives restarts and wins over config
+        let event_mode =
+            kv::get(&config.stats_db_path, &kv::EVENT_MODE).unwrap_or(config.event_mode.enabled);
+        app.set_event_mode(event_mode);
+        app.set_event_fund_id(config.event_mode.fund_id.unwrap_or(0));
+        app.set_event_fund_name(config.event_mode.fund_name.clone().into());
+        if event_mode {
+            info!("🎉 Event mode enabled — anonymous donations only");
+        }
+
+        app.on_event_mode_toggled({
+            let stats_db_path = config.stats_db_path.clone();
+            move |enabled| {
+                info!(
+                    "🎉 Event mode {} from diagnostics",
+                    if enabled { "enabled" } else { "disabled" }
+                );
+                kv::set(&stats_db_path, &kv::EVENT_MODE, &enabled);
+                audit::record(
+                    &stats_db_path,
+                    "event_mode",
+                    if enabled { "enabled" } else { "disabled" },
+                );
+            }
+        });
+
+        app.on_done_clicked({
+            let cashcode_tx = cashcode_tx.clone();
+            let cctalk_tx = cctalk_tx.clone();
+            // A dry run takes bills only, see coin_acceptor
+            let coins = !config.dry_run;
+            let sink = sink.clone();
+            let weak = app.as_weak();
+            move |username, fund_id, _amount| {
+                let Some(window) = weak.upgrade() else {
+                    return;
+                };
+                // A double tap, or Done again while this session is settling
+                // or being sent: only the first press submits
+                if window.get_submitting() {
+                    debug!("Done pressed again while submitting, ignored");
+                    return;
+                }
+                window.set_submitting(true);
+
+                // Stop accepting money immediately
+                if cashcode_tx
+                    .send(bill_acceptor::CashCodeCommand::Disable)
+                    .is_err()
+                {
+                    error!("Failed to send disable command to CashCode on done click");
+                }
+                if cctalk_tx
+                    .send(cctalk::CoinAcceptorCommand::Disable)
+                    .is_err()
+                {
+                    error!("Failed to send disable command to the coin acceptor on done click");
+                }
+
+                // A bill stacked as Done was tapped is credited after the
+                // tap; wait for the driver to have none in transit so it
+                // counts towards this donation rather than getting lost
+                window.set_settling(true);
+                let started = clock::instant();
+                let weak = weak.clone();
+                let sink = sink.clone();
+                let cashcode_tx = cashcode_tx.clone();
+                let cctalk_tx = cctalk_tx.clone();
+                let timer = Rc::new(slint::Timer::default());
+                let timer_weak = Rc::downgrade(&timer);
+                timer.start(
+                    slint::TimerMode::Repeated,
+                    Duration::from_millis(100),
+                    move || {
+                        let Some(window) = weak.upgrade() else {
+                            return;
+                        };
+                        let elapsed = clock::since(started);
+                        let in_transit = window.get_bill_in_transit();
+                        if elapsed < SETTLE_MIN || (in_transit && elapsed < SETTLE_MAX) {
+                            return;
+                        }
+                        if let Some(timer) = timer_weak.upgrade() {
+                            timer.stop();
+                        }
+                        window.set_settling(false);
+                        if in_transit {
+                            warn!(
+                                "⏳ Bill still in transit after {:?}, finalizing without it",
+                                elapsed
+                            );
+                        }
+                        let done = finish_donation(&window, &sink, username.to_string(), fund_id);
+                        if done.accepting_money() {
+                            resume_accepting(&window, &cashcode_tx, coins.then_some(&cctalk_tx));
+                        }
+                    },
+                );
+                // Stopped from inside the callback once settled
+                std::mem::forget(timer);
+            }
+        });
+
+        // session-abandoned: money was inserted but the page was left without
+        // Done (e.g. cancelling the game screen)
+        app.on_session_abandoned({
+            let sink = sink.clone();
+            let weak = app.as_weak();
+            move |amount| {
+                let Some(window) = weak.upgrade() else {
+                    return;
+                };
+                if amount <= 0 {
+                    return;
+                }
+                let username = window.get_session_username();
+                match router::abandoned_to(window.get_session_fund_id(), &username) {
+                    Some((fund_id, username)) => {
+                        info!(
+                            "💰 Session abandoned: committing {} {currency} from {} to fund {}",
+                            amount,
+                            username,
+                            fund_id,
+                            currency = money::currency()
+                        );
+                        sink.submit(
+                            fund_id,
+                            window.get_session_fund_name().to_string(),
+                            username.to_string(),
+                            amount,
+                            true,
+                        );
+                    }
+                    None => {
+                        sink.submit_unattributed(amount, "Session abandoned");
+                    }
+                }
+            }
+        });
+
+        // enter-insert-money: start the inactivity timer + countdown ticker
+        let weak_enter = app.as_weak();
+        let cashcode_tx_enter = cashcode_tx.clone();
+        let sink_enter = sink.clone();
+        let timer_enter = inactivity_timer.clone();
+        let ticker_enter = countdown_ticker.clone();
+        app.on_enter_insert_money(move || {
+            let Some(w) = weak_enter.upgrade() else {
+                return;
+            };
+            // A new session; the last one's send carries on by itself
+            w.set_submitting(false);
+            let timeout = session_timeout(&w, inactivity, event_settle);
+            info!(
+                "⏱️  InsertMoney entered — starting {:?} inactivity timer",
+                timeout
+            );
+            restart_inactivity(
+                &w,
+                &cashcode_tx_enter,
+                &sink_enter,
+                (&timer_enter, &ticker_enter),
+                timeout,
+            );
+        });
+
+        // activity-on-insert-money: reset both timers when a bill is inserted
+        let weak_activity = app.as_weak();
+        let cashcode_tx_activity = cashcode_tx.clone();
+        let sink_activity = sink;
+        let timer_activity = inactivity_timer.clone();
+        let ticker_activity = countdown_ticker.clone();
+        app.on_activity_on_insert_money(move || {
+            let Some(w) = weak_activity.upgrade() else {
+                return;
+            };
+            let timeout = session_timeout(&w, inactivity, event_settle);
+            info!("⏱️  Bill inserted — resetting inactivity timer");
+            restart_inactivity(
+                &w,
+                &cashcode_tx_activity,
+                &sink_activity,
+                (&timer_activity, &ticker_activity),
+                timeout,
+            );
+        });
+
+        // leave-insert-money: stop both timers when user exits normally (cancel or done)
+        let timer_leave = inactivity_timer.clone();
+        let ticker_leave = countdown_ticker.clone();
+        app.on_leave_insert_money(move || {
+            info!("⏱️  InsertMoney left — stopping inactivity timers");
+            *timer_leave.borrow_mut() = None; // drops Timer → cancels it
+            *ticker_leave.borrow_mut() = None; // drops Timer → cancels it
+        });
+
+        // Drive confetti animation from Rust with a two-step approach:
+        // 1. show-confetti is already set to true by the Slint side (overlay is created)
+        // 2. After a brief delay, set confetti-falling = true (triggers the animations)
+        // 3. After animation completes, reset both properties
+        let weak = app.as_weak();
+        app.on_confetti_started(move || {
+            crate::sound::play_yippee();
+            // Step 1: trigger falling after a short delay so the component is fully rendered
+            let weak_fall = weak.clone();
+            slint::Timer::single_shot(std::time::Duration::from_millis(50), move || {
+                if let Some(window) = weak_fall.upgrade() {
+                    window.set_confetti_falling(true);
+                }
+            });
+
+            // Step 2: dismiss everything after animations complete
+            let weak_dismiss = weak.clone();
+            slint::Timer::single_shot(std::time::Duration::from_millis(2500), move || {
+                if let Some(window) = weak_dismiss.upgrade() {
+                    window.set_confetti_falling(false);
+                    window.set_show_confetti(false);
+                }
+            });
+        });
+
+        // Warmup: run the animation once at startup (no sound) so all SVGs are
+        // rasterized and cached before the first real donation triggers it.
+        let weak_warmup = app.as_weak();
+        slint::Timer::single_shot(std::time::Duration::from_millis(500), move || {
+            if let Some(window) = weak_warmup.upgrade() {
+                info!("🎉 Warming up confetti cache...");
+                window.set_show_confetti(true);
+                window.set_confetti_falling(true);
+
+                let weak_done = weak_warmup.clone();
+                slint::Timer::single_shot(std::time::Duration::from_millis(1000), move || {
+                    if let Some(window) = weak_done.upgrade() {
+                        window.set_confetti_falling(false);
+                        window.set_show_confetti(false);
+                    }
+                });
+            }
+        });
+
+        backend
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        #[test]
+        fn blocked_done_keeps_accepting_money() {
+            let corrected = Done::check(Some(1500), 1000, 0);
+            assert_eq!(corrected, Done::Corrected(1500));
+            assert!(corrected.accepting_money());
+
+            let below = Done::check(None, 50, 100);
+            assert_eq!(below, Done::BelowMinimum);
+            assert!(below.accepting_money());
+        }
+
+        #[test]
+        fn submitted_done_stops_accepting_money() {
+            let done = Done::check(None, 100, 100);
+            assert_eq!(done, Done::Submit);
+            assert!(!done.accepting_money());
+        }
+    }
+}
+
+mod logs_handler {
+    use super::*;
+    use slint::{Image, ModelRc, VecModel};
+
+    /// How many past donations to show on the wall. Each entry with a photo
+    /// loads that photo into memory at full resolution, so this is kept modest.
+    const LOG_LIMIT: i64 = 24;
+
+    fn format_relative_time(timestamp: u64) -> String {
+        let diff = clock::timestamp().saturating_sub(timestamp);
+        if diff < 60 {
+            "just now".to_string()
+        } else if diff < 3600 {
+            format!("{}m ago", diff / 60)
+        } else if diff < 86400 {
+            format!("{}h ago", diff / 3600)
+        } else {
+            format!("{}d ago", diff / 86400)
+        }
+    }
+
+    pub fn init(app: &MainWindow, config: &Config) {
+        let stats_db_path = config.stats_db_path.clone();
+        let photos_dir = config.photos_dir.clone();
+        let weak = app.as_weak();
+
+        app.on_fetch_logs(move || {
+            let stats_db_path = stats_db_path.clone();
+            let photos_dir = photos_dir.clone();
+            let weak = weak.clone();
+
+            thread::spawn(move || {
+                // The DB read is the slow part, so it happens off the UI thread.
+                // `slint::Image` isn't `Send`, though, so it can't be built here —
+                // loading each photo has to happen after we hop back to the UI thread.
+                let entries = match donation_log::fetch_recent(&stats_db_path, LOG_LIMIT) {
+                    Ok(entries) => entries,
+                    Err(e) => {
+                        error!("Failed to fetch donation log: {}", e);
+                        Vec::new()
+                    }
+                };
+
+                let _ = slint::invoke_from_event_loop(move || {
+                    let Some(window) = weak.upgrade() else {
+                        return;
+                    };
+                    let items: Vec<DonationLogItem> = entries
+                        .into_iter()
+                        .map(|entry| {
+                            let is_anon = entry.username == "anon";
+                            let photo = if is_anon {
+                                None
+                            } else {
+                                let path = std::path::Path::new(&photos_dir)
+                                    .join(camera::photo_filename(entry.timestamp, &entry.username));
+                                Image::load_from_path(&path).ok()
+                            };
+                            DonationLogItem {
+                                username: entry.username.into(),
+                                amount: entry.amount,
+                                fund_name: entry.fund_name.into(),
+                                when: format_relative_time(entry.timestamp).into(),
+                                is_anon,
+                                has_photo: photo.is_some(),
+                                photo: photo.unwrap_or_default(),
+                            }
+                        })
+                        .collect();
+                    window.set_donation_logs(ModelRc::new(VecModel::from(items)));
+                });
+            });
+        });
+    }
+}
+
+mod stats_handler {
+    use super::*;
+    use slint::{ModelRc, VecModel};
+
+    pub fn init(app: &MainWindow, config: &Config) {
+        let stats_db_path = config.stats_db_path.clone();
+        let weak = app.as_weak();
+        app.set_stats_days(charts::DAYS as i32);
+
+        app.on_fetch_stats(move || {
+            let stats_db_path = stats_db_path.clone();
+            let weak = weak.clone();
+
+            // Scans a month of rows; the models are built back on the UI
+            // thread since they aren't `Send`
+            thread::spawn(move || {
+                let charts = match charts::build(&stats_db_path) {
+                    Ok(charts) => charts,
+                    Err(e) => {
+                        error!("Failed to build stats charts: {}", e);
+                        Vec::new()
+                    }
+                };
+                let (denominations, unattributed) = match denominations::build(&stats_db_path) {
+                    Ok(result) => result,
+                    Err(e) => {
+                        error!("Failed to build denomination stats: {}", e);
+                        (Vec::new(), 0)
+                    }
+                };
+                for denomination in denominations.iter().filter(|d| d.misbehaving) {
+                    warn!(
+                        "{} is rejected {:.0}% of the time; clean the sensors",
+                        money::format(denomination.nominal),
+                        denomination.reject_rate() * 100.0
+                    );
+                }
+
+                let _ = slint::invoke_from_event_loop(move || {
+                    let Some(window) = weak.upgrade() else {
+                        return;
+                    };
+                    let rows: Vec<DenominationRow> = denominations
+                        .iter()
+                        .map(|denomination| DenominationRow {
+                            label: money::format(denomination.nominal).into(),
+                            accepted: denomination.accepted as i32,
+                            rejected: denomination.rejected as i32,
+                            rate: format!("{:.0}%", denomination.reject_rate() * 100.0).into(),
+                            misbehaving: denomination.misbehaving,
+                        })
+                        .collect();
+                    window.set_stats_denominations(ModelRc::new(VecModel::from(rows)));
+                    window.set_stats_unattributed_rejections(unattributed as i32);
+
+                    let items: Vec<ChartData> = charts
+                        .into_iter()
+                        .map(|chart| ChartData {
+                            title: chart.title.into(),
+                            max_label: chart.max_label.into(),
+                            first_day: chart.first_day.into(),
+                            last_day: chart.last_day.into(),
+                            legend: ModelRc::new(VecModel::from(
+                                chart
+                                    .legend
+                                    .into_iter()
+                                    .map(slint::SharedString::from)
+                                    .collect::<Vec<_>>(),
+                            )),
+                            bars: chart.bars,
+                            points: ModelRc::new(VecModel::from(
+                                chart
+                                    .points
+                                    .into_iter()
+                                    .map(|point| ChartPoint {
+                                        x: point.x,
+                                        y: point.y,
+                                        series: point.series,
+                                    })
+                                    .collect::<Vec<_>>(),
+                            )),
+                        })
+                        .collect();
+                    window.set_stats_charts(ModelRc::new(VecModel::from(items)));
+                });
+            });
+        });
+    }
+}
+
+mod admin_handler {
+    use super::*;
+    use bill_acceptor::{CashCodeCommand, CountsReply};
+    use slint::{ModelRc, VecModel};
+    use std::sync::mpsc::channel;
+
+    /// How long to wait for the driver to answer with its counters; it
+    /// only takes commands between polls.
+    const DRIVER_TIMEOUT: Duration = Duration::from_secs(5);
+
+    pub fn init(app: &MainWindow, config: &Config, cashcode_tx: Sender<CashCodeCommand>) {
+        // Also taken from the listener's `POST /collection`, PIN or not
+        let weak = app.as_weak();
+        let stats_db_path = config.stats_db_path.clone();
+        let tx = cashcode_tx.clone();
+        app.on_record_collection(move |collector| {
+            let collector = collector.trim().to_string();
+            if collector.is_empty() {
+                warn!("Not recording a collection without a collector");
+                return;
+            }
+            let stats_db_path = stats_db_path.clone();
+            ask(
+                &tx,
+                CashCodeCommand::ClearCounts,
+                weak.clone(),
+                move |w, before| match before {
+                    Ok(before) => collected(w, &stats_db_path, &collector, &before),
+                    Err(e) => {
+                        error!("Failed to record a collection by {}: {}", collector, e);
+                        w.set_admin_counts_status(LogEntry {
+                            level: 3,
+                            text: format!("Collection not recorded: {}", e).into(),
+                        });
+                    }
+                },
+            );
+        });
+
+        let admin_pin = config.admin_pin.clone().filter(|pin| !pin.is_empty());
+        // Token for looking member PINs up, with features.member_roles
+        let members = match (config.features.member_roles, config.token.clone()) {
+            (true, None) => {
+                warn!("⚠️  features.member_roles needs a token, member PINs are off");
+                None
+            }
+            (enabled, token) => token.filter(|_| enabled),
+        };
+        if admin_pin.is_none() && members.is_none() {
+            info!("No admin_pin configured, the service page is off");
+            return;
+        }
+        app.set_admin_enabled(true);
+        let gate = Rc::new(RefCell::new(admin::PinGate::new(admin_pin)));
+        let service_roles = Rc::new(config.service_roles.clone());
+
+        let weak = app.as_weak();
+        let stats_db_path = config.stats_db_path.clone();
+        app.on_admin_pin_entered(move |entered| {
+            let Some(w) = weak.upgrade() else {
+                return;
+            };
+            if let Some(left) = gate.borrow_mut().refused(clock::instant()) {
+                refuse(
+                    &w,
+                    format!("Locked, try again in {} s", left.as_secs().max(1)),
+                );
+                return;
+            }
+            if gate.borrow().is_admin_pin(&entered) {
+                gate.borrow_mut().granted();
+                unlock(&w, &stats_db_path, None);
+                return;
+            }
+            let Some(token) = members.clone() else {
+                let outcome = gate.borrow_mut().failed(clock::instant());
+                wrong(&w, &stats_db_path, outcome, "");
+                return;
+            };
+
+            let gate = gate.clone();
+            let service_roles = service_roles.clone();
+            let stats_db_path = stats_db_path.clone();
+            let weak = weak.clone();
+            slint::spawn_local(async move {
+                let identity = member::identify(&token, &entered).await;
+                let Some(w) = weak.upgrade() else {
+                    return;
+                };
+                match identity {
+                    Ok(Some(identity)) if identity.has_any(&service_roles) => {
+                        gate.borrow_mut().granted();
+                        unlock(&w, &stats_db_path, Some(&identity.username));
+                    }
+                    Ok(Some(identity)) => {
+                        let outcome = gate.borrow_mut().failed(clock::instant());
+                        let detail = format!("{} has no service role", identity.username);
+                        wrong(&w, &stats_db_path, outcome, &detail);
+                    }
+                    Ok(None) => {
+                        let outcome = gate.borrow_mut().failed(clock::instant());
+                        wrong(&w, &stats_db_path, outcome, "");
+                    }
+                    // Not the member's fault, so no attempt is used up
+                    Err(e) => refuse(&w, format!("Couldn't check the PIN: {}", e)),
+                }
+            })
+            .unwrap();
+        });
+
+        let weak = app.as_weak();
+        let tx = cashcode_tx.clone();
+        app.on_admin_refresh(move || {
+            ask(&tx, CashCodeCommand::Counts, weak.clone(), |w, counts| {
+                show(w, counts);
+            });
+        });
+
+        let stats_db_path = config.stats_db_path.clone();
+        app.on_admin_reset_acceptor(move || {
+            info!("🔄 Service page: resetting bill acceptor");
+            if cashcode_tx.send(CashCodeCommand::Reset).is_err() {
+                error!("Failed to send Reset to bill acceptor");
+                return;
+            }
+            audit::record(&stats_db_path, "bill_reset", "admin");
+        });
+
+        let weak = app.as_weak();
+        let stats_db_path = config.stats_db_path.clone();
+        app.on_admin_reconcile(move |days| {
+            reconcile(&weak, &stats_db_path, days);
+        });
+    }
+
+    /// Compares the last `days` days of donations sent with what the
+    /// gateway recorded for the kiosk.
+    fn reconcile(weak: &slint::Weak<MainWindow>, stats_db_path: &str, days: i32) {
+        let Some(w) = weak.upgrade() else {
+            return;
+        };
+        let to = clock::timestamp();
+        let from = to.saturating_sub(days.max(1) as u64 * 24 * 3600);
+        info!("⚖ Service page: reconciling the last {} day(s)", days);
+        let Some(token) = crate::config::current_token() else {
+            show_lines(
+                &w,
+                vec![(
+                    3,
+                    format!(
+                        "{} Reconciling needs the gateway token",
+                        ErrorCode::ApiNoToken
+                    ),
+                )],
+            );
+            return;
+        };
+        let local = match reconcile::local(stats_db_path, from, to) {
+            Ok(local) => local,
+            Err(e) => {
+                error_code::report(
+                    stats_db_path,
+                    ErrorCode::Database,
+                    &format!("Failed to read the transactions to reconcile: {}", e),
+                );
+                show_lines(&w, vec![(3, format!("{} {}", ErrorCode::Database, e))]);
+                return;
+            }
+        };
+        w.set_admin_reconcile_lines(ModelRc::default());
+        w.set_admin_reconcile_busy(true);
+
+        let weak = weak.clone();
+        let stats_db_path = stats_db_path.to_string();
+        slint::spawn_local(async move {
+            let gateway = reconcile::gateway(&token, from, to).await;
+            let Some(w) = weak.upgrade() else {
+                return;
+            };
+            w.set_admin_reconcile_busy(false);
+            match gateway {
+                Ok(gateway) => {
+                    let result = reconcile::compare(from, to, local, gateway);
+                    info!("⚖ Reconciled: {}", result.summary());
+                    audit::record(&stats_db_path, "reconciled", &result.summary());
+                    show_lines(&w, reconciliation_lines(&result));
+                }
+                Err(e) => {
+                    warn!("⚖ Couldn't fetch the gateway's donations: {}", e);
+                    show_lines(
+                        &w,
+                        vec![(
+                            3,
+                            format!("{} Couldn't fetch the gateway's donations: {}", e.code(), e),
+                        )],
+                    );
+                }
+            }
+        })
+        .unwrap();
+    }
+
+    fn show_lines(window: &MainWindow, lines: Vec<(i32, String)>) {
+        let lines: Vec<LogEntry> = lines
+            .into_iter()
+            .map(|(level, text)| LogEntry {
+                level,
+                text: text.into(),
+            })
+            .collect();
+        window.set_admin_reconcile_lines(ModelRc::new(VecModel::from(lines)));
+    }
+
+    /// The totals, then each mismatch on a line of its own.
+    fn reconciliation_lines(result: &reconcile::Reconciliation) -> Vec<(i32, String)> {
+        use chrono::TimeZone;
+        let when = |timestamp: u64| {
+            chrono::Local
+                .timestamp_opt(timestamp as i64, 0)
+                .single()
+                .map(|t| t.format("%Y-%m-%d %H:%M").to_string())
+                .unwrap_or_default()
+        };
+        let from_whom = |username: &str| match username {
+            "" | "anon" => "anonymous".to_string(),
+            username => format!("@{}", username),
+        };
+        let receipt = |number: Option<i64>| {
+            number
+                .map(|number| format!(", receipt {}", receipt::format(number)))
+                .unwrap_or_default()
+        };
+
+        let mut lines = vec![(
+            0,
+            format!(
+                "{} – {}: {} taken in cash, {} sent, {} on the gateway",
+                when(result.from),
+                when(result.to),
+                money::format(result.cash_in),
+                money::format(result.sent),
+                money::format(result.confirmed)
+            ),
+        )];
+        if result.cancelled > 0 {
+            lines.push((
+                0,
+                format!(
+                    "{} below the minimum or left without a fund",
+                    money::format(result.cancelled)
+                ),
+            ));
+        }
+        if result.balanced() {
+            lines.push((1, "✅ Every donation sent is on the gateway".to_string()));
+        }
+        for sent in &result.missing {
+            lines.push((
+                3,
+                format!(
+                    "❌ {} {} {} to {}{}: sent, but not on the gateway",
+                    when(sent.timestamp),
+                    money::format(sent.amount.into()),
+                    from_whom(&sent.username),
+                    sent.fund_name,
+                    receipt(sent.receipt)
+                ),
+            ));
+        }
+        for queued in &result.queued {
+            lines.push((
+                2,
+                format!(
+                    "⏳ {} {} {} to {}{}: still in the donation queue ({})",
+                    when(queued.timestamp),
+                    money::format(queued.amount.into()),
+                    from_whom(&queued.username),
+                    if queued.fund_name.is_empty() {
+                        "no fund"
+                    } else {
+                        &queued.fund_name
+                    },
+                    receipt(queued.receipt),
+                    queued.note
+                ),
+            ));
+        }
+        for unknown in &result.unknown {
+            lines.push((
+                2,
+                format!(
+                    "❓ {} {} {} to fund {}{}: on the gateway, but not sent from here",
+                    unknown.timestamp.map(when).unwrap_or_default(),
+                    money::format(unknown.amount.into()),
+                    from_whom(&unknown.username),
+                    unknown.fund_id,
+                    receipt(unknown.receipt)
+                ),
+            ));
+        }
+        lines
+    }
+
+    /// Opens the service page, for `admin_pin` or a member with a service
+    /// role.
+    fn unlock(window: &MainWindow, stats_db_path: &str, member: Option<&str>) {
+        info!(
+            "🔓 Service page unlocked{}",
+            member.map(|m| format!(" by {}", m)).unwrap_or_default()
+        );
+        audit::record(stats_db_path, "admin_unlocked", member.unwrap_or_default());
+        window.set_admin_pin_message("".into());
+        // the member who unlocked it, or whoever runs the shift, is the
+        // likely collector
+        let collector = member.map(str::to_string).unwrap_or_else(|| {
+            shift::open_shift(stats_db_path)
+                .ok()
+                .flatten()
+                .map(|shift| shift.operator)
+                .unwrap_or_default()
+        });
+        window.set_admin_collector(collector.into());
+        window.set_admin_unlocked(true);
+        window.invoke_admin_refresh();
+    }
+
+    fn wrong(window: &MainWindow, stats_db_path: &str, outcome: admin::Unlock, detail: &str) {
+        let message = match outcome {
+            admin::Unlock::Wrong { attempts_left } => {
+                audit::record(stats_db_path, "admin_pin_wrong", detail);
+                format!("Wrong PIN, {} attempt(s) left", attempts_left)
+            }
+            admin::Unlock::LockedOut => {
+                let detail = match detail {
+                    "" => "locked out".to_string(),
+                    detail => format!("{}, locked out", detail),
+                };
+                audit::record(stats_db_path, "admin_pin_wrong", &detail);
+                "Wrong PIN, try again in a minute".to_string()
+            }
+        };
+        refuse(window, message);
+    }
+
+    fn refuse(window: &MainWindow, message: String) {
+        warn!("🔒 Service page: {}", message);
+        window.set_admin_pin_message(message.into());
+    }
+
+    /// The driver zeroed its counters and handed back what they held: that
+    /// is the collection.
+    fn collected(window: &MainWindow, stats_db_path: &str, collector: &str, before: &[(i32, i32)]) {
+        let summary = admin::summary(before);
+        match shift::record_counted_collection(stats_db_path, collector, before) {
+            Ok(id) => {
+                info!("💰 Collection #{} by {}: {}", id, collector, summary);
+                audit::record(
+                    stats_db_path,
+                    "collection",
+                    &format!("{} by {}", summary, collector),
+                );
+            }
+            // The counters are already zeroed, so the snapshot goes to the log
+            Err(e) => error_code::report(
+                stats_db_path,
+                ErrorCode::Database,
+                &format!(
+                    "Failed to record the collection by {} ({}): {}",
+                    collector, summary, e
+                ),
+            ),
+        }
+        let cleared = before.iter().map(|&(nominal, _)| (nominal, 0)).collect();
+        show(window, Ok(cleared));
+        window.set_admin_counts_status(LogEntry {
+            level: 1,
+            text: format!(
+                "Collected {} by {}",
+                money::format(admin::total(before)),
+                collector
+            )
+            .into(),
+        });
+    }
+
+    /// Sends `command` to the driver and hands its answer to `then` on the
+    /// UI thread, without blocking it while the driver gets round to it.
+    fn ask(
+        tx: &Sender<CashCodeCommand>,
+        command: fn(CountsReply) -> CashCodeCommand,
+        weak: slint::Weak<MainWindow>,
+        then: impl FnOnce(&MainWindow, Result<Vec<(i32, i32)>, String>) + Send + 'static,
+    ) {
+        let (reply_tx, reply_rx) = channel();
+        if tx.send(command(reply_tx)).is_err() {
+            error!("Failed to send a counters request to the bill acceptor");
+            return;
+        }
+        thread::spawn(move || {
+            let result = reply_rx
+                .recv_timeout(DRIVER_TIMEOUT)
+                .unwrap_or_else(|_| Err("the bill driver didn't answer".to_string()));
+            let _ = slint::invoke_from_event_loop(move || {
+                if let Some(w) = weak.upgrade() {
+                    then(&w, result);
+                }
+            });
+        });
+    }
+
+    fn show(window: &MainWindow, counts: Result<Vec<(i32, i32)>, String>) {
+        match counts {
+            Ok(counts) => {
+                window.set_admin_total(admin::total(&counts) as i32);
+                let rows: Vec<StackerRow> = counts
+                    .into_iter()
+                    .map(|(nominal, count)| StackerRow { nominal, count })
+                    .collect();
+                window.set_admin_rows(ModelRc::new(VecModel::from(rows)));
+                window.set_admin_counts_status(LogEntry {
+                    level: 0,
+                    text: "".into(),
+                });
+            }
+            Err(e) => {
+                warn!("Bill counters unavailable: {}", e);
+                window.set_admin_rows(ModelRc::default());
+                window.set_admin_total(0);
+                window.set_admin_counts_status(LogEntry {
+                    level: 3,
+                    text: format!("Counters unavailable: {}", e).into(),
+                });
+            }
+        }
+    }
+}
+
+mod diagnostics_handler {
+    use super::*;
+    use slint::{ModelRc, Timer, TimerMode, VecModel};
+
+    const MAX_LOG_LINES: usize = 300;
+
+    /// Returns (level, text): level 0=neutral 1=ok 2=warn 3=error
+    async fn check_backend(token: Option<String>) -> (i32, String) {
+        let Some(tok) = token else {
+            return (2, format!("{} No token configured", ErrorCode::ApiNoToken));
+        };
+
+        let request = match api::get(&api::url("/api/funds?status=open"), &tok).body(()) {
+            Ok(r) => r,
+            Err(e) => return (3, format!("{} Request error: {}", ErrorCode::ApiRequest, e)),
+        };
+
+        match api::send(request).await {
+            Ok(r) => {
+                let s = r.status();
+                if s.is_success() {
+                    match api::fallback_gateway() {
+                        Some(gateway) => (2, format!("OK (HTTP {}) via {}", s.as_u16(), gateway)),
+                        None => (1, format!("OK (HTTP {})", s.as_u16())),
+                    }
+                } else if s.as_u16() == 401 {
+                    (
+                        2,
+                        format!(
+                            "{} HTTP 401 — token invalid or expired",
+                            ErrorCode::ApiUnauthorized
+                        ),
+                    )
+                } else if api::is_incompatible_status(s.as_u16()) {
+                    (
+                        3,
+                        format!(
+                            "{} HTTP {} — API incompatible, update the kiosk",
+                            ErrorCode::ApiIncompatible,
+                            s.as_u16()
+                        ),
+                    )
+                } else {
+                    (
+                        2,
+                        format!(
+                            "{} HTTP {} — {}",
+                            ErrorCode::ApiStatus,
+                            s.as_u16(),
+                            s.canonical_reason().unwrap_or("Unknown")
+                        ),
+                    )
+                }
+            }
+            Err(e) => (3, format!("{} {}", e.code(), e)),
+        }
+    }
+
+    pub fn init(
+        app: &MainWindow,
+        config: &Config,
+        log_rx: std::sync::mpsc::Receiver<diag_logger::LogLine>,
+        cashcode_tx: Sender<bill_acceptor::CashCodeCommand>,
+        cctalk_tx: Sender<cctalk::CoinAcceptorCommand>,
+        frame_log: Arc<FrameLog>,
+    ) {
+        let token = config.token.clone();
+        let frame_log_dir = config.frame_log_dir.clone();
+        // Build the model and hand it to the window.
+        let log_model = std::rc::Rc::new(VecModel::<LogEntry>::default());
+        app.set_diag_logs(ModelRc::from(log_model.clone()));
+
+        // Drain the log channel into the model on every tick.
+        let timer = Timer::default();
+        timer.start(
+            TimerMode::Repeated,
+            std::time::Duration::from_millis(500),
+            move || {
+                while let Ok((lvl, text)) = log_rx.try_recv() {
+                    log_model.insert(
+                        0,
+                        LogEntry {
+                            level: lvl as i32,
+                            text: text.into(),
+                        },
+                    );
+                    if log_model.row_count() > MAX_LOG_LINES {
+                        log_model.remove(log_model.row_count() - 1);
+                    }
+                }
+            },
+        );
+        std::mem::forget(timer);
+
+        // Live camera preview — only streams while the Diagnostics page is open.
+        let (preview_cmd_tx, preview_cmd_rx) = std::sync::mpsc::channel::<camera::PreviewCommand>();
+        let (preview_frame_tx, preview_frame_rx) =
+            std::sync::mpsc::sync_channel::<camera::PreviewFrame>(1);
+        camera::spawn_preview(preview_cmd_rx, preview_frame_tx);
+
+        let weak_preview = app.as_weak();
+        let was_on_diagnostics = Rc::new(RefCell::new(false));
+        let preview_timer = Timer::default();
+        preview_timer.start(
+            TimerMode::Repeated,
+            std::time::Duration::from_millis(100),
+            move || {
+                let Some(window) = weak_preview.upgrade() else {
+                    return;
+                };
+                let on_page = window.get_on_diagnostics_page();
+                if on_page != *was_on_diagnostics.borrow() {
+                    *was_on_diagnostics.borrow_mut() = on_page;
+                    if on_page {
+                        let _ = preview_cmd_tx.send(camera::PreviewCommand::Start);
+                    } else {
+                        let _ = preview_cmd_tx.send(camera::PreviewCommand::Stop);
+                        window.set_diag_camera_available(false);
+                    }
+                }
+                // Drain to the most recent frame only — older ones are stale.
+                let mut latest = None;
+                while let Ok(frame) = preview_frame_rx.try_recv() {
+                    latest = Some(frame);
+                }
+                if let Some(frame) = latest {
+                    let pixel_buffer =
+                        slint::SharedPixelBuffer::<slint::Rgb8Pixel>::clone_from_slice(
+                            &frame.rgb,
+                            frame.width,
+                            frame.height,
+                        );
+                    window.set_diag_camera_frame(slint::Image::from_rgb8(pixel_buffer));
+                    window.set_diag_camera_available(true);
+                }
+            },
+        );
+        std::mem::forget(preview_timer);
+
+        // CCNET timing violations, kept up to date while the page is open
+        let weak_timing = app.as_weak();
+        let timing_timer = Timer::default();
+        timing_timer.start(TimerMode::Repeated, Duration::from_secs(1), move || {
+            let Some(window) = weak_timing.upgrade() else {
+                return;
+            };
+            if !window.get_on_diagnostics_page() {
+                return;
+            }
+            let violations = cashcode::timing_violations();
+            window.set_diag_bill_timing(if violations.total() == 0 {
+                LogEntry {
+                    level: 1,
+                    text: "No violations since start".into(),
+                }
+            } else {
+                LogEntry {
+                    level: 2,
+                    text: format!("{} since start: {}", violations.total(), violations).into(),
+                }
+            });
+        });
+        std::mem::forget(timing_timer);
+
+        let weak_test = app.as_weak();
+        let cashcode_tx_test = cashcode_tx.clone();
+        let stats_db_path_test = config.stats_db_path.clone();
+        app.on_diag_toggle_test_bills(move || {
+            let Some(w) = weak_test.upgrade() else {
+                return;
+            };
+            let on = !w.get_diag_test_bills();
+            info!(
+                "🧪 Diagnostics: test-bill mode {}",
+                if on { "on" } else { "off" }
+            );
+            if cashcode_tx_test
+                .send(bill_acceptor::CashCodeCommand::TestMode(on))
+                .is_err()
+            {
+                error!("Failed to send TestMode to bill acceptor");
+                return;
+            }
+            w.set_diag_test_bills(on);
+            audit::record(
+                &stats_db_path_test,
+                "bill_test_mode",
+                if on { "on" } else { "off" },
+            );
+        });
+
+        let cashcode_tx_reset = cashcode_tx;
+        let stats_db_path = config.stats_db_path.clone();
+        app.on_diag_reset_bills(move || {
+            info!("🔄 Diagnostics: resetting bill acceptor");
+            if cashcode_tx_reset
+                .send(bill_acceptor::CashCodeCommand::Reset)
+                .is_err()
+            {
+                error!("Failed to send Reset to bill acceptor");
+                return;
+            }
+            audit::record(&stats_db_path, "bill_reset", "manual");
+        });
+
+        let cctalk_tx_reenumerate = cctalk_tx;
+        app.on_diag_reenumerate_coins(move || {
+            info!("ccTalk: re-enumeration requested from diagnostics");
+            if cctalk_tx_reenumerate
+                .send(cctalk::CoinAcceptorCommand::Reenumerate)
+                .is_err()
+            {
+                error!("Failed to send Reenumerate to coin acceptor");
+            }
+        });
+
+        let weak_frames = app.as_weak();
+        let frame_log_toggle = frame_log.clone();
+        app.on_diag_toggle_frame_log(move || {
+            let enabled = !frame_log_toggle.is_enabled();
+            frame_log_toggle.set_enabled(enabled);
+            info!(
+                "🧾 Diagnostics: CCNET frame logging {}",
+                if enabled { "enabled" } else { "disabled" }
+            );
+            if let Some(w) = weak_frames.upgrade() {
+                w.set_diag_frame_log_enabled(enabled);
+            }
+        });
+
+        app.on_diag_export_frames(move || match frame_log.export(&frame_log_dir, "ccnet") {
+            Ok((path, count)) => info!("🧾 Exported {} CCNET frames to {}", count, path.display()),
+            Err(e) => error!("Failed to export CCNET frames: {}", e),
+        });
+
+        app.on_diag_play_sound(|| {
+            info!("🔊 Diagnostics: playing sound");
+            crate::sound::play_yippee();
+        });
+
+        let weak_backend = app.as_weak();
+        let stats_db_path = config.stats_db_path.clone();
+        app.on_diag_check_backend(move || {
+            let weak = weak_backend.clone();
+            let tok = token.clone();
+            if let Some(w) = weak.upgrade() {
+                w.set_diag_backend_status(LogEntry {
+                    level: 0,
+                    text: "Checking...".into(),
+                });
+            }
+            let stats_db_path = stats_db_path.clone();
+            slint::spawn_local(async move {
+                let (level, mut text) = check_backend(tok).await;
+                if level >= 2
+                    && let Some(synced) = kv::get(&stats_db_path, &kv::LAST_FUNDS_SYNC)
+                {
+                    use chrono::TimeZone;
+                    if let Some(when) = chrono::Local.timestamp_opt(synced as i64, 0).single() {
+                        text += &format!(" · last synced {}", when.format("%Y-%m-%d %H:%M"));
+                    }
+                }
+                if let Some(w) = weak.upgrade() {
+                    w.set_diag_backend_status(LogEntry {
+                        level,
+                        text: text.into(),
+                    });
+                }
+            })
+            .unwrap();
+        });
+    }
+}
+
+mod shift_handler {
+    use super::*;
+    use crate::shift::{self, Shift};
+    use chrono::TimeZone;
+
+    /// Operator shifts from Diagnostics: whoever sets the box up enters
+    /// their name, and ends the shift when packing up or handing over.
+    pub fn init(app: &MainWindow, config: &Config) {
+        match shift::open_shift(&config.stats_db_path) {
+            Ok(open) => show(app, open.as_ref()),
+            Err(e) => error!("Failed to read the open shift: {}", e),
+        }
+
+        let weak = app.as_weak();
+        let stats_db_path = config.stats_db_path.clone();
+        app.on_diag_start_shift(move |operator| {
+            let operator = operator.trim();
+            if operator.is_empty() {
+                return;
+            }
+            let Some(window) = weak.upgrade() else {
+                return;
+            };
+            match shift::open(&stats_db_path, operator) {
+                Ok(opened) => {
+                    info!("👤 Shift opened by {}", opened.operator);
+                    audit::record(&stats_db_path, "shift_opened", &opened.operator);
+                    show(&window, Some(&opened));
+                }
+                Err(e) => error_code::report(
+                    &stats_db_path,
+                    ErrorCode::Database,
+                    &format!("Failed to open shift: {}", e),
+                ),
+            }
+        });
+
+        let weak = app.as_weak();
+        let stats_db_path = config.stats_db_path.clone();
+        app.on_diag_end_shift(move || {
+            let Some(window) = weak.upgrade() else {
+                return;
+            };
+            match shift::close(&stats_db_path) {
+                Ok(Some(closed)) => {
+                    info!("👤 Shift of {} closed", closed.operator);
+                    audit::record(&stats_db_path, "shift_closed", &closed.operator);
+                    show(&window, None);
+                }
+                Ok(None) => show(&window, None),
+                Err(e) => error_code::report(
+                    &stats_db_path,
+                    ErrorCode::Database,
+                    &format!("Failed to close shift: {}", e),
+                ),
+            }
+        });
+    }
+
+    fn show(window: &MainWindow, open: Option<&Shift>) {
+        let text = open
+            .map(
+                |shift| match chrono::Local.timestamp_opt(shift.opened as i64, 0).single() {
+                    Some(when) => format!("{} since {}", shift.operator, when.format("%H:%M")),
+                    None => shift.operator.clone(),
+                },
+            )
+            .unwrap_or_default();
+        window.set_diag_shift(text.into());
+    }
+}
+
+mod tamper_handler {
+    use super::*;
+    use crate::mqtt::Publisher;
+    use crate::tamper::{self, Door};
+    use slint::{Timer, TimerMode};
+    use std::cell::Cell;
+    use std::sync::mpsc::channel;
+
+    /// Watches the cash box door (`[tamper]`). Opening it while an operator
+    /// shift is open is part of a collection and only audited; opening it
+    /// outside one is a tamper incident: audited, flagged on the MQTT tamper
+    /// sensor, sent to the report's Telegram chat and, if enabled, warned
+    /// about on screen until the door is closed. Door changes from HASS come
+    /// in through the returned sender.
+    pub fn init(
+        app: &MainWindow,
+        config: &Config,
+        publisher: Option<&Publisher>,
+    ) -> Option<Sender<Door>> {
+        let settings = &config.tamper;
+        if settings.device.is_none() && settings.hass_entity.is_none() {
+            return None;
+        }
+        let (tx, rx) = channel();
+        tamper::start(settings, tx.clone());
+
+        let mqtt = publisher.map(|publisher| {
+            let topic = publisher.topic("tamper");
+            publisher.announce(
+                "binary_sensor",
+                "tamper",
+                serde_json::json!({
+                    "name": "Cash box tamper",
+                    "state_topic": topic,
+                    "device_class": "tamper",
+                }),
+            );
+            (publisher.clone(), topic)
+        });
+        let telegram = config
+            .report
+            .telegram_bot_token
+            .clone()
+            .zip(config.report.telegram_chat_id.clone());
+        let show_warning = settings.show_warning;
+        let stats_db_path = config.stats_db_path.clone();
+        let open = Cell::new(false);
+        let weak = app.as_weak();
+        let timer = Timer::default();
+        timer.start(TimerMode::Repeated, Duration::from_millis(250), move || {
+            let Some(window) = weak.upgrade() else {
+                return;
+            };
+            while let Ok(door) = rx.try_recv() {
+                if open.replace(door.open) == door.open {
+                    continue;
+                }
+                if !door.open {
+                    info!("🚪 Cash box closed ({})", door.source.name());
+                    if let Some((publisher, topic)) = &mqtt {
+                        publisher.publish(topic, "OFF", true);
+                    }
+                    notification_handler::clear(&window, "tamper");
+                    continue;
+                }
+
+                match shift::open_shift(&stats_db_path) {
+                    Ok(Some(open_shift)) => {
+                        info!("🚪 Cash box opened during {}'s shift", open_shift.operator);
+                        audit::record(&stats_db_path, "door_opened", &open_shift.operator);
+                        continue;
+                    }
+                    Ok(None) => {}
+                    // Can't tell, so it counts as unauthorized
+                    Err(e) => error!("Failed to read the open shift: {}", e),
+                }
+                let details = format!("Cash box opened outside a shift ({})", door.source.name());
+                warn!("🚨 {}", details);
+                audit::record(&stats_db_path, "tamper", &details);
+                if show_warning {
+                    notification_handler::set(
+                        &window,
+                        "tamper",
+                        3,
+                        &i18n::tr("tamper.opened", &[]),
+                    );
+                }
+                if let Some((publisher, topic)) = &mqtt {
+                    publisher.publish(topic, "ON", true);
+                }
+                if let Some((bot_token, chat_id)) = telegram.clone() {
+                    let text = format!(
+                        "🚨 Dramma: {} at {}",
+                        details,
+                        clock::local().format("%Y-%m-%d %H:%M")
+                    );
+                    slint::spawn_local(async move {
+                        match report::send_telegram_text(&bot_token, &chat_id, text).await {
+                            Ok(()) => info!("🚨 Tamper alert sent to Telegram"),
+                            Err(e) => {
+                                warn!("🚨 Failed to send the tamper alert to Telegram: {}", e)
+                            }
+                        }
+                    })
+                    .unwrap();
+                }
+            }
+        });
+        std::mem::forget(timer);
+        Some(tx)
+    }
+}
+
+mod usb_export_handler {
+    use super::*;
+    use crate::usb_export::{self, UsbStick};
+    use slint::{Timer, TimerMode};
+
+    const POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+    /// Watches for a USB stick and, when the admin asks from Diagnostics,
+    /// copies the database, CSV reports and logs onto it and unmounts it, so
+    /// records can be pulled without a laptop.
+    pub fn init(app: &MainWindow, config: &Config) {
+        let stick: Rc<RefCell<Option<UsbStick>>> = Rc::new(RefCell::new(None));
+        let exporting = Rc::new(RefCell::new(false));
+
+        let weak = app.as_weak();
+        let stick_poll = stick.clone();
+        let exporting_poll = exporting.clone();
+        let roots = config.usb_mount_roots.clone();
+        let timer = Timer::default();
+        timer.start(TimerMode::Repeated, POLL_INTERVAL, move || {
+            // Leave the stick alone while it's being written to
+            if *exporting_poll.borrow() {
+                return;
+            }
+            let found = usb_export::find_stick(&roots);
+            if *stick_poll.borrow() == found {
+                return;
+            }
+            let Some(window) = weak.upgrade() else {
+                return;
+            };
+            match &found {
+                Some(s) => {
+                    info!("💾 USB stick mounted at {} ({})", s.mount_point, s.device);
+                    window.set_diag_usb_export_status(LogEntry {
+                        level: 0,
+                        text: "".into(),
+                    });
+                    window.set_diag_usb_stick(s.mount_point.clone().into());
+                    notification_handler::push(&window, 0, &i18n::tr("usb.detected", &[]));
+                }
+                None => {
+                    info!("💾 USB stick removed");
+                    window.set_diag_usb_stick("".into());
+                }
+            }
+            *stick_poll.borrow_mut() = found;
+        });
+        std::mem::forget(timer);
+
+        let weak_export = app.as_weak();
+        let stats_db_path = config.stats_db_path.clone();
+        let frame_log_dir = config.frame_log_dir.clone();
+        app.on_diag_export_usb(move || {
+            let Some(window) = weak_export.upgrade() else {
+                return;
+            };
+            let Some(target) = stick.borrow().clone() else {
+                return;
+            };
+            if *exporting.borrow() {
+                return;
+            }
+            *exporting.borrow_mut() = true;
+
+            let mut log_lines: Vec<String> = window
+                .get_diag_logs()
+                .iter()
+                .map(|entry| entry.text.to_string())
+                .collect();
+            // The model is newest first; a log file reads oldest first
+            log_lines.reverse();
+            window.set_diag_usb_export_status(LogEntry {
+                level: 0,
+                text: "💾 Exporting…".into(),
+            });
+            info!("💾 Exporting records to {}", target.mount_point);
+
+            let weak = weak_export.clone();
+            let stick = stick.clone();
+            let exporting = exporting.clone();
+            let stats_db_path = stats_db_path.clone();
+            let frame_log_dir = frame_log_dir.clone();
+            let (done_tx, done_rx) = std::sync::mpsc::channel();
+            thread::spawn(move || {
+                let result =
+                    usb_export::export(&target, &stats_db_path, &frame_log_dir, &log_lines)
+                        .and_then(|dir| usb_export::eject(&target).map(|_| dir));
+                match &result {
+                    Ok(dir) => audit::record(&stats_db_path, "usb_export", &dir.to_string_lossy()),
+                    Err(e) => {
+                        error_code::report(&stats_db_path, ErrorCode::UsbExport, &e.to_string())
+                    }
+                }
+                let _ = done_tx.send(result.map_err(|e| e.to_string()));
+            });
+
+            // Wait for the export on the UI thread without blocking it
+            let timer = Rc::new(Timer::default());
+            let timer_weak = Rc::downgrade(&timer);
+            timer.start(TimerMode::Repeated, Duration::from_millis(200), move || {
+                let Ok(result) = done_rx.try_recv() else {
+                    return;
+                };
+                *exporting.borrow_mut() = false;
+                if let Some(window) = weak.upgrade() {
+                    let status = match result {
+                        Ok(dir) => {
+                            // Unmounted: forget it so a re-plug is offered again
+                            *stick.borrow_mut() = None;
+                            window.set_diag_usb_stick("".into());
+                            LogEntry {
+                                level: 1,
+                                text: format!(
+                                    "💾 Exported to {}, safe to remove",
+                                    dir.file_name().unwrap_or_default().to_string_lossy()
+                                )
+                                .into(),
+                            }
+                        }
+                        Err(e) => LogEntry {
+                            level: 3,
+                            text: format!("{} Export failed: {}", ErrorCode::UsbExport, e).into(),
+                        },
+                    };
+                    window.set_diag_usb_export_status(status);
+                }
+                if let Some(timer) = timer_weak.upgrade() {
+                    timer.stop();
+                }
+            });
+            std::mem::forget(timer);
+        });
+    }
+}
+
+mod reboot_handler {
+    use super::*;
+    use chrono::NaiveTime;
+    use slint::{Timer, TimerMode};
+    use std::cell::Cell;
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    /// Set when the event loop is ended for a scheduled app restart.
+    static RESTART: AtomicBool = AtomicBool::new(false);
+
+    /// Restarts the app (or runs the configured reboot command) once a day
+    /// inside the reboot window, at a moment when the kiosk is `idle`. A day
+    /// with no idle moment in the window is skipped.
+    pub fn init(app: &MainWindow, config: &Config) {
+        let Some(at) = config.reboot.at.as_deref().and_then(|s| {
+            NaiveTime::parse_from_str(s, "%H:%M")
+                .inspect_err(|e| warn!("Ignoring invalid reboot.at {:?}: {}", s, e))
+                .ok()
+        }) else {
+            return;
+        };
+        let length = chrono::Duration::minutes(config.reboot.window_mins as i64);
+        info!(
+            "♻️ Reboot window: {} for {} min ({})",
+            at,
+            config.reboot.window_mins,
+            config.reboot.command.as_deref().unwrap_or("app restart")
+        );
+
+        // Booting inside the window counts as today's restart, otherwise a
+        // restart would land right back in the window and loop
+        let booted = clock::local();
+        let done_on = Cell::new(in_window(booted.time(), at, length).then(|| booted.date_naive()));
+        let command = config.reboot.command.clone();
+        let stats_db_path = config.stats_db_path.clone();
+        let weak = app.as_weak();
+
+        let timer = Timer::default();
+        timer.start(TimerMode::Repeated, Duration::from_secs(30), move || {
+            let Some(window) = weak.upgrade() else {
+                return;
+            };
+            let now = clock::local();
+            if done_on.get() == Some(now.date_naive()) || !in_window(now.time(), at, length) {
+                return;
+            }
+            if !idle(&window, &stats_db_path) {
+                return;
+            }
+            done_on.set(Some(now.date_naive()));
+
+            match &command {
+                Some(cmd) => {
+                    info!("♻️ Scheduled reboot: running {:?}", cmd);
+                    // Synchronous so the audit row is written before we go
+                    audit::record_now(&stats_db_path, "reboot", cmd);
+                    match std::process::Command::new("sh").args(["-c", cmd]).status() {
+                        Ok(status) if status.success() => {}
+                        Ok(status) => error!("Reboot command exited with {}", status),
+                        Err(e) => error!("Failed to run reboot command: {}", e),
+                    }
+                }
+                None => {
+                    info!("♻️ Scheduled restart of the app");
+                    audit::record_now(&stats_db_path, "reboot", "app restart");
+                    // main shuts down cleanly, then calls `restart_self`
+                    window.invoke_stop_accepting_money();
+                    RESTART.store(true, Ordering::SeqCst);
+                    let _ = slint::quit_event_loop();
+                }
+            }
+        });
+        std::mem::forget(timer);
+    }
+
+    /// Whether the event loop ended for a scheduled app restart.
+    pub fn restart_requested() -> bool {
+        RESTART.load(Ordering::SeqCst)
+    }
+
+    /// No donor is in the middle of anything: the kiosk sits on the main
+    /// page with the acceptors off and no donation held for corrections.
+    pub fn unattended(window: &MainWindow, stats_db_path: &str) -> bool {
+        window.get_current_page() == Page::Main
+            && !window.get_accepting_money()
+            && kv::get(stats_db_path, &kv::HELD_DONATION).is_none()
+    }
+
+    /// Nothing would be cut short right now: the kiosk is `unattended`, no
+    /// donation is batched, being sent or waiting in the queue for the
+    /// backend, and no receipt is left to print. Queued money still waiting
+    /// on staff to pick a fund doesn't count.
+    pub fn idle(window: &MainWindow, stats_db_path: &str) -> bool {
+        if !unattended(window, stats_db_path)
+            || donation_handler::sending()
+            || kv::get(stats_db_path, &kv::EVENT_BATCH).is_some()
+        {
+            return false;
+        }
+        let queued = donation::queued(stats_db_path)
+            .map(|queued| queued.iter().any(|queued| queued.fund_id.is_some()));
+        matches!(queued, Ok(false)) && matches!(printer::pending(stats_db_path), Ok(0))
+    }
+
+    fn in_window(time: NaiveTime, at: NaiveTime, length: chrono::Duration) -> bool {
+        // Wrapping arithmetic so a window across midnight works too
+        let (end, _) = at.overflowing_add_signed(length);
+        if at <= end {
+            time >= at && time < end
+        } else {
+            time >= at || time < end
+        }
+    }
+
+    /// Replaces the process with a fresh copy of itself, same arguments.
+    pub fn restart_self() {
+        use std::os::unix::process::CommandExt;
+
+        let exe = match std::env::current_exe() {
+            Ok(exe) => exe,
+            Err(e) => {
+                error!("Cannot restart, executable path unknown: {}", e);
+                return;
+            }
+        };
+        let err = std::process::Command::new(exe)
+            .args(std::env::args_os().skip(1))
+            .exec();
+        error!("Failed to restart: {}", err);
+    }
+}
+
+mod restart_handler {
+    use super::*;
+    use crate::hass_events;
+    use crate::supervisor::Subsystem;
+    use slint::{ModelRc, VecModel};
+
+    /// Restarts one subsystem at a time, from the Diagnostics buttons or the
+    /// listener's `POST /restart/<name>`. The acceptors aren't touched while
+    /// a donor is inserting money.
+    pub fn init(
+        app: &MainWindow,
+        config: &Config,
+        cashcode_tx: Sender<bill_acceptor::CashCodeCommand>,
+        cctalk_tx: Sender<cctalk::CoinAcceptorCommand>,
+        publisher: Option<mqtt::Publisher>,
+        hass_events: Option<hass_events::Restarter>,
+    ) {
+        let running = |subsystem: &Subsystem| match subsystem {
+            Subsystem::Mqtt => publisher.is_some(),
+            Subsystem::HassEvents => hass_events.is_some(),
+            _ => true,
+        };
+        let targets: Vec<RestartTarget> = Subsystem::ALL
+            .iter()
+            .filter(|subsystem| running(subsystem))
+            .map(|subsystem| RestartTarget {
+                name: subsystem.name().into(),
+                label: subsystem.label().into(),
+            })
+            .collect();
+        app.set_diag_restart_targets(ModelRc::new(VecModel::from(targets)));
+
+        let stats_db_path = config.stats_db_path.clone();
+        let weak = app.as_weak();
+        app.on_restart_subsystem(move |name| {
+            let Some(window) = weak.upgrade() else {
+                return;
+            };
+            let Some(subsystem) = Subsystem::parse(&name) else {
+                warn!("🔄 Unknown subsystem {:?}", name);
+                return;
+            };
+            let label = subsystem.label();
+            if subsystem.takes_money()
+                && (window.get_on_insert_money_page() || window.get_on_insert_coins_page())
+            {
+                notification_handler::push(&window, 2, &i18n::tr("restart.busy", &[&label]));
+                return;
+            }
+            let sent = match subsystem {
+                Subsystem::BillAcceptor => cashcode_tx
+                    .send(bill_acceptor::CashCodeCommand::Restart)
+                    .is_ok(),
+                Subsystem::CoinAcceptor => cctalk_tx
+                    .send(cctalk::CoinAcceptorCommand::Reenumerate)
+                    .is_ok(),
+                Subsystem::Mqtt => publisher.as_ref().map(mqtt::Publisher::restart).is_some(),
+                Subsystem::HassEvents => hass_events
+                    .as_ref()
+                    .map(hass_events::Restarter::restart)
+                    .is_some(),
+                Subsystem::Gateway => {
+                    api::reset_gateway();
+                    window.invoke_fetch_funds();
+                    window.invoke_diag_check_backend();
+                    true
+                }
+            };
+            if !sent {
+                notification_handler::push(&window, 2, &i18n::tr("restart.unavailable", &[&label]));
+                return;
+            }
+            info!("🔄 Restarting {}", subsystem.name());
+            audit::record(&stats_db_path, "restart", subsystem.name());
+            notification_handler::push(&window, 0, &i18n::tr("restart.started", &[&label]));
+        });
+    }
+}
+mod shutdown_handler {
+    use super::*;
+    use crate::home_assistant::ChromiumManager;
+    use std::sync::mpsc::channel;
+
+    /// How long the bill driver gets to disable the acceptor and let a bill
+    /// on its way to the stacker land.
+    const DRIVER_TIMEOUT: Duration = Duration::from_secs(5);
+    /// How long background writes to the stats DB get to finish.
+    const FLUSH_TIMEOUT: Duration = Duration::from_secs(2);
+
+    /// On SIGTERM or SIGINT: stops taking money, keeps a session in
+    /// progress for the next start (see `window_recovery::resume`) and ends
+    /// the event loop; `finish` does the rest once it has.
+    pub fn init(app: &MainWindow, config: &Config) {
+        let weak = app.as_weak();
+        let stats_db_path = config.stats_db_path.clone();
+        shutdown::listen(move |signal| {
+            let _ = weak.upgrade_in_event_loop(move |window| {
+                warn!("🛑 {} received, shutting down", signal);
+                window.invoke_stop_accepting_money();
+                window_recovery::save_session(&window, &stats_db_path);
+                audit::record_now(&stats_db_path, "shutdown", signal);
+                let _ = slint::quit_event_loop();
+            });
+        });
+    }
+
+    /// Called once the event loop has ended on a signal or for a scheduled
+    /// restart: waits for the bill driver to switch the acceptor off and
+    /// stop, closes Chromium and lets the DB writes still running finish.
+    pub fn finish(
+        cashcode_tx: &Sender<bill_acceptor::CashCodeCommand>,
+        chromium: Option<&ChromiumManager>,
+    ) {
+        let (done_tx, done_rx) = channel();
+        if cashcode_tx
+            .send(bill_acceptor::CashCodeCommand::Shutdown(done_tx))
+            .is_err()
+            || done_rx.recv_timeout(DRIVER_TIMEOUT).is_err()
+        {
+            warn!("🛑 The bill driver didn't confirm the acceptor is off");
+        }
+        if let Some(chromium) = chromium {
+            chromium.close();
+        }
+        if !db_writes::flush(FLUSH_TIMEOUT) {
+            warn!("🛑 Some database writes didn't finish in time");
+        }
+        info!("🛑 Shut down");
+    }
+}
+
+mod window_recovery {
     use super::*;
-    use slint::{Image, ModelRc, VecModel};
+    use crate::kv::InterruptedSession;
+    use log::debug;
+    use slint::{CloseRequestResponse, Timer, TimerMode};
+    use std::cell::Cell;
+    use std::os::unix::net::UnixStream;
+    use std::path::PathBuf;
+    use std::time::Instant;
 
-    /// How many past donations to show on the wall. Each entry with a photo
-    /// loads that photo into memory at full resolution, so this is kept modest.
-    const LOG_LIMIT: i64 = 24;
+    /// How long startup waits for a restarting compositor.
+    const COMPOSITOR_TIMEOUT: Duration = Duration::from_secs(60);
+    /// How long a resumed session gets to reach the insert page before its
+    /// money is committed as abandoned.
+    const RESUME_TIMEOUT: Duration = Duration::from_secs(15);
 
-    fn format_relative_time(timestamp: u64) -> String {
-        let diff = donation_log::now_timestamp().saturating_sub(timestamp);
-        if diff < 60 {
-            "just now".to_string()
-        } else if diff < 3600 {
-            format!("{}m ago", diff / 60)
-        } else if diff < 86400 {
-            format!("{}h ago", diff / 3600)
+    /// The Wayland socket the window will connect to, if on Wayland.
+    fn wayland_socket() -> Option<PathBuf> {
+        let display = PathBuf::from(std::env::var_os("WAYLAND_DISPLAY")?);
+        if display.is_absolute() {
+            return Some(display);
+        }
+        Some(PathBuf::from(std::env::var_os("XDG_RUNTIME_DIR")?).join(display))
+    }
+
+    /// Blocks until the compositor accepts connections, so a relaunch right
+    /// after it crashed doesn't fail to open the window.
+    pub fn wait_for_compositor() {
+        let Some(socket) = wayland_socket() else {
+            return;
+        };
+        let started = clock::instant();
+        while UnixStream::connect(&socket).is_err() {
+            if clock::since(started) >= COMPOSITOR_TIMEOUT {
+                warn!("🪟 No compositor at {:?}, trying anyway", socket);
+                return;
+            }
+            clock::sleep(Duration::from_secs(1));
+        }
+    }
+
+    /// A kiosk window is never closed: close requests (a compositor
+    /// shortcut, a stray Alt+F4) are refused and fullscreen is reapplied.
+    pub fn init(app: &MainWindow) {
+        let weak = app.as_weak();
+        app.window().on_close_requested(move || {
+            warn!("🪟 Window close requested, keeping it open");
+            if let Some(window) = weak.upgrade() {
+                window.window().set_fullscreen(true);
+            }
+            CloseRequestResponse::KeepWindowShown
+        });
+    }
+
+    /// Called once the event loop has ended, which on the kiosk means the
+    /// window or the compositor went away; the event loop can't be started
+    /// again in the same process. Saves a donation session in progress and
+    /// starts over as a fresh process, which waits for the compositor.
+    pub fn relaunch(app: &MainWindow, config: &Config, result: Result<(), slint::PlatformError>) {
+        let reason = match result {
+            Ok(()) => "event loop ended".to_string(),
+            Err(e) => e.to_string(),
+        };
+        error!("🪟 Window lost ({}), relaunching", reason);
+        save_session(app, &config.stats_db_path);
+        audit::record_now(&config.stats_db_path, "window_lost", &reason);
+        reboot_handler::restart_self();
+        std::process::exit(1);
+    }
+
+    /// Keeps a donation session in progress for `resume` on the next start:
+    /// who and what fund, and the coins (the bills are in the ledger).
+    /// False when there is none.
+    pub fn save_session(app: &MainWindow, stats_db_path: &str) -> bool {
+        if app.get_current_page() == Page::InsertMoney && app.get_session_amount() > 0 {
+            let session = InterruptedSession {
+                username: app.get_session_username().into(),
+                fund_id: app.get_session_fund_id(),
+                fund_name: app.get_session_fund_name().into(),
+                coins: app.get_session_amount() - app.get_session_bill_amount(),
+            };
+            debug!("🪟 Saving the running session: {:?}", session);
+            kv::set(stats_db_path, &kv::INTERRUPTED_SESSION, &session);
+            return true;
+        }
+        false
+    }
+
+    /// Saves the session every time its amount or the page changes, and
+    /// drops it once it has ended, so a crash in the middle of a donation
+    /// is resumed like a lost window.
+    pub fn persist(app: &MainWindow, config: &Config) {
+        let stats_db_path = config.stats_db_path.clone();
+        let saved = Cell::new(false);
+        let weak = app.as_weak();
+        app.on_session_changed(move || {
+            let Some(window) = weak.upgrade() else {
+                return;
+            };
+            if save_session(&window, &stats_db_path) {
+                saved.set(true);
+            } else if saved.replace(false) {
+                kv::remove(&stats_db_path, &kv::INTERRUPTED_SESSION);
+            }
+        });
+    }
+
+    /// Puts a session saved by `save_session` back on the insert page once the
+    /// app has booted to Main, with its bills taken from the ledger. If the
+    /// acceptors can't be started the money is committed as abandoned.
+    pub fn resume(app: &MainWindow, config: &Config) {
+        let stats_db_path = config.stats_db_path.clone();
+        let Some(session) = kv::get(&stats_db_path, &kv::INTERRUPTED_SESSION) else {
+            return;
+        };
+        kv::remove(&stats_db_path, &kv::INTERRUPTED_SESSION);
+        // Read before the bill driver opens a new ledger session
+        let bills = ledger::current_session_total(&stats_db_path).unwrap_or_else(|e| {
+            error!("Failed to read the interrupted session's bills: {}", e);
+            0
+        });
+        let amount = session.coins + bills;
+        info!(
+            "🪟 Resuming interrupted session: {} {currency} from {} to {}",
+            amount,
+            session.username,
+            session.fund_name,
+            currency = money::currency()
+        );
+        audit::record(
+            &stats_db_path,
+            "session_resumed",
+            &format!(
+                "{} {currency} from {} to fund {}",
+                amount,
+                session.username,
+                session.fund_id,
+                currency = money::currency()
+            ),
+        );
+
+        let navigated: Cell<Option<Instant>> = Cell::new(None);
+        let weak = app.as_weak();
+        let timer = Rc::new(Timer::default());
+        let timer_weak = Rc::downgrade(&timer);
+        timer.start(TimerMode::Repeated, Duration::from_millis(500), move || {
+            let Some(window) = weak.upgrade() else {
+                return;
+            };
+            match navigated.get() {
+                // Still booting
+                None if window.get_current_page() != Page::Main => return,
+                None => {
+                    window.set_session_username(session.username.clone().into());
+                    window.set_session_fund_id(session.fund_id);
+                    window.set_session_fund_name(session.fund_name.clone().into());
+                    window.set_session_amount(amount);
+                    window.set_last_added_amount(0);
+                    navigated.set(Some(clock::instant()));
+                    window.invoke_navigate(Page::InsertMoney);
+                    return;
+                }
+                Some(_) if window.get_current_page() == Page::InsertMoney => {}
+                Some(at) if clock::since(at) < RESUME_TIMEOUT => return,
+                Some(_) => {
+                    warn!("🪟 Interrupted session couldn't be resumed");
+                    window.invoke_session_abandoned(window.get_session_amount());
+                    window.set_session_amount(0);
+                }
+            }
+            if let Some(timer) = timer_weak.upgrade() {
+                timer.stop();
+            }
+        });
+        // Stopped from inside the callback once the session is back or given up
+        std::mem::forget(timer);
+    }
+}
+
+mod lockdown_handler {
+    use super::*;
+    use crate::lockdown::{self, Modifiers};
+    use std::cell::Cell;
+    use std::time::Instant;
+
+    /// Someone mashing Alt+F4 gets one audit entry per this long; every
+    /// press is still logged.
+    const AUDIT_EVERY: Duration = Duration::from_secs(60);
+
+    pub fn init(window: &MainWindow, config: &Config) {
+        let db_path = config.stats_db_path.clone();
+        let last_audit: Cell<Option<Instant>> = Cell::new(None);
+        window
+            .global::<KioskInput>()
+            .on_blocked(move |text, alt, control, meta| {
+                let modifiers = Modifiers { alt, control, meta };
+                if mock_acceptor::key(&text, modifiers) {
+                    return true;
+                }
+                let Some(combo) = lockdown::blocked(&text, modifiers) else {
+                    return false;
+                };
+                warn!("⌨️  Ignored {} from a physical keyboard", combo);
+                if last_audit
+                    .get()
+                    .is_none_or(|at| clock::since(at) >= AUDIT_EVERY)
+                {
+                    last_audit.set(Some(clock::instant()));
+                    audit::record(&db_path, "keyboard_blocked", &combo);
+                }
+                true
+            });
+    }
+}
+
+mod softkey_handler {
+    use super::*;
+    use crate::softkeys::{self, Action};
+    use log::debug;
+    use slint::{Timer, TimerMode};
+    use std::cell::Cell;
+    use std::sync::mpsc::channel;
+    use std::time::Instant;
+
+    /// The highlight goes away this long after the last press, so it
+    /// doesn't linger once touch works again.
+    const IDLE: Duration = Duration::from_secs(60);
+
+    fn press(window: &MainWindow, action: Action) {
+        let key = match action {
+            Action::Previous => SoftKey::Previous,
+            Action::Next => SoftKey::Next,
+            Action::Confirm => SoftKey::Confirm,
+            Action::Cancel => SoftKey::Cancel,
+        };
+        let softkeys = window.global::<SoftKeys>();
+        softkeys.set_active(true);
+
+        if window.get_nav_confirm_open() {
+            match action {
+                Action::Confirm => window.invoke_confirm_navigation(),
+                Action::Cancel => window.invoke_cancel_navigation(),
+                Action::Previous | Action::Next => {}
+            }
+            return;
+        }
+        match window.get_current_page() {
+            // These pages handle the key themselves, see softkeys.slint
+            Page::Main | Page::Donate | Page::InsertMoney | Page::InsertCoins => {
+                if window.get_current_page() == Page::InsertMoney {
+                    window.invoke_activity_on_insert_money();
+                }
+                softkeys.set_last(key);
+                softkeys.set_presses(softkeys.get_presses() + 1);
+            }
+            Page::Boot if action == Action::Confirm && window.get_boot_held() => {
+                window.invoke_boot_finished()
+            }
+            Page::Boot => {}
+            _ if action == Action::Cancel => window.invoke_back(),
+            _ => {}
+        }
+    }
+
+    /// Physical buttons as a fallback when the touchscreen digitizer fails:
+    /// previous/next move the highlight on the donation and game pages,
+    /// confirm presses it, cancel goes back. Devices are read on their own
+    /// threads, see `softkeys`.
+    pub fn init(app: &MainWindow, config: &Config) {
+        if config.softkeys.devices.is_empty() {
+            return;
+        }
+        let (tx, rx) = channel();
+        softkeys::start(&config.softkeys, tx);
+
+        let last_press: Cell<Option<Instant>> = Cell::new(None);
+        let weak = app.as_weak();
+        let timer = Timer::default();
+        timer.start(TimerMode::Repeated, Duration::from_millis(50), move || {
+            let Some(window) = weak.upgrade() else {
+                return;
+            };
+            while let Ok(action) = rx.try_recv() {
+                debug!("⌨️  Softkey {:?}", action);
+                last_press.set(Some(clock::instant()));
+                press(&window, action);
+            }
+            if last_press.get().is_some_and(|at| clock::since(at) >= IDLE) {
+                last_press.set(None);
+                window.global::<SoftKeys>().set_active(false);
+            }
+        });
+        std::mem::forget(timer);
+    }
+}
+
+mod status_light_handler {
+    use super::*;
+    use crate::mqtt::Publisher;
+    use log::debug;
+    use slint::{Timer, TimerMode};
+    use std::cell::Cell;
+
+    /// What the donation box is doing, as the status LED shows it.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum Status {
+        Idle,
+        Accepting,
+        Paused,
+        Fault,
+    }
+
+    impl Status {
+        pub fn name(self) -> &'static str {
+            match self {
+                Status::Idle => "idle",
+                Status::Accepting => "accepting",
+                Status::Paused => "paused",
+                Status::Fault => "fault",
+            }
+        }
+
+        /// JSON-schema light state: accepting pulses green, a fault is solid
+        /// red, paused is solid amber and idle is off.
+        fn light(self) -> serde_json::Value {
+            let (color, effect) = match self {
+                Status::Idle => return serde_json::json!({ "state": "OFF" }),
+                Status::Accepting => ((0, 255, 0), "pulse"),
+                Status::Paused => ((255, 160, 0), "solid"),
+                Status::Fault => ((255, 0, 0), "solid"),
+            };
+            serde_json::json!({
+                "state": "ON",
+                "color_mode": "rgb",
+                "color": { "r": color.0, "g": color.1, "b": color.2 },
+                "effect": effect,
+            })
+        }
+    }
+
+    pub fn current(window: &MainWindow) -> Status {
+        if window.get_notifications().iter().any(|n| n.level >= 3) {
+            Status::Fault
+        } else if window.get_acceptance_locked() {
+            Status::Paused
+        } else if window.get_accepting_money() {
+            Status::Accepting
         } else {
-            format!("{}d ago", diff / 86400)
+            Status::Idle
+        }
+    }
+
+    /// Mirrors the kiosk's status colour to Home Assistant as an MQTT light
+    /// (plus a plain sensor), so the space's LED strip automation can follow
+    /// the donation box. Both are announced through MQTT discovery.
+    pub fn init(app: &MainWindow, publisher: Publisher) {
+        let light_topic = publisher.topic("status_light");
+        let status_topic = publisher.topic("status");
+        publisher.announce(
+            "light",
+            "status_light",
+            serde_json::json!({
+                "name": "Status light",
+                "schema": "json",
+                "state_topic": light_topic,
+                // required by HA; the kiosk owns the colour and ignores commands
+                "command_topic": format!("{}/set", light_topic),
+                "supported_color_modes": ["rgb"],
+                "effect": true,
+                "effect_list": ["pulse", "solid"],
+                "brightness": false,
+            }),
+        );
+        publisher.announce(
+            "sensor",
+            "status",
+            serde_json::json!({
+                "name": "Status",
+                "state_topic": status_topic,
+                "device_class": "enum",
+                "options": ["idle", "accepting", "paused", "fault"],
+                "icon": "mdi:led-on",
+            }),
+        );
+
+        let last = Cell::new(None);
+        let weak = app.as_weak();
+        let timer = Timer::default();
+        timer.start(TimerMode::Repeated, Duration::from_secs(1), move || {
+            let Some(window) = weak.upgrade() else {
+                return;
+            };
+            let status = current(&window);
+            if last.replace(Some(status)) == Some(status) {
+                return;
+            }
+            debug!("💡 Status light: {}", status.name());
+            publisher.publish(&light_topic, &status.light().to_string(), true);
+            publisher.publish(&status_topic, status.name(), true);
+        });
+        std::mem::forget(timer);
+    }
+}
+
+mod status_file_handler {
+    use super::*;
+    use crate::mqtt::Publisher;
+    use crate::status_file::{self, Acceptor, Live, Session};
+    use slint::{Timer, TimerMode};
+    use std::sync::mpsc;
+
+    fn live(window: &MainWindow, capacity: Option<u32>) -> Live {
+        let headroom = window.get_stacker_headroom();
+        let headroom = (headroom >= 0).then_some(headroom);
+        let fill =
+            headroom
+                .zip(capacity.filter(|&capacity| capacity > 0))
+                .map(|(headroom, capacity)| {
+                    let capacity = capacity as i64;
+                    ((capacity - i64::from(headroom)) * 100 / capacity) as i32
+                });
+        Live {
+            acceptor: Acceptor {
+                state: status_light_handler::current(window).name(),
+                stacker_headroom: headroom,
+                stacker_fill: fill,
+            },
+            session: Session {
+                active: window.get_current_page() == Page::InsertMoney,
+                amount: window.get_session_amount(),
+            },
+            faults: window
+                .get_notifications()
+                .iter()
+                .filter(|n| n.level >= 3)
+                .map(|n| n.text.to_string())
+                .collect(),
         }
     }
 
+    /// Rewrites `[status_file] path` every `interval_secs`. The UI state is
+    /// read on the event loop and handed to a writer thread, which adds the
+    /// DB figures; a slow disk only delays the file, never the kiosk.
     pub fn init(app: &MainWindow, config: &Config) {
+        let Some(path) = config.status_file.path.clone() else {
+            return;
+        };
+        info!("📄 Writing status to {}", path);
         let stats_db_path = config.stats_db_path.clone();
-        let photos_dir = config.photos_dir.clone();
+        let (tx, rx) = mpsc::sync_channel::<Live>(1);
+        thread::spawn(move || {
+            let mut failing = false;
+            for live in rx {
+                match status_file::write(&path, &stats_db_path, &live) {
+                    Ok(()) => failing = false,
+                    // Logged once per outage, not on every rewrite
+                    Err(e) if !failing => {
+                        failing = true;
+                        error!("📄 Failed to write status file {}: {}", path, e);
+                    }
+                    Err(_) => {}
+                }
+            }
+        });
+
         let weak = app.as_weak();
+        let capacity = config.stacker.capacity;
+        let timer = Timer::default();
+        let interval = Duration::from_secs(config.status_file.interval_secs.max(1));
+        timer.start(TimerMode::Repeated, interval, move || {
+            if let Some(window) = weak.upgrade() {
+                // A writer still busy with the last one skips this round
+                let _ = tx.try_send(live(&window, capacity));
+            }
+        });
+        std::mem::forget(timer);
+    }
 
-        app.on_fetch_logs(move || {
-            let stats_db_path = stats_db_path.clone();
-            let photos_dir = photos_dir.clone();
-            let weak = weak.clone();
+    /// Publishes the status file's figures as retained `metrics/…` topics
+    /// every `[mqtt] metrics_interval_secs`, announced as sensors, so Home
+    /// Assistant keeps their history without a status file or Prometheus.
+    /// Read off the event loop like the file.
+    pub fn init_mqtt(app: &MainWindow, config: &Config, publisher: Publisher) {
+        if config.mqtt.metrics_interval_secs == 0 {
+            return;
+        }
+        for metric in &status_file::METRICS {
+            let object_id = metric.topic.replace('/', "_");
+            let mut sensor = serde_json::json!({
+                "name": metric.name,
+                "state_topic": publisher.topic(metric.topic),
+                "state_class": "measurement",
+            });
+            if let Some(unit) = metric.unit {
+                sensor["unit_of_measurement"] = unit.symbol().into();
+            }
+            publisher.announce("sensor", &object_id, sensor);
+        }
 
-            thread::spawn(move || {
-                // The DB read is the slow part, so it happens off the UI thread.
-                // `slint::Image` isn't `Send`, though, so it can't be built here —
-                // loading each photo has to happen after we hop back to the UI thread.
-                let entries = match donation_log::fetch_recent(&stats_db_path, LOG_LIMIT) {
-                    Ok(entries) => entries,
+        let stats_db_path = config.stats_db_path.clone();
+        let (tx, rx) = mpsc::sync_channel::<Live>(1);
+        thread::spawn(move || {
+            for live in rx {
+                let values = match status_file::metrics(&stats_db_path, &live) {
+                    Ok(values) => values,
                     Err(e) => {
-                        error!("Failed to fetch donation log: {}", e);
-                        Vec::new()
+                        warn!("📊 Failed to read metrics: {}", e);
+                        continue;
                     }
                 };
+                for (metric, value) in status_file::METRICS.iter().zip(values) {
+                    if let Some(value) = value {
+                        publisher.publish(&publisher.topic(metric.topic), &value.to_string(), true);
+                    }
+                }
+            }
+        });
 
-                let _ = slint::invoke_from_event_loop(move || {
-                    let Some(window) = weak.upgrade() else {
-                        return;
-                    };
-                    let items: Vec<DonationLogItem> = entries
-                        .into_iter()
-                        .map(|entry| {
-                            let is_anon = entry.username == "anon";
-                            let photo = if is_anon {
-                                None
-                            } else {
-                                let path = std::path::Path::new(&photos_dir)
-                                    .join(camera::photo_filename(entry.timestamp, &entry.username));
-                                Image::load_from_path(&path).ok()
-                            };
-                            DonationLogItem {
-                                username: entry.username.into(),
-                                amount: entry.amount,
-                                fund_name: entry.fund_name.into(),
-                                when: format_relative_time(entry.timestamp).into(),
-                                is_anon,
-                                has_photo: photo.is_some(),
-                                photo: photo.unwrap_or_default(),
-                            }
-                        })
-                        .collect();
-                    window.set_donation_logs(ModelRc::new(VecModel::from(items)));
+        let weak = app.as_weak();
+        let capacity = config.stacker.capacity;
+        let timer = Timer::default();
+        let interval = Duration::from_secs(config.mqtt.metrics_interval_secs);
+        let send = move || {
+            if let Some(window) = weak.upgrade() {
+                let _ = tx.try_send(live(&window, capacity));
+            }
+        };
+        // first figures at startup rather than a whole interval later
+        send();
+        timer.start(TimerMode::Repeated, interval, send);
+        std::mem::forget(timer);
+    }
+}
+
+mod report_handler {
+    use super::*;
+    use crate::report::{self, Month, MonthlyReport};
+    use slint::{Timer, TimerMode};
+    use std::path::PathBuf;
+
+    const CHECK_INTERVAL: Duration = Duration::from_secs(10 * 60);
+
+    /// Writes last month's treasurer report once the month is over (the
+    /// file in `report.dir` marks it done), then posts it to the gateway
+    /// and/or Telegram if configured. Checked shortly after boot and every
+    /// ten minutes, so a kiosk that was off on the 1st catches up.
+    pub fn init(config: &Config) {
+        let dir = PathBuf::from(&config.report.dir);
+        let settings = config.report.clone();
+        let token = config.token.clone();
+        let stats_db_path = config.stats_db_path.clone();
+
+        let check = move || {
+            let month = Month::current().previous();
+            if report::markdown_path(&dir, month).exists() {
+                return;
+            }
+            let saved = report::build(&stats_db_path, month)
+                .map_err(|e| e.to_string())
+                .and_then(|r| {
+                    report::save(&r, &dir)
+                        .map(|path| (r, path))
+                        .map_err(|e| e.to_string())
                 });
-            });
-        });
+            let (monthly, path) = match saved {
+                Ok(saved) => saved,
+                Err(e) => {
+                    error_code::report(
+                        &stats_db_path,
+                        ErrorCode::Database,
+                        &format!("Failed to write the {} report: {}", month, e),
+                    );
+                    return;
+                }
+            };
+            info!(
+                "📊 Report for {} written to {} ({})",
+                month,
+                path.display(),
+                if monthly.balanced() {
+                    "balanced"
+                } else {
+                    "needs review"
+                }
+            );
+            audit::record(&stats_db_path, "report", &path.to_string_lossy());
+            deliver(monthly, &settings, token.clone(), stats_db_path.clone());
+        };
+
+        Timer::single_shot(Duration::from_secs(60), check.clone());
+        let timer = Timer::default();
+        timer.start(TimerMode::Repeated, CHECK_INTERVAL, check);
+        std::mem::forget(timer);
+    }
+
+    fn deliver(
+        monthly: MonthlyReport,
+        settings: &config::Report,
+        token: Option<String>,
+        stats_db_path: String,
+    ) {
+        let gateway_token = if settings.post_to_gateway {
+            if token.is_none() {
+                warn!("📊 report.post_to_gateway is set but there is no token");
+            }
+            token
+        } else {
+            None
+        };
+        let telegram = settings
+            .telegram_bot_token
+            .clone()
+            .zip(settings.telegram_chat_id.clone());
+        if gateway_token.is_none() && telegram.is_none() {
+            return;
+        }
+        slint::spawn_local(async move {
+            if let Some(token) = gateway_token {
+                match report::post_to_gateway(&token, &monthly).await {
+                    Ok(()) => info!("📊 Report for {} sent to the gateway", monthly.month),
+                    Err(e) => error_code::report(
+                        &stats_db_path,
+                        e.code(),
+                        &format!("Failed to send the {} report: {}", monthly.month, e),
+                    ),
+                }
+            }
+            if let Some((bot_token, chat_id)) = telegram {
+                match report::send_telegram(&bot_token, &chat_id, &monthly).await {
+                    Ok(()) => info!("📊 Report for {} sent to Telegram", monthly.month),
+                    Err(e) => warn!("📊 Failed to send the report to Telegram: {}", e),
+                }
+            }
+        })
+        .unwrap();
+    }
+}
+
+mod boot_checklist {
+    use super::*;
+    use slint::{ModelRc, VecModel};
+
+    /// A subsystem with a row on the boot screen.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum Check {
+        Config,
+        Database,
+        Bills,
+        Gateway,
+        HomeAssistant,
+        Mqtt,
+    }
+
+    impl Check {
+        pub fn id(self) -> &'static str {
+            match self {
+                Check::Config => "config",
+                Check::Database => "database",
+                Check::Bills => "bills",
+                Check::Gateway => "gateway",
+                Check::HomeAssistant => "hass",
+                Check::Mqtt => "mqtt",
+            }
+        }
+
+        pub fn from_id(id: &str) -> Option<Check> {
+            [
+                Check::Config,
+                Check::Database,
+                Check::Bills,
+                Check::Gateway,
+                Check::HomeAssistant,
+                Check::Mqtt,
+            ]
+            .into_iter()
+            .find(|check| check.id() == id)
+        }
+
+        fn name(self) -> &'static str {
+            match self {
+                Check::Config => "Config",
+                Check::Database => "Database",
+                Check::Bills => "Bill acceptor",
+                Check::Gateway => "Gateway",
+                Check::HomeAssistant => "Home Assistant",
+                Check::Mqtt => "MQTT",
+            }
+        }
+
+        /// Whether the boot screen offers Retry; the listener and MQTT keep
+        /// retrying on their own and the config needs a restart.
+        fn retry(self) -> bool {
+            matches!(self, Check::Database | Check::Bills | Check::Gateway)
+        }
     }
-}
 
-mod diagnostics_handler {
-    use super::*;
-    use slint::{ModelRc, Timer, TimerMode, VecModel};
+    /// Lists the subsystems this config uses, all starting, and checks the
+    /// ones that are known right away.
+    pub fn init(app: &MainWindow, config: &Config, config_error: Option<&str>) {
+        let mut checks = vec![Check::Config, Check::Database, Check::Bills, Check::Gateway];
+        if config.features.home_assistant {
+            checks.push(Check::HomeAssistant);
+        }
+        if config.mqtt.host.is_some() {
+            checks.push(Check::Mqtt);
+        }
+        let rows: Vec<BootCheck> = checks
+            .into_iter()
+            .map(|check| BootCheck {
+                id: check.id().into(),
+                name: check.name().into(),
+                level: 0,
+                text: "Starting…".into(),
+                retry: check.retry(),
+            })
+            .collect();
+        app.set_boot_checks(ModelRc::new(VecModel::from(rows)));
 
-    const MAX_LOG_LINES: usize = 300;
+        match config_error {
+            Some(e) => set(app, Check::Config, 3, &format!("{}, using defaults", e)),
+            None if !config.notices.is_empty() => set(
+                app,
+                Check::Config,
+                2,
+                &format!("Loaded, {} notice(s) below", config.notices.len()),
+            ),
+            None => set(app, Check::Config, 1, "Loaded"),
+        }
+        check_database(app, &config.stats_db_path);
+    }
 
-    /// Returns (level, text): level 0=neutral 1=ok 2=warn 3=error
-    async fn check_backend(token: Option<String>) -> (i32, String) {
-        use http::Request;
+    /// Opens Stats.db and makes sure it takes writes.
+    pub fn check_database(window: &MainWindow, db_path: &str) {
+        let result = rusqlite::Connection::open(db_path).and_then(|db| {
+            audit::init_db(&db)?;
+            db.execute_batch("BEGIN IMMEDIATE; ROLLBACK;")
+        });
+        match result {
+            Ok(()) => set(window, Check::Database, 1, db_path),
+            Err(e) => {
+                error!("🗄️ {} is not usable: {}", db_path, e);
+                set(window, Check::Database, 3, &format!("{}: {}", db_path, e));
+            }
+        }
+    }
 
-        let Some(tok) = token else {
-            return (2, "No token configured".to_string());
+    /// Updates a row. Rows for subsystems not in use are ignored.
+    pub fn set(window: &MainWindow, check: Check, level: i32, text: &str) {
+        let rows = window.get_boot_checks();
+        let Some(index) = rows.iter().position(|row| row.id == check.id()) else {
+            return;
         };
-
-        let request = match Request::get("https://gateway.hackem.cc/api/funds?status=open")
-            .header("Authorization", format!("Bearer {}", tok))
-            .body(())
-        {
-            Ok(r) => r,
-            Err(e) => return (3, format!("Request error: {}", e)),
+        let Some(mut row) = rows.row_data(index) else {
+            return;
         };
+        if row.level == level && row.text == text {
+            return;
+        }
+        info!("🚀 {}: {}", check.name(), text);
+        row.level = level;
+        row.text = text.into();
+        rows.set_row_data(index, row);
+    }
 
-        match isahc::send_async(request).await {
-            Ok(r) => {
-                let s = r.status();
-                if s.is_success() {
-                    (1, format!("OK (HTTP {})", s.as_u16()))
-                } else if s.as_u16() == 401 {
-                    (2, "HTTP 401 — token invalid or expired".to_string())
-                } else {
-                    (
-                        2,
-                        format!(
-                            "HTTP {} — {}",
-                            s.as_u16(),
-                            s.canonical_reason().unwrap_or("Unknown")
-                        ),
-                    )
-                }
+    /// `set` from another thread.
+    pub fn report(weak: &slint::Weak<MainWindow>, check: Check, level: i32, text: String) {
+        let weak = weak.clone();
+        let _ = slint::invoke_from_event_loop(move || {
+            if let Some(window) = weak.upgrade() {
+                set(&window, check, level, &text);
             }
-            Err(e) => (3, format!("Unreachable: {}", e)),
-        }
+        });
+    }
+
+    pub fn any_failed(window: &MainWindow) -> bool {
+        window.get_boot_checks().iter().any(|row| row.level == 3)
     }
 
+    pub fn any_starting(window: &MainWindow) -> bool {
+        window.get_boot_checks().iter().any(|row| row.level == 0)
+    }
+}
+
+mod startup_handler {
+    use super::*;
+    use crate::boot_checklist::Check;
+    use slint::{Timer, TimerMode};
+    use std::cell::Cell;
+    use std::time::Instant;
+
+    /// Longest the boot screen waits for subsystems that haven't reported in.
+    const BOOT_TIMEOUT: Duration = Duration::from_secs(30);
+    /// Shortest boot screen when there are config notices to read on it.
+    const CONFIG_NOTICE_HOLD: Duration = Duration::from_secs(10);
+    /// How long a failure keeps the boot screen up for a retry before the
+    /// kiosk carries on without it.
+    const FAILURE_HOLD: Duration = Duration::from_secs(60);
+
+    /// Keeps the boot screen up while the drivers come up on their own
+    /// threads, and prefetches funds/usernames meanwhile so the donate page
+    /// opens warm. Moves on to the main page once every subsystem on the
+    /// checklist has reported in; when one failed, waits for a retry or
+    /// Continue first. Donate stays disabled until the bill acceptor is
+    /// ready.
     pub fn init(
         app: &MainWindow,
-        log_rx: std::sync::mpsc::Receiver<diag_logger::LogLine>,
+        config: &Config,
         cashcode_tx: Sender<bill_acceptor::CashCodeCommand>,
-        cctalk_tx: Sender<cctalk::CoinAcceptorCommand>,
-        token: Option<String>,
+        publisher: Option<mqtt::Publisher>,
     ) {
-        // Build the model and hand it to the window.
-        let log_model = std::rc::Rc::new(VecModel::<LogEntry>::default());
-        app.set_diag_logs(ModelRc::from(log_model.clone()));
-
-        // Drain the log channel into the model on every tick.
-        let timer = Timer::default();
-        timer.start(
-            TimerMode::Repeated,
-            std::time::Duration::from_millis(500),
-            move || {
-                while let Ok((lvl, text)) = log_rx.try_recv() {
-                    log_model.insert(
-                        0,
-                        LogEntry {
-                            level: lvl as i32,
-                            text: text.into(),
-                        },
-                    );
-                    if log_model.row_count() > MAX_LOG_LINES {
-                        log_model.remove(log_model.row_count() - 1);
-                    }
+        // Deferred so the fetches start once the event loop is running
+        let weak_prefetch = app.as_weak();
+        let prefetch_usernames = config.features.username_entry;
+        Timer::single_shot(Duration::ZERO, move || {
+            if let Some(window) = weak_prefetch.upgrade() {
+                window.invoke_fetch_funds();
+                if prefetch_usernames {
+                    window.invoke_fetch_usernames();
                 }
-            },
-        );
-        std::mem::forget(timer);
+            }
+        });
 
-        // Live camera preview — only streams while the Diagnostics page is open.
-        let (preview_cmd_tx, preview_cmd_rx) = std::sync::mpsc::channel::<camera::PreviewCommand>();
-        let (preview_frame_tx, preview_frame_rx) =
-            std::sync::mpsc::sync_channel::<camera::PreviewFrame>(1);
-        camera::spawn_preview(preview_cmd_rx, preview_frame_tx);
+        let started = Rc::new(Cell::new(clock::instant()));
+        let failed_since: Rc<Cell<Option<Instant>>> = Rc::new(Cell::new(None));
 
-        let weak_preview = app.as_weak();
-        let was_on_diagnostics = Rc::new(RefCell::new(false));
-        let preview_timer = Timer::default();
-        preview_timer.start(
-            TimerMode::Repeated,
-            std::time::Duration::from_millis(100),
-            move || {
-                let Some(window) = weak_preview.upgrade() else {
+        // A retry restarts the wait, so the result shows before moving on
+        app.on_boot_retry({
+            let weak = app.as_weak();
+            let started = started.clone();
+            let failed_since = failed_since.clone();
+            let stats_db_path = config.stats_db_path.clone();
+            move |id| {
+                let Some(window) = weak.upgrade() else {
                     return;
                 };
-                let on_page = window.get_on_diagnostics_page();
-                if on_page != *was_on_diagnostics.borrow() {
-                    *was_on_diagnostics.borrow_mut() = on_page;
-                    if on_page {
-                        let _ = preview_cmd_tx.send(camera::PreviewCommand::Start);
-                    } else {
-                        let _ = preview_cmd_tx.send(camera::PreviewCommand::Stop);
-                        window.set_diag_camera_available(false);
+                let Some(check) = Check::from_id(&id) else {
+                    return;
+                };
+                info!("🚀 Retrying {:?}", check);
+                boot_checklist::set(&window, check, 0, "Retrying…");
+                match check {
+                    Check::Database => boot_checklist::check_database(&window, &stats_db_path),
+                    // A failed driver is started again by a reset
+                    Check::Bills => {
+                        window.set_diag_bill_status(LogEntry {
+                            level: 0,
+                            text: "Restarting...".into(),
+                        });
+                        if cashcode_tx
+                            .send(bill_acceptor::CashCodeCommand::Reset)
+                            .is_err()
+                        {
+                            error!("Failed to send Reset to bill acceptor");
+                        }
                     }
+                    Check::Gateway => window.invoke_fetch_funds(),
+                    _ => {}
                 }
-                // Drain to the most recent frame only — older ones are stale.
-                let mut latest = None;
-                while let Ok(frame) = preview_frame_rx.try_recv() {
-                    latest = Some(frame);
-                }
-                if let Some(frame) = latest {
-                    let pixel_buffer =
-                        slint::SharedPixelBuffer::<slint::Rgb8Pixel>::clone_from_slice(
-                            &frame.rgb,
-                            frame.width,
-                            frame.height,
-                        );
-                    window.set_diag_camera_frame(slint::Image::from_rgb8(pixel_buffer));
-                    window.set_diag_camera_available(true);
-                }
-            },
-        );
-        std::mem::forget(preview_timer);
-
-        let cashcode_tx_reset = cashcode_tx;
-        app.on_diag_reset_bills(move || {
-            info!("🔄 Diagnostics: resetting bill acceptor");
-            if cashcode_tx_reset
-                .send(bill_acceptor::CashCodeCommand::Reset)
-                .is_err()
-            {
-                error!("Failed to send Reset to bill acceptor");
+                started.set(clock::instant());
+                failed_since.set(None);
+                window.set_boot_held(false);
             }
         });
 
-        let cctalk_tx_reenumerate = cctalk_tx;
-        app.on_diag_reenumerate_coins(move || {
-            info!("ccTalk: re-enumeration requested from diagnostics");
-            if cctalk_tx_reenumerate
-                .send(cctalk::CoinAcceptorCommand::Reenumerate)
-                .is_err()
-            {
-                error!("Failed to send Reenumerate to coin acceptor");
+        let hold = if config.notices.is_empty() {
+            Duration::ZERO
+        } else {
+            CONFIG_NOTICE_HOLD
+        };
+        let weak = app.as_weak();
+        let timer = Rc::new(Timer::default());
+        let timer_weak = Rc::downgrade(&timer);
+        timer.start(TimerMode::Repeated, Duration::from_millis(200), move || {
+            let Some(window) = weak.upgrade() else {
+                return;
+            };
+            // Continue was tapped
+            if window.get_current_page() != Page::Boot {
+                if let Some(timer) = timer_weak.upgrade() {
+                    timer.stop();
+                }
+                return;
             }
-        });
-
-        app.on_diag_play_sound(|| {
-            info!("🔊 Diagnostics: playing sound");
-            crate::sound::play_yippee();
-        });
 
-        let weak_backend = app.as_weak();
-        app.on_diag_check_backend(move || {
-            let weak = weak_backend.clone();
-            let tok = token.clone();
-            if let Some(w) = weak.upgrade() {
-                w.set_diag_backend_status(LogEntry {
-                    level: 0,
-                    text: "Checking...".into(),
-                });
+            let ready = window.get_bills_ready();
+            let bill_status = window.get_diag_bill_status();
+            let bill_level = match (ready, bill_status.level) {
+                (true, _) => 1,
+                (false, 3) => 3,
+                _ => 0,
+            };
+            boot_checklist::set(&window, Check::Bills, bill_level, &bill_status.text);
+            if let Some(publisher) = &publisher {
+                match publisher.connection() {
+                    Some(Ok(())) => boot_checklist::set(&window, Check::Mqtt, 1, "Connected"),
+                    Some(Err(e)) => {
+                        boot_checklist::set(&window, Check::Mqtt, 3, &format!("{}, retrying", e))
+                    }
+                    None => {}
+                }
             }
-            slint::spawn_local(async move {
-                let (level, text) = check_backend(tok).await;
-                if let Some(w) = weak.upgrade() {
-                    w.set_diag_backend_status(LogEntry {
-                        level,
-                        text: text.into(),
-                    });
+
+            let elapsed = clock::since(started.get());
+            if boot_checklist::any_failed(&window) {
+                let since = failed_since.get().unwrap_or_else(clock::instant);
+                failed_since.set(Some(since));
+                window.set_boot_held(true);
+                if clock::since(since) < FAILURE_HOLD {
+                    return;
                 }
-            })
-            .unwrap();
+            } else {
+                failed_since.set(None);
+                window.set_boot_held(false);
+            }
+            let timed_out = elapsed >= BOOT_TIMEOUT;
+            if (boot_checklist::any_starting(&window) && !timed_out) || elapsed < hold {
+                return;
+            }
+
+            if ready {
+                info!("🚀 Startup finished in {:?}", elapsed);
+            } else {
+                warn!(
+                    "🚀 Startup finished without bill acceptor after {:?}",
+                    elapsed
+                );
+            }
+            window.invoke_boot_finished();
+            if let Some(timer) = timer_weak.upgrade() {
+                timer.stop();
+            }
         });
+        // Stopped from inside the callback once boot is over
+        std::mem::forget(timer);
     }
 }
 
 mod home_assistant_handler {
     use super::*;
-    use crate::home_assistant::ChromiumManager;
+    use crate::home_assistant::{ChromiumManager, RemoteCommand};
     use std::sync::Arc;
 
-    pub fn init(app: &MainWindow, config: &Config) {
+    /// How often binding the listener is retried after it failed.
+    const LISTENER_RETRY: Duration = Duration::from_secs(30);
+    /// How often a Chromium showing the HASS page is checked on.
+    const CHROMIUM_CHECK: Duration = Duration::from_secs(2);
+
+    pub fn init(
+        app: &MainWindow,
+        config: &Config,
+        cashcode_tx: Sender<bill_acceptor::CashCodeCommand>,
+        cctalk_tx: Sender<cctalk::CoinAcceptorCommand>,
+        booster: boost_handler::Booster,
+    ) -> Arc<ChromiumManager> {
         let chromium = Arc::new(ChromiumManager::new());
         info!(
             "Home Assistant URL configured: {}",
@@ -1181,10 +7529,11 @@ mod home_assistant_handler {
         // Launch Chromium when showing Home Assistant page
         let chromium_show = chromium.clone();
         let url_for_launch = config.home_assistant_url.clone();
+        let stats_db_path = config.stats_db_path.clone();
         app.on_show_home_assistant(move || {
             info!("Showing Home Assistant page, launching Chromium");
             if let Err(e) = chromium_show.launch(&url_for_launch) {
-                error!("Failed to launch Chromium: {}", e);
+                error_code::report(&stats_db_path, ErrorCode::HassLaunch, &e);
             }
         });
 
@@ -1195,24 +7544,219 @@ mod home_assistant_handler {
             chromium_hide.close();
         });
 
-        // HTTP listener so HASS can POST /close-hass to dismiss its own page
-        let (tx, rx) = std::sync::mpsc::channel::<()>();
+        // A Chromium that went away leaves the kiosk's own page behind, not
+        // the desktop; take that back to where the visitor came from
+        let chromium_check = chromium.clone();
+        let weak = app.as_weak();
+        let timer = slint::Timer::default();
+        timer.start(slint::TimerMode::Repeated, CHROMIUM_CHECK, move || {
+            let Some(window) = weak.upgrade() else {
+                return;
+            };
+            if window.get_current_page() == Page::HomeAssistant && chromium_check.exited() {
+                window.invoke_back();
+            }
+        });
+        std::mem::forget(timer);
+
+        // HTTP listener so HASS can dismiss its own page and switch money
+        // acceptance off and on from automations
+        let (tx, rx) = std::sync::mpsc::channel::<RemoteCommand>();
         let port = config.hass_api_port;
+        let token = config.hass_api_token.clone();
+        let stats_db_path = config.stats_db_path.clone();
+        let weak = app.as_weak();
         thread::spawn(move || {
-            home_assistant::start_close_listener(port, tx);
+            let mut reported = false;
+            loop {
+                match home_assistant::bind_listener(port) {
+                    Ok(listener) => {
+                        boot_checklist::report(
+                            &weak,
+                            boot_checklist::Check::HomeAssistant,
+                            1,
+                            format!("Listening on port {}", port),
+                        );
+                        home_assistant::serve_listener(listener, token, tx);
+                        return;
+                    }
+                    Err(e) => {
+                        // The port is usually held by a previous instance
+                        // that is still exiting; keep trying
+                        if !reported {
+                            error_code::report(
+                                &stats_db_path,
+                                ErrorCode::HassListener,
+                                &format!("Failed to bind HASS listener on port {}: {}", port, e),
+                            );
+                            reported = true;
+                        }
+                        boot_checklist::report(
+                            &weak,
+                            boot_checklist::Check::HomeAssistant,
+                            3,
+                            format!("Port {}: {}, retrying", port, e),
+                        );
+                        clock::sleep(LISTENER_RETRY);
+                    }
+                }
+            }
         });
 
+        // A pause survives restarts until HASS lifts it
+        if let Some(reason) = kv::get(&config.stats_db_path, &kv::ACCEPTANCE_LOCK) {
+            lock_acceptors(app, &cashcode_tx, &cctalk_tx, &reason);
+        }
+
         let weak = app.as_weak();
+        let stats_db_path = config.stats_db_path.clone();
         thread::spawn(move || {
-            while rx.recv().is_ok() {
+            while let Ok(command) = rx.recv() {
                 let weak = weak.clone();
+                let cashcode_tx = cashcode_tx.clone();
+                let cctalk_tx = cctalk_tx.clone();
+                let stats_db_path = stats_db_path.clone();
+                let booster = booster.clone();
                 let _ = slint::invoke_from_event_loop(move || {
-                    if let Some(window) = weak.upgrade() {
-                        window.invoke_close_hass_remote();
+                    let Some(window) = weak.upgrade() else {
+                        return;
+                    };
+                    match command {
+                        RemoteCommand::CloseHass => window.invoke_close_hass_remote(),
+                        RemoteCommand::DisableAcceptors(reason) => {
+                            lock_acceptors(&window, &cashcode_tx, &cctalk_tx, &reason);
+                            kv::set(&stats_db_path, &kv::ACCEPTANCE_LOCK, &reason);
+                            audit::record(&stats_db_path, "acceptors_locked", &reason);
+                        }
+                        RemoteCommand::Restart(subsystem) => {
+                            window.invoke_restart_subsystem(subsystem.name().into());
+                        }
+                        RemoteCommand::StartBoost(request) => booster.start(&request),
+                        RemoteCommand::StopBoost => booster.stop(),
+                        RemoteCommand::RecordCollection(collector) => {
+                            window.invoke_record_collection(collector.into())
+                        }
+                        RemoteCommand::EnableAcceptors => {
+                            if window.get_acceptance_locked() {
+                                info!("🏠 Money acceptance re-enabled by Home Assistant");
+                                window.set_acceptance_locked(false);
+                                window.set_acceptance_locked_reason("".into());
+                                notification_handler::clear(&window, "acceptance");
+                                kv::remove(&stats_db_path, &kv::ACCEPTANCE_LOCK);
+                                audit::record(&stats_db_path, "acceptors_unlocked", "");
+                            }
+                        }
                     }
                 });
             }
         });
+
+        chromium
+    }
+
+    /// Switches money acceptance off until HASS lifts it. A running session
+    /// is ended the usual way (money inserted so far is committed by the
+    /// leave hook); the acceptors are disabled regardless, in case they were
+    /// on for some other reason.
+    fn lock_acceptors(
+        window: &MainWindow,
+        cashcode_tx: &Sender<bill_acceptor::CashCodeCommand>,
+        cctalk_tx: &Sender<cctalk::CoinAcceptorCommand>,
+        reason: &str,
+    ) {
+        info!(
+            "🏠 Money acceptance disabled by Home Assistant: {:?}",
+            reason
+        );
+        window.set_acceptance_locked(true);
+        window.set_acceptance_locked_reason(reason.into());
+        notification_handler::set(
+            window,
+            "acceptance",
+            2,
+            &if reason.is_empty() {
+                i18n::tr("acceptance.paused", &[])
+            } else {
+                i18n::tr("acceptance.paused_reason", &[&reason])
+            },
+        );
+
+        if matches!(
+            window.get_current_page(),
+            Page::Donate | Page::InsertMoney | Page::InsertCoins
+        ) {
+            window.invoke_force_navigate(Page::Main);
+        }
+        if cashcode_tx
+            .send(bill_acceptor::CashCodeCommand::Disable)
+            .is_err()
+        {
+            error!("Failed to send disable command to CashCode");
+        }
+        if cctalk_tx
+            .send(cctalk::CoinAcceptorCommand::Disable)
+            .is_err()
+        {
+            error!("Failed to send disable command to the coin acceptor");
+        }
+    }
+}
+
+mod hass_events_handler {
+    use super::*;
+    use crate::hass_events::{self, HassNotice};
+    use crate::tamper::{Door, Source};
+    use slint::{Timer, TimerMode};
+    use std::sync::mpsc::channel;
+
+    /// Puts selected Home Assistant events (alarm, doorbell, printer done…)
+    /// on the notification bar, so the kiosk doubles as a space status
+    /// screen, and forwards the tamper entity's changes to `tamper_handler`.
+    /// The WebSocket client runs on its own thread; the returned handle
+    /// restarts it.
+    pub fn init(
+        app: &MainWindow,
+        config: &Config,
+        tamper_tx: Option<Sender<Door>>,
+    ) -> Option<hass_events::Restarter> {
+        config.hass_events.url.as_ref()?;
+        let watch = tamper_tx.as_ref().and(config.tamper.hass_entity.clone());
+        let open_state = config.tamper.hass_open_state.clone();
+        let (tx, rx) = channel();
+        let restarter = hass_events::start(&config.hass_events, watch, tx);
+
+        let weak = app.as_weak();
+        let timer = Timer::default();
+        timer.start(TimerMode::Repeated, Duration::from_millis(500), move || {
+            let Some(window) = weak.upgrade() else {
+                return;
+            };
+            while let Ok(notice) = rx.try_recv() {
+                match notice {
+                    HassNotice::Show {
+                        key: Some(key),
+                        level,
+                        text,
+                    } => notification_handler::set(&window, &key, level, &text),
+                    HassNotice::Show {
+                        key: None,
+                        level,
+                        text,
+                    } => notification_handler::push(&window, level, &text),
+                    HassNotice::Clear(key) => notification_handler::clear(&window, &key),
+                    HassNotice::State { state, .. } => {
+                        if let Some(tamper_tx) = &tamper_tx {
+                            let _ = tamper_tx.send(Door {
+                                open: state == open_state,
+                                source: Source::Hass,
+                            });
+                        }
+                    }
+                }
+            }
+        });
+        std::mem::forget(timer);
+        restarter
     }
 }
 
@@ -1264,8 +7808,11 @@ mod game_handler {
                 // Compute session duration: 100 AMD = 300 seconds
                 let total_secs = (amount as u64) * 3;
                 info!(
-                    "🎮 Game session: {} AMD → {} sec, game: {}",
-                    amount, total_secs, game_name
+                    "🎮 Game session: {} {currency} → {} sec, game: {}",
+                    amount,
+                    total_secs,
+                    game_name,
+                    currency = money::currency()
                 );
 
                 // Find the matching GameEntry (if configured), otherwise use a blank entry
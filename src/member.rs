@@ -0,0 +1,90 @@
+//! A member's standing as the gateway sees it: this month's dues (the rent
+//! share) and sponsorship tier, shown under the username on the donate page
+//! when `features.member_status` is on. Every field is optional, so a
+//! gateway that only knows one of them still works.
+
+use log::info;
+use serde::Deserialize;
+
+use crate::api;
+use crate::error::RequestError;
+
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct MemberStatus {
+    #[serde(default, alias = "rent")]
+    pub dues: Option<Dues>,
+    /// "Sponsor", "Patron", …; `None` for a member without one.
+    #[serde(default, alias = "sponsorship", alias = "sponsorTier")]
+    pub tier: Option<String>,
+}
+
+/// This month's dues, in AMD.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Dues {
+    #[serde(alias = "due")]
+    pub amount: i32,
+    #[serde(default)]
+    pub paid: i32,
+}
+
+impl Dues {
+    pub fn outstanding(&self) -> i32 {
+        (self.amount - self.paid).max(0)
+    }
+}
+
+/// Fetches `username`'s status. `None` when the gateway doesn't know them,
+/// or has no member status endpoint (404).
+pub async fn fetch_status(
+    token: &str,
+    username: &str,
+) -> Result<Option<MemberStatus>, RequestError> {
+    info!("Fetching member status for {}...", username);
+    let url = api::url(&format!("/api/members/{}/status", api::encode(username)));
+    let request = api::get(&url, token).body(())?;
+
+    let mut response = api::send(request).await?;
+    if response.status().as_u16() == 404 {
+        return Ok(None);
+    }
+    if !response.status().is_success() {
+        return Err(api::error_for_status(&mut response).await);
+    }
+    Ok(Some(api::read_json(&mut response).await?))
+}
+
+/// Who a member PIN belongs to and the roles the gateway gives them
+/// ("admin", "treasurer", …), for features only some members may use.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Identity {
+    pub username: String,
+    #[serde(default)]
+    pub roles: Vec<String>,
+}
+
+impl Identity {
+    /// Whether any of the member's roles is one of `allowed`, ignoring case.
+    pub fn has_any(&self, allowed: &[String]) -> bool {
+        self.roles.iter().any(|role| {
+            allowed
+                .iter()
+                .any(|a| a.trim().eq_ignore_ascii_case(role.trim()))
+        })
+    }
+}
+
+/// Asks the gateway whose PIN `pin` is. `None` when it belongs to no one
+/// (401, 403 or 404; the last also when the gateway has no such endpoint).
+pub async fn identify(token: &str, pin: &str) -> Result<Option<Identity>, RequestError> {
+    let body = serde_json::to_vec(&serde_json::json!({ "pin": pin }))?;
+    let request = api::post(&api::url("/api/kiosk/identify"), token).body(body)?;
+
+    let mut response = api::send(request).await?;
+    if matches!(response.status().as_u16(), 401 | 403 | 404) {
+        return Ok(None);
+    }
+    if !response.status().is_success() {
+        return Err(api::error_for_status(&mut response).await);
+    }
+    Ok(Some(api::read_json(&mut response).await?))
+}
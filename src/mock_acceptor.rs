@@ -0,0 +1,340 @@
+//! A pretend bill acceptor for working on the kiosk without one
+//! (`cashcode_mode = "mock"`). It speaks CCNET on the master side of a pty
+//! and the bill driver opens the other side as usual, so everything above
+//! the serial port runs unchanged. Nothing happens by itself: bills go in
+//! from the kiosk keyboard (Ctrl+Alt+1 for the smallest bill, Ctrl+Alt+2
+//! for the next, …) or from a line on a local socket:
+//!
+//! ```text
+//! echo 5000 | nc -q1 127.0.0.1 8322     # a 5000 bill
+//! echo reject | nc -q1 127.0.0.1 8322   # a bill the acceptor refuses
+//! echo jam | nc -q1 127.0.0.1 8322      # a jam in the acceptor
+//! echo stacker | nc -q1 127.0.0.1 8322  # the stacker pulled and put back
+//! ```
+
+use log::{error, info, warn};
+use serialport::{SerialPort, TTYPort};
+use std::collections::VecDeque;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::OnceLock;
+use std::sync::mpsc::{Receiver, Sender, channel};
+use std::thread;
+use std::time::Duration;
+
+use crate::cashcode;
+use crate::lockdown::Modifiers;
+
+static INSERTS: OnceLock<Sender<Insert>> = OnceLock::new();
+
+/// Something done to the pretend acceptor.
+#[derive(Debug, Clone, Copy)]
+enum Insert {
+    /// A bill worth this much.
+    Bill(i32),
+    /// The nth smallest bill, from 1.
+    Nth(usize),
+    Reject,
+    Jam,
+    StackerRemoved,
+}
+
+impl Insert {
+    fn parse(line: &str) -> Option<Self> {
+        match line.trim() {
+            "reject" => Some(Insert::Reject),
+            "jam" => Some(Insert::Jam),
+            "stacker" => Some(Insert::StackerRemoved),
+            value => value.parse().ok().map(Insert::Bill),
+        }
+    }
+}
+
+/// `frame` with its CCNET CRC appended, low byte first.
+pub fn with_crc(mut frame: Vec<u8>) -> Vec<u8> {
+    let crc = cashcode::crc16(&frame);
+    frame.extend_from_slice(&crc.to_le_bytes());
+    frame
+}
+
+/// Frame carrying `data` from the device to the host.
+pub fn device_frame(data: &[u8]) -> Vec<u8> {
+    let mut frame = vec![0x02, 0x03, (data.len() + 5) as u8];
+    frame.extend_from_slice(data);
+    with_crc(frame)
+}
+
+/// GET BILL TABLE data for bill values by type code: 24 entries of value
+/// digit, country code and power of ten. Values that aren't a digit times
+/// a power of ten can't be told to the driver and are left out.
+pub fn bill_table(values: &[i32], country: &[u8; 3]) -> Vec<u8> {
+    let mut table = vec![0u8; 24 * 5];
+    for (code, &value) in values.iter().enumerate().take(24) {
+        if value <= 0 {
+            continue;
+        }
+        let power = value.ilog10();
+        if value % 10i32.pow(power) != 0 {
+            warn!(
+                "🧪 Bill type {} ({}) doesn't fit a bill table entry",
+                code, value
+            );
+            continue;
+        }
+        let entry = &mut table[code * 5..][..5];
+        entry[0] = (value / 10i32.pow(power)) as u8;
+        entry[1..4].copy_from_slice(country);
+        entry[4] = power as u8;
+    }
+    table
+}
+
+/// Starts the pretend acceptor with `bill_types` and its socket on
+/// `port`; returns the serial port for the bill driver to open.
+pub fn start(bill_types: Vec<i32>, port: u16) -> Result<String, serialport::Error> {
+    let (mut master, slave) = TTYPort::pair()?;
+    let path = slave
+        .name()
+        .ok_or_else(|| serialport::Error::new(serialport::ErrorKind::Unknown, "pty has no name"))?;
+    master.set_timeout(Duration::from_millis(10))?;
+
+    let (tx, rx) = channel();
+    let _ = INSERTS.set(tx.clone());
+    let device = MockAcceptor {
+        port: master,
+        bill_types,
+        enabled: [0; 3],
+        escrow: [0; 3],
+        script: VecDeque::new(),
+        held: None,
+    };
+    thread::spawn(move || {
+        // the driver reopens the slave side on a restart; keeping it open
+        // here keeps the pty alive in between
+        let _slave = slave;
+        device.run(rx);
+    });
+
+    match TcpListener::bind(("127.0.0.1", port)) {
+        Ok(listener) => {
+            thread::spawn(move || {
+                for stream in listener.incoming().flatten() {
+                    let tx = tx.clone();
+                    thread::spawn(move || serve(stream, tx));
+                }
+            });
+            info!(
+                "🧪 Mock bill acceptor on {}, commands on 127.0.0.1:{}",
+                path, port
+            );
+        }
+        Err(e) => error!(
+            "🧪 Mock bill acceptor on {}, but its socket on port {} failed: {}",
+            path, port, e
+        ),
+    }
+    Ok(path)
+}
+
+/// A press on the kiosk keyboard: Ctrl+Alt+N inserts the Nth smallest
+/// bill, Ctrl+Alt+0 one that is rejected. True when the press was taken.
+pub fn key(text: &str, modifiers: Modifiers) -> bool {
+    let Some(tx) = INSERTS.get() else {
+        return false;
+    };
+    if !(modifiers.control && modifiers.alt) {
+        return false;
+    }
+    let Some(n) = text.chars().next().and_then(|key| key.to_digit(10)) else {
+        return false;
+    };
+    let _ = tx.send(if n == 0 {
+        Insert::Reject
+    } else {
+        Insert::Nth(n as usize)
+    });
+    true
+}
+
+/// Takes one command per line from a socket client.
+fn serve(stream: TcpStream, tx: Sender<Insert>) {
+    let Ok(mut writer) = stream.try_clone() else {
+        return;
+    };
+    for line in BufReader::new(stream).lines().map_while(Result::ok) {
+        let reply = match Insert::parse(&line) {
+            Some(insert) => {
+                let _ = tx.send(insert);
+                "ok\n"
+            }
+            None => "unknown command; a bill value, reject, jam or stacker\n",
+        };
+        if writer.write_all(reply.as_bytes()).is_err() {
+            return;
+        }
+    }
+}
+
+/// The device side of the pty.
+struct MockAcceptor {
+    port: TTYPort,
+    bill_types: Vec<i32>,
+    enabled: [u8; 3],
+    /// Bill types held in escrow until STACK or RETURN.
+    escrow: [u8; 3],
+    /// Status payloads for the next polls, one per poll.
+    script: VecDeque<Vec<u8>>,
+    /// Type code of the bill waiting in escrow.
+    held: Option<u8>,
+}
+
+impl MockAcceptor {
+    fn run(mut self, inserts: Receiver<Insert>) {
+        let mut buffer = Vec::new();
+        let mut chunk = [0u8; 64];
+        loop {
+            match self.port.read(&mut chunk) {
+                Ok(n) => buffer.extend_from_slice(&chunk[..n]),
+                Err(e) if e.kind() == std::io::ErrorKind::TimedOut => {}
+                Err(e) => {
+                    error!("🧪 Mock bill acceptor stopped: {}", e);
+                    return;
+                }
+            }
+            while let Ok(insert) = inserts.try_recv() {
+                self.insert(insert);
+            }
+            // skip to a frame start, then handle every complete frame
+            while let Some(start) = buffer.iter().position(|b| *b == 0x02) {
+                buffer.drain(..start);
+                if buffer.len() < 3 || buffer.len() < buffer[2] as usize {
+                    break;
+                }
+                let frame: Vec<u8> = buffer.drain(..buffer[2] as usize).collect();
+                self.handle(&frame);
+            }
+        }
+    }
+
+    fn insert(&mut self, insert: Insert) {
+        let bill = matches!(insert, Insert::Bill(_) | Insert::Nth(_) | Insert::Reject);
+        if bill && self.enabled == [0; 3] {
+            info!("🧪 Mock bill acceptor is disabled, {:?} ignored", insert);
+            return;
+        }
+        match insert {
+            Insert::Bill(_) | Insert::Nth(_) => {
+                let code = match insert {
+                    Insert::Nth(n) => self.nth_smallest(n),
+                    Insert::Bill(value) => self.bill_types.iter().position(|v| *v == value),
+                    _ => None,
+                };
+                let Some(code) = code.map(|code| code as u8) else {
+                    warn!("🧪 Mock bill acceptor has no such bill, rejected");
+                    self.script.extend([vec![0x15], vec![0x1C, 0x66]]);
+                    return;
+                };
+                let bit = |mask: [u8; 3]| mask[2 - usize::from(code / 8)] & (1 << (code % 8)) != 0;
+                if !bit(self.enabled) {
+                    info!("🧪 Bill type {} isn't enabled, rejected", code);
+                    self.script.extend([vec![0x15], vec![0x1C, 0x66]]);
+                } else if bit(self.escrow) {
+                    self.script.extend([vec![0x15], vec![0x80, code]]);
+                    self.held = Some(code);
+                } else {
+                    self.script
+                        .extend([vec![0x15], vec![0x17], vec![0x81, code]]);
+                }
+            }
+            Insert::Reject => self.script.extend([vec![0x15], vec![0x1C, 0x60]]),
+            Insert::Jam => self.script.extend([vec![0x43], vec![0x43], vec![0x43]]),
+            Insert::StackerRemoved => {
+                // the device comes back disabled
+                self.script.extend([vec![0x42], vec![0x42], vec![0x42]]);
+                self.enabled = [0; 3];
+            }
+        }
+    }
+
+    /// Type code of the `n`th smallest bill, counting from 1.
+    fn nth_smallest(&self, n: usize) -> Option<usize> {
+        let mut codes: Vec<usize> = (0..self.bill_types.len())
+            .filter(|&code| self.bill_types[code] > 0)
+            .collect();
+        codes.sort_by_key(|&code| self.bill_types[code]);
+        codes.get(n.checked_sub(1)?).copied()
+    }
+
+    fn reply(&mut self, data: &[u8]) {
+        let frame = device_frame(data);
+        if let Err(e) = self.port.write_all(&frame) {
+            warn!("🧪 Mock bill acceptor failed to answer: {}", e);
+        }
+    }
+
+    fn handle(&mut self, frame: &[u8]) {
+        if frame.len() < 6 || with_crc(frame[..frame.len() - 2].to_vec()) != frame {
+            warn!("🧪 Mock bill acceptor got a bad frame: {:02X?}", frame);
+            return;
+        }
+        match frame[3] {
+            // ACK from the host
+            0x00 => {}
+            // RESET
+            0x30 => {
+                self.enabled = [0; 3];
+                self.held = None;
+                self.script = VecDeque::from([vec![0x10], vec![0x13], vec![0x13]]);
+                self.reply(&[0x00]);
+            }
+            // GET STATUS
+            0x31 => {
+                let mut data = self.enabled.to_vec();
+                data.extend_from_slice(&[0, 0, 0]);
+                self.reply(&data);
+            }
+            // POLL
+            0x33 => {
+                let status = self.script.pop_front().unwrap_or_else(|| match self.held {
+                    Some(code) => vec![0x80, code],
+                    None if self.enabled == [0; 3] => vec![0x19],
+                    None => vec![0x14],
+                });
+                self.reply(&status);
+            }
+            // ENABLE BILL TYPES
+            0x34 if frame.len() >= 12 => {
+                self.enabled = [frame[4], frame[5], frame[6]];
+                self.escrow = [frame[7], frame[8], frame[9]];
+                self.reply(&[0x00]);
+            }
+            // STACK
+            0x35 => {
+                if let Some(code) = self.held.take() {
+                    self.script.extend([vec![0x17], vec![0x81, code]]);
+                }
+                self.reply(&[0x00]);
+            }
+            // RETURN
+            0x36 => {
+                if let Some(code) = self.held.take() {
+                    self.script.extend([vec![0x18], vec![0x82, code]]);
+                }
+                self.reply(&[0x00]);
+            }
+            // IDENTIFICATION
+            0x37 => {
+                let mut data = b"MOCK-ACCEPTOR  MOCK00000001".to_vec();
+                data.extend_from_slice(&[0; 7]);
+                self.reply(&data);
+            }
+            // GET BILL TABLE
+            0x41 => {
+                let table = bill_table(&self.bill_types, b"XXX");
+                self.reply(&table);
+            }
+            // HOLD, anything else
+            _ => self.reply(&[0x00]),
+        }
+    }
+}
@@ -0,0 +1,91 @@
+//! Amounts as donors read them: digits grouped and the currency placed the
+//! way the display language does it. Everything Slint shows goes through
+//! `format` (the `Money` global); logs and reports keep plain numbers.
+//!
+//! Amounts are whole units of the configured currency (`currency`), which
+//! is also what donations are sent to the gateway in.
+
+use std::sync::OnceLock;
+
+use crate::i18n;
+
+static CURRENCY: OnceLock<String> = OnceLock::new();
+
+const DEFAULT_CURRENCY: &str = "AMD";
+
+pub fn set_currency(code: &str) {
+    let _ = CURRENCY.set(code.trim().to_uppercase());
+}
+
+/// The kiosk's currency, e.g. "AMD".
+pub fn currency() -> &'static str {
+    CURRENCY
+        .get()
+        .map(String::as_str)
+        .unwrap_or(DEFAULT_CURRENCY)
+}
+
+/// The usual CashCode bill table for `currency`: the value of each bill
+/// type the firmware reports, by type number, 0 where unused. Empty for a
+/// currency without one, which then needs `bill_types` in config.
+pub fn bill_table(currency: &str) -> &'static [i32] {
+    match currency {
+        "AMD" => &[1000, 5000, 10000, 20000, 0, 0, 0, 0, 0, 0, 0, 0, 2000],
+        "USD" => &[1, 2, 5, 10, 20, 50, 100],
+        "EUR" => &[5, 10, 20, 50, 100, 200, 500],
+        _ => &[],
+    }
+}
+
+/// How one language writes an amount.
+struct Style {
+    /// Between groups of three digits.
+    separator: char,
+    currency: &'static str,
+}
+
+fn style(language: &str) -> Style {
+    match (language, currency()) {
+        // No-break spaces, so an amount never wraps across lines
+        ("hy" | "ru", "AMD") => Style {
+            separator: '\u{a0}',
+            currency: "֏",
+        },
+        ("hy" | "ru", code) => Style {
+            separator: '\u{a0}',
+            currency: code,
+        },
+        (_, code) => Style {
+            separator: ',',
+            currency: code,
+        },
+    }
+}
+
+/// `amount` with its digits grouped: 20000 → "20,000" in English.
+fn digits_in(language: &str, amount: i64) -> String {
+    let separator = style(language).separator;
+    let plain = amount.unsigned_abs().to_string();
+    let mut grouped = String::with_capacity(plain.len() + plain.len() / 3 + 1);
+    if amount < 0 {
+        grouped.push('-');
+    }
+    for (i, digit) in plain.chars().enumerate() {
+        if i > 0 && (plain.len() - i).is_multiple_of(3) {
+            grouped.push(separator);
+        }
+        grouped.push(digit);
+    }
+    grouped
+}
+
+/// `amount` with the currency: "20,000 AMD" in English, "20 000 ֏" in
+/// Armenian, "20 USD" for a kiosk taking dollars.
+pub fn format(amount: i64) -> String {
+    let language = i18n::language();
+    format!(
+        "{}\u{a0}{}",
+        digits_in(language, amount),
+        style(language).currency
+    )
+}
@@ -0,0 +1,362 @@
+use log::{debug, info, warn};
+use std::collections::BTreeMap;
+use std::io::{self, Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::sync::mpsc::{Receiver, RecvTimeoutError, Sender, channel};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use crate::config::Mqtt;
+use crate::{clock, version};
+
+const KEEP_ALIVE: Duration = Duration::from_secs(60);
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+const MIN_BACKOFF: Duration = Duration::from_secs(5);
+const MAX_BACKOFF: Duration = Duration::from_secs(120);
+
+/// A message for the broker. Retained ones are also kept locally and sent
+/// again after a reconnect, so Home Assistant always sees the latest state.
+#[derive(Debug, Clone)]
+pub struct Publish {
+    pub topic: String,
+    pub payload: String,
+    pub retain: bool,
+}
+
+/// What the connection thread is asked to do.
+enum Message {
+    Publish(Publish),
+    /// Drop the connection (or the wait before the next attempt) and
+    /// connect again now.
+    Restart,
+}
+
+/// Outcome of the latest connection attempt: `None` until the first one
+/// finishes, then `Ok` while connected or the reason it isn't.
+type Link = Arc<Mutex<Option<Result<(), String>>>>;
+
+/// Handle for publishing; cheap to clone. Publishing never blocks, and
+/// messages published while the broker is away are dropped unless retained.
+#[derive(Clone)]
+pub struct Publisher {
+    tx: Sender<Message>,
+    link: Link,
+    /// Topics live under this, e.g. `dramma/status`.
+    pub topic_prefix: String,
+    /// Home Assistant's discovery prefix, usually `homeassistant`.
+    pub discovery_prefix: String,
+    /// Messages are logged instead of sent (`dry_run`).
+    dry_run: bool,
+}
+
+impl Publisher {
+    pub fn publish(&self, topic: &str, payload: &str, retain: bool) {
+        if self.dry_run {
+            debug!("🧪 Dry run, not publishing to {}: {}", topic, payload);
+            return;
+        }
+        let _ = self.tx.send(Message::Publish(Publish {
+            topic: topic.to_string(),
+            payload: payload.to_string(),
+            retain,
+        }));
+    }
+
+    /// Reconnects to the broker at once, e.g. when the connection looks
+    /// up but nothing arrives in Home Assistant.
+    pub fn restart(&self) {
+        let _ = self.tx.send(Message::Restart);
+    }
+
+    /// Whether the broker is connected; `None` while the first attempt is
+    /// still under way.
+    pub fn connection(&self) -> Option<Result<(), String>> {
+        self.link.lock().map(|link| link.clone()).unwrap_or(None)
+    }
+
+    /// Full topic for `name` under the kiosk's prefix.
+    pub fn topic(&self, name: &str) -> String {
+        format!("{}/{}", self.topic_prefix, name)
+    }
+
+    /// Topic the broker marks the kiosk offline on (last will).
+    pub fn availability_topic(&self) -> String {
+        self.topic("availability")
+    }
+
+    /// Announces an entity through MQTT discovery, attached to the kiosk's
+    /// device. `component` is the HA platform (`sensor`, `light`, …) and
+    /// `config` the entity's discovery fields.
+    pub fn announce(&self, component: &str, object_id: &str, mut config: serde_json::Value) {
+        if let Some(fields) = config.as_object_mut() {
+            fields.insert(
+                "unique_id".to_string(),
+                format!("dramma_{}", object_id).into(),
+            );
+            fields.insert(
+                "availability_topic".to_string(),
+                self.availability_topic().into(),
+            );
+            fields.insert("device".to_string(), device());
+        }
+        self.publish(
+            &format!(
+                "{}/{}/dramma/{}/config",
+                self.discovery_prefix, component, object_id
+            ),
+            &config.to_string(),
+            true,
+        );
+    }
+}
+
+/// The Home Assistant device every dramma entity belongs to.
+pub fn device() -> serde_json::Value {
+    serde_json::json!({
+        "identifiers": ["dramma"],
+        "name": "Dramma kiosk",
+        "manufacturer": "Hacker Embassy",
+        "model": "dramma",
+        "sw_version": version::describe(),
+    })
+}
+
+/// Starts the MQTT connection on its own thread. `None` without a broker
+/// configured. In a dry run the broker is never connected.
+pub fn start(config: &Mqtt, dry_run: bool) -> Option<Publisher> {
+    let host = config.host.clone()?;
+    let (tx, rx) = channel();
+    let link = Link::default();
+    let publisher = Publisher {
+        tx,
+        link: link.clone(),
+        topic_prefix: config.topic_prefix.clone(),
+        discovery_prefix: config.discovery_prefix.clone(),
+        dry_run,
+    };
+    if dry_run {
+        info!("🧪 Dry run, MQTT messages for {} are only logged", host);
+        *link.lock().unwrap() = Some(Err("dry run".to_string()));
+        return Some(publisher);
+    }
+    let options = ConnectOptions {
+        address: format!("{}:{}", host, config.port),
+        client_id: config.client_id.clone(),
+        username: config.username.clone(),
+        password: config.password.clone(),
+        will_topic: publisher.availability_topic(),
+    };
+    thread::spawn(move || run(options, rx, link));
+    Some(publisher)
+}
+
+struct ConnectOptions {
+    address: String,
+    client_id: String,
+    username: Option<String>,
+    password: Option<String>,
+    will_topic: String,
+}
+
+/// Keeps a connection up and forwards messages until every `Publisher` is
+/// dropped, reconnecting with backoff (or at once when asked to restart).
+fn run(options: ConnectOptions, rx: Receiver<Message>, link: Link) {
+    let mut retained: BTreeMap<String, String> = BTreeMap::new();
+    let mut backoff = MIN_BACKOFF;
+    let report = |result: Result<(), String>| {
+        if let Ok(mut link) = link.lock() {
+            *link = Some(result);
+        }
+    };
+    'connect: loop {
+        match connect(&options) {
+            Ok(mut stream) => {
+                info!("📡 MQTT connected to {}", options.address);
+                report(Ok(()));
+                backoff = MIN_BACKOFF;
+                // Ok(true) when asked to restart, Ok(false) once every
+                // Publisher is gone
+                let result = (|| -> io::Result<bool> {
+                    write_publish(&mut stream, &options.will_topic, "online", true)?;
+                    for (topic, payload) in &retained {
+                        write_publish(&mut stream, topic, payload, true)?;
+                    }
+                    loop {
+                        match rx.recv_timeout(KEEP_ALIVE / 2) {
+                            Ok(Message::Publish(message)) => {
+                                if message.retain {
+                                    retained.insert(message.topic.clone(), message.payload.clone());
+                                }
+                                write_publish(
+                                    &mut stream,
+                                    &message.topic,
+                                    &message.payload,
+                                    message.retain,
+                                )?;
+                            }
+                            Err(RecvTimeoutError::Timeout) => {
+                                // PINGREQ; a dead broker shows up as a write error
+                                stream.write_all(&[0xC0, 0x00])?;
+                                drain(&mut stream)?;
+                            }
+                            Ok(Message::Restart) => {
+                                let _ = stream.write_all(&[0xE0, 0x00]);
+                                return Ok(true);
+                            }
+                            Err(RecvTimeoutError::Disconnected) => {
+                                let _ = stream.write_all(&[0xE0, 0x00]);
+                                return Ok(false);
+                            }
+                        }
+                    }
+                })();
+                match result {
+                    Ok(true) => {
+                        info!("📡 MQTT restarting");
+                        continue 'connect;
+                    }
+                    Ok(false) => return,
+                    Err(e) => {
+                        warn!("📡 MQTT connection lost: {}", e);
+                        report(Err(format!("connection lost: {}", e)));
+                    }
+                }
+            }
+            Err(e) => {
+                debug!("📡 MQTT connect to {} failed: {}", options.address, e);
+                report(Err(e.to_string()));
+            }
+        }
+
+        // Offline: keep the latest retained state, drop the rest
+        let deadline = clock::instant() + backoff;
+        while let Some(wait) = deadline.checked_duration_since(clock::instant()) {
+            match rx.recv_timeout(wait) {
+                Ok(Message::Publish(message)) if message.retain => {
+                    retained.insert(message.topic, message.payload);
+                }
+                Ok(Message::Restart) => {
+                    info!("📡 MQTT restarting");
+                    backoff = MIN_BACKOFF;
+                    continue 'connect;
+                }
+                Ok(_) | Err(RecvTimeoutError::Timeout) => {}
+                Err(RecvTimeoutError::Disconnected) => return,
+            }
+        }
+        backoff = (backoff * 2).min(MAX_BACKOFF);
+    }
+}
+
+/// MQTT remaining-length encoding.
+fn encode_length(mut length: usize, out: &mut Vec<u8>) {
+    loop {
+        let mut byte = (length % 128) as u8;
+        length /= 128;
+        if length > 0 {
+            byte |= 0x80;
+        }
+        out.push(byte);
+        if length == 0 {
+            break;
+        }
+    }
+}
+
+fn push_str(s: &str, out: &mut Vec<u8>) {
+    out.extend_from_slice(&(s.len() as u16).to_be_bytes());
+    out.extend_from_slice(s.as_bytes());
+}
+
+fn packet(header: u8, body: &[u8]) -> Vec<u8> {
+    let mut packet = vec![header];
+    encode_length(body.len(), &mut packet);
+    packet.extend_from_slice(body);
+    packet
+}
+
+/// Opens the socket and does the MQTT 3.1.1 handshake, with a retained
+/// "offline" last will on the availability topic.
+fn connect(options: &ConnectOptions) -> io::Result<TcpStream> {
+    let address = options
+        .address
+        .to_socket_addrs()?
+        .next()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "broker address not found"))?;
+    let mut stream = TcpStream::connect_timeout(&address, CONNECT_TIMEOUT)?;
+    stream.set_read_timeout(Some(CONNECT_TIMEOUT))?;
+
+    // clean session, will (QoS 0, retained)
+    let mut flags = 0x02 | 0x04 | 0x20;
+    if options.username.is_some() {
+        flags |= 0x80;
+    }
+    if options.password.is_some() {
+        flags |= 0x40;
+    }
+    let mut body = Vec::new();
+    push_str("MQTT", &mut body);
+    body.push(4);
+    body.push(flags);
+    body.extend_from_slice(&(KEEP_ALIVE.as_secs() as u16).to_be_bytes());
+    push_str(&options.client_id, &mut body);
+    push_str(&options.will_topic, &mut body);
+    push_str("offline", &mut body);
+    if let Some(username) = &options.username {
+        push_str(username, &mut body);
+    }
+    if let Some(password) = &options.password {
+        push_str(password, &mut body);
+    }
+    stream.write_all(&packet(0x10, &body))?;
+
+    let mut connack = [0u8; 4];
+    stream.read_exact(&mut connack)?;
+    if connack[0] != 0x20 || connack[3] != 0 {
+        return Err(io::Error::new(
+            io::ErrorKind::ConnectionRefused,
+            format!("broker refused connection (code {})", connack[3]),
+        ));
+    }
+    stream.set_read_timeout(Some(Duration::from_millis(10)))?;
+    Ok(stream)
+}
+
+fn write_publish(
+    stream: &mut TcpStream,
+    topic: &str,
+    payload: &str,
+    retain: bool,
+) -> io::Result<()> {
+    let mut body = Vec::new();
+    push_str(topic, &mut body);
+    body.extend_from_slice(payload.as_bytes());
+    stream.write_all(&packet(0x30 | u8::from(retain), &body))
+}
+
+/// Reads and discards whatever the broker sent (PINGRESP). A closed
+/// socket is an error.
+fn drain(stream: &mut TcpStream) -> io::Result<()> {
+    let mut buffer = [0u8; 64];
+    loop {
+        match stream.read(&mut buffer) {
+            Ok(0) => {
+                return Err(io::Error::new(
+                    io::ErrorKind::ConnectionAborted,
+                    "broker closed the connection",
+                ));
+            }
+            Ok(_) => {}
+            Err(e)
+                if matches!(
+                    e.kind(),
+                    io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+                ) =>
+            {
+                return Ok(());
+            }
+            Err(e) => return Err(e),
+        }
+    }
+}
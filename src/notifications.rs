@@ -0,0 +1,120 @@
+use std::time::{Duration, Instant};
+
+use crate::clock;
+
+/// How long a transient notification stays up.
+pub const TRANSIENT_TTL: Duration = Duration::from_secs(8);
+
+/// At most this many transient notifications are kept; older ones drop off.
+const MAX_TRANSIENT: usize = 3;
+
+/// A status-bar notification. Levels follow `LogEntry`: 0=neutral 1=ok
+/// 2=warn 3=error.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Notification {
+    /// Sticky notifications have a key and stay until cleared by it;
+    /// transient ones have none and expire on their own.
+    pub key: Option<String>,
+    pub level: i32,
+    pub text: String,
+    expires: Option<Instant>,
+}
+
+/// Conditions the kiosk wants to surface (gateway offline, stacker out, …)
+/// collected in one place instead of a bespoke property each. Sticky entries
+/// are keyed by condition, so raising the same one twice updates it.
+#[derive(Default)]
+pub struct NotificationCenter {
+    items: Vec<Notification>,
+}
+
+impl NotificationCenter {
+    /// Raises or updates the sticky notification for `key`. Returns whether
+    /// anything changed.
+    pub fn set(&mut self, key: &str, level: i32, text: &str) -> bool {
+        let notification = Notification {
+            key: Some(key.to_string()),
+            level,
+            text: text.to_string(),
+            expires: None,
+        };
+        match self
+            .items
+            .iter_mut()
+            .find(|n| n.key.as_deref() == Some(key))
+        {
+            Some(existing) if *existing == notification => false,
+            Some(existing) => {
+                *existing = notification;
+                true
+            }
+            None => {
+                self.items.push(notification);
+                true
+            }
+        }
+    }
+
+    /// Shows a one-off notification for `TRANSIENT_TTL`.
+    pub fn push(&mut self, level: i32, text: &str) {
+        self.items.push(Notification {
+            key: None,
+            level,
+            text: text.to_string(),
+            expires: Some(clock::instant() + TRANSIENT_TTL),
+        });
+        let transient = self.items.iter().filter(|n| n.key.is_none()).count();
+        if transient > MAX_TRANSIENT
+            && let Some(oldest) = self.items.iter().position(|n| n.key.is_none())
+        {
+            self.items.remove(oldest);
+        }
+    }
+
+    /// Clears the sticky notification for `key`. Returns whether there was one.
+    pub fn clear(&mut self, key: &str) -> bool {
+        let before = self.items.len();
+        self.items.retain(|n| n.key.as_deref() != Some(key));
+        self.items.len() != before
+    }
+
+    /// Drops expired transient notifications. Returns whether any were.
+    pub fn expire(&mut self, now: Instant) -> bool {
+        let before = self.items.len();
+        self.items
+            .retain(|n| n.expires.is_none_or(|expires| expires > now));
+        self.items.len() != before
+    }
+
+    /// What to show, most severe first; sticky before transient at the same
+    /// level, otherwise in the order raised.
+    pub fn visible(&self) -> Vec<&Notification> {
+        let mut items: Vec<&Notification> = self.items.iter().collect();
+        items.sort_by_key(|n| (std::cmp::Reverse(n.level), n.key.is_none()));
+        items
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::UNIX_EPOCH;
+
+    #[test]
+    fn transient_expires_after_ttl() {
+        let clock = clock::mock(UNIX_EPOCH);
+        let mut center = NotificationCenter::default();
+        center.set("gateway", 2, "Gateway offline");
+        center.push(1, "Thank you!");
+
+        clock.advance(TRANSIENT_TTL - Duration::from_secs(1));
+        assert!(!center.expire(clock::instant()));
+        assert_eq!(center.visible().len(), 2);
+
+        clock.advance(Duration::from_secs(1));
+        assert!(center.expire(clock::instant()));
+        let left = center.visible();
+        assert_eq!(left.len(), 1);
+        assert_eq!(left[0].key.as_deref(), Some("gateway"));
+    }
+}
@@ -0,0 +1,166 @@
+//! Checks a device node before a driver opens it, so a setup problem reads
+//! "Permission denied on /dev/ttyUSB0 (are you in dialout?)" rather than
+//! whatever the serialport crate makes of the errno. Reads /proc, so off
+//! Linux only a missing device is caught.
+
+use std::fs;
+use std::io;
+use std::os::unix::fs::{MetadataExt, PermissionsExt};
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+
+/// Where UUCP-style lock files (`LCK..ttyUSB0`) are kept.
+const LOCK_DIRS: &[&str] = &["/run/lock", "/var/lock"];
+
+#[derive(Debug, Error)]
+pub enum Problem {
+    #[error("{0} not found (is the device plugged in?)")]
+    Missing(String),
+
+    #[error("Permission denied on {path} (are you in {group}?)")]
+    Permission { path: String, group: String },
+
+    #[error("{path} is in use by {holder}")]
+    InUse { path: String, holder: String },
+
+    #[error("{0}: {1}")]
+    Io(String, io::Error),
+}
+
+/// A serial port: there, readable and writable by us, and not held by
+/// another process (a lock file, or an open file descriptor we can see).
+pub fn check_serial(path: &str) -> Result<(), Problem> {
+    check_device(path, true)?;
+    if let Some(holder) = holder(path) {
+        return Err(Problem::InUse {
+            path: path.to_string(),
+            holder,
+        });
+    }
+    Ok(())
+}
+
+/// Any other device node (printer, input device): there, and readable (and
+/// writable, with `write`) by us.
+pub fn check_device(path: &str, write: bool) -> Result<(), Problem> {
+    let metadata = fs::metadata(path).map_err(|e| match e.kind() {
+        io::ErrorKind::NotFound => Problem::Missing(path.to_string()),
+        _ => Problem::Io(path.to_string(), e),
+    })?;
+    let Some(ids) = Ids::current() else {
+        return Ok(());
+    };
+    if ids.uid == 0 {
+        return Ok(());
+    }
+    let mode = metadata.permissions().mode();
+    // rw- bits for whichever of owner, group or others we count as
+    let bits = if ids.uid == metadata.uid() {
+        mode >> 6
+    } else if ids.gids.contains(&metadata.gid()) {
+        mode >> 3
+    } else {
+        mode
+    };
+    let needed = if write { 0o6 } else { 0o4 };
+    if bits & needed == needed {
+        return Ok(());
+    }
+    Err(Problem::Permission {
+        path: path.to_string(),
+        group: group_name(metadata.gid()).unwrap_or_else(|| format!("group {}", metadata.gid())),
+    })
+}
+
+/// Effective user and groups of this process, from /proc.
+struct Ids {
+    uid: u32,
+    gids: Vec<u32>,
+}
+
+impl Ids {
+    fn current() -> Option<Ids> {
+        let status = fs::read_to_string("/proc/self/status").ok()?;
+        let field = |name: &str| -> Vec<u32> {
+            status
+                .lines()
+                .find_map(|line| line.strip_prefix(name))
+                .map(|values| {
+                    values
+                        .split_whitespace()
+                        .filter_map(|v| v.parse().ok())
+                        .collect()
+                })
+                .unwrap_or_default()
+        };
+        // Uid:/Gid: list real, effective, saved, filesystem
+        let uid = *field("Uid:").get(1)?;
+        let mut gids = field("Groups:");
+        gids.extend(field("Gid:").get(1));
+        Some(Ids { uid, gids })
+    }
+}
+
+fn group_name(gid: u32) -> Option<String> {
+    fs::read_to_string("/etc/group")
+        .ok()?
+        .lines()
+        .find_map(|line| {
+            let mut fields = line.split(':');
+            let name = fields.next()?;
+            let id: u32 = fields.nth(1)?.parse().ok()?;
+            (id == gid).then(|| name.to_string())
+        })
+}
+
+/// Who else has the port: the owner of a lock file that's still running,
+/// or another process with it open. Processes of other users are only
+/// seen when we're root.
+fn holder(path: &str) -> Option<String> {
+    let device = fs::canonicalize(path).unwrap_or_else(|_| PathBuf::from(path));
+    let name = device.file_name()?.to_string_lossy().into_owned();
+    let ours = std::process::id();
+
+    for dir in LOCK_DIRS {
+        let Ok(contents) = fs::read_to_string(Path::new(dir).join(format!("LCK..{}", name))) else {
+            continue;
+        };
+        if let Ok(pid) = contents.trim().parse::<u32>()
+            && pid != ours
+            && Path::new(&format!("/proc/{}", pid)).exists()
+        {
+            return Some(describe(pid));
+        }
+    }
+
+    for entry in fs::read_dir("/proc").ok()?.flatten() {
+        let Some(pid) = entry
+            .file_name()
+            .to_str()
+            .and_then(|s| s.parse::<u32>().ok())
+        else {
+            continue;
+        };
+        if pid == ours {
+            continue;
+        }
+        let Ok(fds) = fs::read_dir(entry.path().join("fd")) else {
+            continue;
+        };
+        let open = fds
+            .flatten()
+            .any(|fd| fs::read_link(fd.path()).is_ok_and(|target| target == device));
+        if open {
+            return Some(describe(pid));
+        }
+    }
+    None
+}
+
+/// "ModemManager (pid 812)"
+fn describe(pid: u32) -> String {
+    match fs::read_to_string(format!("/proc/{}/comm", pid)) {
+        Ok(name) => format!("{} (pid {})", name.trim(), pid),
+        Err(_) => format!("pid {}", pid),
+    }
+}
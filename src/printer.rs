@@ -0,0 +1,269 @@
+use chrono::{Local, TimeZone};
+use log::{debug, error, info, warn};
+use rusqlite::{Connection, OptionalExtension, Result as SqlResult, params};
+use std::fs::OpenOptions;
+use std::io::{self, Write};
+use std::sync::mpsc::{Receiver, RecvTimeoutError, Sender};
+use std::time::Duration;
+use thiserror::Error;
+
+use crate::i18n::Message;
+use crate::{clock, money, receipt};
+
+/// ESC @ — reset the printer to defaults.
+const ESC_INIT: &[u8] = &[0x1B, 0x40];
+/// GS V 66 n — feed n lines and cut.
+const GS_FEED_CUT: &[u8] = &[0x1D, 0x56, 0x42, 0x04];
+
+/// Retry delay after a failed attempt; doubles up to `MAX_BACKOFF`.
+const MIN_BACKOFF: Duration = Duration::from_secs(5);
+const MAX_BACKOFF: Duration = Duration::from_secs(120);
+
+/// How often the queue is looked at when nothing wakes the worker.
+const IDLE_POLL: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Error)]
+pub enum PrintError {
+    #[error("out of paper")]
+    PaperOut,
+
+    #[error("printer offline: {0}")]
+    Offline(io::Error),
+}
+
+impl PrintError {
+    pub fn message(&self) -> Message {
+        Message::new(match self {
+            PrintError::PaperOut => "printer.paper_out",
+            PrintError::Offline(_) => "printer.offline",
+        })
+    }
+}
+
+/// Reported to the UI whenever it changes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PrinterStatus {
+    Ready,
+    /// Jobs are waiting because of the given problem.
+    Stalled {
+        reason: Message,
+        pending: usize,
+    },
+}
+
+pub fn init_db(db: &Connection) -> SqlResult<()> {
+    db.execute(
+        "CREATE TABLE IF NOT EXISTS print_jobs (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            created INTEGER NOT NULL,
+            receipt INTEGER,
+            body TEXT NOT NULL,
+            printed INTEGER,
+            attempts INTEGER NOT NULL DEFAULT 0,
+            last_error TEXT
+        )",
+        [],
+    )?;
+    Ok(())
+}
+
+/// Queues a receipt for printing. The queue lives in the stats DB, so a
+/// confirmation survives a jammed printer or a restart.
+pub fn enqueue(db_path: &str, receipt: Option<i64>, body: &str) -> SqlResult<i64> {
+    let db = Connection::open(db_path)?;
+    init_db(&db)?;
+    db.execute(
+        "INSERT INTO print_jobs (created, receipt, body) VALUES (?1, ?2, ?3)",
+        params![clock::timestamp() as i64, receipt, body],
+    )?;
+    Ok(db.last_insert_rowid())
+}
+
+/// How many receipts are waiting to be printed.
+pub fn pending(db_path: &str) -> SqlResult<usize> {
+    let db = Connection::open(db_path)?;
+    init_db(&db)?;
+    db.query_row(
+        "SELECT COUNT(*) FROM print_jobs WHERE printed IS NULL",
+        [],
+        |row| row.get(0),
+    )
+}
+
+/// Queues another copy of the most recent receipt. Returns `false` if
+/// nothing was ever printed.
+pub fn reprint_last(db_path: &str) -> SqlResult<bool> {
+    let db = Connection::open(db_path)?;
+    init_db(&db)?;
+    let last: Option<(Option<i64>, String)> = db
+        .query_row(
+            "SELECT receipt, body FROM print_jobs ORDER BY id DESC LIMIT 1",
+            [],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .optional()?;
+    let Some((receipt, body)) = last else {
+        return Ok(false);
+    };
+    db.execute(
+        "INSERT INTO print_jobs (created, receipt, body) VALUES (?1, ?2, ?3)",
+        params![clock::timestamp() as i64, receipt, body],
+    )?;
+    Ok(true)
+}
+
+/// The receipt as printed. Plain ASCII; the printer's code page can't be
+/// relied on for anything else.
+pub fn format_receipt(
+    number: Option<i64>,
+    username: &str,
+    amount: i32,
+    fund_name: &str,
+    timestamp: u64,
+) -> String {
+    let when = Local
+        .timestamp_opt(timestamp as i64, 0)
+        .single()
+        .map(|t| t.format("%Y-%m-%d %H:%M").to_string())
+        .unwrap_or_default();
+    let ascii = |s: &str| -> String {
+        s.chars()
+            .map(|c| if c.is_ascii() { c } else { '?' })
+            .collect()
+    };
+    format!(
+        "Hacker Embassy\nDonation receipt {}\n{}\n\nFrom:   {}\nFund:   {}\nAmount: {} {}\n\nThank you!\n",
+        number.map(receipt::format).unwrap_or_default(),
+        when,
+        ascii(username),
+        ascii(fund_name),
+        amount,
+        money::currency()
+    )
+}
+
+fn print(device: &str, body: &str) -> Result<(), PrintError> {
+    let classify = |e: io::Error| {
+        // usblp reports a paper-out printer as ENOSPC on write
+        if e.kind() == io::ErrorKind::StorageFull {
+            PrintError::PaperOut
+        } else {
+            PrintError::Offline(e)
+        }
+    };
+    let mut port = OpenOptions::new()
+        .write(true)
+        .open(device)
+        .map_err(classify)?;
+    port.write_all(ESC_INIT).map_err(classify)?;
+    port.write_all(body.as_bytes()).map_err(classify)?;
+    port.write_all(GS_FEED_CUT).map_err(classify)?;
+    port.flush().map_err(classify)
+}
+
+/// Prints `body`, or only logs it in a dry run; the job counts as printed
+/// either way.
+fn print_job(device: &str, body: &str, dry_run: bool) -> Result<(), PrintError> {
+    if dry_run {
+        info!("🧪 Dry run, not printing:\n{}", body);
+        return Ok(());
+    }
+    print(device, body)
+}
+
+/// Oldest job not printed yet, with the number waiting.
+fn next_job(db: &Connection) -> SqlResult<Option<(i64, String, usize)>> {
+    let pending: usize = db.query_row(
+        "SELECT COUNT(*) FROM print_jobs WHERE printed IS NULL",
+        [],
+        |row| row.get(0),
+    )?;
+    let job = db
+        .query_row(
+            "SELECT id, body FROM print_jobs WHERE printed IS NULL ORDER BY id LIMIT 1",
+            [],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .optional()?;
+    Ok(job.map(|(id, body)| (id, body, pending)))
+}
+
+/// Print worker: prints queued jobs in order, retrying with backoff while
+/// the printer is offline or out of paper. `wake` nudges it when a job is
+/// queued. Runs until the wake channel closes.
+pub fn run(
+    device: String,
+    db_path: String,
+    dry_run: bool,
+    wake: Receiver<()>,
+    status_tx: Sender<PrinterStatus>,
+) {
+    if dry_run {
+        info!("🧪 Dry run, receipts for {} are only logged", device);
+    } else {
+        info!("🖨️  Receipt printer on {}", device);
+    }
+    let db = match Connection::open(&db_path).and_then(|db| init_db(&db).map(|_| db)) {
+        Ok(db) => db,
+        Err(e) => {
+            error!("Print queue unavailable: {}", e);
+            return;
+        }
+    };
+
+    let mut backoff = MIN_BACKOFF;
+    let mut last_status = PrinterStatus::Ready;
+    loop {
+        let job = match next_job(&db) {
+            Ok(job) => job,
+            Err(e) => {
+                error!("Failed to read print queue: {}", e);
+                None
+            }
+        };
+
+        let wait = match job {
+            None => IDLE_POLL,
+            Some((id, body, pending)) => match print_job(&device, &body, dry_run) {
+                Ok(()) => {
+                    debug!("🖨️  Printed job {}", id);
+                    let _ = db.execute(
+                        "UPDATE print_jobs SET printed = ?1, attempts = attempts + 1 WHERE id = ?2",
+                        params![clock::timestamp() as i64, id],
+                    );
+                    backoff = MIN_BACKOFF;
+                    if pending == 1 && last_status != PrinterStatus::Ready {
+                        info!("🖨️  Printer caught up");
+                        last_status = PrinterStatus::Ready;
+                        let _ = status_tx.send(last_status.clone());
+                    }
+                    continue;
+                }
+                Err(e) => {
+                    warn!("🖨️  Print job {} failed: {}", id, e);
+                    let _ = db.execute(
+                        "UPDATE print_jobs SET attempts = attempts + 1, last_error = ?1 WHERE id = ?2",
+                        params![e.to_string(), id],
+                    );
+                    let status = PrinterStatus::Stalled {
+                        reason: e.message(),
+                        pending,
+                    };
+                    if status != last_status {
+                        last_status = status;
+                        let _ = status_tx.send(last_status.clone());
+                    }
+                    let wait = backoff;
+                    backoff = (backoff * 2).min(MAX_BACKOFF);
+                    wait
+                }
+            },
+        };
+
+        // A wake (new job, reprint after refilling paper) retries right away
+        match wake.recv_timeout(wait) {
+            Ok(()) | Err(RecvTimeoutError::Timeout) => {}
+            Err(RecvTimeoutError::Disconnected) => return,
+        }
+    }
+}
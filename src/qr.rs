@@ -0,0 +1,130 @@
+//! Paying by QR code, for donors without cash. The QR holds a payment link:
+//! `qr_payment.link` with the amount and a reference filled in, or one the
+//! gateway's invoice endpoint hands out. Either way the gateway hears from
+//! the payment provider and tells the kiosk, by reference, once it's paid.
+
+use log::info;
+use qrcode::{Color, QrCode, QrResult};
+use serde::Deserialize;
+use std::sync::atomic::{AtomicU32, Ordering};
+
+use crate::api;
+use crate::clock;
+use crate::error::RequestError;
+use crate::money;
+
+/// Light modules around the code, as the QR spec asks for.
+const QUIET_ZONE: usize = 4;
+
+/// A payment waiting to be made.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Payment {
+    pub reference: String,
+    /// What goes in the QR code.
+    pub url: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Status {
+    Pending,
+    Paid,
+    /// Cancelled or timed out on the provider's side.
+    Expired,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct PaymentState {
+    pub status: Status,
+    /// What was actually paid, when the provider lets the donor change it.
+    #[serde(default)]
+    pub amount: Option<i32>,
+}
+
+/// A reference no other payment from this kiosk has.
+pub fn reference() -> String {
+    static COUNTER: AtomicU32 = AtomicU32::new(0);
+    format!(
+        "dramma-{}-{}",
+        clock::timestamp_ms(),
+        COUNTER.fetch_add(1, Ordering::Relaxed)
+    )
+}
+
+/// A payment through the configured link; `{amount}`, `{fund_id}` and
+/// `{reference}` in it are filled in.
+pub fn from_link(template: &str, amount: i32, fund_id: i32) -> Payment {
+    let reference = reference();
+    let url = template
+        .replace("{amount}", &amount.to_string())
+        .replace("{fund_id}", &fund_id.to_string())
+        .replace("{reference}", &api::encode(&reference));
+    Payment { reference, url }
+}
+
+/// Asks the gateway for an invoice, and the link to pay it.
+pub async fn invoice(
+    token: &str,
+    amount: i32,
+    fund_id: i32,
+    username: &str,
+) -> Result<Payment, RequestError> {
+    info!(
+        "Creating a {} {} invoice for fund {}...",
+        amount,
+        money::currency(),
+        fund_id
+    );
+    let body = serde_json::to_vec(&serde_json::json!({
+        "amount": amount,
+        "currency": money::currency(),
+        "fund_id": fund_id,
+        "username": username,
+        "reference": reference(),
+    }))?;
+    let request = api::post(&api::url("/api/kiosk/invoices"), token).body(body)?;
+    let mut response = api::send(request).await?;
+    if !response.status().is_success() {
+        return Err(api::error_for_status(&mut response).await);
+    }
+    api::read_json(&mut response).await
+}
+
+/// Whether the payment went through. A reference the gateway hasn't heard
+/// of yet (404) is pending.
+pub async fn status(token: &str, reference: &str) -> Result<PaymentState, RequestError> {
+    let url = api::url(&format!("/api/kiosk/payments/{}", api::encode(reference)));
+    let request = api::get(&url, token).body(())?;
+    let mut response = api::send(request).await?;
+    if response.status().as_u16() == 404 {
+        return Ok(PaymentState {
+            status: Status::Pending,
+            amount: None,
+        });
+    }
+    if !response.status().is_success() {
+        return Err(api::error_for_status(&mut response).await);
+    }
+    api::read_json(&mut response).await
+}
+
+/// `data` as a QR code in RGB, `scale` pixels per module, quiet zone
+/// included. Returns the side length and the pixels.
+pub fn render(data: &str, scale: usize) -> QrResult<(u32, Vec<u8>)> {
+    let code = QrCode::new(data.as_bytes())?;
+    let modules = code.width();
+    let colors = code.to_colors();
+    let side = (modules + 2 * QUIET_ZONE) * scale;
+    let mut pixels = vec![0xFF; side * side * 3];
+    for (i, color) in colors.iter().enumerate() {
+        if *color != Color::Dark {
+            continue;
+        }
+        let (x, y) = (i % modules + QUIET_ZONE, i / modules + QUIET_ZONE);
+        for row in y * scale..(y + 1) * scale {
+            let start = (row * side + x * scale) * 3;
+            pixels[start..start + scale * 3].fill(0);
+        }
+    }
+    Ok((side as u32, pixels))
+}
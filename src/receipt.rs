@@ -0,0 +1,82 @@
+use rusqlite::{Connection, Result as SqlResult, params};
+
+use crate::clock;
+
+pub fn init_db(db: &Connection) -> SqlResult<()> {
+    // AUTOINCREMENT: numbers only ever go up, even after rows are deleted
+    // or a crash rolls back a half-written insert.
+    db.execute(
+        "CREATE TABLE IF NOT EXISTS receipts (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            timestamp INTEGER NOT NULL,
+            username TEXT NOT NULL,
+            amount INTEGER NOT NULL,
+            fund_id INTEGER NOT NULL,
+            fund_name TEXT NOT NULL
+        )",
+        [],
+    )?;
+    Ok(())
+}
+
+/// The highest receipt number issued so far, 0 before the first. Read once
+/// at startup; the numbers after it are handed out in memory, see `issue`.
+pub fn last(db_path: &str) -> SqlResult<i64> {
+    let db = Connection::open(db_path)?;
+    init_db(&db)?;
+    db.query_row(
+        "SELECT COALESCE(MAX(seq), 0) FROM sqlite_sequence WHERE name = 'receipts'",
+        [],
+        |row| row.get(0),
+    )
+}
+
+/// Records what receipt `number` was for. The number is handed out before
+/// this runs, on a `db_writes` thread, so it can go on the thank-you screen
+/// and into the API payload without the UI waiting on SQLite.
+pub fn issue(
+    db_path: &str,
+    number: i64,
+    username: &str,
+    amount: i32,
+    fund_id: i32,
+    fund_name: &str,
+) -> SqlResult<()> {
+    let db = Connection::open(db_path)?;
+    init_db(&db)?;
+    db.execute(
+        "INSERT INTO receipts (id, timestamp, username, amount, fund_id, fund_name) VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+        params![
+            number,
+            clock::timestamp() as i64,
+            username,
+            amount,
+            fund_id,
+            fund_name
+        ],
+    )?;
+    Ok(())
+}
+
+/// Changes who a receipt is from and which fund it went to, for a donor's
+/// correction within the edit grace period.
+pub fn amend(
+    db_path: &str,
+    number: i64,
+    username: &str,
+    fund_id: i32,
+    fund_name: &str,
+) -> SqlResult<()> {
+    let db = Connection::open(db_path)?;
+    init_db(&db)?;
+    db.execute(
+        "UPDATE receipts SET username = ?1, fund_id = ?2, fund_name = ?3 WHERE id = ?4",
+        params![username, fund_id, fund_name, number],
+    )?;
+    Ok(())
+}
+
+/// How a receipt number is shown to donors, e.g. `#000042`.
+pub fn format(number: i64) -> String {
+    format!("#{:06}", number)
+}
@@ -0,0 +1,212 @@
+//! Compares the donations this kiosk sent (the `transactions` table) with
+//! the ones the gateway recorded for it over the same time, from the
+//! service page. Money taken in cash that never reached the gateway shows
+//! up as sent but unknown to the gateway, or as still waiting in the
+//! donation queue.
+
+use log::info;
+use rusqlite::{Connection, Result as SqlResult, params};
+use serde::Deserialize;
+
+use crate::api;
+use crate::donation::{self, Queued};
+use crate::error::RequestError;
+use crate::transactions;
+
+/// Gateway records a little outside the range are fetched too, so a
+/// donation sent just before the end but dated by the gateway just after
+/// still finds its match.
+const MARGIN_SECS: u64 = 3600;
+
+/// A donation as the gateway recorded it.
+#[derive(Debug, Clone, Deserialize)]
+pub struct GatewayDonation {
+    pub fund_id: i32,
+    #[serde(default)]
+    pub username: String,
+    pub amount: i32,
+    #[serde(default)]
+    pub receipt: Option<i64>,
+    #[serde(default)]
+    pub timestamp: Option<u64>,
+}
+
+/// A donation the kiosk sent and the backend took (`donation_committed`).
+#[derive(Debug, Clone)]
+pub struct Sent {
+    /// When it was sent.
+    pub timestamp: u64,
+    pub fund_id: i32,
+    pub fund_name: String,
+    pub username: String,
+    pub amount: i32,
+    pub receipt: Option<i64>,
+}
+
+/// The kiosk's side of `[from, to)`.
+#[derive(Debug, Clone, Default)]
+pub struct Local {
+    /// Bills and coins taken.
+    pub cash_in: i64,
+    pub sent: Vec<Sent>,
+    /// Money that went in and is still waiting in the donation queue.
+    pub queued: Vec<Queued>,
+    /// Below the minimum or left unattributed (which also waits in the
+    /// queue until a fund is picked).
+    pub cancelled: i64,
+}
+
+/// Where the two sides disagree.
+#[derive(Debug, Clone, Default)]
+pub struct Reconciliation {
+    pub from: u64,
+    pub to: u64,
+    pub cash_in: i64,
+    /// Sent by the kiosk, in total.
+    pub sent: i64,
+    /// Recorded by the gateway within the range, in total.
+    pub confirmed: i64,
+    pub cancelled: i64,
+    /// Sent and taken, but the gateway has no record of it.
+    pub missing: Vec<Sent>,
+    pub queued: Vec<Queued>,
+    /// Recorded by the gateway, but the kiosk doesn't know it sent it.
+    pub unknown: Vec<GatewayDonation>,
+}
+
+impl Reconciliation {
+    pub fn balanced(&self) -> bool {
+        self.missing.is_empty() && self.queued.is_empty() && self.unknown.is_empty()
+    }
+
+    /// One line for the audit trail.
+    pub fn summary(&self) -> String {
+        format!(
+            "{} in, {} sent, {} confirmed; {} missing at the gateway, {} queued, {} unknown to the kiosk",
+            self.cash_in,
+            self.sent,
+            self.confirmed,
+            self.missing.len(),
+            self.queued.len(),
+            self.unknown.len()
+        )
+    }
+}
+
+/// The kiosk's records for `[from, to)`. Blocking.
+pub fn local(db_path: &str, from: u64, to: u64) -> SqlResult<Local> {
+    let db = Connection::open(db_path)?;
+    transactions::init_db(&db)?;
+    let total = |kinds: &str| -> SqlResult<i64> {
+        db.query_row(
+            &format!(
+                "SELECT COALESCE(SUM(amount), 0) FROM transactions
+                 WHERE kind IN ({}) AND timestamp >= ?1 AND timestamp < ?2",
+                kinds
+            ),
+            params![from as i64, to as i64],
+            |row| row.get(0),
+        )
+    };
+    let cash_in = total("'bill_accepted', 'coin_accepted'")?;
+    let cancelled = total("'donation_cancelled'")?;
+
+    let mut stmt = db.prepare(
+        "SELECT timestamp, amount, data FROM transactions
+         WHERE kind = 'donation_committed' AND timestamp >= ?1 AND timestamp < ?2
+         ORDER BY id",
+    )?;
+    let sent = stmt
+        .query_map(params![from as i64, to as i64], |row| {
+            let data: serde_json::Value =
+                serde_json::from_str(&row.get::<_, String>(2)?).unwrap_or_default();
+            Ok(Sent {
+                timestamp: row.get::<_, i64>(0)? as u64,
+                amount: row.get(1)?,
+                fund_id: data["fund_id"].as_i64().unwrap_or_default() as i32,
+                fund_name: data["fund_name"].as_str().unwrap_or_default().to_string(),
+                username: data["username"].as_str().unwrap_or_default().to_string(),
+                receipt: data["receipt"].as_i64(),
+            })
+        })?
+        .collect::<SqlResult<Vec<_>>>()?;
+
+    let queued = donation::queued(db_path)?
+        .into_iter()
+        .filter(|queued| (from..to).contains(&queued.timestamp))
+        .collect();
+
+    Ok(Local {
+        cash_in,
+        sent,
+        queued,
+        cancelled,
+    })
+}
+
+/// What the gateway recorded for this kiosk around `[from, to)`.
+pub async fn gateway(
+    token: &str,
+    from: u64,
+    to: u64,
+) -> Result<Vec<GatewayDonation>, RequestError> {
+    info!(
+        "Fetching the gateway's donations from {} to {}...",
+        from, to
+    );
+    api::fetch_all(
+        &format!(
+            "/api/kiosk/donations?from={}&to={}",
+            from.saturating_sub(MARGIN_SECS),
+            to + MARGIN_SECS
+        ),
+        token,
+    )
+    .await
+}
+
+/// Pairs each donation sent with a gateway record: by receipt number when
+/// both have one, else by fund, amount and username.
+pub fn compare(from: u64, to: u64, local: Local, gateway: Vec<GatewayDonation>) -> Reconciliation {
+    let mut unmatched: Vec<Option<GatewayDonation>> = gateway.into_iter().map(Some).collect();
+    let mut take = |found: &dyn Fn(&GatewayDonation) -> bool| {
+        unmatched
+            .iter_mut()
+            .find(|slot| slot.as_ref().is_some_and(found))
+            .and_then(Option::take)
+    };
+
+    let mut missing = Vec::new();
+    for sent in &local.sent {
+        let by_receipt = sent
+            .receipt
+            .and_then(|receipt| take(&|g| g.receipt == Some(receipt)));
+        let matched = by_receipt.or_else(|| {
+            take(&|g| {
+                g.fund_id == sent.fund_id && g.amount == sent.amount && g.username == sent.username
+            })
+        });
+        if matched.is_none() {
+            missing.push(sent.clone());
+        }
+    }
+
+    // gateway records from the margins only count when they pair up
+    let in_range = |g: &GatewayDonation| g.timestamp.is_none_or(|t| (from..to).contains(&t));
+    let unknown: Vec<GatewayDonation> = unmatched.into_iter().flatten().filter(in_range).collect();
+    let sent: i64 = local.sent.iter().map(|sent| sent.amount as i64).sum();
+    let confirmed = sent - missing.iter().map(|sent| sent.amount as i64).sum::<i64>()
+        + unknown.iter().map(|g| g.amount as i64).sum::<i64>();
+
+    Reconciliation {
+        from,
+        to,
+        cash_in: local.cash_in,
+        sent,
+        confirmed,
+        cancelled: local.cancelled,
+        missing,
+        queued: local.queued,
+        unknown,
+    }
+}
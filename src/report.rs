@@ -0,0 +1,582 @@
+use chrono::{Datelike, Local, NaiveDate, TimeZone};
+use http::Request;
+use isahc::AsyncReadResponseExt;
+use rusqlite::{Connection, Result as SqlResult, params};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::api;
+use crate::error::RequestError;
+use crate::{audit, clock, donation_log, ledger, money, receipt, shift};
+
+/// Telegram rejects longer messages.
+const TELEGRAM_LIMIT: usize = 4096;
+
+/// A calendar month, local time.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Month {
+    pub year: i32,
+    pub month: u32,
+}
+
+impl Month {
+    pub fn current() -> Self {
+        let today = clock::local().date_naive();
+        Month {
+            year: today.year(),
+            month: today.month(),
+        }
+    }
+
+    pub fn previous(self) -> Self {
+        if self.month == 1 {
+            Month {
+                year: self.year - 1,
+                month: 12,
+            }
+        } else {
+            Month {
+                year: self.year,
+                month: self.month - 1,
+            }
+        }
+    }
+
+    fn next(self) -> Self {
+        if self.month == 12 {
+            Month {
+                year: self.year + 1,
+                month: 1,
+            }
+        } else {
+            Month {
+                year: self.year,
+                month: self.month + 1,
+            }
+        }
+    }
+
+    /// Unix timestamp of local midnight on the 1st.
+    fn start(self) -> i64 {
+        NaiveDate::from_ymd_opt(self.year, self.month, 1)
+            .and_then(|day| day.and_hms_opt(0, 0, 0))
+            .and_then(|midnight| Local.from_local_datetime(&midnight).earliest())
+            .map(|t| t.timestamp())
+            .unwrap_or(0)
+    }
+
+    /// `[start, end)` of the month as unix timestamps.
+    fn range(self) -> (i64, i64) {
+        (self.start(), self.next().start())
+    }
+}
+
+impl std::fmt::Display for Month {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}-{:02}", self.year, self.month)
+    }
+}
+
+/// A count and a sum in the kiosk's currency.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Tally {
+    pub count: i64,
+    pub amount: i64,
+}
+
+/// An operator shift opened during the month, with what the box took while
+/// it was open.
+#[derive(Debug, Clone)]
+pub struct ShiftTally {
+    pub operator: String,
+    pub opened: i64,
+    pub closed: Option<i64>,
+    /// Sessions started and bills stacked during the shift.
+    pub bills: Tally,
+    pub sessions: i64,
+    /// Taken out of the stacker during the shift.
+    pub collected: i64,
+}
+
+/// Everything the treasurer needs for one month, read from Stats.db.
+#[derive(Debug, Clone)]
+pub struct MonthlyReport {
+    pub month: Month,
+    /// Sessions that got a receipt number, i.e. money the kiosk took.
+    pub sessions: Tally,
+    /// Donations the gateway accepted.
+    pub sent: Tally,
+    pub auto_committed: Tally,
+    pub bills: Tally,
+    /// Per fund, largest first.
+    pub funds: Vec<(String, Tally)>,
+    /// Amounts under the minimum, kept in the box and only audited.
+    pub below_minimum: i64,
+    /// Money left without a fund, less what staff attributed since.
+    pub unattributed: i64,
+    /// Times the UI total was corrected from the bill ledger.
+    pub reconciliations: i64,
+    /// Audit events worth a look (errors by code, resets, reboots…), most
+    /// frequent first.
+    pub incidents: Vec<(String, i64)>,
+    pub shifts: Vec<ShiftTally>,
+}
+
+/// Audit events that are routine bookkeeping, not incidents.
+const ROUTINE_EVENTS: &[&str] = &[
+    "donation_committed",
+    "donation_auto_committed",
+    "below_minimum",
+    "reconciliation",
+    "unattributed",
+    "unattributed_resolved",
+    "queue_edited",
+    "usb_export",
+    "report",
+    "bill_rejected",
+    "collection",
+    "admin_unlocked",
+    "shift_opened",
+    "shift_closed",
+    "door_opened",
+    "event_batch",
+];
+
+fn tally(db: &Connection, sql: &str, (start, end): (i64, i64)) -> SqlResult<Tally> {
+    db.query_row(sql, params![start, end], |row| {
+        Ok(Tally {
+            count: row.get(0)?,
+            amount: row.get(1)?,
+        })
+    })
+}
+
+/// Reads the month's figures. Blocking, a handful of small queries.
+pub fn build(db_path: &str, month: Month) -> SqlResult<MonthlyReport> {
+    let db = Connection::open(db_path)?;
+    // The report may run before anything else touched a fresh DB
+    audit::init_db(&db)?;
+    donation_log::init_db(&db)?;
+    ledger::init_db(&db)?;
+    receipt::init_db(&db)?;
+    shift::init_db(&db)?;
+    let range = month.range();
+
+    let sessions = tally(
+        &db,
+        "SELECT COUNT(*), COALESCE(SUM(amount), 0) FROM receipts
+         WHERE timestamp >= ?1 AND timestamp < ?2",
+        range,
+    )?;
+    let sent = tally(
+        &db,
+        "SELECT COUNT(*), COALESCE(SUM(amount), 0) FROM donation_log
+         WHERE timestamp >= ?1 AND timestamp < ?2",
+        range,
+    )?;
+    let auto_committed = tally(
+        &db,
+        "SELECT COUNT(*), COALESCE(SUM(amount), 0) FROM donation_log
+         WHERE timestamp >= ?1 AND timestamp < ?2 AND auto_committed",
+        range,
+    )?;
+    let bills = tally(
+        &db,
+        "SELECT COUNT(*), COALESCE(SUM(nominal), 0) FROM bill_ledger
+         WHERE timestamp >= ?1 AND timestamp < ?2",
+        range,
+    )?;
+
+    let funds = db
+        .prepare(
+            "SELECT fund_name, COUNT(*), SUM(amount) FROM donation_log
+             WHERE timestamp >= ?1 AND timestamp < ?2
+             GROUP BY fund_name ORDER BY SUM(amount) DESC",
+        )?
+        .query_map(params![range.0, range.1], |row| {
+            Ok((
+                row.get(0)?,
+                Tally {
+                    count: row.get(1)?,
+                    amount: row.get(2)?,
+                },
+            ))
+        })?
+        .collect::<SqlResult<_>>()?;
+
+    // Audit details carry the amount as "<n> <currency>"
+    let audited_amount = |event: &str| -> SqlResult<i64> {
+        let mut stmt = db.prepare(
+            "SELECT details FROM audit_log WHERE event = ?1 AND timestamp >= ?2 AND timestamp < ?3",
+        )?;
+        let details = stmt.query_map(params![event, range.0, range.1], |row| {
+            row.get::<_, String>(0)
+        })?;
+        let mut total = 0;
+        for detail in details {
+            total += audited_amount(&detail?, money::currency());
+        }
+        Ok(total)
+    };
+    let below_minimum = audited_amount("below_minimum")?;
+    let unattributed = audited_amount("unattributed")? - audited_amount("unattributed_resolved")?;
+
+    let reconciliations = db.query_row(
+        "SELECT COUNT(*) FROM audit_log
+         WHERE event = 'reconciliation' AND timestamp >= ?1 AND timestamp < ?2",
+        params![range.0, range.1],
+        |row| row.get(0),
+    )?;
+
+    // Errors are grouped by their code ("E-SER-03 …"), other events by name
+    let mut incidents: Vec<(String, i64)> = db
+        .prepare(
+            "SELECT CASE WHEN event = 'error' THEN substr(details, 1, instr(details || ' ', ' ') - 1)
+                         ELSE event END AS kind,
+                    COUNT(*)
+             FROM audit_log WHERE timestamp >= ?1 AND timestamp < ?2
+             GROUP BY kind ORDER BY COUNT(*) DESC, kind",
+        )?
+        .query_map(params![range.0, range.1], |row| Ok((row.get(0)?, row.get(1)?)))?
+        .collect::<SqlResult<_>>()?;
+    incidents.retain(|(kind, _)| !ROUTINE_EVENTS.contains(&kind.as_str()));
+
+    let shifts = db
+        .prepare(
+            "SELECT s.operator, s.opened, s.closed,
+                    COUNT(DISTINCT l.id), COUNT(b.id), COALESCE(SUM(b.nominal), 0),
+                    (SELECT COALESCE(SUM(c.amount), 0) FROM collections c WHERE c.shift = s.id)
+             FROM shifts s
+             LEFT JOIN ledger_sessions l ON l.shift = s.id
+             LEFT JOIN bill_ledger b ON b.session = l.id
+             WHERE s.opened >= ?1 AND s.opened < ?2
+             GROUP BY s.id ORDER BY s.opened",
+        )?
+        .query_map(params![range.0, range.1], |row| {
+            Ok(ShiftTally {
+                operator: row.get(0)?,
+                opened: row.get(1)?,
+                closed: row.get(2)?,
+                sessions: row.get(3)?,
+                bills: Tally {
+                    count: row.get(4)?,
+                    amount: row.get(5)?,
+                },
+                collected: row.get(6)?,
+            })
+        })?
+        .collect::<SqlResult<_>>()?;
+
+    Ok(MonthlyReport {
+        month,
+        sessions,
+        sent,
+        auto_committed,
+        bills,
+        funds,
+        below_minimum,
+        unattributed,
+        reconciliations,
+        incidents,
+        shifts,
+    })
+}
+
+/// "Campaign -1 gone: 250 AMD" → 250 for `currency` "AMD"; no amount
+/// counts as 0.
+fn audited_amount(details: &str, currency: &str) -> i64 {
+    let words: Vec<&str> = details.split_whitespace().collect();
+    words
+        .windows(2)
+        .find_map(|pair| {
+            pair[1]
+                .trim_end_matches([',', ':'])
+                .eq_ignore_ascii_case(currency)
+                .then(|| pair[0].parse().ok())
+                .flatten()
+        })
+        .unwrap_or(0)
+}
+
+/// A titled table, rendered the same way to Markdown and HTML.
+struct Section {
+    title: &'static str,
+    headers: &'static [&'static str],
+    rows: Vec<Vec<String>>,
+    note: Option<String>,
+}
+
+impl MonthlyReport {
+    /// Money the kiosk took but the gateway never confirmed.
+    pub fn unsent(&self) -> i64 {
+        self.sessions.amount - self.sent.amount
+    }
+
+    pub fn balanced(&self) -> bool {
+        self.unsent() == 0 && self.unattributed == 0
+    }
+
+    fn sections(&self) -> Vec<Section> {
+        let row = |label: &str, tally: Tally| {
+            vec![
+                label.to_string(),
+                tally.count.to_string(),
+                money::format(tally.amount),
+            ]
+        };
+        let collections = Section {
+            title: "Collections",
+            headers: &["", "Count", "Amount"],
+            rows: vec![
+                row("Sessions (receipts issued)", self.sessions),
+                row("Sent to the gateway", self.sent),
+                row("… of which auto-committed", self.auto_committed),
+                row("Bills stacked (ledger)", self.bills),
+            ],
+            note: None,
+        };
+
+        let funds = Section {
+            title: "Per fund",
+            headers: &["Fund", "Donations", "Amount"],
+            rows: self
+                .funds
+                .iter()
+                .map(|(name, tally)| row(name, *tally))
+                .collect(),
+            note: self
+                .funds
+                .is_empty()
+                .then(|| "No donations this month.".to_string()),
+        };
+
+        let reconciliation = Section {
+            title: "Reconciliation",
+            headers: &["Check", "Value"],
+            rows: vec![
+                vec![
+                    "Issued but not confirmed by the gateway".to_string(),
+                    money::format(self.unsent()),
+                ],
+                vec![
+                    "Unattributed (no fallback fund)".to_string(),
+                    money::format(self.unattributed),
+                ],
+                vec![
+                    "Below minimum, left in the box".to_string(),
+                    money::format(self.below_minimum),
+                ],
+                vec![
+                    "Ledger corrections".to_string(),
+                    self.reconciliations.to_string(),
+                ],
+            ],
+            note: Some(if self.balanced() {
+                "✅ Balanced: every session was confirmed by the gateway.".to_string()
+            } else {
+                "⚠️ Needs review: compare the audit log with the gateway for the amounts above."
+                    .to_string()
+            }),
+        };
+
+        let incidents = Section {
+            title: "Incidents",
+            headers: &["Event / code", "Count"],
+            rows: self
+                .incidents
+                .iter()
+                .map(|(kind, count)| vec![kind.clone(), count.to_string()])
+                .collect(),
+            note: self
+                .incidents
+                .is_empty()
+                .then(|| "No incidents this month.".to_string()),
+        };
+
+        let time = |timestamp: i64| {
+            Local
+                .timestamp_opt(timestamp, 0)
+                .single()
+                .map(|t| t.format("%m-%d %H:%M").to_string())
+                .unwrap_or_default()
+        };
+        let shifts = Section {
+            title: "Shifts",
+            headers: &[
+                "Operator",
+                "Opened",
+                "Closed",
+                "Sessions",
+                "Bills",
+                "Collected",
+            ],
+            rows: self
+                .shifts
+                .iter()
+                .map(|shift| {
+                    vec![
+                        shift.operator.clone(),
+                        time(shift.opened),
+                        shift.closed.map(time).unwrap_or_else(|| "open".to_string()),
+                        shift.sessions.to_string(),
+                        money::format(shift.bills.amount),
+                        money::format(shift.collected),
+                    ]
+                })
+                .collect(),
+            note: self
+                .shifts
+                .is_empty()
+                .then(|| "No shifts recorded this month.".to_string()),
+        };
+
+        vec![collections, funds, reconciliation, shifts, incidents]
+    }
+
+    fn title(&self) -> String {
+        format!("Dramma monthly report — {}", self.month)
+    }
+
+    pub fn to_markdown(&self) -> String {
+        let mut out = format!(
+            "# {}\n\nGenerated {}\n",
+            self.title(),
+            clock::local().format("%Y-%m-%d %H:%M")
+        );
+        for section in self.sections() {
+            out += &format!("\n## {}\n", section.title);
+            if !section.rows.is_empty() {
+                out += &format!("\n| {} |\n", section.headers.join(" | "));
+                out += &format!("|{}\n", "---|".repeat(section.headers.len()));
+                for row in &section.rows {
+                    out += &format!("| {} |\n", row.join(" | ").replace('\n', " "));
+                }
+            }
+            if let Some(note) = &section.note {
+                out += &format!("\n{}\n", note);
+            }
+        }
+        out
+    }
+
+    /// Self-contained page; print it to PDF from any browser.
+    pub fn to_html(&self) -> String {
+        let title = escape(&self.title());
+        let mut out = format!(
+            "<!DOCTYPE html>\n<html><head><meta charset=\"utf-8\"><title>{title}</title>\n\
+             <style>body{{font-family:sans-serif;max-width:48em;margin:2em auto}}\
+             table{{border-collapse:collapse;width:100%}}\
+             td,th{{border:1px solid #ccc;padding:4px 8px;text-align:left}}</style>\n\
+             </head><body>\n<h1>{title}</h1>\n<p>Generated {}</p>\n",
+            clock::local().format("%Y-%m-%d %H:%M")
+        );
+        for section in self.sections() {
+            out += &format!("<h2>{}</h2>\n", section.title);
+            if !section.rows.is_empty() {
+                out += "<table>\n<tr>";
+                for header in section.headers {
+                    out += &format!("<th>{}</th>", escape(header));
+                }
+                out += "</tr>\n";
+                for row in &section.rows {
+                    out += "<tr>";
+                    for cell in row {
+                        out += &format!("<td>{}</td>", escape(cell));
+                    }
+                    out += "</tr>\n";
+                }
+                out += "</table>\n";
+            }
+            if let Some(note) = &section.note {
+                out += &format!("<p>{}</p>\n", escape(note));
+            }
+        }
+        out + "</body></html>\n"
+    }
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Where `save` puts the Markdown version for `month`; its existence marks
+/// the month as done for the scheduled report.
+pub fn markdown_path(dir: &Path, month: Month) -> PathBuf {
+    dir.join(format!("dramma-report-{}.md", month))
+}
+
+/// Writes the Markdown and HTML versions into `dir`.
+pub fn save(report: &MonthlyReport, dir: &Path) -> io::Result<PathBuf> {
+    fs::create_dir_all(dir)?;
+    fs::write(
+        dir.join(format!("dramma-report-{}.html", report.month)),
+        report.to_html(),
+    )?;
+    // Markdown last: it's the marker that the month is done
+    let path = markdown_path(dir, report.month);
+    fs::write(&path, report.to_markdown())?;
+    Ok(path)
+}
+
+/// Hands the report to the gateway, which files it for the treasurer.
+pub async fn post_to_gateway(token: &str, report: &MonthlyReport) -> Result<(), RequestError> {
+    let body = serde_json::to_vec(&serde_json::json!({
+        "month": report.month.to_string(),
+        "balanced": report.balanced(),
+        "markdown": report.to_markdown(),
+        "html": report.to_html(),
+    }))?;
+    let request = api::post(&api::url("/api/kiosk/reports"), token).body(body)?;
+    let mut response = api::send(request).await?;
+    if response.status().is_success() {
+        Ok(())
+    } else {
+        Err(api::error_for_status(&mut response).await)
+    }
+}
+
+/// Posts the Markdown version to a Telegram chat as plain text.
+pub async fn send_telegram(
+    bot_token: &str,
+    chat_id: &str,
+    report: &MonthlyReport,
+) -> Result<(), RequestError> {
+    send_telegram_text(bot_token, chat_id, report.to_markdown()).await
+}
+
+/// Posts `text` to a Telegram chat, cut to Telegram's limit. Goes out
+/// directly, not through the gateway client and its pinned CA.
+pub async fn send_telegram_text(
+    bot_token: &str,
+    chat_id: &str,
+    mut text: String,
+) -> Result<(), RequestError> {
+    if text.len() > TELEGRAM_LIMIT {
+        let mut cut = TELEGRAM_LIMIT - 1;
+        while !text.is_char_boundary(cut) {
+            cut -= 1;
+        }
+        text.truncate(cut);
+        text.push('…');
+    }
+    let body = serde_json::to_vec(&serde_json::json!({ "chat_id": chat_id, "text": text }))?;
+    let request = Request::post(format!(
+        "https://api.telegram.org/bot{}/sendMessage",
+        bot_token
+    ))
+    .header("Content-Type", "application/json")
+    .body(body)?;
+    let mut response = isahc::send_async(request).await?;
+    if response.status().is_success() {
+        Ok(())
+    } else {
+        Err(RequestError::Api {
+            status: response.status().as_u16(),
+            message: response.text().await.unwrap_or_default(),
+        })
+    }
+}
@@ -0,0 +1,243 @@
+use crate::Page;
+
+/// Outcome of a navigation request, applied to the window by
+/// `navigation_handler`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Navigation {
+    /// Switch pages; `from` gets its leave hook, `to` its enter hook.
+    Go { from: Page, to: Page },
+    /// Leaving the current page needs confirmation first; the target is kept
+    /// until `confirm()` or `cancel()`.
+    Confirm(Page),
+    /// Nothing to do.
+    Stay,
+}
+
+/// Page state and history, independent of the UI so the navigation rules
+/// can be checked without a window.
+///
+/// Rules:
+/// - the insert pages can't be left while the acceptors are enabled unless
+///   the move is confirmed (or forced, for timeouts);
+/// - going to Main clears the history, Main is the root;
+/// - Boot and DiagnosticsAuth are never returned to with `back()`.
+pub struct Router {
+    current: Page,
+    history: Vec<Page>,
+    pending: Option<Page>,
+}
+
+impl Router {
+    pub fn new(start: Page) -> Self {
+        Self {
+            current: start,
+            history: Vec::new(),
+            pending: None,
+        }
+    }
+
+    /// Requests a move to `to`. `accepting` is whether the acceptors are
+    /// currently enabled.
+    pub fn navigate(&mut self, to: Page, accepting: bool) -> Navigation {
+        if to == self.current {
+            return Navigation::Stay;
+        }
+        if accepting && is_guarded(self.current) {
+            self.pending = Some(to);
+            return Navigation::Confirm(to);
+        }
+        self.go(to)
+    }
+
+    /// Goes to the previous page, or Main when there is none.
+    pub fn back(&mut self, accepting: bool) -> Navigation {
+        let to = self.history.last().copied().unwrap_or(Page::Main);
+        self.navigate(to, accepting)
+    }
+
+    /// Moves without guards, for timeouts and remote requests that already
+    /// took care of the acceptors.
+    pub fn force(&mut self, to: Page) -> Navigation {
+        self.pending = None;
+        if to == self.current {
+            return Navigation::Stay;
+        }
+        self.go(to)
+    }
+
+    /// Completes the navigation that was waiting for confirmation.
+    pub fn confirm(&mut self) -> Navigation {
+        match self.pending.take() {
+            Some(to) => self.go(to),
+            None => Navigation::Stay,
+        }
+    }
+
+    pub fn cancel(&mut self) {
+        self.pending = None;
+    }
+
+    fn go(&mut self, to: Page) -> Navigation {
+        let from = self.current;
+        if to == Page::Main {
+            self.history.clear();
+        } else if self.history.last() == Some(&to) {
+            self.history.pop();
+        } else if !is_transient(from) {
+            self.history.push(from);
+        }
+        self.current = to;
+        Navigation::Go { from, to }
+    }
+}
+
+/// Pages holding a live acceptor session.
+fn is_guarded(page: Page) -> bool {
+    matches!(page, Page::InsertMoney | Page::InsertCoins)
+}
+
+/// Pages that are passed through and never returned to.
+fn is_transient(page: Page) -> bool {
+    matches!(page, Page::Boot | Page::DiagnosticsAuth)
+}
+
+/// Who money left on an insert page is committed for when the page is left
+/// without Done: the fund the donor already picked, under their name (or
+/// anonymously), or `None` for the fallback fund.
+pub fn abandoned_to(fund_id: i32, username: &str) -> Option<(i32, &str)> {
+    let username = if username.is_empty() {
+        "anon"
+    } else {
+        username
+    };
+    (fund_id != 0).then_some((fund_id, username))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(page: Page) -> Router {
+        let mut router = Router::new(Page::Main);
+        router.force(page);
+        router
+    }
+
+    #[test]
+    fn insert_pages_need_confirmation_while_accepting() {
+        for page in [Page::InsertMoney, Page::InsertCoins] {
+            let mut router = at(page);
+            assert_eq!(
+                router.navigate(Page::Main, true),
+                Navigation::Confirm(Page::Main)
+            );
+            assert_eq!(router.current, page);
+            assert_eq!(router.back(true), Navigation::Confirm(Page::Main));
+
+            assert_eq!(
+                router.confirm(),
+                Navigation::Go {
+                    from: page,
+                    to: Page::Main
+                }
+            );
+            assert_eq!(router.current, Page::Main);
+            assert_eq!(router.confirm(), Navigation::Stay);
+        }
+    }
+
+    #[test]
+    fn insert_pages_leave_freely_when_not_accepting() {
+        let mut router = at(Page::InsertMoney);
+        assert_eq!(
+            router.navigate(Page::Main, false),
+            Navigation::Go {
+                from: Page::InsertMoney,
+                to: Page::Main
+            }
+        );
+    }
+
+    #[test]
+    fn cancel_clears_pending() {
+        let mut router = at(Page::InsertMoney);
+        router.navigate(Page::Donate, true);
+        router.cancel();
+        assert_eq!(router.pending, None);
+        assert_eq!(router.confirm(), Navigation::Stay);
+        assert_eq!(router.current, Page::InsertMoney);
+    }
+
+    #[test]
+    fn force_skips_the_guard_and_drops_pending() {
+        let mut router = at(Page::InsertMoney);
+        router.navigate(Page::Donate, true);
+        assert_eq!(
+            router.force(Page::Main),
+            Navigation::Go {
+                from: Page::InsertMoney,
+                to: Page::Main
+            }
+        );
+        assert_eq!(router.pending, None);
+        assert_eq!(router.force(Page::Main), Navigation::Stay);
+    }
+
+    #[test]
+    fn back_returns_through_history_to_main() {
+        let mut router = Router::new(Page::Main);
+        router.navigate(Page::Donate, false);
+        router.navigate(Page::InsertMoney, false);
+        assert_eq!(router.history, [Page::Main, Page::Donate]);
+
+        assert_eq!(
+            router.back(false),
+            Navigation::Go {
+                from: Page::InsertMoney,
+                to: Page::Donate
+            }
+        );
+        assert_eq!(router.back(false).to_page(), Some(Page::Main));
+        assert!(router.history.is_empty());
+        // nothing left: back stays on Main
+        assert_eq!(router.back(false), Navigation::Stay);
+    }
+
+    #[test]
+    fn transient_pages_never_enter_history() {
+        let mut router = Router::new(Page::Boot);
+        router.force(Page::Main);
+        router.navigate(Page::DiagnosticsAuth, false);
+        router.navigate(Page::Diagnostics, false);
+        assert!(!router.history.contains(&Page::Boot));
+        assert!(!router.history.contains(&Page::DiagnosticsAuth));
+        assert_eq!(router.back(false).to_page(), Some(Page::Main));
+    }
+
+    #[test]
+    fn abandoned_money_goes_to_the_picked_fund() {
+        assert_eq!(abandoned_to(3, "alice"), Some((3, "alice")));
+        assert_eq!(abandoned_to(3, ""), Some((3, "anon")));
+    }
+
+    #[test]
+    fn abandoned_money_without_a_fund_falls_back() {
+        assert_eq!(abandoned_to(0, "alice"), None);
+        assert_eq!(abandoned_to(0, ""), None);
+    }
+
+    #[test]
+    fn same_page_is_a_no_op() {
+        let mut router = Router::new(Page::Main);
+        assert_eq!(router.navigate(Page::Main, true), Navigation::Stay);
+    }
+
+    impl Navigation {
+        fn to_page(self) -> Option<Page> {
+            match self {
+                Navigation::Go { to, .. } => Some(to),
+                _ => None,
+            }
+        }
+    }
+}
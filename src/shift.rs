@@ -0,0 +1,194 @@
+use rusqlite::{Connection, OptionalExtension, Result as SqlResult, params};
+
+use crate::{clock, ledger};
+
+/// Operator shifts: who set the box up and who closed it, entered on the
+/// Diagnostics page. Ledger sessions and stacker collections carry the shift
+/// open at the time, so a discrepancy found later points at a person.
+pub fn init_db(db: &Connection) -> SqlResult<()> {
+    db.execute(
+        "CREATE TABLE IF NOT EXISTS shifts (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            operator TEXT NOT NULL,
+            opened INTEGER NOT NULL,
+            closed INTEGER
+        )",
+        [],
+    )?;
+    // One row each time the stacker is pulled and put back
+    db.execute(
+        "CREATE TABLE IF NOT EXISTS collections (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            timestamp INTEGER NOT NULL,
+            bills INTEGER NOT NULL,
+            amount INTEGER NOT NULL,
+            shift INTEGER
+        )",
+        [],
+    )?;
+    // Who took the cash, for a collection recorded on the service page
+    let has_collector = db
+        .prepare("SELECT 1 FROM pragma_table_info('collections') WHERE name = 'collector'")?
+        .exists([])?;
+    if !has_collector {
+        db.execute("ALTER TABLE collections ADD COLUMN collector TEXT", [])?;
+    }
+    // The bill counters a recorded collection took, per denomination
+    db.execute(
+        "CREATE TABLE IF NOT EXISTS collection_bills (
+            collection INTEGER NOT NULL,
+            nominal INTEGER NOT NULL,
+            count INTEGER NOT NULL
+        )",
+        [],
+    )?;
+    Ok(())
+}
+
+#[derive(Debug, Clone)]
+pub struct Shift {
+    pub id: i64,
+    pub operator: String,
+    pub opened: u64,
+}
+
+/// The shift open right now, if any, on an open connection (the ledger
+/// reads it while starting a session).
+pub fn current(db: &Connection) -> SqlResult<Option<Shift>> {
+    init_db(db)?;
+    db.query_row(
+        "SELECT id, operator, opened FROM shifts WHERE closed IS NULL ORDER BY id DESC LIMIT 1",
+        [],
+        |row| {
+            Ok(Shift {
+                id: row.get(0)?,
+                operator: row.get(1)?,
+                opened: row.get::<_, i64>(2)? as u64,
+            })
+        },
+    )
+    .optional()
+}
+
+/// The open shift, read on its own. Blocking, one small query.
+pub fn open_shift(db_path: &str) -> SqlResult<Option<Shift>> {
+    current(&Connection::open(db_path)?)
+}
+
+/// Opens a shift for `operator`, closing any left open (a handover without
+/// an explicit close).
+pub fn open(db_path: &str, operator: &str) -> SqlResult<Shift> {
+    let db = Connection::open(db_path)?;
+    init_db(&db)?;
+    let now = clock::timestamp();
+    db.execute(
+        "UPDATE shifts SET closed = ?1 WHERE closed IS NULL",
+        [now as i64],
+    )?;
+    db.execute(
+        "INSERT INTO shifts (operator, opened) VALUES (?1, ?2)",
+        params![operator, now as i64],
+    )?;
+    Ok(Shift {
+        id: db.last_insert_rowid(),
+        operator: operator.to_string(),
+        opened: now,
+    })
+}
+
+/// Closes the open shift and returns it, or `None` if none was open.
+pub fn close(db_path: &str) -> SqlResult<Option<Shift>> {
+    let db = Connection::open(db_path)?;
+    let Some(shift) = current(&db)? else {
+        return Ok(None);
+    };
+    db.execute(
+        "UPDATE shifts SET closed = ?1 WHERE id = ?2",
+        params![clock::timestamp() as i64, shift.id],
+    )?;
+    Ok(Some(shift))
+}
+
+/// A stacker pull this recent, with nobody recorded for it yet, is the
+/// collection being recorded on the service page rather than another one.
+const SAME_COLLECTION_SECS: i64 = 30 * 60;
+
+/// Records a stacker collection: the bills stacked since `since` (the last
+/// collection), under the open shift. Returns (bills, amount).
+pub fn record_collection(db_path: &str, since: u64) -> SqlResult<(i64, i64)> {
+    let db = Connection::open(db_path)?;
+    ledger::init_db(&db)?;
+    let shift = current(&db)?;
+    let (bills, amount): (i64, i64) = db.query_row(
+        "SELECT COUNT(*), COALESCE(SUM(nominal), 0) FROM bill_ledger WHERE timestamp >= ?1",
+        [since as i64],
+        |row| Ok((row.get(0)?, row.get(1)?)),
+    )?;
+    db.execute(
+        "INSERT INTO collections (timestamp, bills, amount, shift) VALUES (?1, ?2, ?3, ?4)",
+        params![
+            clock::timestamp() as i64,
+            bills,
+            amount,
+            shift.map(|shift| shift.id)
+        ],
+    )?;
+    Ok((bills, amount))
+}
+
+/// Records the cash `collector` took: the bill counters (nominal, count) as
+/// they were, under the open shift. A stacker pull from the last
+/// `SAME_COLLECTION_SECS` that nobody was recorded for is the same
+/// collection and gets completed rather than counted twice. Returns the
+/// collection's id.
+pub fn record_counted_collection(
+    db_path: &str,
+    collector: &str,
+    counts: &[(i32, i32)],
+) -> SqlResult<i64> {
+    let mut db = Connection::open(db_path)?;
+    init_db(&db)?;
+    let shift = current(&db)?;
+    let now = clock::timestamp() as i64;
+    let bills: i64 = counts.iter().map(|&(_, count)| i64::from(count)).sum();
+    let amount: i64 = counts
+        .iter()
+        .map(|&(nominal, count)| i64::from(nominal) * i64::from(count))
+        .sum();
+
+    let tx = db.transaction()?;
+    let pulled: Option<i64> = tx
+        .query_row(
+            "SELECT id FROM collections
+             WHERE collector IS NULL AND timestamp >= ?1
+             ORDER BY id DESC LIMIT 1",
+            [now - SAME_COLLECTION_SECS],
+            |row| row.get(0),
+        )
+        .optional()?;
+    let id = match pulled {
+        Some(id) => {
+            tx.execute(
+                "UPDATE collections SET bills = ?1, amount = ?2, collector = ?3 WHERE id = ?4",
+                params![bills, amount, collector, id],
+            )?;
+            id
+        }
+        None => {
+            tx.execute(
+                "INSERT INTO collections (timestamp, bills, amount, shift, collector)
+                 VALUES (?1, ?2, ?3, ?4, ?5)",
+                params![now, bills, amount, shift.map(|shift| shift.id), collector],
+            )?;
+            tx.last_insert_rowid()
+        }
+    };
+    for &(nominal, count) in counts.iter().filter(|&&(_, count)| count > 0) {
+        tx.execute(
+            "INSERT INTO collection_bills (collection, nominal, count) VALUES (?1, ?2, ?3)",
+            params![id, nominal, count],
+        )?;
+    }
+    tx.commit()?;
+    Ok(id)
+}
@@ -0,0 +1,69 @@
+//! SIGTERM (systemd stopping the service) and SIGINT (Ctrl+C). Without
+//! handling them the process just dies: the bill validator stays enabled
+//! with nothing left to count what it takes, and a session in progress is
+//! lost. On the first one the kiosk shuts down in order (see
+//! `shutdown_handler` in main); a second one exits right away, for a
+//! shutdown that hangs.
+
+use log::{error, warn};
+use std::future::poll_fn;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::task::Poll;
+use std::thread;
+use tokio::signal::unix::{Signal, SignalKind, signal};
+
+static REQUESTED: AtomicBool = AtomicBool::new(false);
+
+/// Whether a signal asked the kiosk to shut down.
+pub fn requested() -> bool {
+    REQUESTED.load(Ordering::SeqCst)
+}
+
+/// Starts listening for SIGTERM and SIGINT; `on_signal` gets the name of
+/// the first one, on the listening thread.
+pub fn listen(on_signal: impl FnOnce(&'static str) + Send + 'static) {
+    thread::spawn(move || {
+        let runtime = match tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+        {
+            Ok(runtime) => runtime,
+            Err(e) => {
+                error!("Failed to start the signal listener: {}", e);
+                return;
+            }
+        };
+        runtime.block_on(async move {
+            let (mut term, mut int) = match (
+                signal(SignalKind::terminate()),
+                signal(SignalKind::interrupt()),
+            ) {
+                (Ok(term), Ok(int)) => (term, int),
+                (Err(e), _) | (_, Err(e)) => {
+                    error!("Failed to listen for SIGTERM/SIGINT: {}", e);
+                    return;
+                }
+            };
+            let first = next(&mut term, &mut int).await;
+            REQUESTED.store(true, Ordering::SeqCst);
+            on_signal(first);
+            let second = next(&mut term, &mut int).await;
+            warn!("🛑 {} during shutdown, exiting now", second);
+            std::process::exit(1);
+        });
+    });
+}
+
+/// The name of the next signal to arrive.
+async fn next(term: &mut Signal, int: &mut Signal) -> &'static str {
+    poll_fn(|cx| {
+        if term.poll_recv(cx).is_ready() {
+            Poll::Ready("SIGTERM")
+        } else if int.poll_recv(cx).is_ready() {
+            Poll::Ready("SIGINT")
+        } else {
+            Poll::Pending
+        }
+    })
+    .await
+}
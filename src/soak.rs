@@ -0,0 +1,582 @@
+use log::{error, info, warn};
+use serialport::{SerialPort, TTYPort};
+use std::collections::VecDeque;
+use std::io::{Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, AtomicI64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use crate::acceptor::BillAcceptor;
+use crate::cashcode::{self, BillEvent, CashCode};
+use crate::frame_log::FrameLog;
+use crate::mock_acceptor::{device_frame, with_crc};
+use crate::{api, clock, donation, ledger, mock_acceptor};
+
+/// Memory the process may grow by after warm-up before the run fails.
+const MAX_RSS_GROWTH: u64 = 64 * 1024 * 1024;
+/// RSS is taken as the baseline once this much of the run has passed.
+const WARMUP: Duration = Duration::from_secs(60);
+const HEALTH_CHECK_INTERVAL: Duration = Duration::from_secs(30);
+const READY_TIMEOUT: Duration = Duration::from_secs(15);
+
+/// `--soak [hours] [--seed N]` from the command line.
+pub struct SoakArgs {
+    pub duration: Duration,
+    pub seed: u64,
+}
+
+/// Parses the soak flags; `None` for a normal kiosk start.
+pub fn requested() -> Option<SoakArgs> {
+    let args: Vec<String> = std::env::args().collect();
+    let at = args.iter().position(|a| a == "--soak")?;
+    let hours: f64 = args.get(at + 1).and_then(|h| h.parse().ok()).unwrap_or(1.0);
+    let seed = args
+        .iter()
+        .position(|a| a == "--seed")
+        .and_then(|i| args.get(i + 1))
+        .and_then(|s| s.parse().ok())
+        .unwrap_or_else(|| clock::timestamp() ^ u64::from(std::process::id()));
+    Some(SoakArgs {
+        duration: Duration::from_secs_f64(hours * 3600.0),
+        seed,
+    })
+}
+
+/// Small xorshift generator, so a failing run can be replayed by seed.
+struct Rng(u64);
+
+impl Rng {
+    fn new(seed: u64) -> Self {
+        Rng(seed.max(1))
+    }
+
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    fn below(&mut self, n: u64) -> u64 {
+        self.next() % n
+    }
+
+    /// True with the given chance in thousandths.
+    fn chance(&mut self, per_mille: u64) -> bool {
+        self.below(1000) < per_mille
+    }
+}
+
+/// Bill type codes the simulated device stacks, with their value.
+const BILLS: [(u8, i64); 5] = [
+    (0x00, 1000),
+    (0x0C, 2000),
+    (0x01, 5000),
+    (0x02, 10000),
+    (0x03, 20000),
+];
+
+/// GET BILL TABLE data for `BILLS`.
+fn bill_table() -> Vec<u8> {
+    let mut values = vec![0; 24];
+    for (code, value) in BILLS {
+        values[code as usize] = value as i32;
+    }
+    mock_acceptor::bill_table(&values, b"ARM")
+}
+
+/// A CCNET bill validator on the master side of a pty. Enabled, it
+/// randomly accepts, rejects and jams bills, has its stacker pulled, drops
+/// answers and forgets its enable mask. `stacked` is what it really took in.
+struct SimulatedAcceptor {
+    port: TTYPort,
+    rng: Rng,
+    enabled: [u8; 3],
+    /// Status payloads for the next polls, one per poll.
+    script: VecDeque<Vec<u8>>,
+    stacked: Arc<AtomicI64>,
+}
+
+impl SimulatedAcceptor {
+    fn run(mut self, stop: Arc<AtomicBool>) {
+        let mut buffer = Vec::new();
+        let mut chunk = [0u8; 64];
+        while !stop.load(Ordering::Relaxed) {
+            match self.port.read(&mut chunk) {
+                Ok(n) => buffer.extend_from_slice(&chunk[..n]),
+                Err(e) if e.kind() == std::io::ErrorKind::TimedOut => continue,
+                Err(e) => panic!("simulated acceptor read failed: {}", e),
+            }
+            // skip to a frame start, then handle every complete frame
+            while let Some(start) = buffer.iter().position(|b| *b == 0x02) {
+                buffer.drain(..start);
+                if buffer.len() < 3 || buffer.len() < buffer[2] as usize {
+                    break;
+                }
+                let frame: Vec<u8> = buffer.drain(..buffer[2] as usize).collect();
+                self.handle(&frame);
+            }
+        }
+    }
+
+    fn reply(&mut self, data: &[u8]) {
+        let frame = device_frame(data);
+        self.port
+            .write_all(&frame)
+            .expect("simulated acceptor write failed");
+    }
+
+    fn handle(&mut self, frame: &[u8]) {
+        if frame.len() < 6 || with_crc(frame[..frame.len() - 2].to_vec()) != frame {
+            warn!("🧪 Simulated acceptor got a bad frame: {:02X?}", frame);
+            return;
+        }
+        match frame[3] {
+            // ACK from the host
+            0x00 => {}
+            // RESET
+            0x30 => {
+                self.enabled = [0; 3];
+                self.script = VecDeque::from([vec![0x10], vec![0x13], vec![0x13]]);
+                self.reply(&[0x00]);
+            }
+            // GET STATUS
+            0x31 => {
+                let mut data = self.enabled.to_vec();
+                data.extend_from_slice(&[0, 0, 0]);
+                self.reply(&data);
+            }
+            // POLL
+            0x33 => {
+                // line noise: the host hears nothing this time
+                if self.rng.chance(5) {
+                    return;
+                }
+                if self.script.is_empty() {
+                    self.next_scenario();
+                }
+                let status = self
+                    .script
+                    .pop_front()
+                    .unwrap_or_else(|| vec![if self.enabled == [0; 3] { 0x19 } else { 0x14 }]);
+                if status[0] == 0x81 {
+                    let value = BILLS.iter().find(|(code, _)| *code == status[1]).unwrap().1;
+                    self.stacked.fetch_add(value, Ordering::SeqCst);
+                }
+                self.reply(&status);
+            }
+            // IDENTIFICATION
+            0x37 => {
+                let mut data = b"SM-SOAK-SIM    SIM000000001".to_vec();
+                data.extend_from_slice(&[0; 7]);
+                self.reply(&data);
+            }
+            // GET BILL TABLE
+            0x41 => self.reply(&bill_table()),
+            // ENABLE BILL TYPES
+            0x34 => {
+                self.enabled = [frame[4], frame[5], frame[6]];
+                self.reply(&[0x00]);
+            }
+            // RETURN, anything else
+            _ => self.reply(&[0x00]),
+        }
+    }
+
+    /// Queues what happens over the next polls while enabled.
+    fn next_scenario(&mut self) {
+        if self.enabled == [0; 3] {
+            return;
+        }
+        let rng = &mut self.rng;
+        if rng.chance(80) {
+            let (code, _) = BILLS[rng.below(BILLS.len() as u64) as usize];
+            self.script
+                .extend([vec![0x15], vec![0x17], vec![0x81, code]]);
+        } else if rng.chance(30) {
+            let reason = [0x60, 0x64, 0x65, 0x66][rng.below(4) as usize];
+            self.script.extend([vec![0x15], vec![0x1C, reason]]);
+        } else if rng.chance(10) {
+            self.script
+                .push_back(vec![[0x43, 0x44][rng.below(2) as usize]]);
+        } else if rng.chance(5) {
+            // stacker pulled and put back; the device comes back disabled
+            self.script.extend([vec![0x42], vec![0x42], vec![0x42]]);
+            self.enabled = [0; 3];
+        } else if rng.chance(5) {
+            // falls back to nothing enabled, only GET STATUS shows it
+            self.enabled = [0; 3];
+        }
+    }
+}
+
+/// What the mock gateway has taken in.
+#[derive(Default)]
+struct GatewayTally {
+    donations: u64,
+    amount: i64,
+}
+
+/// A local stand-in for the gateway's donation endpoint that flaps: it
+/// goes down for stretches, answers 503, hangs up and answers slowly.
+/// Only donations it answered 201 for are counted.
+fn run_mock_gateway(listener: TcpListener, seed: u64, tally: Arc<Mutex<GatewayTally>>) {
+    let mut rng = Rng::new(seed);
+    let mut down_for = 0;
+    for stream in listener.incoming() {
+        let Ok(mut stream) = stream else { continue };
+        if down_for == 0 && rng.chance(20) {
+            down_for = 1 + rng.below(10);
+        }
+        if down_for > 0 {
+            down_for -= 1;
+            continue;
+        }
+        let Some(amount) = read_donation(&mut stream) else {
+            let _ = stream.write_all(b"HTTP/1.1 400 Bad Request\r\nContent-Length: 0\r\n\r\n");
+            continue;
+        };
+        if rng.chance(50) {
+            let _ = stream.write_all(
+                b"HTTP/1.1 503 Service Unavailable\r\nContent-Length: 4\r\nConnection: close\r\n\r\nbusy",
+            );
+            continue;
+        }
+        if rng.chance(50) {
+            clock::sleep(Duration::from_millis(500 + rng.below(2500)));
+        }
+        let mut tally = tally.lock().unwrap();
+        tally.donations += 1;
+        tally.amount += amount;
+        let _ = stream
+            .write_all(b"HTTP/1.1 201 Created\r\nContent-Length: 0\r\nConnection: close\r\n\r\n");
+    }
+}
+
+/// Reads one POST and returns the donation amount in its JSON body.
+fn read_donation(stream: &mut TcpStream) -> Option<i64> {
+    let _ = stream.set_read_timeout(Some(Duration::from_secs(5)));
+    let mut request = Vec::new();
+    let mut chunk = [0u8; 1024];
+    let header_end = loop {
+        let n = stream.read(&mut chunk).ok()?;
+        if n == 0 {
+            return None;
+        }
+        request.extend_from_slice(&chunk[..n]);
+        if let Some(end) = request.windows(4).position(|w| w == b"\r\n\r\n") {
+            break end + 4;
+        }
+    };
+    let headers = String::from_utf8_lossy(&request[..header_end]).to_lowercase();
+    let length: usize = headers
+        .lines()
+        .find_map(|l| l.strip_prefix("content-length:"))
+        .and_then(|l| l.trim().parse().ok())?;
+    while request.len() < header_end + length {
+        let n = stream.read(&mut chunk).ok()?;
+        if n == 0 {
+            return None;
+        }
+        request.extend_from_slice(&chunk[..n]);
+    }
+    let body: serde_json::Value = serde_json::from_slice(&request[header_end..]).ok()?;
+    body.get("amount")?.as_i64()
+}
+
+/// Resident set size of this process in bytes.
+fn rss_bytes() -> Option<u64> {
+    let statm = std::fs::read_to_string("/proc/self/statm").ok()?;
+    let pages: u64 = statm.split_whitespace().nth(1)?.parse().ok()?;
+    Some(pages * 4096)
+}
+
+/// Sum of the bills the driver recorded, all sessions.
+fn ledger_total(db_path: &str) -> rusqlite::Result<i64> {
+    let db = rusqlite::Connection::open(db_path)?;
+    db.query_row(
+        "SELECT COALESCE(SUM(nominal), 0) FROM bill_ledger",
+        [],
+        |row| row.get(0),
+    )
+}
+
+/// Runs the real bill driver against a simulated acceptor and sends each
+/// session to a flapping mock gateway for `args.duration`, with a throwaway
+/// stats DB. Checks as it goes that the ledger matches the bills reported
+/// per session, and at the end that the device, driver totals, ledger and
+/// gateway all agree and memory stayed bounded. Returns the exit code.
+pub fn run(args: &SoakArgs) -> i32 {
+    info!(
+        "🧪 Soak test for {:?}, seed {} (replay with --seed {})",
+        args.duration, args.seed, args.seed
+    );
+    let dir: PathBuf = std::env::temp_dir().join(format!("dramma-soak-{}", args.seed));
+    if let Err(e) = std::fs::create_dir_all(&dir) {
+        error!("🧪 Cannot create {}: {}", dir.display(), e);
+        return 2;
+    }
+    let db_path = dir.join("Stats.db").to_string_lossy().to_string();
+    let _ = std::fs::remove_file(&db_path);
+
+    let mut failures: Vec<String> = Vec::new();
+    let mut check = |ok: bool, what: String| {
+        if !ok {
+            error!("🧪 ❌ {}", what);
+            failures.push(what);
+        }
+    };
+
+    // simulated acceptor on a pty pair
+    let (master, slave) = match TTYPort::pair() {
+        Ok(pair) => pair,
+        Err(e) => {
+            error!("🧪 Cannot create a pty pair: {}", e);
+            return 2;
+        }
+    };
+    let Some(slave_path) = slave.name() else {
+        error!("🧪 pty has no name");
+        return 2;
+    };
+    let mut master = master;
+    let _ = master.set_timeout(Duration::from_millis(10));
+    let stacked = Arc::new(AtomicI64::new(0));
+    let stop = Arc::new(AtomicBool::new(false));
+    let acceptor = SimulatedAcceptor {
+        port: master,
+        rng: Rng::new(args.seed),
+        enabled: [0; 3],
+        script: VecDeque::new(),
+        stacked: stacked.clone(),
+    };
+    let device = {
+        let stop = stop.clone();
+        thread::spawn(move || acceptor.run(stop))
+    };
+
+    // mock gateway on a local port
+    let listener = match TcpListener::bind("127.0.0.1:0") {
+        Ok(listener) => listener,
+        Err(e) => {
+            error!("🧪 Cannot start the mock gateway: {}", e);
+            return 2;
+        }
+    };
+    let gateway = Arc::new(Mutex::new(GatewayTally::default()));
+    api::set_gateway(&format!("http://{}", listener.local_addr().unwrap()));
+    {
+        let gateway = gateway.clone();
+        let seed = args.seed.rotate_left(32);
+        thread::spawn(move || run_mock_gateway(listener, seed, gateway));
+    }
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_time()
+        .build()
+        .expect("soak runtime");
+
+    let mut cashcode = match CashCode::new(&slave_path, &db_path, Arc::new(FrameLog::new(0))) {
+        Ok(cashcode) => cashcode,
+        Err(e) => {
+            error!("🧪 Driver failed to open the simulated acceptor: {}", e);
+            return 2;
+        }
+    };
+    let ready = cashcode
+        .reset()
+        .and_then(|_| cashcode.wait_ready(READY_TIMEOUT));
+    if !matches!(ready, Ok(true)) {
+        error!("🧪 Simulated acceptor never became ready: {:?}", ready);
+        return 2;
+    }
+    // Bill values come from the device, as on a real kiosk
+    match cashcode.read_bill_table() {
+        Ok(Some(table)) if cashcode.set_bill_types(&table.values).is_ok() => {
+            info!(
+                "🧪 Bill table from the simulated acceptor: {:?}",
+                table.values
+            )
+        }
+        other => {
+            error!("🧪 Simulated acceptor's bill table unusable: {:?}", other);
+            return 2;
+        }
+    }
+
+    let mut rng = Rng::new(args.seed.wrapping_mul(31));
+    let started = clock::instant();
+    let mut baseline_rss = None;
+    let mut peak_rss = 0;
+    let mut last_health_check = clock::instant();
+    let mut last_device_status = None;
+    let (mut sessions, mut accepted, mut rejected, mut jams) = (0u64, 0i64, 0u64, 0u64);
+    let (mut sent, mut unsent) = (0i64, 0i64);
+
+    while clock::since(started) < args.duration && !device.is_finished() {
+        sessions += 1;
+        let _ = cashcode.begin_session();
+        let _ = cashcode.enable();
+
+        // a donor feeding bills for a while, then walking away
+        let mut session_total = 0i64;
+        let session_end = clock::instant() + Duration::from_millis(2000 + rng.below(20000));
+        let mut drain_polls = 0;
+        loop {
+            if clock::instant() >= session_end {
+                if drain_polls == 0 {
+                    let _ = cashcode.disable();
+                }
+                // let a bill already in the acceptor finish
+                drain_polls += 1;
+                if cashcode.is_idle() || drain_polls > 20 {
+                    break;
+                }
+            }
+            if clock::since(last_health_check) >= HEALTH_CHECK_INTERVAL && cashcode.is_idle() {
+                last_health_check = clock::instant();
+                crate::check_bill_health(&mut cashcode, &db_path, &mut last_device_status);
+            }
+            match cashcode.poll() {
+                Ok(Some(BillEvent::Accepted(nominal))) => session_total += nominal.value() as i64,
+                Ok(Some(BillEvent::Rejected(_))) => rejected += 1,
+                Ok(Some(BillEvent::Jam(_))) => jams += 1,
+                Ok(_) => {}
+                Err(e) => check(false, format!("poll error: {}", e)),
+            }
+            clock::sleep(Duration::from_millis(50));
+        }
+        accepted += session_total;
+
+        match ledger::current_session_total(&db_path) {
+            Ok(total) => check(
+                i64::from(total) == session_total,
+                format!(
+                    "session {}: ledger has {}, driver reported {}",
+                    sessions, total, session_total
+                ),
+            ),
+            Err(e) => check(false, format!("ledger unreadable: {}", e)),
+        }
+
+        if session_total > 0 {
+            let fund_id = 1 + rng.below(5) as i32;
+            let result = runtime.block_on(donation::send_donation(
+                "soak",
+                fund_id,
+                "soak",
+                session_total as i32,
+                None,
+            ));
+            match result {
+                Ok(()) => sent += session_total,
+                Err(e) => {
+                    info!("🧪 Gateway refused session {}: {}", sessions, e);
+                    unsent += session_total;
+                }
+            }
+        }
+
+        // now and then a reset, as the scheduled one would do
+        if rng.chance(10) {
+            let ready = cashcode
+                .reset()
+                .and_then(|_| cashcode.wait_ready(READY_TIMEOUT));
+            check(
+                matches!(ready, Ok(true)),
+                format!("not ready after reset: {:?}", ready),
+            );
+        }
+
+        if let Some(rss) = rss_bytes() {
+            peak_rss = peak_rss.max(rss);
+            match baseline_rss {
+                None if clock::since(started) >= WARMUP.min(args.duration / 10) => {
+                    baseline_rss = Some(rss)
+                }
+                Some(baseline) => check(
+                    rss <= baseline + MAX_RSS_GROWTH,
+                    format!("RSS grew from {} to {} bytes", baseline, rss),
+                ),
+                None => {}
+            }
+        }
+        if sessions % 50 == 0 {
+            info!(
+                "🧪 {} sessions, {} ֏ accepted, {} rejected, {} jams, RSS {} KiB",
+                sessions,
+                accepted,
+                rejected,
+                jams,
+                peak_rss / 1024
+            );
+        }
+    }
+
+    stop.store(true, Ordering::Relaxed);
+    let device_ok = device.join().is_ok();
+    check(device_ok, "simulated acceptor panicked".to_string());
+
+    let stacked = stacked.load(Ordering::SeqCst);
+    check(
+        stacked == accepted,
+        format!("device stacked {}, driver reported {}", stacked, accepted),
+    );
+    match cashcode.get_total_amount() {
+        Ok(total) => check(
+            i64::from(total) == stacked,
+            format!("accepted_bills total {}, device stacked {}", total, stacked),
+        ),
+        Err(e) => check(false, format!("accepted_bills unreadable: {}", e)),
+    }
+    match ledger_total(&db_path) {
+        Ok(total) => check(
+            total == stacked,
+            format!("ledger total {}, device stacked {}", total, stacked),
+        ),
+        Err(e) => check(false, format!("ledger unreadable: {}", e)),
+    }
+    let gateway = gateway.lock().unwrap();
+    check(
+        gateway.amount == sent,
+        format!(
+            "gateway received {}, kiosk saw {} confirmed",
+            gateway.amount, sent
+        ),
+    );
+    check(
+        sent + unsent == accepted,
+        format!("sent {} + unsent {} != accepted {}", sent, unsent, accepted),
+    );
+
+    info!(
+        "🧪 Soak finished after {:?}: {} sessions, {} ֏ accepted ({} ֏ sent in {} donations, {} ֏ refused), {} rejects, {} jams, peak RSS {} KiB",
+        clock::since(started),
+        sessions,
+        accepted,
+        sent,
+        gateway.donations,
+        unsent,
+        rejected,
+        jams,
+        peak_rss / 1024
+    );
+    info!(
+        "🧪 CCNET timing violations: {}",
+        cashcode::timing_violations()
+    );
+    if failures.is_empty() {
+        info!("🧪 ✅ All invariants held (DB in {})", dir.display());
+        0
+    } else {
+        error!(
+            "🧪 {} invariant(s) failed, seed {}, DB in {}",
+            failures.len(),
+            args.seed,
+            dir.display()
+        );
+        1
+    }
+}
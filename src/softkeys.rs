@@ -0,0 +1,160 @@
+use log::{info, warn};
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, Read};
+use std::mem::size_of;
+use std::sync::mpsc::Sender;
+use std::thread;
+use std::time::Duration;
+
+use crate::clock;
+use crate::config::SoftKeys;
+
+/// How often a missing or unplugged device is looked for again.
+const RETRY: Duration = Duration::from_secs(5);
+
+/// `struct input_event`: a `timeval` (two C longs) then type, code, value.
+const TIME_SIZE: usize = 2 * size_of::<usize>();
+const EVENT_SIZE: usize = TIME_SIZE + 8;
+pub const EV_KEY: u16 = 1;
+/// Event values: released, pressed, auto-repeat.
+const KEY_PRESS: i32 = 1;
+const KEY_REPEAT: i32 = 2;
+
+/// What a physical button does, see `softkey_handler`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    Previous,
+    Next,
+    Confirm,
+    Cancel,
+}
+
+/// Linux key codes (input-event-codes.h) for the keys a keypad or a row
+/// of GPIO buttons is likely to send. Anything else is given by number.
+const KEY_NAMES: &[(&str, u16)] = &[
+    ("ESC", 1),
+    ("BACKSPACE", 14),
+    ("TAB", 15),
+    ("ENTER", 28),
+    ("SPACE", 57),
+    ("KP7", 71),
+    ("KP8", 72),
+    ("KP9", 73),
+    ("KPMINUS", 74),
+    ("KP4", 75),
+    ("KP5", 76),
+    ("KP6", 77),
+    ("KPPLUS", 78),
+    ("KP1", 79),
+    ("KP2", 80),
+    ("KP3", 81),
+    ("KP0", 82),
+    ("KPDOT", 83),
+    ("KPENTER", 96),
+    ("UP", 103),
+    ("LEFT", 105),
+    ("RIGHT", 106),
+    ("DOWN", 108),
+    ("BTN_0", 256),
+    ("BTN_1", 257),
+    ("BTN_2", 258),
+    ("BTN_3", 259),
+];
+
+/// Key code for a name like "LEFT", "KEY_LEFT" or "105".
+pub fn key_code(name: &str) -> Option<u16> {
+    let name = name.trim().to_uppercase();
+    if let Ok(code) = name.parse() {
+        return Some(code);
+    }
+    let name = name.strip_prefix("KEY_").unwrap_or(&name);
+    KEY_NAMES
+        .iter()
+        .find(|(known, _)| *known == name)
+        .map(|(_, code)| *code)
+}
+
+fn key_map(config: &SoftKeys) -> HashMap<u16, Action> {
+    let mut map = HashMap::new();
+    for (names, action) in [
+        (&config.previous, Action::Previous),
+        (&config.next, Action::Next),
+        (&config.confirm, Action::Confirm),
+        (&config.cancel, Action::Cancel),
+    ] {
+        for name in names {
+            match key_code(name) {
+                Some(code) => {
+                    map.insert(code, action);
+                }
+                None => warn!("⌨️  Unknown softkey \"{}\", ignored", name),
+            }
+        }
+    }
+    map
+}
+
+/// Reads key presses from each configured device on its own thread and
+/// sends the mapped actions. A device that is missing or goes away is
+/// retried; the kiosk user needs read access (the `input` group).
+pub fn start(config: &SoftKeys, tx: Sender<Action>) {
+    let keys = key_map(config);
+    for device in &config.devices {
+        let device = device.clone();
+        let keys = keys.clone();
+        let tx = tx.clone();
+        thread::spawn(move || {
+            let mut failing = false;
+            loop {
+                match run(&device, &keys, &tx) {
+                    // the UI is gone
+                    Ok(()) => return,
+                    // Logged once per outage, not on every retry
+                    Err(e) if !failing => {
+                        failing = true;
+                        warn!("⌨️  Softkeys on {}: {}", device, e);
+                    }
+                    Err(_) => {}
+                }
+                clock::sleep(RETRY);
+            }
+        });
+    }
+}
+
+/// Next input event from an evdev device: type, code and value.
+pub fn read_event(file: &mut File) -> io::Result<(u16, u16, i32)> {
+    let mut event = [0u8; EVENT_SIZE];
+    file.read_exact(&mut event)?;
+    let kind = u16::from_ne_bytes([event[TIME_SIZE], event[TIME_SIZE + 1]]);
+    let code = u16::from_ne_bytes([event[TIME_SIZE + 2], event[TIME_SIZE + 3]]);
+    let value = i32::from_ne_bytes([
+        event[TIME_SIZE + 4],
+        event[TIME_SIZE + 5],
+        event[TIME_SIZE + 6],
+        event[TIME_SIZE + 7],
+    ]);
+    Ok((kind, code, value))
+}
+
+/// Forwards presses until the device fails. `Ok` only when the receiver is
+/// gone.
+fn run(device: &str, keys: &HashMap<u16, Action>, tx: &Sender<Action>) -> io::Result<()> {
+    let mut file = File::open(device)?;
+    info!("⌨️  Softkeys on {}", device);
+    loop {
+        let (kind, code, value) = read_event(&mut file)?;
+        let Some(&action) = keys.get(&code) else {
+            continue;
+        };
+        // Holding a key scrolls, but never confirms twice
+        let repeat_ok = matches!(action, Action::Previous | Action::Next);
+        if kind == EV_KEY
+            && (value == KEY_PRESS || (value == KEY_REPEAT && repeat_ok))
+            && tx.send(action).is_err()
+        {
+            return Ok(());
+        }
+    }
+}
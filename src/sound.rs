@@ -67,7 +67,8 @@ fn play_sound(event: SoundEvent) {
                 log::warn!("Audio busy, skipping sound: {}", e);
             }
         }
-        None => log::error!("Audio not initialized — call sound::init() at startup"),
+        // Not initialised means sound is disabled in `[features]`
+        None => log::debug!("Audio not initialized, skipping sound"),
     }
 }
 
@@ -0,0 +1,239 @@
+use chrono::{Local, TimeZone};
+use rusqlite::{Connection, Result as SqlResult};
+use serde::Serialize;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use crate::{audit, clock, donation, donation_log, money, printer, receipt, version};
+
+/// Errors from the audit log included in the file, newest first.
+const LAST_ERRORS: usize = 5;
+
+/// What the UI knows right now, gathered on the event loop.
+#[derive(Debug, Clone, Serialize)]
+pub struct Live {
+    pub acceptor: Acceptor,
+    pub session: Session,
+    /// Error-level notifications showing on the kiosk.
+    pub faults: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Acceptor {
+    /// "idle", "accepting", "paused" or "fault", as on the status light.
+    pub state: &'static str,
+    /// Bills that still fit, when the stacker is tracked.
+    pub stacker_headroom: Option<i32>,
+    /// How full the stacker is in percent, when it is tracked.
+    pub stacker_fill: Option<i32>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Session {
+    pub active: bool,
+    /// Money inserted so far, in AMD.
+    pub amount: i32,
+}
+
+#[derive(Debug, Serialize)]
+struct Today {
+    /// Completed sessions.
+    sessions: i64,
+    /// Money taken in those sessions.
+    taken: i64,
+    /// Money the backend accepted.
+    sent: i64,
+    /// Errors logged, see `error_code::report`.
+    errors: i64,
+}
+
+#[derive(Debug, Serialize)]
+struct Queue {
+    /// Receipts waiting for the printer.
+    print_jobs: i64,
+    /// Donations waiting to be retried or attributed, see `donation::queued`.
+    donations: i64,
+}
+
+#[derive(Debug, Serialize)]
+struct LoggedError {
+    timestamp: u64,
+    code: String,
+    details: String,
+}
+
+#[derive(Debug, Serialize)]
+struct Status<'a> {
+    updated: u64,
+    /// The build running, see `version::describe`.
+    version: String,
+    #[serde(flatten)]
+    live: &'a Live,
+    today: Today,
+    queue: Queue,
+    errors: Vec<LoggedError>,
+}
+
+/// Unix time of local midnight today.
+fn midnight() -> i64 {
+    clock::local()
+        .date_naive()
+        .and_hms_opt(0, 0, 0)
+        .and_then(|midnight| Local.from_local_datetime(&midnight).earliest())
+        .map(|t| t.timestamp())
+        .unwrap_or(0)
+}
+
+fn read(db_path: &str) -> SqlResult<(Today, Queue, Vec<LoggedError>)> {
+    let db = Connection::open(db_path)?;
+    receipt::init_db(&db)?;
+    donation_log::init_db(&db)?;
+    audit::init_db(&db)?;
+    printer::init_db(&db)?;
+    donation::init_db(&db)?;
+    let since = midnight();
+    let (sessions, taken) = db.query_row(
+        "SELECT COUNT(*), COALESCE(SUM(amount), 0) FROM receipts WHERE timestamp >= ?1",
+        [since],
+        |row| Ok((row.get(0)?, row.get(1)?)),
+    )?;
+    let sent = db.query_row(
+        "SELECT COALESCE(SUM(amount), 0) FROM donation_log WHERE timestamp >= ?1",
+        [since],
+        |row| row.get(0),
+    )?;
+    let errors_today = db.query_row(
+        "SELECT COUNT(*) FROM audit_log WHERE event = 'error' AND timestamp >= ?1",
+        [since],
+        |row| row.get(0),
+    )?;
+    let print_jobs = db.query_row(
+        "SELECT COUNT(*) FROM print_jobs WHERE printed IS NULL",
+        [],
+        |row| row.get(0),
+    )?;
+    let donations = db.query_row("SELECT COUNT(*) FROM donation_queue", [], |row| row.get(0))?;
+    let errors = db
+        .prepare(
+            "SELECT timestamp, details FROM audit_log WHERE event = 'error' ORDER BY id DESC LIMIT ?1",
+        )?
+        .query_map([LAST_ERRORS as i64], |row| {
+            let details: String = row.get(1)?;
+            // Reported as "<code> <details>", see error_code::report
+            let (code, details) = details.split_once(' ').unwrap_or((&details, ""));
+            Ok(LoggedError {
+                timestamp: row.get::<_, i64>(0)? as u64,
+                code: code.to_string(),
+                details: details.to_string(),
+            })
+        })?
+        .collect::<SqlResult<_>>()?;
+    Ok((
+        Today {
+            sessions,
+            taken,
+            sent,
+            errors: errors_today,
+        },
+        Queue {
+            print_jobs,
+            donations,
+        },
+        errors,
+    ))
+}
+
+/// A figure from the status file mirrored as a retained MQTT topic (and a
+/// Home Assistant sensor): topic under the kiosk's prefix, sensor name and
+/// unit.
+pub struct Metric {
+    pub topic: &'static str,
+    pub name: &'static str,
+    pub unit: Option<Unit>,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum Unit {
+    /// The kiosk's currency, e.g. "AMD".
+    Currency,
+    Percent,
+}
+
+impl Unit {
+    pub fn symbol(self) -> &'static str {
+        match self {
+            Unit::Currency => money::currency(),
+            Unit::Percent => "%",
+        }
+    }
+}
+
+pub const METRICS: [Metric; 6] = [
+    Metric {
+        topic: "metrics/taken_today",
+        name: "Taken today",
+        unit: Some(Unit::Currency),
+    },
+    Metric {
+        topic: "metrics/sent_today",
+        name: "Sent today",
+        unit: Some(Unit::Currency),
+    },
+    Metric {
+        topic: "metrics/sessions_today",
+        name: "Sessions today",
+        unit: None,
+    },
+    Metric {
+        topic: "metrics/errors_today",
+        name: "Errors today",
+        unit: None,
+    },
+    Metric {
+        topic: "metrics/queued_donations",
+        name: "Queued donations",
+        unit: None,
+    },
+    Metric {
+        topic: "metrics/stacker_fill",
+        name: "Stacker fill",
+        unit: Some(Unit::Percent),
+    },
+];
+
+/// The current value of each of `METRICS`, in that order; `None` for the
+/// stacker fill while it isn't tracked. Blocking.
+pub fn metrics(db_path: &str, live: &Live) -> SqlResult<[Option<i64>; 6]> {
+    let (today, queue, _) = read(db_path)?;
+    Ok([
+        Some(today.taken),
+        Some(today.sent),
+        Some(today.sessions),
+        Some(today.errors),
+        Some(queue.donations),
+        live.acceptor.stacker_fill.map(i64::from),
+    ])
+}
+
+/// Writes the status file: `live` plus today's totals, the print queue and
+/// the last errors from the stats DB. Written to a temporary file and
+/// renamed, so a reader never sees half a file. Blocking.
+pub fn write(path: &str, db_path: &str, live: &Live) -> io::Result<()> {
+    let (today, queue, errors) = read(db_path).map_err(io::Error::other)?;
+    let status = Status {
+        updated: clock::timestamp(),
+        version: version::describe(),
+        live,
+        today,
+        queue,
+        errors,
+    };
+    let path = Path::new(path);
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let tmp = path.with_extension("json.tmp");
+    fs::write(&tmp, serde_json::to_vec_pretty(&status)?)?;
+    fs::rename(&tmp, path)
+}
@@ -0,0 +1,59 @@
+//! Subsystems staff can restart one at a time, from Diagnostics or the
+//! listener's `POST /restart/<name>`, while the rest of the kiosk keeps
+//! running. Each one restarts through its own handle (see
+//! `restart_handler` in main.rs).
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Subsystem {
+    /// Closes the serial port and starts the CashCode driver over.
+    BillAcceptor,
+    /// Re-enumerates the ccTalk device and reconnects.
+    CoinAcceptor,
+    /// Drops the broker connection and reconnects at once.
+    Mqtt,
+    /// Drops the HASS WebSocket and reconnects at once.
+    HassEvents,
+    /// Goes back to the preferred gateway and fetches funds again.
+    Gateway,
+}
+
+impl Subsystem {
+    pub const ALL: [Subsystem; 5] = [
+        Subsystem::BillAcceptor,
+        Subsystem::CoinAcceptor,
+        Subsystem::Mqtt,
+        Subsystem::HassEvents,
+        Subsystem::Gateway,
+    ];
+
+    /// Name in the listener's URL and the audit trail.
+    pub fn name(self) -> &'static str {
+        match self {
+            Subsystem::BillAcceptor => "bill_acceptor",
+            Subsystem::CoinAcceptor => "coin_acceptor",
+            Subsystem::Mqtt => "mqtt",
+            Subsystem::HassEvents => "hass_events",
+            Subsystem::Gateway => "gateway",
+        }
+    }
+
+    pub fn parse(name: &str) -> Option<Subsystem> {
+        Subsystem::ALL.into_iter().find(|s| s.name() == name)
+    }
+
+    /// Button label on Diagnostics.
+    pub fn label(self) -> &'static str {
+        match self {
+            Subsystem::BillAcceptor => "Bill driver",
+            Subsystem::CoinAcceptor => "Coin driver",
+            Subsystem::Mqtt => "MQTT",
+            Subsystem::HassEvents => "HASS events",
+            Subsystem::Gateway => "Gateway",
+        }
+    }
+
+    /// Whether restarting it would drop money a donor is inserting.
+    pub fn takes_money(self) -> bool {
+        matches!(self, Subsystem::BillAcceptor | Subsystem::CoinAcceptor)
+    }
+}
@@ -0,0 +1,91 @@
+use log::{info, warn};
+use std::fs::File;
+use std::io;
+use std::sync::mpsc::Sender;
+use std::thread;
+use std::time::Duration;
+
+use crate::clock;
+use crate::config::Tamper;
+use crate::softkeys::{self, EV_KEY};
+
+/// How often a missing or unplugged device is looked for again.
+const RETRY: Duration = Duration::from_secs(5);
+/// Switch events, for gpio-keys set up with `linux,input-type = <5>`.
+const EV_SW: u16 = 5;
+
+/// Where a door reading came from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Source {
+    Gpio,
+    Hass,
+}
+
+impl Source {
+    pub fn name(self) -> &'static str {
+        match self {
+            Source::Gpio => "GPIO switch",
+            Source::Hass => "Home Assistant",
+        }
+    }
+}
+
+/// The cash box door opened or closed, see `tamper_handler`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Door {
+    pub open: bool,
+    pub source: Source,
+}
+
+/// Watches the switch on `device` on its own thread and sends each change.
+/// The switch's state before its first change isn't known.
+pub fn start(config: &Tamper, tx: Sender<Door>) {
+    let Some(device) = config.device.clone() else {
+        return;
+    };
+    let Some(code) = softkeys::key_code(&config.key) else {
+        warn!(
+            "🚪 Unknown tamper key \"{}\", the switch is not watched",
+            config.key
+        );
+        return;
+    };
+    let open_when_pressed = config.open_when_pressed;
+    thread::spawn(move || {
+        let mut failing = false;
+        loop {
+            match run(&device, code, open_when_pressed, &tx) {
+                // the UI is gone
+                Ok(()) => return,
+                // Logged once per outage, not on every retry
+                Err(e) if !failing => {
+                    failing = true;
+                    warn!("🚪 Tamper switch on {}: {}", device, e);
+                }
+                Err(_) => {}
+            }
+            clock::sleep(RETRY);
+        }
+    });
+}
+
+/// Forwards door changes until the device fails. `Ok` only when the
+/// receiver is gone.
+fn run(device: &str, code: u16, open_when_pressed: bool, tx: &Sender<Door>) -> io::Result<()> {
+    let mut file = File::open(device)?;
+    info!("🚪 Tamper switch on {}", device);
+    loop {
+        let (kind, event_code, value) = softkeys::read_event(&mut file)?;
+        // Auto-repeat (2) of a held key says nothing new
+        if (kind != EV_KEY && kind != EV_SW) || event_code != code || value > 1 {
+            continue;
+        }
+        let door = Door {
+            open: (value == 1) == open_when_pressed,
+            source: Source::Gpio,
+        };
+        if tx.send(door).is_err() {
+            return Ok(());
+        }
+    }
+}
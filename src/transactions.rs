@@ -0,0 +1,169 @@
+use log::error;
+use rusqlite::{Connection, Result as SqlResult, params};
+
+use crate::backend::Donation;
+use crate::error_code::ErrorCode;
+use crate::{clock, db_writes, money};
+
+/// One money event, as a row of the `transactions` table: every bill and
+/// coin taken, every donation sent or given up on, and every failure shown
+/// with an error code. Unlike the `accepted_bills` counters this keeps each
+/// event on its own with its time, so the treasurer can match the cash in
+/// the box against what the gateway received, one donation at a time.
+pub enum Transaction<'a> {
+    /// Stacked under ledger session `session` (see `ledger`).
+    BillAccepted {
+        nominal: i32,
+        session: Option<i64>,
+    },
+    CoinAccepted {
+        value: i32,
+    },
+    DonationCommitted(&'a Donation),
+    /// Money that went in but wasn't sent to a fund: below the minimum, or
+    /// left unattributed.
+    DonationCancelled {
+        amount: i32,
+        fund_id: Option<i32>,
+        reason: &'a str,
+    },
+    Error {
+        code: ErrorCode,
+        details: &'a str,
+    },
+}
+
+impl Transaction<'_> {
+    fn kind(&self) -> &'static str {
+        match self {
+            Transaction::BillAccepted { .. } => "bill_accepted",
+            Transaction::CoinAccepted { .. } => "coin_accepted",
+            Transaction::DonationCommitted(_) => "donation_committed",
+            Transaction::DonationCancelled { .. } => "donation_cancelled",
+            Transaction::Error { .. } => "error",
+        }
+    }
+
+    fn amount(&self) -> Option<i32> {
+        match self {
+            Transaction::BillAccepted { nominal, .. } => Some(*nominal),
+            Transaction::CoinAccepted { value } => Some(*value),
+            Transaction::DonationCommitted(donation) => Some(donation.amount),
+            Transaction::DonationCancelled { amount, .. } => Some(*amount),
+            Transaction::Error { .. } => None,
+        }
+    }
+
+    /// Everything else about the event, as a JSON object.
+    fn data(&self) -> serde_json::Value {
+        match self {
+            Transaction::BillAccepted { session, .. } => serde_json::json!({ "session": session }),
+            Transaction::CoinAccepted { .. } => serde_json::json!({}),
+            Transaction::DonationCommitted(donation) => serde_json::json!({
+                "fund_id": donation.fund_id,
+                "fund_name": donation.fund_name,
+                "username": donation.username,
+                "receipt": donation.receipt,
+                "donated_at": donation.timestamp,
+                "auto_committed": donation.auto_committed,
+            }),
+            Transaction::DonationCancelled {
+                fund_id, reason, ..
+            } => serde_json::json!({ "fund_id": fund_id, "reason": reason }),
+            Transaction::Error { code, details } => serde_json::json!({
+                "code": code.as_str(),
+                "description": code.description(),
+                "details": details,
+            }),
+        }
+    }
+}
+
+pub fn init_db(db: &Connection) -> SqlResult<()> {
+    db.execute(
+        "CREATE TABLE IF NOT EXISTS transactions (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            timestamp INTEGER NOT NULL,
+            kind TEXT NOT NULL,
+            amount INTEGER,
+            currency TEXT NOT NULL,
+            data TEXT NOT NULL
+        )",
+        [],
+    )?;
+    Ok(())
+}
+
+fn insert_row(
+    db: &Connection,
+    timestamp: u64,
+    kind: &str,
+    amount: Option<i32>,
+    data: &str,
+) -> SqlResult<()> {
+    db.execute(
+        "INSERT INTO transactions (timestamp, kind, amount, currency, data)
+         VALUES (?1, ?2, ?3, ?4, ?5)",
+        params![timestamp as i64, kind, amount, money::currency(), data],
+    )?;
+    Ok(())
+}
+
+/// Writes `transaction` through an open connection, for callers that
+/// already hold one (the bill driver records its bills this way, next to
+/// the ledger).
+pub fn insert(db: &Connection, transaction: &Transaction) -> SqlResult<()> {
+    insert_row(
+        db,
+        clock::timestamp(),
+        transaction.kind(),
+        transaction.amount(),
+        &transaction.data().to_string(),
+    )
+}
+
+/// Appends `transaction` to the stats DB. Like `audit::record`, this runs
+/// on its own thread and is best-effort.
+pub fn record(db_path: &str, transaction: &Transaction) {
+    let db_path = db_path.to_string();
+    let timestamp = clock::timestamp();
+    let kind = transaction.kind();
+    let amount = transaction.amount();
+    let data = transaction.data().to_string();
+
+    db_writes::spawn(move || {
+        let result = Connection::open(&db_path).and_then(|db| {
+            init_db(&db)?;
+            insert_row(&db, timestamp, kind, amount, &data)
+        });
+        if let Err(e) = result {
+            error!("Failed to record transaction ({}): {}", kind, e);
+        }
+    });
+}
+
+/// The whole table as JSON lines, oldest first, with each event's data
+/// merged into its line.
+pub fn json_lines(db: &Connection) -> SqlResult<Vec<String>> {
+    init_db(db)?;
+    let mut stmt = db.prepare(
+        "SELECT id, timestamp, kind, amount, currency, data FROM transactions ORDER BY id",
+    )?;
+    stmt.query_map([], |row| {
+        let mut line = serde_json::json!({
+            "id": row.get::<_, i64>(0)?,
+            "timestamp": row.get::<_, i64>(1)?,
+            "kind": row.get::<_, String>(2)?,
+            "amount": row.get::<_, Option<i64>>(3)?,
+            "currency": row.get::<_, String>(4)?,
+        });
+        if let (Some(line), Ok(serde_json::Value::Object(data))) = (
+            line.as_object_mut(),
+            serde_json::from_str(&row.get::<_, String>(5)?),
+        ) {
+            line.extend(data);
+        }
+        Ok(line.to_string())
+    })?
+    .collect()
+}
@@ -0,0 +1,175 @@
+use log::{info, warn};
+use rusqlite::Connection;
+use rusqlite::types::ValueRef;
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use thiserror::Error;
+
+use crate::clock;
+use crate::report::{self, Month};
+use crate::transactions;
+
+#[derive(Debug, Error)]
+pub enum ExportError {
+    #[error("io error: {0}")]
+    Io(#[from] io::Error),
+
+    #[error("database error: {0}")]
+    Database(#[from] rusqlite::Error),
+
+    #[error("could not unmount {0}")]
+    Eject(String),
+}
+
+/// A mounted removable drive.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UsbStick {
+    pub device: String,
+    pub mount_point: String,
+}
+
+/// Looks for a drive mounted below one of `roots` (where the desktop
+/// automounter puts sticks, e.g. `/media/<user>/<label>`). Linux only;
+/// elsewhere nothing is ever found.
+pub fn find_stick(roots: &[String]) -> Option<UsbStick> {
+    let mounts = fs::read_to_string("/proc/mounts").ok()?;
+    mounts.lines().find_map(|line| {
+        let mut fields = line.split_whitespace();
+        let device = fields.next()?;
+        let mount_point = unescape(fields.next()?);
+        let under_root = roots.iter().any(|root| {
+            Path::new(&mount_point).starts_with(root) && Path::new(&mount_point) != Path::new(root)
+        });
+        (device.starts_with("/dev/") && under_root).then(|| UsbStick {
+            device: device.to_string(),
+            mount_point,
+        })
+    })
+}
+
+/// `/proc/mounts` escapes spaces and friends as `\\040`-style octal.
+fn unescape(field: &str) -> String {
+    let mut out = String::with_capacity(field.len());
+    let mut chars = field.chars();
+    while let Some(c) = chars.next() {
+        if c == '\\' {
+            let code: String = chars.by_ref().take(3).collect();
+            match u8::from_str_radix(&code, 8) {
+                Ok(byte) => out.push(byte as char),
+                Err(_) => {
+                    out.push('\\');
+                    out.push_str(&code);
+                }
+            }
+        } else {
+            out.push(c);
+        }
+    }
+    out
+}
+
+/// Copies the records onto the stick in a fresh `dramma-export-<ts>`
+/// folder: a consistent snapshot of Stats.db, one CSV per table, the
+/// transactions as JSON lines, monthly reports for last month and this
+/// month so far, the recent log lines and any exported frame logs.
+/// Blocking.
+pub fn export(
+    stick: &UsbStick,
+    stats_db_path: &str,
+    frame_log_dir: &str,
+    log_lines: &[String],
+) -> Result<PathBuf, ExportError> {
+    let dir = Path::new(&stick.mount_point).join(format!("dramma-export-{}", clock::timestamp()));
+    fs::create_dir_all(&dir)?;
+
+    let db = Connection::open(stats_db_path)?;
+    // VACUUM INTO gives a consistent copy even while the app keeps writing
+    let snapshot = dir.join("Stats.db");
+    db.execute("VACUUM INTO ?1", [snapshot.to_string_lossy()])?;
+
+    let tables: Vec<String> = db
+        .prepare(
+            "SELECT name FROM sqlite_master WHERE type = 'table' AND name NOT LIKE 'sqlite_%'",
+        )?
+        .query_map([], |row| row.get(0))?
+        .collect::<Result<_, _>>()?;
+    for table in &tables {
+        write_csv(&db, table, &dir.join(format!("{}.csv", table)))?;
+    }
+
+    let this_month = Month::current();
+    for month in [this_month.previous(), this_month] {
+        report::save(&report::build(stats_db_path, month)?, &dir.join("reports"))?;
+    }
+
+    let transactions: String = transactions::json_lines(&db)?
+        .into_iter()
+        .map(|line| line + "\n")
+        .collect();
+    fs::write(dir.join("transactions.jsonl"), transactions)?;
+
+    fs::write(dir.join("dramma.log"), log_lines.join("\n"))?;
+
+    if let Ok(entries) = fs::read_dir(frame_log_dir) {
+        let frames_dir = dir.join("frames");
+        fs::create_dir_all(&frames_dir)?;
+        for entry in entries.flatten() {
+            fs::copy(entry.path(), frames_dir.join(entry.file_name()))?;
+        }
+    }
+
+    info!("💾 Exported {} tables to {}", tables.len(), dir.display());
+    Ok(dir)
+}
+
+fn write_csv(db: &Connection, table: &str, path: &Path) -> Result<(), ExportError> {
+    let mut stmt = db.prepare(&format!("SELECT * FROM \"{}\"", table))?;
+    let columns: Vec<String> = stmt.column_names().iter().map(|c| c.to_string()).collect();
+    let mut file = io::BufWriter::new(fs::File::create(path)?);
+    writeln!(file, "{}", columns.join(","))?;
+
+    let mut rows = stmt.query([])?;
+    while let Some(row) = rows.next()? {
+        let fields: Vec<String> = (0..columns.len())
+            .map(|i| match row.get_ref(i) {
+                Ok(ValueRef::Null) | Err(_) => String::new(),
+                Ok(ValueRef::Integer(v)) => v.to_string(),
+                Ok(ValueRef::Real(v)) => v.to_string(),
+                Ok(ValueRef::Text(v)) => csv_quote(&String::from_utf8_lossy(v)),
+                Ok(ValueRef::Blob(v)) => format!("<{} bytes>", v.len()),
+            })
+            .collect();
+        writeln!(file, "{}", fields.join(","))?;
+    }
+    file.flush()?;
+    Ok(())
+}
+
+pub fn csv_quote(text: &str) -> String {
+    if text.contains([',', '"', '\n']) {
+        format!("\"{}\"", text.replace('"', "\"\""))
+    } else {
+        text.to_string()
+    }
+}
+
+/// Flushes writes and unmounts the stick so it can be pulled safely. Tries
+/// udisks first (works unprivileged), then plain umount.
+pub fn eject(stick: &UsbStick) -> Result<(), ExportError> {
+    let _ = Command::new("sync").status();
+
+    let udisks = Command::new("udisksctl")
+        .args(["unmount", "-b", &stick.device])
+        .status();
+    if matches!(udisks, Ok(status) if status.success()) {
+        return Ok(());
+    }
+    warn!("udisksctl unmount failed, trying umount");
+
+    match Command::new("umount").arg(&stick.mount_point).status() {
+        Ok(status) if status.success() => Ok(()),
+        _ => Err(ExportError::Eject(stick.mount_point.clone())),
+    }
+}
@@ -0,0 +1,159 @@
+use std::collections::HashMap;
+
+use crate::donation::Username;
+
+/// How a username is tagged in the suggestions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Tier {
+    Member,
+    Guest,
+    /// The gateway gave no role, or one we don't know.
+    Unknown,
+}
+
+impl Tier {
+    fn from_role(role: Option<&str>) -> Self {
+        match role.map(|r| r.trim().to_lowercase()).as_deref() {
+            Some("member" | "resident") => Tier::Member,
+            Some("guest" | "visitor") => Tier::Guest,
+            _ => Tier::Unknown,
+        }
+    }
+
+    /// Tag shown next to a matching username; empty for `Unknown`.
+    pub fn tag(&self) -> &'static str {
+        match self {
+            Tier::Member => "member",
+            Tier::Guest => "guest",
+            Tier::Unknown => "",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Suggestion {
+    pub name: String,
+    pub tier: Tier,
+}
+
+/// Turns the gateway's username list into autocomplete suggestions: one
+/// entry per name ignoring case (the gateway's first spelling, with the
+/// first role given for any variant), names donated with here recently
+/// first in that order (`recent`, newest first), the rest alphabetically.
+pub fn suggestions(fetched: Vec<Username>, recent: &[String]) -> Vec<Suggestion> {
+    let mut by_name: HashMap<String, usize> = HashMap::new();
+    let mut list: Vec<Suggestion> = Vec::new();
+    for username in fetched {
+        let name = username.name.trim();
+        if name.is_empty() {
+            continue;
+        }
+        let tier = Tier::from_role(username.role.as_deref());
+        match by_name.get(&name.to_lowercase()) {
+            Some(&i) => {
+                if list[i].tier == Tier::Unknown {
+                    list[i].tier = tier;
+                }
+            }
+            None => {
+                by_name.insert(name.to_lowercase(), list.len());
+                list.push(Suggestion {
+                    name: name.to_string(),
+                    tier,
+                });
+            }
+        }
+    }
+
+    let recency: HashMap<String, usize> = recent
+        .iter()
+        .enumerate()
+        .map(|(rank, name)| (name.to_lowercase(), rank))
+        .collect();
+    list.sort_by_cached_key(|s| {
+        let lower = s.name.to_lowercase();
+        (recency.get(&lower).copied().unwrap_or(usize::MAX), lower)
+    });
+    list
+}
+
+/// How well `name` matches what was typed, both lowercase; lower is better,
+/// `None` when it doesn't. Starts of the name beat starts of a word in it
+/// ("kate" in "big_kate"), then anywhere in it, then the typed letters in
+/// order with gaps ("jsmth"), then a slip or two of the finger.
+fn rank(typed: &str, name: &str) -> Option<u8> {
+    if name.starts_with(typed) {
+        return Some(0);
+    }
+    if let Some(at) = name.find(typed) {
+        let word_start = name[..at]
+            .chars()
+            .next_back()
+            .is_some_and(|c| !c.is_alphanumeric());
+        return Some(if word_start { 1 } else { 2 });
+    }
+    let typed: Vec<char> = typed.chars().collect();
+    if typed.len() < 2 {
+        return None;
+    }
+    let mut rest = name.chars();
+    if typed.iter().all(|c| rest.any(|n| n == *c)) {
+        return Some(3);
+    }
+    // Typos: against the start of the name as long as what was typed, or
+    // the whole name when it's about that long
+    let allowed = match typed.len() {
+        0..=2 => return None,
+        3..=5 => 1,
+        _ => 2,
+    };
+    let name: Vec<char> = name.chars().collect();
+    let start = &name[..typed.len().min(name.len())];
+    if edit_distance(&typed, start) <= allowed || edit_distance(&typed, &name) <= allowed {
+        return Some(4);
+    }
+    None
+}
+
+/// Levenshtein distance, counting a swap of neighbours as one edit.
+fn edit_distance(a: &[char], b: &[char]) -> usize {
+    let mut rows = vec![(0..=b.len()).collect::<Vec<usize>>()];
+    for (i, ca) in a.iter().enumerate() {
+        let mut row = vec![i + 1; b.len() + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let above = &rows[i];
+            row[j + 1] = (above[j] + usize::from(ca != cb))
+                .min(above[j + 1] + 1)
+                .min(row[j] + 1);
+            if i > 0 && j > 0 && *ca == b[j - 1] && a[i - 1] == *cb {
+                row[j + 1] = row[j + 1].min(rows[i - 1][j - 1] + 1);
+            }
+        }
+        rows.push(row);
+    }
+    rows[a.len()][b.len()]
+}
+
+/// Indices into `names` of the best matches for `typed` (ignoring case),
+/// best first and at most `limit`; among equal matches the order of
+/// `names` (regulars first, see `suggestions`) is kept. A name that is
+/// exactly what was typed has nothing left to suggest and isn't included.
+pub fn matches(typed: &str, names: &[&str], limit: usize) -> Vec<usize> {
+    let typed = typed.trim().to_lowercase();
+    if typed.is_empty() {
+        return Vec::new();
+    }
+    let mut ranked: Vec<(u8, usize)> = names
+        .iter()
+        .enumerate()
+        .filter_map(|(i, name)| {
+            let name = name.to_lowercase();
+            if name == typed {
+                return None;
+            }
+            rank(&typed, &name).map(|rank| (rank, i))
+        })
+        .collect();
+    ranked.sort();
+    ranked.into_iter().take(limit).map(|(_, i)| i).collect()
+}
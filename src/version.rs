@@ -0,0 +1,14 @@
+/// Which build is running, for the Diagnostics page, MQTT discovery and the
+/// status file. Filled in by build.rs from git.
+pub const VERSION: &str = env!("CARGO_PKG_VERSION");
+/// Short commit hash, with "-dirty" for uncommitted changes, or "unknown".
+pub const GIT_HASH: &str = env!("DRAMMA_GIT_HASH");
+/// Date of that commit, "YYYY-MM-DD".
+pub const COMMIT_DATE: &str = env!("DRAMMA_COMMIT_DATE");
+/// Recent commits, newest first, one "YYYY-MM-DD hash subject" per line.
+pub const CHANGELOG: &str = include_str!(concat!(env!("OUT_DIR"), "/changelog.txt"));
+
+/// "0.4.0 (1a2b3c4)", as shown to staff.
+pub fn describe() -> String {
+    format!("{} ({})", VERSION, GIT_HASH)
+}
@@ -0,0 +1,78 @@
+//! Paper vouchers sold ahead of time (at events), redeemed at the kiosk as
+//! a donation without cash. The gateway knows the codes and what each is
+//! worth, and marks one used when it is redeemed; the kiosk only asks.
+
+use log::info;
+use serde::Deserialize;
+
+use crate::api;
+use crate::clock;
+use crate::error::RequestError;
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Voucher {
+    /// In AMD.
+    pub amount: i32,
+    #[serde(default)]
+    pub used: bool,
+}
+
+/// Outcome of a redemption.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Redeemed {
+    Done,
+    /// Someone got there first (409).
+    AlreadyUsed,
+}
+
+/// The code as the gateway knows it: printed codes are typed with spaces,
+/// dashes and in lower case, scanners may add a prefix of their own.
+pub fn normalize(code: &str) -> String {
+    code.chars()
+        .filter(char::is_ascii_alphanumeric)
+        .map(|c| c.to_ascii_uppercase())
+        .collect()
+}
+
+/// Looks `code` up. `None` when the gateway doesn't know it (404).
+pub async fn check(token: &str, code: &str) -> Result<Option<Voucher>, RequestError> {
+    info!("Checking voucher {}...", code);
+    let url = api::url(&format!("/api/kiosk/vouchers/{}", api::encode(code)));
+    let request = api::get(&url, token).body(())?;
+
+    let mut response = api::send(request).await?;
+    if response.status().as_u16() == 404 {
+        return Ok(None);
+    }
+    if !response.status().is_success() {
+        return Err(api::error_for_status(&mut response).await);
+    }
+    Ok(Some(api::read_json(&mut response).await?))
+}
+
+/// Redeems `code` as a donation to `fund_id` from `username`; the gateway
+/// records the donation and marks the code used in one go.
+pub async fn redeem(
+    token: &str,
+    code: &str,
+    fund_id: i32,
+    username: &str,
+) -> Result<Redeemed, RequestError> {
+    info!("Redeeming voucher {} for fund {}...", code, fund_id);
+    let body = serde_json::to_vec(&serde_json::json!({
+        "fund_id": fund_id,
+        "username": username,
+        "timestamp": clock::timestamp(),
+    }))?;
+    let url = api::url(&format!("/api/kiosk/vouchers/{}/redeem", api::encode(code)));
+    let request = api::post(&url, token).body(body)?;
+
+    let mut response = api::send(request).await?;
+    if response.status().as_u16() == 409 {
+        return Ok(Redeemed::AlreadyUsed);
+    }
+    if !response.status().is_success() {
+        return Err(api::error_for_status(&mut response).await);
+    }
+    Ok(Redeemed::Done)
+}